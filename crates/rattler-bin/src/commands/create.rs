@@ -8,7 +8,7 @@ use rattler::{
     package_cache::PackageCache,
 };
 use rattler_conda_types::{
-    Channel, ChannelConfig, GenericVirtualPackage, MatchSpec, PackageRecord, Platform,
+    Channel, CondaRc, GenericVirtualPackage, MatchSpec, PackageRecord, ParseStrictness, Platform,
     PrefixRecord, RepoDataRecord, Version,
 };
 use rattler_networking::{
@@ -19,7 +19,6 @@ use rattler_repodata_gateway::fetch::{
 };
 use rattler_repodata_gateway::sparse::SparseRepoData;
 use rattler_solve::{libsolv_c, resolvo, SolverImpl, SolverTask};
-use reqwest::Client;
 use std::{
     borrow::Cow,
     env,
@@ -54,7 +53,10 @@ pub struct Opt {
 }
 
 pub async fn create(opt: Opt) -> anyhow::Result<()> {
-    let channel_config = ChannelConfig::default();
+    // Read the `.condarc` configuration of the user, this determines how channels are resolved and
+    // how packages are downloaded and cached.
+    let condarc = CondaRc::load()?;
+    let channel_config = condarc.channel_config()?;
     let target_prefix = env::current_dir()?.join(".prefix");
 
     // Determine the platform we're going to install for
@@ -71,7 +73,7 @@ pub async fn create(opt: Opt) -> anyhow::Result<()> {
     let specs = opt
         .specs
         .iter()
-        .map(|spec| MatchSpec::from_str(spec))
+        .map(|spec| MatchSpec::from_str(spec, ParseStrictness::Strict))
         .collect::<Result<Vec<_>, _>>()?;
 
     // Find the default cache directory. Create it if it doesnt exist yet.
@@ -81,12 +83,16 @@ pub async fn create(opt: Opt) -> anyhow::Result<()> {
 
     // Determine the channels to use from the command line or select the default. Like matchspecs
     // this also requires the use of the `channel_config` so we have to do this manually.
+    // Multichannels like `defaults` are expanded into the channels they refer to.
     let channels = opt
         .channels
         .unwrap_or_else(|| vec![String::from("conda-forge")])
         .into_iter()
-        .map(|channel_str| Channel::from_str(channel_str, &channel_config))
-        .collect::<Result<Vec<_>, _>>()?;
+        .map(|channel_str| Channel::from_str_multi(channel_str, &channel_config))
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
 
     // Each channel contains multiple subdirectories. Users can specify the subdirectories they want
     // to use when specifying their channels. If the user didn't specify the default subdirectories
@@ -109,7 +115,7 @@ pub async fn create(opt: Opt) -> anyhow::Result<()> {
     // For each channel/subdirectory combination, download and cache the `repodata.json` that should
     // be available from the corresponding Url. The code below also displays a nice CLI progress-bar
     // to give users some more information about what is going on.
-    let download_client = Client::builder()
+    let download_client = rattler::condarc::client_builder(&condarc)?
         .no_gzip()
         .build()
         .expect("failed to create client");
@@ -214,11 +220,10 @@ pub async fn create(opt: Opt) -> anyhow::Result<()> {
         .collect();
 
     let solver_task = SolverTask {
-        available_packages: &repodatas,
         locked_packages,
         virtual_packages,
         specs,
-        pinned_packages: Vec::new(),
+        ..SolverTask::new(&repodatas)
     };
 
     // Next, use a solver to solve this specific problem. This provides us with all the operations
@@ -280,7 +285,15 @@ pub async fn create(opt: Opt) -> anyhow::Result<()> {
 
     if !transaction.operations.is_empty() {
         // Execute the operations that are returned by the solver.
-        execute_transaction(transaction, target_prefix, cache_dir, download_client).await?;
+        let package_cache_dir = rattler::condarc::package_cache_dir(&condarc)
+            .map_or_else(|| cache_dir.join("pkgs"), Path::to_path_buf);
+        execute_transaction(
+            transaction,
+            target_prefix,
+            package_cache_dir,
+            download_client,
+        )
+        .await?;
         println!(
             "{} Successfully updated the environment",
             console::style(console::Emoji("✔", "")).green(),
@@ -299,11 +312,11 @@ pub async fn create(opt: Opt) -> anyhow::Result<()> {
 async fn execute_transaction(
     transaction: Transaction<PrefixRecord, RepoDataRecord>,
     target_prefix: PathBuf,
-    cache_dir: PathBuf,
+    package_cache_dir: PathBuf,
     download_client: AuthenticatedClient,
 ) -> anyhow::Result<()> {
     // Open the package cache
-    let package_cache = PackageCache::new(cache_dir.join("pkgs"));
+    let package_cache = PackageCache::new(package_cache_dir);
 
     // Create an install driver which helps limit the number of concurrent fileystem operations
     let install_driver = InstallDriver::default();
@@ -620,12 +633,12 @@ async fn fetch_repo_data_records_with_progress(
 
     // Deserialize the data. This is a hefty blocking operation so we spawn it as a tokio blocking
     // task.
-    let repo_data_json_path = result.repo_data_json_path.clone();
+    let cache_result = result.cache_result;
     match tokio::task::spawn_blocking(move || {
-        SparseRepoData::new(
+        SparseRepoData::from_cached_repo_data(
             channel,
             platform.to_string(),
-            repo_data_json_path,
+            &result,
             Some(|record: &mut PackageRecord| {
                 if record.name.as_normalized() == "python" {
                     record.depends.push("pip".to_string());
@@ -638,7 +651,7 @@ async fn fetch_repo_data_records_with_progress(
         Ok(Ok(repodata)) => {
             progress_bar.set_style(finished_progress_style());
             let is_cache_hit = matches!(
-                result.cache_result,
+                cache_result,
                 CacheResult::CacheHit | CacheResult::CacheHitAfterFetch
             );
             progress_bar.finish_with_message(if is_cache_hit { "Using cache" } else { "Done" });
@@ -669,7 +682,7 @@ fn friendly_channel_name(channel: &Channel) -> String {
         .name
         .as_ref()
         .map(String::from)
-        .unwrap_or_else(|| channel.canonical_name())
+        .unwrap_or_else(|| channel.base_url().to_string())
 }
 
 /// Returns the style to use for a progressbar that is currently in progress.
@@ -0,0 +1,158 @@
+//! Applies the network and package cache settings of a `.condarc` configuration. See
+//! [`CondaRc`] for how the configuration is loaded.
+//!
+//! The channel related settings are converted through [`CondaRc::channel_config`] and
+//! [`CondaRc::channels`], the linking related settings through the conversion into
+//! [`crate::install::InstallOptions`].
+
+#[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+use rattler_conda_types::condarc::SslVerify;
+use rattler_conda_types::CondaRc;
+use reqwest::{ClientBuilder, Proxy};
+use std::path::{Path, PathBuf};
+
+/// An error that can occur when applying the settings of a [`CondaRc`].
+#[derive(Debug, thiserror::Error)]
+pub enum CondaRcError {
+    /// One of the `proxy_servers` is not a valid proxy url.
+    #[error("invalid proxy server '{0}'")]
+    InvalidProxy(String, #[source] reqwest::Error),
+
+    /// The certificate bundle of `ssl_verify` could not be read.
+    #[error("failed to read the certificate bundle '{0}'")]
+    IoError(PathBuf, #[source] std::io::Error),
+
+    /// The certificate bundle of `ssl_verify` contains an invalid certificate.
+    #[error("invalid certificate in the certificate bundle '{0}'")]
+    InvalidCertificate(PathBuf, #[source] reqwest::Error),
+}
+
+/// Returns a [`ClientBuilder`] that is configured with the `proxy_servers` and `ssl_verify`
+/// settings of the configuration.
+///
+/// Like in conda, the keys of `proxy_servers` are either a scheme (`http` or `https`), `all`, or
+/// a scheme and a host (e.g. `https://repo.corp`). Proxies for a specific host take precedence
+/// over proxies for a scheme. If `ssl_verify` refers to a certificate bundle, only the
+/// certificates in the bundle are trusted.
+pub fn client_builder(condarc: &CondaRc) -> Result<ClientBuilder, CondaRcError> {
+    let mut builder = ClientBuilder::new();
+
+    if let Some(proxy_servers) = &condarc.proxy_servers {
+        let (host_proxies, scheme_proxies): (Vec<_>, Vec<_>) = proxy_servers
+            .iter()
+            .partition(|(key, _)| key.contains("://"));
+        for (key, proxy_url) in host_proxies.into_iter().chain(scheme_proxies) {
+            builder = builder.proxy(proxy(key, proxy_url)?);
+        }
+    }
+
+    #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+    match &condarc.ssl_verify {
+        Some(SslVerify::Enabled(false)) => {
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+        Some(SslVerify::CertificateBundle(path)) => {
+            builder = builder.tls_built_in_root_certs(false);
+            for certificate in read_certificate_bundle(path)? {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        Some(SslVerify::Enabled(true)) | None => {}
+    }
+
+    Ok(builder)
+}
+
+/// Returns the directory in which packages should be cached, this is the first of the
+/// `pkgs_dirs` of the configuration, or `None` if no package directories are configured.
+pub fn package_cache_dir(condarc: &CondaRc) -> Option<&Path> {
+    condarc
+        .pkgs_dirs
+        .as_ref()
+        .and_then(|pkgs_dirs| pkgs_dirs.first())
+        .map(PathBuf::as_path)
+}
+
+/// Constructs the proxy for a single entry of the `proxy_servers`.
+fn proxy(key: &str, proxy_url: &str) -> Result<Proxy, CondaRcError> {
+    let invalid_proxy = |e| CondaRcError::InvalidProxy(proxy_url.to_owned(), e);
+    match key {
+        "http" => Proxy::http(proxy_url).map_err(invalid_proxy),
+        "https" => Proxy::https(proxy_url).map_err(invalid_proxy),
+        "all" => Proxy::all(proxy_url).map_err(invalid_proxy),
+        key => {
+            // A custom proxy only reports an invalid url when it is used, so the url is checked up
+            // front the same way the other proxies are.
+            Proxy::all(proxy_url).map_err(invalid_proxy)?;
+            let (scheme, host) = key.split_once("://").unwrap_or(("", key));
+            let (scheme, host) = (scheme.to_owned(), host.trim_end_matches('/').to_owned());
+            let proxy_url = proxy_url.to_owned();
+            Ok(Proxy::custom(move |url| {
+                (url.scheme() == scheme && url.host_str() == Some(host.as_str()))
+                    .then(|| proxy_url.clone())
+            }))
+        }
+    }
+}
+
+/// Reads all the certificates from a PEM encoded certificate bundle.
+#[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+fn read_certificate_bundle(path: &Path) -> Result<Vec<reqwest::Certificate>, CondaRcError> {
+    const END_MARKER: &str = "-----END CERTIFICATE-----";
+    let bundle =
+        std::fs::read_to_string(path).map_err(|e| CondaRcError::IoError(path.to_owned(), e))?;
+    bundle
+        .split_inclusive(END_MARKER)
+        .filter(|pem| pem.contains(END_MARKER))
+        .map(|pem| {
+            reqwest::Certificate::from_pem(pem.trim().as_bytes())
+                .map_err(|e| CondaRcError::InvalidCertificate(path.to_owned(), e))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{client_builder, package_cache_dir, CondaRcError};
+    use rattler_conda_types::CondaRc;
+    use std::{path::Path, str::FromStr};
+
+    #[test]
+    fn test_client_builder() {
+        let condarc = CondaRc::from_str(
+            r#"
+proxy_servers:
+  http: http://proxy.corp:8080
+  https://repo.corp: http://other-proxy.corp:8080
+ssl_verify: false
+"#,
+        )
+        .unwrap();
+        assert!(client_builder(&condarc).unwrap().build().is_ok());
+
+        let condarc = CondaRc::from_str("proxy_servers: {https: 'not a url'}").unwrap();
+        assert!(matches!(
+            client_builder(&condarc),
+            Err(CondaRcError::InvalidProxy(_, _))
+        ));
+
+        #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+        {
+            let condarc = CondaRc::from_str("ssl_verify: /does/not/exist.pem").unwrap();
+            assert!(matches!(
+                client_builder(&condarc),
+                Err(CondaRcError::IoError(_, _))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_package_cache_dir() {
+        let condarc = CondaRc::from_str("pkgs_dirs: [/opt/conda/pkgs, /tmp/pkgs]").unwrap();
+        assert_eq!(
+            package_cache_dir(&condarc),
+            Some(Path::new("/opt/conda/pkgs"))
+        );
+        assert_eq!(package_cache_dir(&CondaRc::default()), None);
+    }
+}
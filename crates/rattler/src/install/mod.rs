@@ -33,7 +33,7 @@ use futures::FutureExt;
 pub use python::PythonInfo;
 use rattler_conda_types::package::{IndexJson, LinkJson, NoArchLinks, PackageFile};
 use rattler_conda_types::prefix_record::PathsEntry;
-use rattler_conda_types::{package::PathsJson, Platform};
+use rattler_conda_types::{package::PathsJson, CondaRc, Platform};
 use std::cmp::Ordering;
 use std::collections::binary_heap::PeekMut;
 use std::collections::BinaryHeap;
@@ -183,6 +183,26 @@ pub struct InstallOptions {
     pub apple_codesign_behavior: AppleCodeSignBehavior,
 }
 
+impl From<&CondaRc> for InstallOptions {
+    /// Constructs the options from the linking related settings of a `.condarc` configuration.
+    fn from(condarc: &CondaRc) -> Self {
+        let (allow_symbolic_links, allow_hard_links) = if condarc.always_copy == Some(true) {
+            (Some(false), Some(false))
+        } else if condarc.always_softlink == Some(true) {
+            (Some(true), Some(false))
+        } else if condarc.allow_softlinks == Some(false) {
+            (Some(false), None)
+        } else {
+            (None, None)
+        };
+        Self {
+            allow_symbolic_links,
+            allow_hard_links,
+            ..Self::default()
+        }
+    }
+}
+
 /// Given an extracted package archive (`package_dir`), installs its files to the `target_dir`.
 ///
 /// Returns a [`PathsEntry`] for every file that was linked into the target directory. The entries
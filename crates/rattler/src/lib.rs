@@ -12,6 +12,7 @@
 
 use std::path::PathBuf;
 
+pub mod condarc;
 pub mod install;
 pub mod package_cache;
 pub mod validation;
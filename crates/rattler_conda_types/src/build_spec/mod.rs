@@ -5,7 +5,9 @@ pub mod parse;
 
 pub use parse::ParseBuildNumberSpecError;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
 use std::fmt::{Display, Formatter};
+use std::ops::RangeInclusive;
 
 /// Named type for the BuildNumber instead of explicit u64 floating about the project
 pub type BuildNumber = u64;
@@ -38,10 +40,61 @@ impl<Operator, Element> OperatorConstraint<Operator, Element> {
     pub fn new(op: Operator, rhs: Element) -> Self {
         Self { op, rhs }
     }
+
+    /// Returns the operator of the constraint.
+    pub fn op(&self) -> &Operator {
+        &self.op
+    }
+
+    /// Returns the element the operator compares against.
+    pub fn rhs(&self) -> &Element {
+        &self.rhs
+    }
 }
 
 /// Define match from OrdOperator and BuildNumber as Element
-pub type BuildNumberSpec = OperatorConstraint<OrdOperator, BuildNumber>;
+pub type BuildNumberConstraint = OperatorConstraint<OrdOperator, BuildNumber>;
+
+/// A specification of build numbers, e.g. `>=3` or `>=3,<5`. A build number matches the spec if it
+/// matches all the constraints of the spec.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd, Serialize, Deserialize)]
+#[serde(
+    from = "SerializableBuildNumberSpec",
+    into = "SerializableBuildNumberSpec"
+)]
+pub struct BuildNumberSpec {
+    constraints: Vec<BuildNumberConstraint>,
+}
+
+/// The serialized form of a [`BuildNumberSpec`], a spec with a single constraint is serialized as
+/// that constraint.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum SerializableBuildNumberSpec {
+    Single(BuildNumberConstraint),
+    Conjunction(Vec<BuildNumberConstraint>),
+}
+
+impl From<SerializableBuildNumberSpec> for BuildNumberSpec {
+    fn from(spec: SerializableBuildNumberSpec) -> Self {
+        match spec {
+            SerializableBuildNumberSpec::Single(constraint) => Self {
+                constraints: vec![constraint],
+            },
+            SerializableBuildNumberSpec::Conjunction(constraints) => Self { constraints },
+        }
+    }
+}
+
+impl From<BuildNumberSpec> for SerializableBuildNumberSpec {
+    fn from(mut spec: BuildNumberSpec) -> Self {
+        if spec.constraints.len() == 1 {
+            Self::Single(spec.constraints.remove(0))
+        } else {
+            Self::Conjunction(spec.constraints)
+        }
+    }
+}
 
 impl Display for OrdOperator {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
@@ -56,15 +109,26 @@ impl Display for OrdOperator {
     }
 }
 
-impl Display for BuildNumberSpec {
+impl Display for BuildNumberConstraint {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}{}", self.op, self.rhs)
     }
 }
 
-impl BuildNumberSpec {
-    /// Returns whether the number matches the specification.
-    /// Expected use is within match_spec::MatchSpec::matches
+impl Display for BuildNumberSpec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (index, constraint) in self.constraints.iter().enumerate() {
+            if index > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{constraint}")?;
+        }
+        Ok(())
+    }
+}
+
+impl BuildNumberConstraint {
+    /// Returns whether the number matches the constraint.
     pub fn matches(&self, build_num: &BuildNumber) -> bool {
         match self.op {
             OrdOperator::Gt => build_num.gt(&self.rhs),
@@ -75,11 +139,114 @@ impl BuildNumberSpec {
             OrdOperator::Ne => build_num.ne(&self.rhs),
         }
     }
+
+    /// Returns the inclusive range of build numbers that match the constraint, or `None` if the
+    /// matching numbers do not form a single range (`!=`).
+    fn range(&self) -> Option<RangeInclusive<BuildNumber>> {
+        match self.op {
+            OrdOperator::Gt => Some(match self.rhs.checked_add(1) {
+                Some(start) => start..=BuildNumber::MAX,
+                None => RangeInclusive::new(1, 0),
+            }),
+            OrdOperator::Ge => Some(self.rhs..=BuildNumber::MAX),
+            OrdOperator::Lt => Some(match self.rhs.checked_sub(1) {
+                Some(end) => 0..=end,
+                None => RangeInclusive::new(1, 0),
+            }),
+            OrdOperator::Le => Some(0..=self.rhs),
+            OrdOperator::Eq => Some(self.rhs..=self.rhs),
+            OrdOperator::Ne => None,
+        }
+    }
+}
+
+impl BuildNumberSpec {
+    /// Constructs a spec with a single constraint.
+    pub fn new(op: OrdOperator, rhs: BuildNumber) -> Self {
+        Self {
+            constraints: vec![OperatorConstraint::new(op, rhs)],
+        }
+    }
+
+    /// Returns the constraints that a build number has to match.
+    pub fn constraints(&self) -> &[BuildNumberConstraint] {
+        &self.constraints
+    }
+
+    /// Returns whether the number matches the specification.
+    /// Expected use is within match_spec::MatchSpec::matches
+    pub fn matches(&self, build_num: &BuildNumber) -> bool {
+        self.constraints
+            .iter()
+            .all(|constraint| constraint.matches(build_num))
+    }
+
+    /// Returns a spec that matches the build numbers that are matched by both `self` and `other`,
+    /// or `None` if no build number matches both specs.
+    pub fn intersection(&self, other: &BuildNumberSpec) -> Option<BuildNumberSpec> {
+        let (start, end, excluded) =
+            matching_numbers(self.constraints.iter().chain(other.constraints.iter()))?;
+
+        let mut constraints = if start == end {
+            vec![OperatorConstraint::new(OrdOperator::Eq, start)]
+        } else {
+            let lower = (start > 0).then(|| OperatorConstraint::new(OrdOperator::Ge, start));
+            let upper =
+                (end < BuildNumber::MAX).then(|| OperatorConstraint::new(OrdOperator::Le, end));
+            lower
+                .into_iter()
+                .chain(upper)
+                .chain(
+                    excluded
+                        .iter()
+                        .map(|&number| OperatorConstraint::new(OrdOperator::Ne, number)),
+                )
+                .collect()
+        };
+        if constraints.is_empty() {
+            // Every build number matches
+            constraints.push(OperatorConstraint::new(OrdOperator::Ge, 0));
+        }
+
+        // Keep one of the original specs if it already describes the intersection, this retains
+        // the operators that were used.
+        [self, other]
+            .into_iter()
+            .find(|spec| {
+                matching_numbers(spec.constraints.iter()) == Some((start, end, excluded.clone()))
+            })
+            .cloned()
+            .or(Some(BuildNumberSpec { constraints }))
+    }
+}
+
+/// Returns the build numbers that match all the constraints as the bounds of an inclusive range and
+/// the numbers in that range that are excluded. Returns `None` if no build number matches.
+fn matching_numbers<'a>(
+    constraints: impl Iterator<Item = &'a BuildNumberConstraint> + Clone,
+) -> Option<(BuildNumber, BuildNumber, BTreeSet<BuildNumber>)> {
+    // The numbers that match all the ordering constraints form a single range
+    let (start, end) = constraints
+        .clone()
+        .filter_map(BuildNumberConstraint::range)
+        .fold((0, BuildNumber::MAX), |(start, end), range| {
+            (start.max(*range.start()), end.min(*range.end()))
+        });
+    let excluded = constraints
+        .filter(|constraint| constraint.op == OrdOperator::Ne)
+        .map(|constraint| constraint.rhs)
+        .filter(|number| (start..=end).contains(number))
+        .collect::<BTreeSet<_>>();
+    if start > end || excluded.len() as u64 > end - start {
+        return None;
+    }
+    Some((start, end, excluded))
 }
 
 #[cfg(test)]
 mod tests {
     use super::{BuildNumberSpec, OrdOperator};
+    use std::str::FromStr;
 
     #[test]
     fn test_matches() {
@@ -95,4 +262,43 @@ mod tests {
             assert_eq!(spec.matches(&test_val), is_match);
         }
     }
+
+    #[test]
+    fn test_intersection() {
+        let intersection = |a: &str, b: &str| {
+            BuildNumberSpec::from_str(a)
+                .unwrap()
+                .intersection(&BuildNumberSpec::from_str(b).unwrap())
+                .map(|spec| spec.to_string())
+        };
+        assert_eq!(intersection(">=2", "<=5").as_deref(), Some(">=2,<=5"));
+        assert_eq!(intersection(">2", ">=5").as_deref(), Some(">=5"));
+        assert_eq!(intersection(">=2", "<=2").as_deref(), Some("==2"));
+        assert_eq!(
+            intersection(">=2,<4", "!=3").as_deref(),
+            Some(">=2,<=3,!=3")
+        );
+        assert_eq!(intersection("!=3", "!=3").as_deref(), Some("!=3"));
+        assert_eq!(intersection(">=2,<=3", "!=2,!=3"), None);
+        assert_eq!(intersection(">3", "<2"), None);
+        assert_eq!(intersection("==3", "!=3"), None);
+    }
+
+    #[test]
+    fn test_serialize() {
+        let spec = BuildNumberSpec::from_str(">=2").unwrap();
+        let json = serde_json::to_string(&spec).unwrap();
+        assert_eq!(json, r#"{"op":"Ge","rhs":2}"#);
+        assert_eq!(
+            serde_json::from_str::<BuildNumberSpec>(&json).unwrap(),
+            spec
+        );
+
+        let spec = BuildNumberSpec::from_str(">=2,<5").unwrap();
+        let json = serde_json::to_string(&spec).unwrap();
+        assert_eq!(
+            serde_json::from_str::<BuildNumberSpec>(&json).unwrap(),
+            spec
+        );
+    }
 }
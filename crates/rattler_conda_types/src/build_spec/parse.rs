@@ -1,8 +1,12 @@
 //! this module supports the parsing features of the build number spec
 
-use super::{BuildNumber, BuildNumberSpec, OrdOperator};
+use super::{BuildNumber, BuildNumberConstraint, BuildNumberSpec, OrdOperator};
 
-use nom::{bytes::complete::take_while1, character::complete::digit1, Finish, IResult};
+use nom::{
+    bytes::complete::take_while1,
+    character::complete::{char, digit1},
+    Finish, IResult,
+};
 use std::str::FromStr;
 use thiserror::Error;
 
@@ -18,8 +22,23 @@ impl FromStr for BuildNumberSpec {
 }
 
 impl BuildNumberSpec {
-    /// Parses a build number spec, string representation is optional operator preceding whole number
+    /// Parses a build number spec, string representation is a comma separated list of constraints
+    /// which are each an optional operator preceding whole number
     pub fn parser(input: &str) -> IResult<&str, BuildNumberSpec, ParseBuildNumberSpecError> {
+        let (mut input, constraint) = BuildNumberConstraint::parser(input)?;
+        let mut constraints = vec![constraint];
+        while let Ok((rest, _)) = char::<_, nom::error::Error<&str>>(',')(input) {
+            let (rest, constraint) = BuildNumberConstraint::parser(rest)?;
+            constraints.push(constraint);
+            input = rest;
+        }
+        Ok((input, BuildNumberSpec { constraints }))
+    }
+}
+
+impl BuildNumberConstraint {
+    /// Parses a single constraint, an optional operator preceding whole number
+    fn parser(input: &str) -> IResult<&str, BuildNumberConstraint, ParseBuildNumberSpecError> {
         // Parse the optional preceding operator
         let (input, op) = match OrdOperator::parser(input) {
             Err(
@@ -52,10 +71,10 @@ impl BuildNumberSpec {
                 ))
             })?;
 
-        match op {
-            Some(op) => Ok((rest, BuildNumberSpec::new(op, build_num))),
-            None => Ok((rest, BuildNumberSpec::new(OrdOperator::Eq, build_num))),
-        }
+        Ok((
+            rest,
+            BuildNumberConstraint::new(op.unwrap_or(OrdOperator::Eq), build_num),
+        ))
     }
 }
 
@@ -192,5 +211,12 @@ mod test {
             BuildNumberSpec::parser(">=build3").finish(),
             Err(_)
         ));
+
+        let spec = BuildNumberSpec::parser(">=1,<5,!=3").unwrap().1;
+        assert_eq!(spec.to_string(), ">=1,<5,!=3");
+        assert!(spec.matches(&1));
+        assert!(!spec.matches(&3));
+        assert!(!spec.matches(&5));
+        assert!(BuildNumberSpec::parser(">=1,").finish().is_err());
     }
 }
@@ -1,4 +1,5 @@
 use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::path::{Component, Path, PathBuf};
 use std::str::FromStr;
 
@@ -26,6 +27,32 @@ pub struct ChannelConfig {
     ///
     /// The default value is: <https://conda.anaconda.org>
     pub channel_alias: Url,
+
+    /// Channels that are not hosted at the [`Self::channel_alias`]. The key is the name of the
+    /// channel and the value is the location of the server that hosts it. Like in conda, the url of
+    /// the channel is the location joined with the name of the channel, e.g. the channel
+    /// `internal` with location `https://artifactory.corp/conda` refers to
+    /// `<https://artifactory.corp/conda/internal>`.
+    #[serde(default)]
+    pub custom_channels: BTreeMap<String, Url>,
+
+    /// Names that refer to multiple channels at once. The `defaults` multichannel refers to the
+    /// [`Self::default_channels`] unless it is explicitly overwritten here.
+    #[serde(default)]
+    pub custom_multichannels: BTreeMap<String, Vec<Url>>,
+
+    /// The channels that make up the `defaults` multichannel.
+    ///
+    /// The default value is `<https://repo.anaconda.com/pkgs/main>` and
+    /// `<https://repo.anaconda.com/pkgs/r>` (and `<https://repo.anaconda.com/pkgs/msys2>` on
+    /// Windows).
+    #[serde(default = "default_channels")]
+    pub default_channels: Vec<Url>,
+
+    /// Channel aliases that were used in the past. Channel urls that start with one of these
+    /// aliases are rebased onto the current [`Self::channel_alias`].
+    #[serde(default)]
+    pub migrated_channel_aliases: Vec<Url>,
 }
 
 impl Default for ChannelConfig {
@@ -33,10 +60,110 @@ impl Default for ChannelConfig {
         ChannelConfig {
             channel_alias: Url::from_str("https://conda.anaconda.org")
                 .expect("could not parse default channel alias"),
+            custom_channels: BTreeMap::new(),
+            custom_multichannels: BTreeMap::new(),
+            default_channels: default_channels(),
+            migrated_channel_aliases: Vec::new(),
         }
     }
 }
 
+impl ChannelConfig {
+    /// Returns the urls of the channels that make up the multichannel with the specified name, or
+    /// `None` if the name does not refer to a multichannel.
+    pub fn multichannel(&self, name: &str) -> Option<&[Url]> {
+        match self.custom_multichannels.get(name) {
+            Some(urls) => Some(urls.as_slice()),
+            None if name == DEFAULTS_CHANNEL_NAME => Some(self.default_channels.as_slice()),
+            None => None,
+        }
+    }
+
+    /// Returns the names and urls of all multichannels including `defaults`.
+    fn multichannels(&self) -> impl Iterator<Item = (&str, &[Url])> + '_ {
+        let defaults = (!self
+            .custom_multichannels
+            .contains_key(DEFAULTS_CHANNEL_NAME))
+        .then_some((DEFAULTS_CHANNEL_NAME, self.default_channels.as_slice()));
+        self.custom_multichannels
+            .iter()
+            .map(|(name, urls)| (name.as_str(), urls.as_slice()))
+            .chain(defaults)
+    }
+
+    /// Returns the base url and the name of the channel located at `base_url` if the channel is
+    /// known by name in this configuration, following the first three rules of
+    /// [`Channel::from_url`].
+    fn known_channel(&self, base_url: &Url) -> Option<(Url, String)> {
+        // Case 1: migrated_channel_aliases
+        let migrated_name = self
+            .migrated_channel_aliases
+            .iter()
+            .filter_map(|alias| strip_url_prefix(base_url, alias))
+            .find(|name| !name.is_empty());
+        if let Some(name) = migrated_name {
+            let base_url = join_directory(&with_trailing_slash(&self.channel_alias), name);
+            return Some((base_url, name.to_owned()));
+        }
+
+        // Case 2: custom_channels matches
+        let custom_name = self
+            .custom_channels
+            .iter()
+            .filter_map(|(name, location)| {
+                let channel_url = join_directory(&with_trailing_slash(location), name);
+                let rest = strip_url_prefix(base_url, &channel_url)?;
+                Some(if rest.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{name}/{rest}")
+                })
+            })
+            .max_by_key(String::len);
+        if let Some(name) = custom_name {
+            return Some((base_url.clone(), name));
+        }
+
+        // Case 3: channel_alias match
+        strip_url_prefix(base_url, &self.channel_alias)
+            .filter(|name| !name.is_empty())
+            .map(|name| (base_url.clone(), name.to_owned()))
+    }
+
+    /// Returns the url of the channel with the specified name if it is hosted by one of the custom
+    /// channels. If multiple custom channels match, the one with the longest name is used.
+    fn custom_channel(&self, name: &str) -> Option<Url> {
+        self.custom_channels
+            .iter()
+            .filter(|(custom_name, _)| {
+                name == custom_name.as_str()
+                    || name
+                        .strip_prefix(custom_name.as_str())
+                        .is_some_and(|rest| rest.starts_with('/'))
+            })
+            .max_by_key(|(custom_name, _)| custom_name.len())
+            .map(|(_, location)| join_directory(&with_trailing_slash(location), name))
+    }
+}
+
+/// The name of the multichannel that refers to the [`ChannelConfig::default_channels`].
+const DEFAULTS_CHANNEL_NAME: &str = "defaults";
+
+/// Returns the default value of [`ChannelConfig::default_channels`].
+fn default_channels() -> Vec<Url> {
+    let mut channels = vec![
+        Url::from_str("https://repo.anaconda.com/pkgs/main/").expect("invalid default channel"),
+        Url::from_str("https://repo.anaconda.com/pkgs/r/").expect("invalid default channel"),
+    ];
+    if cfg!(windows) {
+        channels.push(
+            Url::from_str("https://repo.anaconda.com/pkgs/msys2/")
+                .expect("invalid default channel"),
+        );
+    }
+    channels
+}
+
 /// `Channel`s are the primary source of package information.
 #[derive(Debug, Clone, Serialize, Eq, PartialEq, Hash)]
 pub struct Channel {
@@ -54,6 +181,9 @@ pub struct Channel {
 
 impl Channel {
     /// Parses a [`Channel`] from a string and a channel configuration.
+    ///
+    /// Names of multichannels (e.g. `defaults`) refer to more than one channel and result in an
+    /// error, use [`Channel::from_str_multi`] to parse them.
     pub fn from_str(
         str: impl AsRef<str>,
         config: &ChannelConfig,
@@ -81,6 +211,8 @@ impl Channel {
                     name: Some(channel.to_owned()),
                 }
             }
+        } else if config.multichannel(channel).is_some() {
+            return Err(ParseChannelError::MultiChannel(channel.to_owned()));
         } else {
             Channel::from_name(channel, platforms, config)
         };
@@ -88,47 +220,71 @@ impl Channel {
         Ok(channel)
     }
 
+    /// Parses a string that might refer to a multichannel (e.g. `defaults`) into all the channels
+    /// it refers to. Strings that do not refer to a multichannel result in a single channel, the
+    /// same as [`Channel::from_str`].
+    pub fn from_str_multi(
+        str: impl AsRef<str>,
+        config: &ChannelConfig,
+    ) -> Result<Vec<Self>, ParseChannelError> {
+        let str = str.as_ref();
+        let (platforms, channel) = parse_platforms(str)?;
+        match config.multichannel(channel) {
+            Some(urls) => Ok(urls
+                .iter()
+                .map(|url| Channel::from_url(url.clone(), platforms.clone(), config))
+                .collect()),
+            None => Ok(vec![Channel::from_str(str, config)?]),
+        }
+    }
+
     /// Constructs a new [`Channel`] from a `Url` and associated platforms.
+    ///
+    /// The name of the channel is determined using the same rules as conda:
+    ///
+    /// 1. Urls that start with one of the [`ChannelConfig::migrated_channel_aliases`] are rebased
+    ///    onto the [`ChannelConfig::channel_alias`].
+    /// 2. Urls that point to one of the [`ChannelConfig::custom_channels`] are named after it.
+    /// 3. Urls that start with the [`ChannelConfig::channel_alias`] are named by the rest of the
+    ///    path.
+    /// 4. Otherwise the name is derived from the path of the url.
     pub fn from_url(
         url: Url,
         platforms: Option<impl Into<SmallVec<[Platform; 2]>>>,
-        _config: &ChannelConfig,
+        config: &ChannelConfig,
     ) -> Self {
         // Get the path part of the URL but trim the directory suffix
         let path = url.path().trim_end_matches('/');
 
         // Ensure that the base_url does always ends in a `/`
-        let base_url = if !url.path().ends_with('/') {
-            let mut url = url.clone();
-            url.set_path(&format!("{path}/"));
-            url
-        } else {
-            url.clone()
-        };
-
-        // Case 1: No path give, channel name is ""
+        let base_url = with_trailing_slash(&url);
+        let platforms = platforms.map(Into::into);
 
-        // Case 2: migrated_custom_channels
-        // Case 3: migrated_channel_aliases
-        // Case 4: custom_channels matches
-        // Case 5: channel_alias match
+        // Case 1 to 3: channels that are known by name in the configuration
+        if let Some((base_url, name)) = config.known_channel(&base_url) {
+            return Self {
+                platforms,
+                base_url,
+                name: Some(name),
+            };
+        }
 
         if base_url.has_host() {
-            // Case 7: Fallback
+            // Case 4: Fallback
             let name = path.trim_start_matches('/');
             Self {
-                platforms: platforms.map(Into::into),
+                platforms,
                 name: (!name.is_empty()).then_some(name).map(str::to_owned),
                 base_url,
             }
         } else {
-            // Case 6: non-otherwise-specified file://-type urls
+            // Case 5: non-otherwise-specified file://-type urls
             let name = path
                 .rsplit_once('/')
                 .map(|(_, path_part)| path_part)
                 .unwrap_or_else(|| base_url.path());
             Self {
-                platforms: platforms.map(Into::into),
+                platforms,
                 name: (!name.is_empty()).then_some(name).map(str::to_owned),
                 base_url,
             }
@@ -136,26 +292,23 @@ impl Channel {
     }
 
     /// Construct a channel from a name, platform and configuration.
+    ///
+    /// If the name refers to one of the [`ChannelConfig::custom_channels`] the channel is located
+    /// at the location of the custom channel, otherwise it is located at the
+    /// [`ChannelConfig::channel_alias`].
     pub fn from_name(
         name: &str,
         platforms: Option<SmallVec<[Platform; 2]>>,
         config: &ChannelConfig,
     ) -> Self {
-        // TODO: custom channels
-
-        let dir_name = if !name.ends_with('/') {
-            Cow::Owned(format!("{name}/"))
-        } else {
-            Cow::Borrowed(name)
-        };
-
         let name = name.trim_end_matches('/');
+        let base_url = config
+            .custom_channel(name)
+            .unwrap_or_else(|| join_directory(&with_trailing_slash(&config.channel_alias), name));
+
         Self {
             platforms,
-            base_url: config
-                .channel_alias
-                .join(dir_name.as_ref())
-                .expect("name is not a valid Url"),
+            base_url,
             name: (!name.is_empty()).then_some(name).map(str::to_owned),
         }
     }
@@ -190,9 +343,27 @@ impl Channel {
         }
     }
 
-    /// Returns the canonical name of the channel
-    pub fn canonical_name(&self) -> String {
-        self.base_url.to_string()
+    /// Returns the canonical name of the channel, the name by which the channel is known in the
+    /// specified configuration.
+    ///
+    /// Like in conda, this is the name of the multichannel the channel is part of (e.g.
+    /// `defaults`), or the name of the channel if it is hosted at the
+    /// [`ChannelConfig::channel_alias`] or is one of the [`ChannelConfig::custom_channels`]. For
+    /// any other channel this is the base url of the channel.
+    pub fn canonical_name(&self, config: &ChannelConfig) -> String {
+        let base_url = with_trailing_slash(&self.base_url);
+        let known_channel = config.known_channel(&base_url);
+        let channel_url = known_channel.as_ref().map_or(&base_url, |(url, _)| url);
+        let multichannel_name = config
+            .multichannels()
+            .find(|(_, urls)| {
+                urls.iter()
+                    .any(|url| with_trailing_slash(url) == *channel_url)
+            })
+            .map(|(name, _)| name.to_owned());
+        multichannel_name
+            .or(known_channel.map(|(_, name)| name))
+            .unwrap_or_else(|| base_url.to_string())
     }
 }
 
@@ -210,6 +381,10 @@ pub enum ParseChannelError {
     /// Error when the path is invalid.
     #[error("invalid path '{0}")]
     InvalidPath(PathBuf),
+
+    /// Error when the name refers to a multichannel which consists of multiple channels.
+    #[error("'{0}' refers to multiple channels, use `Channel::from_str_multi` to parse it")]
+    MultiChannel(String),
 }
 
 impl From<ParsePlatformError> for ParseChannelError {
@@ -282,6 +457,48 @@ fn parse_scheme(channel: &str) -> Option<&str> {
     }
 }
 
+/// Returns the url with a trailing slash so other paths can be joined to it.
+fn with_trailing_slash(url: &Url) -> Url {
+    if url.path().ends_with('/') {
+        url.clone()
+    } else {
+        let mut url = url.clone();
+        let path = format!("{}/", url.path());
+        url.set_path(&path);
+        url
+    }
+}
+
+/// Joins a relative directory path to a url that ends with a slash.
+fn join_directory(url: &Url, path: &str) -> Url {
+    if path.is_empty() {
+        return url.clone();
+    }
+    url.join(&format!("{path}/"))
+        .expect("name is not a valid Url")
+}
+
+/// If `url` is located under `prefix`, returns the rest of the path of `url` without leading and
+/// trailing slashes. Credentials in either of the urls are ignored.
+fn strip_url_prefix<'u>(url: &'u Url, prefix: &Url) -> Option<&'u str> {
+    if url.scheme() != prefix.scheme()
+        || url.host_str() != prefix.host_str()
+        || url.port_or_known_default() != prefix.port_or_known_default()
+    {
+        return None;
+    }
+
+    // Make sure the prefix ends at a path segment boundary
+    let rest = url
+        .path()
+        .strip_prefix(prefix.path().trim_end_matches('/'))?;
+    if !rest.is_empty() && !rest.starts_with('/') {
+        return None;
+    }
+
+    Some(rest.trim_matches('/'))
+}
+
 /// Returns true if the specified string is considered to be a path
 fn is_path(path: &str) -> bool {
     lazy_regex::regex!(r"(\./|\.\.|~|/|[a-zA-Z]:[/\\]|\\\\|//)").is_match(path)
@@ -525,4 +742,163 @@ mod tests {
         assert_eq!(channel.name.as_deref(), Some("pkgs/main"));
         assert_eq!(channel.platforms, Some(smallvec![platform]));
     }
+
+    #[test]
+    fn parse_custom_channel() {
+        let mut config = ChannelConfig::default();
+        config.custom_channels.insert(
+            String::from("internal"),
+            Url::from_str("https://artifactory.corp/conda").unwrap(),
+        );
+
+        let channel = Channel::from_str("internal", &config).unwrap();
+        assert_eq!(
+            channel.base_url,
+            Url::from_str("https://artifactory.corp/conda/internal/").unwrap()
+        );
+        assert_eq!(channel.name.as_deref(), Some("internal"));
+        assert_eq!(channel.canonical_name(&config), "internal");
+
+        let channel = Channel::from_name("internal/label/dev", None, &config);
+        assert_eq!(
+            channel.base_url,
+            Url::from_str("https://artifactory.corp/conda/internal/label/dev/").unwrap()
+        );
+
+        let channel =
+            Channel::from_str("https://artifactory.corp/conda/internal/label/dev", &config)
+                .unwrap();
+        assert_eq!(channel.name.as_deref(), Some("internal/label/dev"));
+        assert_eq!(channel.canonical_name(&config), "internal/label/dev");
+
+        // Other channels are still resolved using the channel alias
+        let channel = Channel::from_str("conda-forge", &config).unwrap();
+        assert_eq!(
+            channel.base_url,
+            Url::from_str("https://conda.anaconda.org/conda-forge/").unwrap()
+        );
+        assert_eq!(channel.canonical_name(&config), "conda-forge");
+    }
+
+    #[test]
+    fn parse_multichannel() {
+        let config = ChannelConfig {
+            default_channels: vec![
+                Url::from_str("https://repo.anaconda.com/pkgs/main").unwrap(),
+                Url::from_str("https://repo.anaconda.com/pkgs/r").unwrap(),
+            ],
+            custom_multichannels: [(
+                String::from("corp"),
+                vec![
+                    Url::from_str("https://artifactory.corp/conda/internal").unwrap(),
+                    Url::from_str("https://conda.anaconda.org/conda-forge").unwrap(),
+                ],
+            )]
+            .into(),
+            ..ChannelConfig::default()
+        };
+
+        let channels = Channel::from_str_multi("defaults[linux-64]", &config).unwrap();
+        assert_eq!(
+            channels
+                .iter()
+                .map(|c| c.base_url.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "https://repo.anaconda.com/pkgs/main/",
+                "https://repo.anaconda.com/pkgs/r/"
+            ]
+        );
+        assert!(channels
+            .iter()
+            .all(|c| c.platforms == Some(smallvec![Platform::Linux64])));
+        assert!(channels
+            .iter()
+            .all(|c| c.canonical_name(&config) == "defaults"));
+
+        let channel = Channel::from_str("https://repo.anaconda.com/pkgs/main", &config).unwrap();
+        assert_eq!(channel.name.as_deref(), Some("pkgs/main"));
+        assert_eq!(channel.canonical_name(&config), "defaults");
+
+        let channels = Channel::from_str_multi("corp", &config).unwrap();
+        assert_eq!(channels.len(), 2);
+        assert!(channels.iter().all(|c| c.canonical_name(&config) == "corp"));
+
+        let channels = Channel::from_str_multi("bioconda", &config).unwrap();
+        assert_eq!(
+            channels,
+            vec![Channel::from_str("bioconda", &config).unwrap()]
+        );
+    }
+
+    #[test]
+    fn parse_migrated_channel_alias() {
+        let config = ChannelConfig {
+            channel_alias: Url::from_str("https://conda.corp/mirror/").unwrap(),
+            migrated_channel_aliases: vec![Url::from_str("https://conda.anaconda.org").unwrap()],
+            ..ChannelConfig::default()
+        };
+
+        let channel =
+            Channel::from_str("https://conda.anaconda.org/conda-forge/", &config).unwrap();
+        assert_eq!(
+            channel.base_url,
+            Url::from_str("https://conda.corp/mirror/conda-forge/").unwrap()
+        );
+        assert_eq!(channel.name.as_deref(), Some("conda-forge"));
+        assert_eq!(channel.canonical_name(&config), "conda-forge");
+        assert_eq!(channel, Channel::from_str("conda-forge", &config).unwrap());
+    }
+
+    #[test]
+    fn canonical_name() {
+        let config = ChannelConfig::default();
+        let canonical_name = |channel: &str| {
+            Channel::from_str(channel, &config)
+                .unwrap()
+                .canonical_name(&config)
+        };
+        assert_eq!(canonical_name("conda-forge"), "conda-forge");
+        assert_eq!(
+            canonical_name("https://conda.anaconda.org/conda-forge/label/dev"),
+            "conda-forge/label/dev"
+        );
+        assert_eq!(
+            canonical_name("https://repo.anaconda.com/pkgs/main"),
+            "defaults"
+        );
+        assert_eq!(
+            canonical_name("http://localhost:1234/channel"),
+            "http://localhost:1234/channel/"
+        );
+        assert_eq!(
+            canonical_name("file:///var/channels/conda-forge"),
+            "file:///var/channels/conda-forge/"
+        );
+    }
+
+    #[test]
+    fn parse_multichannel_name() {
+        let config = ChannelConfig {
+            custom_multichannels: [(
+                String::from("corp"),
+                vec![Url::from_str("https://artifactory.corp/conda/internal").unwrap()],
+            )]
+            .into(),
+            ..ChannelConfig::default()
+        };
+        for name in ["defaults", "corp", "corp[linux-64]"] {
+            assert!(
+                matches!(
+                    Channel::from_str(name, &config),
+                    Err(ParseChannelError::MultiChannel(_))
+                ),
+                "{name}"
+            );
+        }
+
+        // Urls and paths never refer to a multichannel
+        assert!(Channel::from_str("https://conda.anaconda.org/defaults", &config).is_ok());
+        assert!(Channel::from_str("./defaults", &config).is_ok());
+    }
 }
@@ -14,7 +14,7 @@ use crate::{
 };
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, skip_serializing_none, DisplayFromStr, OneOrMany, Same};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use url::Url;
 
 use crate::package::RunExportsJson;
@@ -26,6 +26,7 @@ pub struct ChannelData {
     pub channeldata_version: u32,
 
     /// A mapping of all packages in the channel
+    #[serde(serialize_with = "sort_map_alphabetically")]
     pub packages: HashMap<String, ChannelDataPackage>,
 
     /// The availalble subdirs for this channel
@@ -33,6 +34,13 @@ pub struct ChannelData {
     pub subdirs: Vec<String>,
 }
 
+fn sort_map_alphabetically<T: Serialize, S: serde::Serializer>(
+    value: &HashMap<String, T>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    BTreeMap::from_iter(value.iter()).serialize(serializer)
+}
+
 /// Information on a single package in a channel.
 #[serde_as]
 #[skip_serializing_none]
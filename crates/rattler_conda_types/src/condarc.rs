@@ -0,0 +1,693 @@
+//! A `.condarc` file is the configuration file that is used by conda to configure, among others,
+//! the channels to install packages from, the location of the package cache and network settings.
+//!
+//! Conda reads multiple of these files from a number of locations and merges them together,
+//! [`CondaRc::load`] follows the same search path and merge semantics. See
+//! <https://docs.conda.io/projects/conda/en/latest/user-guide/configuration/use-condarc.html> for
+//! more information.
+
+use crate::{Channel, ChannelConfig, ParseChannelError};
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use url::Url;
+
+/// The flag that marks a key, or all the keys in a file, as final. The value of a final key cannot
+/// be overwritten by files with a higher priority.
+const FINAL_FLAG: &str = "#!final";
+
+/// The typed contents of one or more merged `.condarc` files.
+///
+/// All fields are optional, a field that is `None` is not set by any of the files, in which case
+/// the default of conda should be used.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CondaRc {
+    /// The channels to search for packages. If not set, conda uses the `defaults` multichannel.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channels: Option<Vec<String>>,
+
+    /// The url that is prefixed to channel names, see [`ChannelConfig::channel_alias`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel_alias: Option<Url>,
+
+    /// The channels that make up the `defaults` multichannel, see
+    /// [`ChannelConfig::default_channels`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_channels: Option<Vec<Url>>,
+
+    /// Channels that are not hosted at the channel alias, see [`ChannelConfig::custom_channels`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_channels: Option<BTreeMap<String, Url>>,
+
+    /// Names that refer to multiple channels, see [`ChannelConfig::custom_multichannels`]. The
+    /// channels can either be specified by name or by url.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_multichannels: Option<BTreeMap<String, Vec<String>>>,
+
+    /// Channel aliases that were used in the past, see
+    /// [`ChannelConfig::migrated_channel_aliases`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub migrated_channel_aliases: Option<Vec<Url>>,
+
+    /// How strictly the order of the channels is respected by the solver.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel_priority: Option<ChannelPriority>,
+
+    /// The directories in which packages are cached, in order of preference.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pkgs_dirs: Option<Vec<PathBuf>>,
+
+    /// The directories in which named environments are located, in order of preference.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub envs_dirs: Option<Vec<PathBuf>>,
+
+    /// Proxy servers to use per protocol or per host, e.g. `https: http://proxy.corp:8080`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy_servers: Option<BTreeMap<String, String>>,
+
+    /// Whether to verify SSL certificates, or the path of the certificate bundle to use.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssl_verify: Option<SslVerify>,
+
+    /// Whether to only use the local package and repodata caches.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offline: Option<bool>,
+
+    /// Whether to allow the use of soft links when linking packages that are not noarch python
+    /// packages.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allow_softlinks: Option<bool>,
+
+    /// Whether to always copy files instead of using hard or soft links.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub always_copy: Option<bool>,
+
+    /// Whether to always use soft links instead of hard links or copies.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub always_softlink: Option<bool>,
+
+    /// The number of times a failed network request is retried.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_max_retries: Option<u32>,
+
+    /// Any keys that are not explicitly modelled by this struct.
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+
+    /// The keys whose values can no longer be overwritten when merging.
+    #[serde(skip)]
+    final_keys: BTreeSet<String>,
+}
+
+/// Determines how strictly the order of the channels is respected by the solver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelPriority {
+    /// Packages from lower priority channels are never used if a package with the same name exists
+    /// in a higher priority channel.
+    Strict,
+
+    /// The solver prefers packages from higher priority channels but may use packages from lower
+    /// priority channels.
+    Flexible,
+
+    /// The order of the channels is ignored.
+    Disabled,
+}
+
+/// The value of the `ssl_verify` key.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SslVerify {
+    /// Whether or not to verify certificates.
+    Enabled(bool),
+
+    /// Verify certificates using the certificate bundle at the specified path.
+    CertificateBundle(PathBuf),
+}
+
+/// An error that can occur when loading a [`CondaRc`].
+#[derive(Debug, thiserror::Error)]
+pub enum ParseCondaRcError {
+    /// The file could not be read.
+    #[error("failed to read '{0}'")]
+    IoError(PathBuf, #[source] std::io::Error),
+
+    /// The contents of a file are not a valid configuration.
+    #[error("failed to parse '{0}'")]
+    InvalidFile(PathBuf, #[source] serde_yaml::Error),
+
+    /// The configuration is not valid.
+    #[error(transparent)]
+    InvalidConfig(#[from] serde_yaml::Error),
+}
+
+impl CondaRc {
+    /// Returns the locations that are searched for configuration files, in order of increasing
+    /// priority. Locations that refer to a `condarc.d` directory contain any number of `.yml` or
+    /// `.yaml` files that are read in alphabetical order.
+    ///
+    /// This follows the search path of conda:
+    ///
+    /// * `/etc/conda/` and `/var/lib/conda/` (on unix)
+    /// * `$XDG_CONFIG_HOME/conda/`, `~/.config/conda/` and `~/.conda/`
+    /// * `~/.condarc`
+    /// * `$CONDA_PREFIX/`
+    /// * the file specified by `$CONDARC`
+    ///
+    /// Each directory is searched for `.condarc`, `condarc` and `condarc.d`.
+    pub fn search_paths() -> Vec<PathBuf> {
+        fn config_dir_paths(dir: &Path) -> [PathBuf; 3] {
+            [
+                dir.join(".condarc"),
+                dir.join("condarc"),
+                dir.join("condarc.d"),
+            ]
+        }
+
+        let mut paths = Vec::new();
+        if cfg!(unix) {
+            paths.extend(config_dir_paths(Path::new("/etc/conda")));
+            paths.extend(config_dir_paths(Path::new("/var/lib/conda")));
+        }
+
+        let xdg_config_home = std::env::var_os("XDG_CONFIG_HOME").map(PathBuf::from);
+        if let Some(xdg_config_home) = &xdg_config_home {
+            paths.extend(config_dir_paths(&xdg_config_home.join("conda")));
+        }
+        if let Some(home_dir) = dirs::home_dir() {
+            let config_dir = home_dir.join(".config");
+            if xdg_config_home.as_ref() != Some(&config_dir) {
+                paths.extend(config_dir_paths(&config_dir.join("conda")));
+            }
+            paths.extend(config_dir_paths(&home_dir.join(".conda")));
+            paths.push(home_dir.join(".condarc"));
+        }
+
+        if let Some(conda_prefix) = std::env::var_os("CONDA_PREFIX") {
+            paths.extend(config_dir_paths(Path::new(&conda_prefix)));
+        }
+
+        if let Some(condarc) = std::env::var_os("CONDARC") {
+            paths.push(PathBuf::from(condarc));
+        }
+
+        paths
+    }
+
+    /// Loads and merges all the configuration files in the [`CondaRc::search_paths`].
+    pub fn load() -> Result<Self, ParseCondaRcError> {
+        Self::from_paths(Self::search_paths())
+    }
+
+    /// Loads and merges the configuration files at the specified locations. The locations are
+    /// specified in order of increasing priority. Locations that do not exist are ignored and
+    /// locations that refer to a directory are searched for `.yml` and `.yaml` files.
+    pub fn from_paths(
+        paths: impl IntoIterator<Item = impl AsRef<Path>>,
+    ) -> Result<Self, ParseCondaRcError> {
+        let mut result = Self::default();
+        for path in paths {
+            let path = path.as_ref();
+            if path.is_dir() {
+                for file in condarc_dir_files(path)? {
+                    result = result.merge(Self::from_path(&file)?);
+                }
+            } else if path.is_file() {
+                result = result.merge(Self::from_path(path)?);
+            }
+        }
+        Ok(result)
+    }
+
+    /// Parses a single configuration file.
+    pub fn from_path(path: &Path) -> Result<Self, ParseCondaRcError> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| ParseCondaRcError::IoError(path.to_path_buf(), e))?;
+        Self::from_str(&contents).map_err(|e| match e {
+            ParseCondaRcError::InvalidConfig(e) => {
+                ParseCondaRcError::InvalidFile(path.to_path_buf(), e)
+            }
+            e => e,
+        })
+    }
+
+    /// Merges the `other` configuration, which has a higher priority, into this one.
+    ///
+    /// Like in conda, sequences are merged with the values of `other` first, mappings are merged
+    /// key by key and other values of `other` replace the values in this configuration. Keys that
+    /// were marked as final (with `#!final`) are not modified.
+    pub fn merge(self, other: CondaRc) -> CondaRc {
+        let Value::Mapping(mut merged) = to_value(&self) else {
+            unreachable!("CondaRc is always serialized as a mapping")
+        };
+        let Value::Mapping(other_values) = to_value(&other) else {
+            unreachable!("CondaRc is always serialized as a mapping")
+        };
+
+        for (key, value) in other_values {
+            let is_final = key
+                .as_str()
+                .is_some_and(|key| self.final_keys.contains(key));
+            if is_final {
+                continue;
+            }
+            let value = match merged.remove(&key) {
+                Some(existing) => merge_values(existing, value),
+                None => value,
+            };
+            merged.insert(key, value);
+        }
+
+        let mut result: CondaRc = serde_yaml::from_value(Value::Mapping(merged))
+            .expect("merging two configurations always results in a valid configuration");
+        result.final_keys = self.final_keys;
+        result.final_keys.extend(other.final_keys);
+        result
+    }
+
+    /// Constructs the [`ChannelConfig`] that resolves channels as configured.
+    pub fn channel_config(&self) -> Result<ChannelConfig, ParseChannelError> {
+        let mut config = ChannelConfig::default();
+        if let Some(channel_alias) = &self.channel_alias {
+            config.channel_alias = channel_alias.clone();
+        }
+        if let Some(default_channels) = &self.default_channels {
+            config.default_channels = default_channels.clone();
+        }
+        if let Some(custom_channels) = &self.custom_channels {
+            config.custom_channels = custom_channels.clone();
+        }
+        if let Some(migrated_channel_aliases) = &self.migrated_channel_aliases {
+            config.migrated_channel_aliases = migrated_channel_aliases.clone();
+        }
+
+        // The channels of a multichannel can be referred to by name, these are resolved with the
+        // rest of the configuration.
+        if let Some(custom_multichannels) = &self.custom_multichannels {
+            let mut multichannels = BTreeMap::new();
+            for (name, channels) in custom_multichannels {
+                let mut urls = Vec::new();
+                for channel in channels {
+                    urls.extend(
+                        Channel::from_str_multi(channel, &config)?
+                            .into_iter()
+                            .map(|channel| channel.base_url),
+                    );
+                }
+                multichannels.insert(name.clone(), urls);
+            }
+            config.custom_multichannels = multichannels;
+        }
+
+        Ok(config)
+    }
+
+    /// Returns the configured channels in order of priority. Multichannels, like `defaults`, are
+    /// expanded into the channels they refer to.
+    pub fn channels(&self) -> Result<Vec<Channel>, ParseChannelError> {
+        let config = self.channel_config()?;
+        let default_channels = [String::from("defaults")];
+        let names = self.channels.as_deref().unwrap_or(&default_channels);
+
+        let mut channels: Vec<Channel> = Vec::new();
+        for name in names {
+            for channel in Channel::from_str_multi(name, &config)? {
+                if !channels.contains(&channel) {
+                    channels.push(channel);
+                }
+            }
+        }
+        Ok(channels)
+    }
+
+    /// Returns true if the specified key was marked as final.
+    pub fn is_final(&self, key: &str) -> bool {
+        self.final_keys.contains(key)
+    }
+}
+
+impl FromStr for CondaRc {
+    type Err = ParseCondaRcError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // An empty file, or a file with only comments, is parsed as `null`.
+        let value: Value = serde_yaml::from_str(s)?;
+        let mut condarc: CondaRc = match value {
+            Value::Null => CondaRc::default(),
+            value => serde_yaml::from_value(value)?,
+        };
+
+        // Determine which of the parsed keys have been marked as final.
+        let Value::Mapping(values) = to_value(&condarc) else {
+            unreachable!("CondaRc is always serialized as a mapping")
+        };
+        for key in final_keys(s) {
+            match key {
+                FinalKey::All => condarc
+                    .final_keys
+                    .extend(values.keys().filter_map(Value::as_str).map(str::to_owned)),
+                FinalKey::Key(key) if values.contains_key(key.as_str()) => {
+                    condarc.final_keys.insert(key);
+                }
+                FinalKey::Key(_) => {}
+            }
+        }
+
+        Ok(condarc)
+    }
+}
+
+/// A top-level key, or all keys of a file, marked as final with the [`FINAL_FLAG`].
+enum FinalKey {
+    All,
+    Key(String),
+}
+
+/// Finds the top-level keys of a configuration file that are marked as final.
+///
+/// YAML comments are not part of the parsed document, so the flag is found on the top-level
+/// lines of the file: a line that only consists of the flag marks the whole file as final, a
+/// top-level key whose line ends with the flag as a comment marks that key as final. Top-level
+/// lines start at the first column, so lines inside nested values or block scalars are never
+/// considered.
+fn final_keys(contents: &str) -> Vec<FinalKey> {
+    let mut keys = Vec::new();
+    for line in contents.lines() {
+        if line.starts_with(char::is_whitespace) {
+            continue;
+        }
+        if line.trim_end() == FINAL_FLAG {
+            keys.push(FinalKey::All);
+            continue;
+        }
+
+        // Only a comment that consists of the flag marks the key as final, a `#!final` inside a
+        // quoted value is not a comment.
+        let Some((entry, comment)) = split_comment(line) else {
+            continue;
+        };
+        if comment.trim_end() != FINAL_FLAG {
+            continue;
+        }
+
+        // Parse the part before the comment to find the key, the value might continue on the
+        // next lines so only the key is parsed.
+        let Some((key, _)) = entry.split_once(": ").or_else(|| entry.trim_end().split_once(':'))
+        else {
+            continue;
+        };
+        if let Ok(Value::String(key)) = serde_yaml::from_str(key) {
+            keys.push(FinalKey::Key(key));
+        }
+    }
+    keys
+}
+
+/// Splits a line into its content and the text of its trailing comment, if the line has a
+/// comment. A `#` only starts a comment at the start of the line or after whitespace, and not
+/// inside a quoted string.
+fn split_comment(line: &str) -> Option<(&str, &str)> {
+    let mut quote = None;
+    let mut previous = None;
+    for (index, c) in line.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '#') if previous.map_or(true, char::is_whitespace) => {
+                return Some((&line[..index], &line[index..]));
+            }
+            _ => {}
+        }
+        previous = Some(c);
+    }
+    None
+}
+
+/// Returns the configuration files in a `condarc.d` directory in alphabetical order.
+fn condarc_dir_files(dir: &Path) -> Result<Vec<PathBuf>, ParseCondaRcError> {
+    let entries =
+        std::fs::read_dir(dir).map_err(|e| ParseCondaRcError::IoError(dir.to_path_buf(), e))?;
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry
+            .map_err(|e| ParseCondaRcError::IoError(dir.to_path_buf(), e))?
+            .path();
+        let is_yaml = matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("yml" | "yaml")
+        );
+        if is_yaml && path.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Converts the configuration to a yaml value.
+fn to_value(condarc: &CondaRc) -> Value {
+    serde_yaml::to_value(condarc).expect("CondaRc can always be converted to yaml")
+}
+
+/// Merges the value of a key with the value of the same key from a configuration with a higher
+/// priority.
+fn merge_values(lower: Value, higher: Value) -> Value {
+    match (lower, higher) {
+        (lower, Value::Null) => lower,
+        (Value::Sequence(lower), Value::Sequence(mut higher)) => {
+            for value in lower {
+                if !higher.contains(&value) {
+                    higher.push(value);
+                }
+            }
+            Value::Sequence(higher)
+        }
+        (Value::Mapping(mut lower), Value::Mapping(higher)) => {
+            lower.extend(higher);
+            Value::Mapping(lower)
+        }
+        (_, higher) => higher,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ChannelPriority, CondaRc, SslVerify};
+    use std::{path::PathBuf, str::FromStr};
+    use url::Url;
+
+    #[test]
+    fn test_parse() {
+        let condarc = CondaRc::from_str(
+            r#"
+channels:
+  - conda-forge
+  - defaults
+channel_alias: https://conda.corp/mirror
+channel_priority: strict
+pkgs_dirs:
+  - /opt/conda/pkgs
+ssl_verify: /etc/ssl/corp.pem
+proxy_servers:
+  https: http://proxy.corp:8080
+auto_activate_base: false
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            condarc.channels,
+            Some(vec![String::from("conda-forge"), String::from("defaults")])
+        );
+        assert_eq!(
+            condarc.channel_alias,
+            Some(Url::parse("https://conda.corp/mirror").unwrap())
+        );
+        assert_eq!(condarc.channel_priority, Some(ChannelPriority::Strict));
+        assert_eq!(
+            condarc.pkgs_dirs,
+            Some(vec![PathBuf::from("/opt/conda/pkgs")])
+        );
+        assert_eq!(
+            condarc.ssl_verify,
+            Some(SslVerify::CertificateBundle(PathBuf::from(
+                "/etc/ssl/corp.pem"
+            )))
+        );
+        assert_eq!(
+            condarc.extra.get("auto_activate_base"),
+            Some(&serde_yaml::Value::Bool(false))
+        );
+
+        assert_eq!(
+            CondaRc::from_str("# only comments").unwrap(),
+            CondaRc::default()
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let system = CondaRc::from_str(
+            "channels: [defaults]\nssl_verify: false\ncustom_channels:\n  a: https://a.corp\n",
+        )
+        .unwrap();
+        let user = CondaRc::from_str(
+            "channels: [conda-forge, defaults]\nssl_verify: true\ncustom_channels:\n  b: https://b.corp\n",
+        )
+        .unwrap();
+
+        let merged = system.merge(user);
+        assert_eq!(
+            merged.channels,
+            Some(vec![String::from("conda-forge"), String::from("defaults")])
+        );
+        assert_eq!(merged.ssl_verify, Some(SslVerify::Enabled(true)));
+        assert_eq!(
+            merged
+                .custom_channels
+                .unwrap()
+                .keys()
+                .map(String::as_str)
+                .collect::<Vec<_>>(),
+            vec!["a", "b"]
+        );
+    }
+
+    #[test]
+    fn test_merge_final() {
+        let system =
+            CondaRc::from_str("channels: [internal]  #!final\nchannel_alias: https://conda.corp\n")
+                .unwrap();
+        assert!(system.is_final("channels"));
+        assert!(!system.is_final("channel_alias"));
+
+        let user = CondaRc::from_str(
+            "channels: [conda-forge]\nchannel_alias: https://conda.anaconda.org\n",
+        )
+        .unwrap();
+        let merged = system.merge(user);
+        assert_eq!(merged.channels, Some(vec![String::from("internal")]));
+        assert_eq!(
+            merged.channel_alias,
+            Some(Url::parse("https://conda.anaconda.org").unwrap())
+        );
+
+        // A flag on its own line marks all keys in the file as final
+        let system = CondaRc::from_str("#!final\noffline: true\n").unwrap();
+        let merged = system.merge(CondaRc::from_str("offline: false").unwrap());
+        assert_eq!(merged.offline, Some(true));
+
+        // Only the flag as a comment after a top-level key marks that key as final
+        let condarc = CondaRc::from_str(
+            r#"
+"channels": #!final
+  - internal
+channel_alias: "https://conda.corp/#!final"
+custom_channels:
+  internal: https://artifactory.corp/conda #!final
+offline: true #!finalize
+"#,
+        )
+        .unwrap();
+        assert!(condarc.is_final("channels"));
+        assert!(!condarc.is_final("channel_alias"));
+        assert!(!condarc.is_final("custom_channels"));
+        assert!(!condarc.is_final("internal"));
+        assert!(!condarc.is_final("offline"));
+    }
+
+    #[test]
+    fn test_from_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let condarc_d = dir.path().join("condarc.d");
+        std::fs::create_dir(&condarc_d).unwrap();
+        std::fs::write(condarc_d.join("01-channels.yml"), "channels: [defaults]").unwrap();
+        std::fs::write(condarc_d.join("02-channels.yaml"), "channels: [bioconda]").unwrap();
+        std::fs::write(condarc_d.join("ignored.txt"), "channels: [ignored]").unwrap();
+        std::fs::write(dir.path().join(".condarc"), "channels: [conda-forge]").unwrap();
+
+        let condarc = CondaRc::from_paths([
+            dir.path().join("condarc.d"),
+            dir.path().join("does-not-exist"),
+            dir.path().join(".condarc"),
+        ])
+        .unwrap();
+        assert_eq!(
+            condarc.channels,
+            Some(vec![
+                String::from("conda-forge"),
+                String::from("bioconda"),
+                String::from("defaults")
+            ])
+        );
+    }
+
+    #[test]
+    fn test_channels() {
+        let condarc = CondaRc::from_str(
+            r#"
+channels: [internal, corp, defaults]
+default_channels:
+  - https://repo.anaconda.com/pkgs/main
+custom_channels:
+  internal: https://artifactory.corp/conda
+custom_multichannels:
+  corp:
+    - internal
+    - https://conda.anaconda.org/conda-forge
+"#,
+        )
+        .unwrap();
+
+        // Channels that are part of a multichannel are known by the name of the multichannel
+        let config = condarc.channel_config().unwrap();
+        let channels = condarc.channels().unwrap();
+        assert_eq!(
+            channels
+                .iter()
+                .map(|c| (c.base_url.as_str(), c.canonical_name(&config)))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    "https://artifactory.corp/conda/internal/",
+                    String::from("corp")
+                ),
+                (
+                    "https://conda.anaconda.org/conda-forge/",
+                    String::from("corp")
+                ),
+                (
+                    "https://repo.anaconda.com/pkgs/main/",
+                    String::from("defaults")
+                ),
+            ]
+        );
+
+        // A multichannel can include other multichannels
+        let condarc = CondaRc::from_str(
+            r#"
+custom_multichannels:
+  all: [defaults, conda-forge]
+"#,
+        )
+        .unwrap();
+        let config = condarc.channel_config().unwrap();
+        assert_eq!(
+            config.multichannel("all").unwrap().len(),
+            config.default_channels.len() + 1
+        );
+
+        // Without any channels the defaults are used
+        let condarc = CondaRc::default();
+        let config = condarc.channel_config().unwrap();
+        let channels = condarc.channels().unwrap();
+        assert!(channels
+            .iter()
+            .all(|c| c.canonical_name(&config) == "defaults"));
+    }
+}
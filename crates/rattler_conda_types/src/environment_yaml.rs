@@ -0,0 +1,282 @@
+//! An `environment.yml` file describes a conda environment in terms of the channels to use and the
+//! packages that it should contain. Unlike an explicit environment file (see
+//! [`crate::ExplicitEnvironmentSpec`]) the packages are specified with [`MatchSpec`]s that still
+//! have to be solved.
+//!
+//! To create an `environment.yml` file, you can use the `conda env export` command.
+
+use crate::{MatchSpec, ParseStrictness};
+use indexmap::IndexMap;
+use serde::{
+    de::{Error, MapAccess, Visitor},
+    ser::SerializeMap,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use serde_with::{serde_as, DefaultOnNull};
+use std::{
+    fmt::Formatter,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// The contents of an `environment.yml` file.
+///
+/// Serializing an [`EnvironmentYaml`] produces the same layout as conda (`name`, `channels`,
+/// `dependencies`, `variables` and `prefix`) and omits any field that is not set.
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnvironmentYaml {
+    /// The name of the environment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// The channels to use to solve the environment, in order of priority. Channels are either
+    /// the name of a channel or a url.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[serde_as(as = "DefaultOnNull")]
+    pub channels: Vec<String>,
+
+    /// The packages of the environment and sub sections of packages that are installed by other
+    /// package managers (e.g. `pip`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[serde_as(as = "DefaultOnNull")]
+    pub dependencies: Vec<MatchSpecOrSubSection>,
+
+    /// Environment variables that are set when the environment is activated.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    #[serde_as(as = "DefaultOnNull")]
+    pub variables: IndexMap<String, String>,
+
+    /// The location at which the environment should be created.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<PathBuf>,
+}
+
+/// An entry in the `dependencies` of an [`EnvironmentYaml`]. This is either a [`MatchSpec`] or a
+/// sub section that contains the requirements for another package manager, e.g.:
+///
+/// ```yaml
+/// dependencies:
+///   - python 3.11.*
+///   - pip
+///   - pip:
+///     - requests>=2.31
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::large_enum_variant)]
+pub enum MatchSpecOrSubSection {
+    /// A conda package
+    MatchSpec(MatchSpec),
+
+    /// A sub section with the name of the package manager (e.g. `pip`) and its requirements.
+    SubSection(String, Vec<String>),
+}
+
+/// An error that can occur when reading an [`EnvironmentYaml`].
+#[derive(Debug, thiserror::Error)]
+pub enum ParseEnvironmentYamlError {
+    /// The file could not be read
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+
+    /// The contents are not a valid `environment.yml`. The error includes the location of the
+    /// problem, see [`ParseEnvironmentYamlError::location`].
+    #[error(transparent)]
+    InvalidYaml(#[from] serde_yaml::Error),
+}
+
+impl ParseEnvironmentYamlError {
+    /// Returns the location in the file at which the error occurred, if known.
+    pub fn location(&self) -> Option<serde_yaml::Location> {
+        match self {
+            ParseEnvironmentYamlError::InvalidYaml(e) => e.location(),
+            ParseEnvironmentYamlError::IoError(_) => None,
+        }
+    }
+}
+
+impl EnvironmentYaml {
+    /// Parses an `environment.yml` from a string.
+    pub fn from_yaml_str(content: &str) -> Result<Self, ParseEnvironmentYamlError> {
+        Ok(serde_yaml::from_str(content)?)
+    }
+
+    /// Reads an `environment.yml` file.
+    pub fn from_path(path: &Path) -> Result<Self, ParseEnvironmentYamlError> {
+        Self::from_yaml_str(&std::fs::read_to_string(path)?)
+    }
+
+    /// Serializes the environment to a yaml string.
+    pub fn to_yaml_string(&self) -> String {
+        serde_yaml::to_string(self).expect("an environment can always be serialized to yaml")
+    }
+
+    /// Writes the environment to a file.
+    pub fn to_path(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, self.to_yaml_string())
+    }
+
+    /// Returns the conda packages of the environment.
+    pub fn match_specs(&self) -> impl Iterator<Item = &MatchSpec> + '_ {
+        self.dependencies.iter().filter_map(|entry| match entry {
+            MatchSpecOrSubSection::MatchSpec(spec) => Some(spec),
+            MatchSpecOrSubSection::SubSection(_, _) => None,
+        })
+    }
+
+    /// Returns the requirements of the sub section with the specified name, e.g. `pip`.
+    pub fn sub_section(&self, name: &str) -> Option<&[String]> {
+        self.dependencies.iter().find_map(|entry| match entry {
+            MatchSpecOrSubSection::SubSection(key, specs) if key == name => Some(specs.as_slice()),
+            _ => None,
+        })
+    }
+
+    /// Returns the requirements of the `pip` sub section.
+    pub fn pip_specs(&self) -> Option<&[String]> {
+        self.sub_section("pip")
+    }
+}
+
+impl FromStr for EnvironmentYaml {
+    type Err = ParseEnvironmentYamlError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_yaml_str(s)
+    }
+}
+
+impl Serialize for MatchSpecOrSubSection {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            MatchSpecOrSubSection::MatchSpec(spec) => serializer.collect_str(spec),
+            MatchSpecOrSubSection::SubSection(name, specs) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(name, specs)?;
+                map.end()
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for MatchSpecOrSubSection {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // A visitor is used instead of an untagged enum to preserve the error that occurs when
+        // parsing the match spec.
+        struct EntryVisitor;
+
+        impl<'de> Visitor<'de> for EntryVisitor {
+            type Value = MatchSpecOrSubSection;
+
+            fn expecting(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+                formatter.write_str("a match spec or a sub section (e.g. `pip: [...]`)")
+            }
+
+            fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+                MatchSpec::from_str(v, ParseStrictness::Lenient)
+                    .map(MatchSpecOrSubSection::MatchSpec)
+                    .map_err(|e| E::custom(format!("invalid match spec '{v}': {e}")))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let (name, specs) = map
+                    .next_entry::<String, Vec<String>>()?
+                    .ok_or_else(|| A::Error::invalid_length(0, &self))?;
+                if map.next_key::<String>()?.is_some() {
+                    return Err(A::Error::custom(
+                        "a sub section must contain exactly one package manager",
+                    ));
+                }
+                Ok(MatchSpecOrSubSection::SubSection(name, specs))
+            }
+        }
+
+        deserializer.deserialize_any(EntryVisitor)
+    }
+}
+
+impl From<MatchSpec> for MatchSpecOrSubSection {
+    fn from(spec: MatchSpec) -> Self {
+        MatchSpecOrSubSection::MatchSpec(spec)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{EnvironmentYaml, MatchSpecOrSubSection};
+    use crate::{MatchSpec, ParseStrictness};
+
+    const ENVIRONMENT_YAML: &str = r#"name: science
+channels:
+- conda-forge
+- defaults
+dependencies:
+- python 3.11.*
+- conda-forge::numpy >=1.26
+- pip
+- pip:
+  - requests>=2.31
+  - -e .
+variables:
+  OMP_NUM_THREADS: '4'
+"#;
+
+    #[test]
+    fn test_parse() {
+        let env = EnvironmentYaml::from_yaml_str(ENVIRONMENT_YAML).unwrap();
+        assert_eq!(env.name.as_deref(), Some("science"));
+        assert_eq!(env.channels, vec!["conda-forge", "defaults"]);
+        assert_eq!(
+            env.match_specs()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec!["python 3.11.*", "conda-forge::numpy >=1.26", "pip"]
+        );
+        assert_eq!(
+            env.pip_specs(),
+            Some(&[String::from("requests>=2.31"), String::from("-e .")][..])
+        );
+        assert_eq!(
+            env.variables.get("OMP_NUM_THREADS").map(String::as_str),
+            Some("4")
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let env = EnvironmentYaml::from_yaml_str(ENVIRONMENT_YAML).unwrap();
+        assert_eq!(env.to_yaml_string(), ENVIRONMENT_YAML);
+
+        let env = EnvironmentYaml {
+            name: Some(String::from("minimal")),
+            dependencies: vec![MatchSpecOrSubSection::from(
+                MatchSpec::from_str("python >=3.8", ParseStrictness::Strict).unwrap(),
+            )],
+            ..EnvironmentYaml::default()
+        };
+        assert_eq!(
+            EnvironmentYaml::from_yaml_str(&env.to_yaml_string()).unwrap(),
+            env
+        );
+    }
+
+    #[test]
+    fn test_parse_empty_sections() {
+        let env =
+            EnvironmentYaml::from_yaml_str("name: empty\nchannels:\ndependencies:\n").unwrap();
+        assert!(env.channels.is_empty());
+        assert!(env.dependencies.is_empty());
+    }
+
+    #[test]
+    fn test_parse_error_location() {
+        let err = EnvironmentYaml::from_yaml_str(
+            "name: broken\ndependencies:\n  - python\n  - numpy >=1.0,,<2\n",
+        )
+        .unwrap_err();
+        let location = err.location().unwrap();
+        assert_eq!(location.line(), 4);
+        assert_eq!(location.column(), 5);
+        assert!(err.to_string().contains("numpy >=1.0,,<2"));
+    }
+}
@@ -7,11 +7,18 @@
 //! but instead directly refer to the download location of the package. This makes them useful
 //! to quickly install an environment.
 //!
-//! To create an explicit environment file, you can use the `conda env export` command.
+//! To create an explicit environment file, you can use the `conda list --explicit` command or
+//! [`ExplicitEnvironmentSpec::from_records`].
 
-use crate::{ParsePlatformError, Platform};
+use crate::{PackageRecord, ParsePlatformError, Platform, RepoDataRecord};
 use serde::{Deserialize, Serialize};
-use std::{fs::File, io::Read, path::Path, str::FromStr};
+use std::{
+    fmt::{Display, Formatter},
+    fs::File,
+    io::{Read, Write},
+    path::Path,
+    str::FromStr,
+};
 use url::Url;
 
 /// An [`ExplicitEnvironmentSpec`] represents an explicit environment specification. Packages are
@@ -150,6 +157,59 @@ impl ExplicitEnvironmentSpec {
     pub fn from_path(path: &Path) -> Result<Self, ParseExplicitEnvironmentSpecError> {
         Self::from_reader(File::open(path)?)
     }
+
+    /// Constructs an explicit environment from records, e.g. the result of a solve or the
+    /// [`crate::PrefixRecord`]s of an installed environment.
+    ///
+    /// The packages are sorted topologically (see [`PackageRecord::sort_topologically`]) so
+    /// dependencies are always listed before the packages that depend on them. The hash of each
+    /// package is added to its url, the sha256 hash is used if it is known, otherwise the md5 hash.
+    pub fn from_records<T: AsRef<RepoDataRecord> + AsRef<PackageRecord> + Clone>(
+        records: Vec<T>,
+        platform: Option<Platform>,
+    ) -> Self {
+        let packages = PackageRecord::sort_topologically(records)
+            .iter()
+            .map(|record| {
+                let record: &RepoDataRecord = record.as_ref();
+                let mut url = record.url.clone();
+                let hash = match (&record.package_record.sha256, &record.package_record.md5) {
+                    (Some(sha256), _) => Some(format!("sha256:{sha256:x}")),
+                    (None, Some(md5)) => Some(format!("{md5:x}")),
+                    (None, None) => None,
+                };
+                url.set_fragment(hash.as_deref());
+                ExplicitEnvironmentEntry { url }
+            })
+            .collect();
+
+        ExplicitEnvironmentSpec { platform, packages }
+    }
+
+    /// Writes the explicit environment file to a writer.
+    pub fn to_writer(&self, mut writer: impl Write) -> std::io::Result<()> {
+        write!(writer, "{self}")
+    }
+
+    /// Writes the explicit environment file to a file.
+    pub fn to_path(&self, path: &Path) -> std::io::Result<()> {
+        self.to_writer(std::io::BufWriter::new(File::create(path)?))
+    }
+}
+
+impl Display for ExplicitEnvironmentSpec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "# This file may be used to create an environment using:")?;
+        writeln!(f, "# $ conda create --name <env> --file <this file>")?;
+        if let Some(platform) = self.platform {
+            writeln!(f, "# platform: {platform}")?;
+        }
+        writeln!(f, "@EXPLICIT")?;
+        for package in &self.packages {
+            writeln!(f, "{}", package.url)?;
+        }
+        Ok(())
+    }
 }
 
 impl FromStr for ExplicitEnvironmentSpec {
@@ -196,7 +256,8 @@ mod test {
     use super::{ExplicitEnvironmentSpec, ParseExplicitEnvironmentSpecError};
     use crate::{
         explicit_environment_spec::{PackageArchiveHash, ParsePackageArchiveHashError},
-        get_test_data_dir, ExplicitEnvironmentEntry,
+        get_test_data_dir, ExplicitEnvironmentEntry, PackageName, PackageRecord, Platform,
+        RepoDataRecord, Version,
     };
     use assert_matches::assert_matches;
     use hex_literal::hex;
@@ -298,4 +359,59 @@ mod test {
             Err(ParsePackageArchiveHashError::InvalidMd5Hash(_))
         );
     }
+
+    fn record(name: &str, depends: &[&str]) -> RepoDataRecord {
+        let mut package_record = PackageRecord::new(
+            PackageName::new_unchecked(name),
+            Version::from_str("1.0").unwrap(),
+            String::from("h123_0"),
+        );
+        package_record.subdir = String::from("linux-64");
+        package_record.depends = depends.iter().map(ToString::to_string).collect();
+        let file_name = format!("{name}-1.0-h123_0.conda");
+        RepoDataRecord {
+            url: Url::parse(&format!(
+                "https://conda.anaconda.org/conda-forge/linux-64/{file_name}"
+            ))
+            .unwrap(),
+            file_name,
+            channel: String::from("https://conda.anaconda.org/conda-forge/"),
+            package_record,
+        }
+    }
+
+    #[test]
+    fn test_from_records() {
+        let mut python = record("python", &["libzlib >=1.2"]);
+        python.package_record.sha256 =
+            Some(hex!("315f5bdb76d078c43b8ac0064e4a0164612b1fce77c869345bfc94c75894edd3").into());
+        let mut libzlib = record("libzlib", &[]);
+        libzlib.package_record.md5 = Some(hex!("a98ea1e3abfdbbd201d60ff6b43ea7e4").into());
+        let numpy = record("numpy", &["python >=3.8"]);
+
+        let spec = ExplicitEnvironmentSpec::from_records(
+            vec![numpy, python, libzlib],
+            Some(Platform::Linux64),
+        );
+        insta::assert_snapshot!(spec.to_string());
+
+        // The written file can be parsed again
+        let parsed = ExplicitEnvironmentSpec::from_str(&spec.to_string()).unwrap();
+        assert_eq!(parsed.platform, Some(Platform::Linux64));
+        assert_eq!(
+            parsed
+                .packages
+                .iter()
+                .map(|p| p.url.clone())
+                .collect::<Vec<_>>(),
+            spec.packages
+                .iter()
+                .map(|p| p.url.clone())
+                .collect::<Vec<_>>()
+        );
+        assert_matches!(
+            parsed.packages[1].package_archive_hash(),
+            Ok(Some(PackageArchiveHash::Sha256(_)))
+        );
+    }
 }
@@ -1,9 +1,10 @@
 use crate::{PackageName, Version};
+use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
 
 /// A `GenericVirtualPackage` is a Conda package description that contains a `name` and a
 /// `version` and a `build_string`.
-#[derive(Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd, Serialize, Deserialize)]
 pub struct GenericVirtualPackage {
     /// The name of the package
     pub name: PackageName,
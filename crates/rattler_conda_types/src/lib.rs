@@ -5,6 +5,8 @@
 mod build_spec;
 mod channel;
 mod channel_data;
+pub mod condarc;
+mod environment_yaml;
 mod explicit_environment_spec;
 mod match_spec;
 mod no_arch_type;
@@ -19,21 +21,30 @@ pub mod version_spec;
 mod generic_virtual_package;
 pub mod package;
 mod package_name;
+mod parse_mode;
+#[cfg(feature = "pep440")]
+mod pep440;
 pub mod prefix_record;
 
 pub use build_spec::{BuildNumber, BuildNumberSpec, ParseBuildNumberSpecError};
 pub use channel::{Channel, ChannelConfig, ParseChannelError};
 pub use channel_data::{ChannelData, ChannelDataPackage};
+pub use condarc::{CondaRc, ParseCondaRcError};
+pub use environment_yaml::{EnvironmentYaml, MatchSpecOrSubSection, ParseEnvironmentYamlError};
 pub use explicit_environment_spec::{
     ExplicitEnvironmentEntry, ExplicitEnvironmentSpec, PackageArchiveHash,
     ParseExplicitEnvironmentSpecError, ParsePackageArchiveHashError,
 };
 pub use generic_virtual_package::GenericVirtualPackage;
 pub use match_spec::matcher::StringMatcher;
+pub use match_spec::merge::IntersectMatchSpecError;
 pub use match_spec::parse::ParseMatchSpecError;
 pub use match_spec::{MatchSpec, NamelessMatchSpec};
 pub use no_arch_type::{NoArchKind, NoArchType};
 pub use package_name::{InvalidPackageNameError, PackageName};
+pub use parse_mode::ParseStrictness;
+#[cfg(feature = "pep440")]
+pub use pep440::{python_matches_requires_python, Pep440ConversionError};
 pub use platform::{Arch, ParseArchError, ParsePlatformError, Platform};
 pub use prefix_record::PrefixRecord;
 pub use repo_data::patches::{PackageRecordPatch, PatchInstructions, RepoDataPatch};
@@ -0,0 +1,276 @@
+//! Functionality to combine several [`MatchSpec`]s into a single spec that only matches the records
+//! that are matched by all of them.
+
+use super::{matcher::StringMatcher, MatchSpec, NamelessMatchSpec};
+use crate::{build_spec::BuildNumberSpec, VersionSpec};
+use itertools::Itertools;
+use std::{collections::BTreeSet, fmt::Display};
+use thiserror::Error;
+use url::Url;
+
+/// An error that can occur when intersecting two [`MatchSpec`]s.
+#[derive(Debug, Clone, Eq, PartialEq, Error)]
+pub enum IntersectMatchSpecError {
+    /// The specs contradict each other, no record can match both.
+    #[error("conflicting {field}: '{first}' and '{second}'")]
+    Conflict {
+        /// The name of the field that conflicts
+        field: &'static str,
+        /// The value of the field in the first spec
+        first: String,
+        /// The value of the field in the second spec
+        second: String,
+    },
+
+    /// The specs might both be satisfiable but their intersection cannot be expressed as a single
+    /// spec.
+    #[error("cannot combine {field} '{first}' and '{second}' into a single spec")]
+    Unsupported {
+        /// The name of the field that cannot be combined
+        field: &'static str,
+        /// The value of the field in the first spec
+        first: String,
+        /// The value of the field in the second spec
+        second: String,
+    },
+}
+
+impl IntersectMatchSpecError {
+    fn conflict(field: &'static str, first: impl Display, second: impl Display) -> Self {
+        Self::Conflict {
+            field,
+            first: first.to_string(),
+            second: second.to_string(),
+        }
+    }
+
+    fn unsupported(field: &'static str, first: impl Display, second: impl Display) -> Self {
+        Self::Unsupported {
+            field,
+            first: first.to_string(),
+            second: second.to_string(),
+        }
+    }
+}
+
+impl MatchSpec {
+    /// Returns a spec that only matches the records that are matched by both `self` and `other`.
+    ///
+    /// Version and build number specs are combined with a logical AND, a channel name is combined
+    /// with a channel url that ends with that name, and features are combined into the union of
+    /// both sets. The other fields must either be equal or be specified by only one of the specs. An error is returned if the specs contradict each other
+    /// (e.g. they request different channels or disjoint exact versions).
+    pub fn intersect(&self, other: &MatchSpec) -> Result<MatchSpec, IntersectMatchSpecError> {
+        let name = intersect_eq("name", &self.name, &other.name, |name| {
+            name.as_source().to_owned()
+        })?;
+        let spec = NamelessMatchSpec::from(self.clone())
+            .intersect(&NamelessMatchSpec::from(other.clone()))?;
+        Ok(MatchSpec::from_nameless(spec, name))
+    }
+
+    /// Merges all the specs into a single spec that only matches the records that are matched by
+    /// every spec, see [`MatchSpec::intersect`]. Merging an empty iterator results in a spec that
+    /// matches everything.
+    pub fn merge(
+        specs: impl IntoIterator<Item = MatchSpec>,
+    ) -> Result<MatchSpec, IntersectMatchSpecError> {
+        let mut specs = specs.into_iter();
+        let Some(first) = specs.next() else {
+            return Ok(MatchSpec::default());
+        };
+        specs.try_fold(first, |merged, spec| merged.intersect(&spec))
+    }
+}
+
+impl NamelessMatchSpec {
+    /// Returns a spec that only matches the records that are matched by both `self` and `other`.
+    /// See [`MatchSpec::intersect`] for more information.
+    pub fn intersect(
+        &self,
+        other: &NamelessMatchSpec,
+    ) -> Result<NamelessMatchSpec, IntersectMatchSpecError> {
+        Ok(NamelessMatchSpec {
+            version: intersect_version(&self.version, &other.version)?,
+            build: intersect_build(&self.build, &other.build)?,
+            build_number: intersect_build_number(&self.build_number, &other.build_number)?,
+            file_name: intersect_eq(
+                "file name",
+                &self.file_name,
+                &other.file_name,
+                String::clone,
+            )?,
+            channel: intersect_channel(&self.channel, &other.channel)?,
+            subdir: intersect_eq("subdir", &self.subdir, &other.subdir, String::clone)?,
+            namespace: intersect_eq(
+                "namespace",
+                &self.namespace,
+                &other.namespace,
+                String::clone,
+            )?,
+            md5: intersect_eq("md5", &self.md5, &other.md5, |hash| format!("{hash:x}"))?,
+            sha256: intersect_eq("sha256", &self.sha256, &other.sha256, |hash| {
+                format!("{hash:x}")
+            })?,
+            features: intersect_features("features", &self.features, &other.features)?,
+            track_features: intersect_features(
+                "track_features",
+                &self.track_features,
+                &other.track_features,
+            )?,
+            url: intersect_eq("url", &self.url, &other.url, Url::to_string)?,
+            license: intersect_eq("license", &self.license, &other.license, String::clone)?,
+            license_family: intersect_eq(
+                "license_family",
+                &self.license_family,
+                &other.license_family,
+                String::clone,
+            )?,
+        })
+    }
+}
+
+/// Intersects two fields that can only be matched exactly.
+fn intersect_eq<T: Clone + PartialEq>(
+    field: &'static str,
+    first: &Option<T>,
+    second: &Option<T>,
+    display: impl Fn(&T) -> String,
+) -> Result<Option<T>, IntersectMatchSpecError> {
+    match (first, second) {
+        (Some(a), Some(b)) if a != b => Err(IntersectMatchSpecError::conflict(
+            field,
+            display(a),
+            display(b),
+        )),
+        (Some(value), _) | (None, Some(value)) => Ok(Some(value.clone())),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Intersects two version specs, see [`VersionSpec::intersection`]. Returns an error if no version
+/// can match both specs.
+fn intersect_version(
+    first: &Option<VersionSpec>,
+    second: &Option<VersionSpec>,
+) -> Result<Option<VersionSpec>, IntersectMatchSpecError> {
+    let (a, b) = match (first, second) {
+        (Some(a), Some(b)) => (a, b),
+        (Some(spec), None) | (None, Some(spec)) => return Ok(Some(spec.clone())),
+        (None, None) => return Ok(None),
+    };
+
+    if a == b {
+        return Ok(Some(a.clone()));
+    }
+
+    let intersection = a.intersection(b);
+    if intersection.is_empty() {
+        Err(IntersectMatchSpecError::conflict("version", a, b))
+    } else {
+        Ok(Some(intersection))
+    }
+}
+
+/// Intersects two build string matchers. Only exact build strings can be combined with other
+/// matchers.
+fn intersect_build(
+    first: &Option<StringMatcher>,
+    second: &Option<StringMatcher>,
+) -> Result<Option<StringMatcher>, IntersectMatchSpecError> {
+    let (a, b) = match (first, second) {
+        (Some(a), Some(b)) => (a, b),
+        (Some(matcher), None) | (None, Some(matcher)) => return Ok(Some(matcher.clone())),
+        (None, None) => return Ok(None),
+    };
+
+    if a == b {
+        return Ok(Some(a.clone()));
+    }
+
+    for (exact, other) in [(a, b), (b, a)] {
+        if let StringMatcher::Exact(build) = exact {
+            return if other.matches(build) {
+                Ok(Some(exact.clone()))
+            } else {
+                Err(IntersectMatchSpecError::conflict("build", a, b))
+            };
+        }
+    }
+
+    Err(IntersectMatchSpecError::unsupported("build", a, b))
+}
+
+/// Intersects two build number specs, see [`BuildNumberSpec::intersection`]. Returns an error if
+/// no build number can match both specs.
+fn intersect_build_number(
+    first: &Option<BuildNumberSpec>,
+    second: &Option<BuildNumberSpec>,
+) -> Result<Option<BuildNumberSpec>, IntersectMatchSpecError> {
+    let (a, b) = match (first, second) {
+        (Some(a), Some(b)) => (a, b),
+        (Some(spec), None) | (None, Some(spec)) => return Ok(Some(spec.clone())),
+        (None, None) => return Ok(None),
+    };
+
+    if a == b {
+        return Ok(Some(a.clone()));
+    }
+
+    a.intersection(b)
+        .map(Some)
+        .ok_or_else(|| IntersectMatchSpecError::conflict("build number", a, b))
+}
+
+/// Intersects two channels. A channel is either a url or a name which matches any channel url
+/// that ends with that name, so a name and a url that ends with that name intersect to the url.
+fn intersect_channel(
+    first: &Option<String>,
+    second: &Option<String>,
+) -> Result<Option<String>, IntersectMatchSpecError> {
+    // `*` matches every channel
+    let first = first.as_ref().filter(|channel| *channel != "*");
+    let second = second.as_ref().filter(|channel| *channel != "*");
+    let (a, b) = match (first, second) {
+        (Some(a), Some(b)) => (a, b),
+        (Some(channel), None) | (None, Some(channel)) => return Ok(Some(channel.clone())),
+        (None, None) => return Ok(None),
+    };
+
+    let (trimmed_a, trimmed_b) = (a.trim_end_matches('/'), b.trim_end_matches('/'));
+    if trimmed_a == trimmed_b {
+        return Ok(Some(a.clone()));
+    }
+
+    // Returns true if every channel that is matched by `specific` is also matched by `general`.
+    let is_subset = |specific: &str, general: &str| {
+        !general.contains("://")
+            && specific
+                .strip_suffix(general)
+                .is_some_and(|rest| rest.ends_with('/'))
+    };
+    if is_subset(trimmed_a, trimmed_b) {
+        Ok(Some(a.clone()))
+    } else if is_subset(trimmed_b, trimmed_a) {
+        Ok(Some(b.clone()))
+    } else {
+        Err(IntersectMatchSpecError::conflict("channel", a, b))
+    }
+}
+
+/// Intersects two sets of features. A record matches a set of features if it has all of them, or
+/// if it has no features at all when the set is empty.
+fn intersect_features(
+    field: &'static str,
+    first: &Option<BTreeSet<String>>,
+    second: &Option<BTreeSet<String>>,
+) -> Result<Option<BTreeSet<String>>, IntersectMatchSpecError> {
+    match (first, second) {
+        (Some(a), Some(b)) if a.is_empty() != b.is_empty() => Err(
+            IntersectMatchSpecError::conflict(field, a.iter().join(" "), b.iter().join(" ")),
+        ),
+        (Some(a), Some(b)) => Ok(Some(a.union(b).cloned().collect())),
+        (Some(features), None) | (None, Some(features)) => Ok(Some(features.clone())),
+        (None, None) => Ok(None),
+    }
+}
@@ -1,11 +1,15 @@
-use crate::{build_spec::BuildNumberSpec, PackageName, PackageRecord, VersionSpec};
+use crate::{build_spec::BuildNumberSpec, PackageName, PackageRecord, RepoDataRecord, VersionSpec};
+use itertools::Itertools;
 use rattler_digest::{serde::SerializableHash, Md5Hash, Sha256Hash};
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, skip_serializing_none, DisplayFromStr};
+use std::collections::BTreeSet;
 use std::fmt::{Debug, Display, Formatter};
 use std::hash::Hash;
+use url::Url;
 
 pub mod matcher;
+pub mod merge;
 pub mod parse;
 
 use matcher::StringMatcher;
@@ -64,37 +68,37 @@ use matcher::StringMatcher;
 /// # Examples:
 ///
 /// ```rust
-/// use rattler_conda_types::{MatchSpec, VersionSpec, StringMatcher, PackageName};
+/// use rattler_conda_types::{MatchSpec, VersionSpec, StringMatcher, PackageName, ParseStrictness};
 /// use std::str::FromStr;
 ///
-/// let spec = MatchSpec::from_str("foo 1.0 py27_0").unwrap();
+/// let spec = MatchSpec::from_str("foo 1.0 py27_0", ParseStrictness::Strict).unwrap();
 /// assert_eq!(spec.name, Some(PackageName::new_unchecked("foo")));
-/// assert_eq!(spec.version, Some(VersionSpec::from_str("1.0").unwrap()));
+/// assert_eq!(spec.version, Some(VersionSpec::from_str("1.0", ParseStrictness::Strict).unwrap()));
 /// assert_eq!(spec.build, Some(StringMatcher::from_str("py27_0").unwrap()));
 ///
-/// let spec = MatchSpec::from_str("foo=1.0=py27_0").unwrap();
+/// let spec = MatchSpec::from_str("foo=1.0=py27_0", ParseStrictness::Strict).unwrap();
 /// assert_eq!(spec.name, Some(PackageName::new_unchecked("foo")));
-/// assert_eq!(spec.version, Some(VersionSpec::from_str("==1.0").unwrap()));
+/// assert_eq!(spec.version, Some(VersionSpec::from_str("==1.0", ParseStrictness::Strict).unwrap()));
 /// assert_eq!(spec.build, Some(StringMatcher::from_str("py27_0").unwrap()));
 ///
-/// let spec = MatchSpec::from_str(r#"conda-forge::foo[version="1.0.*"]"#).unwrap();
+/// let spec = MatchSpec::from_str(r#"conda-forge::foo[version="1.0.*"]"#, ParseStrictness::Strict).unwrap();
 /// assert_eq!(spec.name, Some(PackageName::new_unchecked("foo")));
-/// assert_eq!(spec.version, Some(VersionSpec::from_str("1.0.*").unwrap()));
+/// assert_eq!(spec.version, Some(VersionSpec::from_str("1.0.*", ParseStrictness::Strict).unwrap()));
 /// assert_eq!(spec.channel, Some("conda-forge".to_string()));
 ///
-/// let spec = MatchSpec::from_str("conda-forge/linux-64::foo>=1.0").unwrap();
+/// let spec = MatchSpec::from_str("conda-forge/linux-64::foo>=1.0", ParseStrictness::Strict).unwrap();
 /// assert_eq!(spec.name, Some(PackageName::new_unchecked("foo")));
-/// assert_eq!(spec.version, Some(VersionSpec::from_str(">=1.0").unwrap()));
+/// assert_eq!(spec.version, Some(VersionSpec::from_str(">=1.0", ParseStrictness::Strict).unwrap()));
 /// assert_eq!(spec.channel, Some("conda-forge".to_string()));
 /// assert_eq!(spec.subdir, Some("linux-64".to_string()));
 ///
-/// let spec = MatchSpec::from_str("*/linux-64::foo>=1.0").unwrap();
+/// let spec = MatchSpec::from_str("*/linux-64::foo>=1.0", ParseStrictness::Strict).unwrap();
 /// assert_eq!(spec.name, Some(PackageName::new_unchecked("foo")));
-/// assert_eq!(spec.version, Some(VersionSpec::from_str(">=1.0").unwrap()));
+/// assert_eq!(spec.version, Some(VersionSpec::from_str(">=1.0", ParseStrictness::Strict).unwrap()));
 /// assert_eq!(spec.channel, Some("*".to_string()));
 /// assert_eq!(spec.subdir, Some("linux-64".to_string()));
 ///
-/// let spec = MatchSpec::from_str(r#"foo[build="py2*"]"#).unwrap();
+/// let spec = MatchSpec::from_str(r#"foo[build="py2*"]"#, ParseStrictness::Strict).unwrap();
 /// assert_eq!(spec.name, Some(PackageName::new_unchecked("foo")));
 /// assert_eq!(spec.build, Some(StringMatcher::from_str("py2*").unwrap()));
 /// ```
@@ -136,17 +140,33 @@ pub struct MatchSpec {
     /// The sha256 hash of the package
     #[serde_as(as = "Option<SerializableHash::<rattler_digest::Sha256>>")]
     pub sha256: Option<Sha256Hash>,
+    /// The legacy features of the package. Matches if the package has all of these features, an
+    /// empty set only matches packages without features.
+    pub features: Option<BTreeSet<String>>,
+    /// The features tracked by the package. Matches if the package tracks all of these features,
+    /// an empty set only matches packages that do not track any features.
+    pub track_features: Option<BTreeSet<String>>,
+    /// The url of the package
+    pub url: Option<Url>,
+    /// The license of the package, compared case-insensitively
+    pub license: Option<String>,
+    /// The license family of the package, compared case-insensitively
+    pub license_family: Option<String>,
 }
 
 impl Display for MatchSpec {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         if let Some(channel) = &self.channel {
-            // TODO: namespace
-            write!(f, "{}", channel)?;
+            match &self.subdir {
+                Some(subdir) => write!(f, "{}/{}", channel, subdir)?,
+                None => write!(f, "{}", channel)?,
+            }
         }
 
-        if let Some(subdir) = &self.subdir {
-            write!(f, "/{}", subdir)?;
+        if let Some(namespace) = &self.namespace {
+            write!(f, ":{}:", namespace)?;
+        } else if self.channel.is_some() {
+            write!(f, "::")?;
         }
 
         match &self.name {
@@ -154,12 +174,6 @@ impl Display for MatchSpec {
             None => write!(f, "*")?,
         }
 
-        if let Some(namespace) = &self.namespace {
-            write!(f, ":{}:", namespace)?;
-        } else if self.channel.is_some() || self.subdir.is_some() {
-            write!(f, "::")?;
-        }
-
         if let Some(version) = &self.version {
             write!(f, " {}", version)?;
         }
@@ -170,6 +184,30 @@ impl Display for MatchSpec {
 
         let mut keys = Vec::new();
 
+        // The subdir can only be written in front of the name together with the channel.
+        if let (None, Some(subdir)) = (&self.channel, &self.subdir) {
+            keys.push(format_bracket_key("subdir", subdir));
+        }
+
+        if let Some(build_number) = &self.build_number {
+            keys.push(format_bracket_key("build_number", build_number));
+        }
+
+        if let Some(track_features) = &self.track_features {
+            keys.push(format_bracket_key(
+                "track_features",
+                track_features.iter().join(" "),
+            ));
+        }
+
+        if let Some(features) = &self.features {
+            keys.push(format_bracket_key("features", features.iter().join(" ")));
+        }
+
+        if let Some(url) = &self.url {
+            keys.push(format_bracket_key("url", url));
+        }
+
         if let Some(md5) = &self.md5 {
             keys.push(format!("md5={md5:x}"));
         }
@@ -178,6 +216,18 @@ impl Display for MatchSpec {
             keys.push(format!("sha256={sha256:x}"));
         }
 
+        if let Some(license) = &self.license {
+            keys.push(format_bracket_key("license", license));
+        }
+
+        if let Some(license_family) = &self.license_family {
+            keys.push(format_bracket_key("license_family", license_family));
+        }
+
+        if let Some(file_name) = &self.file_name {
+            keys.push(format_bracket_key("fn", file_name));
+        }
+
         if !keys.is_empty() {
             write!(f, "[{}]", keys.join(", "))?;
         }
@@ -188,6 +238,10 @@ impl Display for MatchSpec {
 
 impl MatchSpec {
     /// Match a MatchSpec against a PackageRecord
+    ///
+    /// The `channel`, `url` and `fn` of the spec are not taken into account because this
+    /// information is not part of a [`PackageRecord`], use [`MatchSpec::matches_record`] to also
+    /// match these.
     pub fn matches(&self, record: &PackageRecord) -> bool {
         if let Some(name) = self.name.as_ref() {
             if name != &record.name {
@@ -213,6 +267,12 @@ impl MatchSpec {
             }
         }
 
+        if let Some(subdir) = self.subdir.as_ref() {
+            if subdir != &record.subdir {
+                return false;
+            }
+        }
+
         if let Some(md5_spec) = self.md5.as_ref() {
             if Some(md5_spec) != record.md5.as_ref() {
                 return false;
@@ -225,9 +285,42 @@ impl MatchSpec {
             }
         }
 
+        if let Some(features) = self.features.as_ref() {
+            if !feature_set_matches(features, record.feature_names()) {
+                return false;
+            }
+        }
+
+        if let Some(track_features) = self.track_features.as_ref() {
+            if !feature_set_matches(track_features, record.track_feature_names()) {
+                return false;
+            }
+        }
+
+        if !license_matches(self.license.as_deref(), record.license.as_deref())
+            || !license_matches(
+                self.license_family.as_deref(),
+                record.license_family.as_deref(),
+            )
+        {
+            return false;
+        }
+
         true
     }
 
+    /// Match a MatchSpec against a RepoDataRecord. Unlike [`MatchSpec::matches`] this also takes
+    /// the `channel`, `url` and `fn` of the spec into account.
+    pub fn matches_record(&self, record: &RepoDataRecord) -> bool {
+        self.matches(&record.package_record)
+            && source_matches(
+                self.channel.as_deref(),
+                self.url.as_ref(),
+                self.file_name.as_deref(),
+                record,
+            )
+    }
+
     /// Decomposes this instance into a [`NamelessMatchSpec`] and a name.
     pub fn into_nameless(self) -> (Option<PackageName>, NamelessMatchSpec) {
         (
@@ -242,6 +335,11 @@ impl MatchSpec {
                 namespace: self.namespace,
                 md5: self.md5,
                 sha256: self.sha256,
+                features: self.features,
+                track_features: self.track_features,
+                url: self.url,
+                license: self.license,
+                license_family: self.license_family,
             },
         )
     }
@@ -275,10 +373,26 @@ pub struct NamelessMatchSpec {
     /// The sha256 hash of the package
     #[serde_as(as = "Option<SerializableHash::<rattler_digest::Sha256>>")]
     pub sha256: Option<Sha256Hash>,
+    /// The legacy features of the package. Matches if the package has all of these features, an
+    /// empty set only matches packages without features.
+    pub features: Option<BTreeSet<String>>,
+    /// The features tracked by the package. Matches if the package tracks all of these features,
+    /// an empty set only matches packages that do not track any features.
+    pub track_features: Option<BTreeSet<String>>,
+    /// The url of the package
+    pub url: Option<Url>,
+    /// The license of the package, compared case-insensitively
+    pub license: Option<String>,
+    /// The license family of the package, compared case-insensitively
+    pub license_family: Option<String>,
 }
 
 impl NamelessMatchSpec {
     /// Match a MatchSpec against a PackageRecord
+    ///
+    /// The `channel`, `url` and `fn` of the spec are not taken into account because this
+    /// information is not part of a [`PackageRecord`], use [`NamelessMatchSpec::matches_record`]
+    /// to also match these.
     pub fn matches(&self, record: &PackageRecord) -> bool {
         if let Some(spec) = self.version.as_ref() {
             if !spec.matches(&record.version) {
@@ -292,6 +406,18 @@ impl NamelessMatchSpec {
             }
         }
 
+        if let Some(build_number) = self.build_number.as_ref() {
+            if !build_number.matches(&record.build_number) {
+                return false;
+            }
+        }
+
+        if let Some(subdir) = self.subdir.as_ref() {
+            if subdir != &record.subdir {
+                return false;
+            }
+        }
+
         if let Some(md5_spec) = self.md5.as_ref() {
             if Some(md5_spec) != record.md5.as_ref() {
                 return false;
@@ -304,8 +430,41 @@ impl NamelessMatchSpec {
             }
         }
 
+        if let Some(features) = self.features.as_ref() {
+            if !feature_set_matches(features, record.feature_names()) {
+                return false;
+            }
+        }
+
+        if let Some(track_features) = self.track_features.as_ref() {
+            if !feature_set_matches(track_features, record.track_feature_names()) {
+                return false;
+            }
+        }
+
+        if !license_matches(self.license.as_deref(), record.license.as_deref())
+            || !license_matches(
+                self.license_family.as_deref(),
+                record.license_family.as_deref(),
+            )
+        {
+            return false;
+        }
+
         true
     }
+
+    /// Match a MatchSpec against a RepoDataRecord. Unlike [`NamelessMatchSpec::matches`] this
+    /// also takes the `channel`, `url` and `fn` of the spec into account.
+    pub fn matches_record(&self, record: &RepoDataRecord) -> bool {
+        self.matches(&record.package_record)
+            && source_matches(
+                self.channel.as_deref(),
+                self.url.as_ref(),
+                self.file_name.as_deref(),
+                record,
+            )
+    }
 }
 
 impl Display for NamelessMatchSpec {
@@ -321,6 +480,33 @@ impl Display for NamelessMatchSpec {
 
         let mut keys = Vec::new();
 
+        if let Some(channel) = &self.channel {
+            keys.push(format_bracket_key("channel", channel));
+        }
+
+        if let Some(subdir) = &self.subdir {
+            keys.push(format_bracket_key("subdir", subdir));
+        }
+
+        if let Some(build_number) = &self.build_number {
+            keys.push(format_bracket_key("build_number", build_number));
+        }
+
+        if let Some(track_features) = &self.track_features {
+            keys.push(format_bracket_key(
+                "track_features",
+                track_features.iter().join(" "),
+            ));
+        }
+
+        if let Some(features) = &self.features {
+            keys.push(format_bracket_key("features", features.iter().join(" ")));
+        }
+
+        if let Some(url) = &self.url {
+            keys.push(format_bracket_key("url", url));
+        }
+
         if let Some(md5) = &self.md5 {
             keys.push(format!("md5={md5:x}"));
         }
@@ -329,6 +515,18 @@ impl Display for NamelessMatchSpec {
             keys.push(format!("sha256={sha256:x}"));
         }
 
+        if let Some(license) = &self.license {
+            keys.push(format_bracket_key("license", license));
+        }
+
+        if let Some(license_family) = &self.license_family {
+            keys.push(format_bracket_key("license_family", license_family));
+        }
+
+        if let Some(file_name) = &self.file_name {
+            keys.push(format_bracket_key("fn", file_name));
+        }
+
         if !keys.is_empty() {
             write!(f, "[{}]", keys.join(", "))?;
         }
@@ -349,6 +547,11 @@ impl From<MatchSpec> for NamelessMatchSpec {
             namespace: spec.namespace,
             md5: spec.md5,
             sha256: spec.sha256,
+            features: spec.features,
+            track_features: spec.track_features,
+            url: spec.url,
+            license: spec.license,
+            license_family: spec.license_family,
         }
     }
 }
@@ -367,41 +570,141 @@ impl MatchSpec {
             namespace: spec.namespace,
             md5: spec.md5,
             sha256: spec.sha256,
+            features: spec.features,
+            track_features: spec.track_features,
+            url: spec.url,
+            license: spec.license,
+            license_family: spec.license_family,
         }
     }
 }
 
+/// Returns true if a record has all the features of a spec. An empty set of features only matches
+/// records without features.
+fn feature_set_matches<'a>(
+    features: &BTreeSet<String>,
+    record_features: impl Iterator<Item = &'a str>,
+) -> bool {
+    let record_features = record_features.collect::<BTreeSet<_>>();
+    if features.is_empty() {
+        return record_features.is_empty();
+    }
+    features
+        .iter()
+        .all(|feature| record_features.contains(feature.as_str()))
+}
+
+/// Formats a `key=value` pair of the bracket section of a match spec. Like conda, the value is
+/// quoted if it contains a space, a comma or an equals sign or if it is empty.
+fn format_bracket_key(key: &str, value: impl Display) -> String {
+    let value = value.to_string();
+    if value.is_empty() || value.contains([' ', ',', '=']) {
+        format!("{key}='{value}'")
+    } else {
+        format!("{key}={value}")
+    }
+}
+
+/// Returns true if the license of a record matches the license of a spec. Licenses are compared
+/// case-insensitively.
+fn license_matches(spec: Option<&str>, record: Option<&str>) -> bool {
+    match (spec, record) {
+        (None, _) => true,
+        (Some(spec), Some(record)) => spec.eq_ignore_ascii_case(record),
+        (Some(_), None) => false,
+    }
+}
+
+/// Returns true if the channel, url and filename of a spec match the source of a record.
+///
+/// The channel of the spec can either be a url or the name of a channel, a name matches if the
+/// channel of the record ends with it.
+fn source_matches(
+    channel: Option<&str>,
+    url: Option<&Url>,
+    file_name: Option<&str>,
+    record: &RepoDataRecord,
+) -> bool {
+    if let Some(channel) = channel.filter(|channel| *channel != "*") {
+        let channel = channel.trim_end_matches('/');
+        let record_channel = record.channel.trim_end_matches('/');
+        let matches = record_channel == channel
+            || (!channel.contains("://")
+                && record_channel
+                    .strip_suffix(channel)
+                    .is_some_and(|rest| rest.ends_with('/')));
+        if !matches {
+            return false;
+        }
+    }
+
+    if let Some(url) = url {
+        if url != &record.url {
+            return false;
+        }
+    }
+
+    if let Some(file_name) = file_name {
+        if file_name != record.file_name {
+            return false;
+        }
+    }
+
+    true
+}
+
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
 
     use rattler_digest::{parse_digest_from_hex, Md5, Sha256};
 
-    use crate::{MatchSpec, NamelessMatchSpec, PackageName, PackageRecord, Version};
+    use url::Url;
+
+    use crate::{
+        IntersectMatchSpecError, MatchSpec, NamelessMatchSpec, PackageName, PackageRecord,
+        ParseStrictness, RepoDataRecord, Version, VersionSpec,
+    };
     use std::hash::{Hash, Hasher};
 
     #[test]
     fn test_matchspec_format_eq() {
-        let spec = MatchSpec::from_str("mamba[version==1.0, sha256=aaac4bc9c6916ecc0e33137431645b029ade22190c7144eead61446dcbcc6f97, md5=dede6252c964db3f3e41c7d30d07f6bf]").unwrap();
+        let spec = MatchSpec::from_str("mamba[version==1.0, sha256=aaac4bc9c6916ecc0e33137431645b029ade22190c7144eead61446dcbcc6f97, md5=dede6252c964db3f3e41c7d30d07f6bf]", ParseStrictness::Strict).unwrap();
         let spec_as_string = spec.to_string();
-        let rebuild_spec = MatchSpec::from_str(&spec_as_string).unwrap();
+        let rebuild_spec = MatchSpec::from_str(&spec_as_string, ParseStrictness::Strict).unwrap();
 
         assert_eq!(spec, rebuild_spec)
     }
 
+    #[test]
+    fn test_matchspec_format_channel() {
+        for str in [
+            "conda-forge::mamba >=1.0",
+            "conda-forge/linux-64::mamba >=1.0",
+        ] {
+            let spec = MatchSpec::from_str(str, ParseStrictness::Strict).unwrap();
+            assert_eq!(spec.to_string(), str);
+            assert_eq!(
+                MatchSpec::from_str(&spec.to_string(), ParseStrictness::Strict).unwrap(),
+                spec
+            );
+        }
+    }
+
     #[test]
     fn test_nameless_matchspec_format_eq() {
-        let spec = NamelessMatchSpec::from_str("*[version==1.0, sha256=aaac4bc9c6916ecc0e33137431645b029ade22190c7144eead61446dcbcc6f97, md5=dede6252c964db3f3e41c7d30d07f6bf]").unwrap();
+        let spec = NamelessMatchSpec::from_str("*[version==1.0, sha256=aaac4bc9c6916ecc0e33137431645b029ade22190c7144eead61446dcbcc6f97, md5=dede6252c964db3f3e41c7d30d07f6bf]", ParseStrictness::Strict).unwrap();
         let spec_as_string = spec.to_string();
-        let rebuild_spec = NamelessMatchSpec::from_str(&spec_as_string).unwrap();
+        let rebuild_spec =
+            NamelessMatchSpec::from_str(&spec_as_string, ParseStrictness::Strict).unwrap();
 
         assert_eq!(spec, rebuild_spec)
     }
 
     #[test]
     fn test_hash_match() {
-        let spec1 = MatchSpec::from_str("tensorflow 2.6.*").unwrap();
-        let spec2 = MatchSpec::from_str("tensorflow 2.6.*").unwrap();
+        let spec1 = MatchSpec::from_str("tensorflow 2.6.*", ParseStrictness::Strict).unwrap();
+        let spec2 = MatchSpec::from_str("tensorflow 2.6.*", ParseStrictness::Strict).unwrap();
         assert_eq!(spec1, spec2);
 
         let mut hasher = std::collections::hash_map::DefaultHasher::new();
@@ -413,8 +716,8 @@ mod tests {
 
     #[test]
     fn test_hash_no_match() {
-        let spec1 = MatchSpec::from_str("tensorflow 2.6.0.*").unwrap();
-        let spec2 = MatchSpec::from_str("tensorflow 2.6.*").unwrap();
+        let spec1 = MatchSpec::from_str("tensorflow 2.6.0.*", ParseStrictness::Strict).unwrap();
+        let spec2 = MatchSpec::from_str("tensorflow 2.6.*", ParseStrictness::Strict).unwrap();
         assert_ne!(spec1, spec2);
 
         let mut hasher = std::collections::hash_map::DefaultHasher::new();
@@ -440,24 +743,359 @@ mod tests {
             )
         };
 
-        let spec = MatchSpec::from_str("mamba[version==1.0, sha256=aaac4bc9c6916ecc0e33137431645b029ade22190c7144eead61446dcbcc6f97]").unwrap();
+        let spec = MatchSpec::from_str("mamba[version==1.0, sha256=aaac4bc9c6916ecc0e33137431645b029ade22190c7144eead61446dcbcc6f97]", ParseStrictness::Strict).unwrap();
         assert!(!spec.matches(&record));
 
-        let spec = MatchSpec::from_str("mamba[version==1.0, sha256=f44c4bc9c6916ecc0e33137431645b029ade22190c7144eead61446dcbcc6f97]").unwrap();
+        let spec = MatchSpec::from_str("mamba[version==1.0, sha256=f44c4bc9c6916ecc0e33137431645b029ade22190c7144eead61446dcbcc6f97]", ParseStrictness::Strict).unwrap();
+        assert!(spec.matches(&record));
+
+        let spec = MatchSpec::from_str(
+            "mamba[version==1.0, md5=aaaa6252c964db3f3e41c7d30d07f6bf]",
+            ParseStrictness::Strict,
+        )
+        .unwrap();
+        assert!(!spec.matches(&record));
+
+        let spec = MatchSpec::from_str(
+            "mamba[version==1.0, md5=dede6252c964db3f3e41c7d30d07f6bf]",
+            ParseStrictness::Strict,
+        )
+        .unwrap();
+        assert!(spec.matches(&record));
+
+        let spec = MatchSpec::from_str("mamba[version==1.0, md5=dede6252c964db3f3e41c7d30d07f6bf, sha256=f44c4bc9c6916ecc0e33137431645b029ade22190c7144eead61446dcbcc6f97]", ParseStrictness::Strict).unwrap();
         assert!(spec.matches(&record));
 
-        let spec = MatchSpec::from_str("mamba[version==1.0, md5=aaaa6252c964db3f3e41c7d30d07f6bf]")
-            .unwrap();
+        let spec = MatchSpec::from_str("mamba[version==1.0, md5=dede6252c964db3f3e41c7d30d07f6bf, sha256=aaac4bc9c6916ecc0e33137431645b029ade22190c7144eead61446dcbcc6f97]", ParseStrictness::Strict).unwrap();
         assert!(!spec.matches(&record));
+    }
+
+    #[test]
+    fn test_features_match() {
+        let record = PackageRecord {
+            features: Some(String::from("mkl vc9")),
+            track_features: vec![String::from("debug")],
+            ..PackageRecord::new(
+                PackageName::new_unchecked("numpy"),
+                Version::from_str("1.0").unwrap(),
+                String::from(""),
+            )
+        };
+
+        let spec =
+            MatchSpec::from_str("numpy[features='mkl vc9']", ParseStrictness::Strict).unwrap();
+        assert!(spec.matches(&record));
+
+        let spec =
+            MatchSpec::from_str("numpy[features=\"vc9,mkl\"]", ParseStrictness::Strict).unwrap();
+        assert!(spec.matches(&record));
 
-        let spec = MatchSpec::from_str("mamba[version==1.0, md5=dede6252c964db3f3e41c7d30d07f6bf]")
-            .unwrap();
+        let spec = MatchSpec::from_str("numpy[features=mkl]", ParseStrictness::Strict).unwrap();
         assert!(spec.matches(&record));
 
-        let spec = MatchSpec::from_str("mamba[version==1.0, md5=dede6252c964db3f3e41c7d30d07f6bf, sha256=f44c4bc9c6916ecc0e33137431645b029ade22190c7144eead61446dcbcc6f97]").unwrap();
+        let spec =
+            MatchSpec::from_str("numpy[features='mkl nomkl']", ParseStrictness::Strict).unwrap();
+        assert!(!spec.matches(&record));
+
+        let spec =
+            MatchSpec::from_str("numpy[track_features=debug]", ParseStrictness::Strict).unwrap();
         assert!(spec.matches(&record));
 
-        let spec = MatchSpec::from_str("mamba[version==1.0, md5=dede6252c964db3f3e41c7d30d07f6bf, sha256=aaac4bc9c6916ecc0e33137431645b029ade22190c7144eead61446dcbcc6f97]").unwrap();
+        let spec =
+            MatchSpec::from_str("numpy[track_features='']", ParseStrictness::Strict).unwrap();
         assert!(!spec.matches(&record));
+
+        let spec = NamelessMatchSpec::from_str(
+            "*[track_features=debug, features='mkl vc9']",
+            ParseStrictness::Strict,
+        )
+        .unwrap();
+        assert!(spec.matches(&record));
+    }
+
+    #[test]
+    fn test_features_format_eq() {
+        for spec in [
+            "numpy[features=mkl]",
+            "numpy[features='mkl vc9', track_features=debug]",
+            "numpy[track_features='']",
+        ] {
+            let spec = MatchSpec::from_str(spec, ParseStrictness::Strict).unwrap();
+            let rebuild_spec =
+                MatchSpec::from_str(&spec.to_string(), ParseStrictness::Strict).unwrap();
+            assert_eq!(spec, rebuild_spec);
+        }
+    }
+
+    #[test]
+    fn test_all_bracket_keys() {
+        let spec = MatchSpec::from_str(
+            "numpy[channel=conda-forge, subdir=linux-64, build_number='>=2', \
+            url=https://conda.anaconda.org/conda-forge/linux-64/numpy-1.26.0-py311_2.conda, \
+            md5=dede6252c964db3f3e41c7d30d07f6bf, license=BSD-3-Clause, license_family=BSD, \
+            fn=numpy-1.26.0-py311_2.conda]",
+            ParseStrictness::Strict,
+        )
+        .unwrap();
+        assert_eq!(spec.channel.as_deref(), Some("conda-forge"));
+        assert_eq!(spec.subdir.as_deref(), Some("linux-64"));
+        assert_eq!(spec.license.as_deref(), Some("BSD-3-Clause"));
+        assert_eq!(spec.license_family.as_deref(), Some("BSD"));
+        assert_eq!(
+            spec.url.as_ref().map(Url::as_str),
+            Some("https://conda.anaconda.org/conda-forge/linux-64/numpy-1.26.0-py311_2.conda")
+        );
+        assert_eq!(
+            spec.file_name.as_deref(),
+            Some("numpy-1.26.0-py311_2.conda")
+        );
+        assert!(spec.build_number.is_some());
+        assert!(spec.md5.is_some());
+
+        // The canonical representation contains all the keys and can be parsed again
+        assert_eq!(
+            spec.to_string(),
+            "conda-forge/linux-64::numpy[build_number='>=2', \
+            url=https://conda.anaconda.org/conda-forge/linux-64/numpy-1.26.0-py311_2.conda, \
+            md5=dede6252c964db3f3e41c7d30d07f6bf, license=BSD-3-Clause, license_family=BSD, \
+            fn=numpy-1.26.0-py311_2.conda]"
+        );
+        assert_eq!(
+            MatchSpec::from_str(&spec.to_string(), ParseStrictness::Strict).unwrap(),
+            spec
+        );
+
+        // A nameless spec writes the channel and subdir inside the brackets
+        let (_, nameless) = spec.into_nameless();
+        let nameless = NamelessMatchSpec {
+            version: Some(VersionSpec::Any),
+            ..nameless
+        };
+        assert_eq!(
+            NamelessMatchSpec::from_str(&nameless.to_string(), ParseStrictness::Strict).unwrap(),
+            nameless
+        );
+    }
+
+    #[test]
+    fn test_bracket_keys_take_precedence() {
+        let spec = MatchSpec::from_str(
+            "conda-forge/linux-64::numpy 1.0[subdir=noarch, version='>=2']",
+            ParseStrictness::Strict,
+        )
+        .unwrap();
+        assert_eq!(spec.channel.as_deref(), Some("conda-forge"));
+        assert_eq!(spec.subdir.as_deref(), Some("noarch"));
+        assert_eq!(
+            spec.version,
+            Some(VersionSpec::from_str(">=2", ParseStrictness::Strict).unwrap())
+        );
+
+        let spec = MatchSpec::from_str("numpy[subdir=linux-64]", ParseStrictness::Strict).unwrap();
+        assert_eq!(spec.to_string(), "numpy[subdir=linux-64]");
+    }
+
+    #[test]
+    fn test_license_and_subdir_match() {
+        let record = PackageRecord {
+            license: Some(String::from("BSD-3-Clause")),
+            license_family: Some(String::from("BSD")),
+            subdir: String::from("linux-64"),
+            build_number: 2,
+            ..PackageRecord::new(
+                PackageName::new_unchecked("numpy"),
+                Version::from_str("1.0").unwrap(),
+                String::from(""),
+            )
+        };
+
+        assert!(
+            MatchSpec::from_str("numpy[license=bsd-3-clause]", ParseStrictness::Strict)
+                .unwrap()
+                .matches(&record)
+        );
+        assert!(
+            !MatchSpec::from_str("numpy[license=MIT]", ParseStrictness::Strict)
+                .unwrap()
+                .matches(&record)
+        );
+        assert!(
+            !MatchSpec::from_str("numpy[license_family=GPL]", ParseStrictness::Strict)
+                .unwrap()
+                .matches(&record)
+        );
+        assert!(
+            !MatchSpec::from_str("numpy[subdir=noarch]", ParseStrictness::Strict)
+                .unwrap()
+                .matches(&record)
+        );
+        assert!(NamelessMatchSpec::from_str(
+            "*[subdir=linux-64, license_family=BSD]",
+            ParseStrictness::Strict
+        )
+        .unwrap()
+        .matches(&record));
+        assert!(
+            !NamelessMatchSpec::from_str("*[build_number='>=3']", ParseStrictness::Strict)
+                .unwrap()
+                .matches(&record)
+        );
+    }
+
+    #[test]
+    fn test_matches_record() {
+        let record = RepoDataRecord {
+            package_record: PackageRecord::new(
+                PackageName::new_unchecked("numpy"),
+                Version::from_str("1.0").unwrap(),
+                String::from("py311_0"),
+            ),
+            file_name: String::from("numpy-1.0-py311_0.conda"),
+            url: Url::parse(
+                "https://conda.anaconda.org/conda-forge/linux-64/numpy-1.0-py311_0.conda",
+            )
+            .unwrap(),
+            channel: String::from("https://conda.anaconda.org/conda-forge/"),
+        };
+
+        for spec in [
+            "conda-forge::numpy",
+            "numpy[channel='https://conda.anaconda.org/conda-forge']",
+            "*::numpy",
+            "numpy[fn=numpy-1.0-py311_0.conda]",
+            "numpy[url=https://conda.anaconda.org/conda-forge/linux-64/numpy-1.0-py311_0.conda]",
+        ] {
+            assert!(
+                MatchSpec::from_str(spec, ParseStrictness::Strict)
+                    .unwrap()
+                    .matches_record(&record),
+                "{spec}"
+            );
+        }
+
+        for spec in [
+            "bioconda::numpy",
+            "forge::numpy",
+            "numpy[fn=numpy-1.0-py311_0.tar.bz2]",
+            "numpy[url=https://conda.anaconda.org/bioconda/linux-64/numpy-1.0-py311_0.conda]",
+        ] {
+            assert!(
+                !MatchSpec::from_str(spec, ParseStrictness::Strict)
+                    .unwrap()
+                    .matches_record(&record),
+                "{spec}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_intersect() {
+        let intersect = |a: &str, b: &str| {
+            MatchSpec::from_str(a, ParseStrictness::Strict)
+                .unwrap()
+                .intersect(&MatchSpec::from_str(b, ParseStrictness::Strict).unwrap())
+                .map(|spec| spec.to_string())
+        };
+
+        assert_eq!(
+            intersect("numpy >=1.20", "conda-forge::numpy <2").unwrap(),
+            "conda-forge::numpy >=1.20,<2"
+        );
+        assert_eq!(
+            intersect("numpy >=1.20", "numpy ==1.26.0").unwrap(),
+            "numpy ==1.26.0"
+        );
+        assert_eq!(
+            intersect("numpy * py311*", "numpy[build_number='>=2']").unwrap(),
+            "numpy * py311*[build_number='>=2']"
+        );
+        assert_eq!(
+            intersect("numpy[build_number='>=2']", "numpy[build_number='<=2']").unwrap(),
+            "numpy[build_number='==2']"
+        );
+        assert_eq!(
+            intersect("numpy[build_number='>2']", "numpy[build_number='>=5']").unwrap(),
+            "numpy[build_number='>=5']"
+        );
+        assert_eq!(
+            intersect("numpy[build_number='>=2']", "numpy[build_number='<=5']").unwrap(),
+            "numpy[build_number='>=2,<=5']"
+        );
+        assert_eq!(
+            intersect("numpy 1.* py311_0", "numpy 1.* py311*").unwrap(),
+            "numpy 1.* py311_0"
+        );
+        assert_eq!(
+            intersect(
+                "conda-forge::numpy",
+                "numpy[channel='https://conda.anaconda.org/conda-forge']"
+            )
+            .unwrap(),
+            "https://conda.anaconda.org/conda-forge::numpy"
+        );
+        assert_eq!(
+            intersect("numpy[features=mkl]", "numpy[features=vc9]").unwrap(),
+            "numpy[features='mkl vc9']"
+        );
+
+        assert_eq!(
+            intersect("conda-forge::numpy", "bioconda::numpy").unwrap_err(),
+            IntersectMatchSpecError::Conflict {
+                field: "channel",
+                first: String::from("conda-forge"),
+                second: String::from("bioconda"),
+            }
+        );
+        assert!(matches!(
+            intersect("numpy ==1.0", "numpy ==2.0"),
+            Err(IntersectMatchSpecError::Conflict {
+                field: "version",
+                ..
+            })
+        ));
+        assert!(matches!(
+            intersect(
+                "numpy[channel='https://conda.anaconda.org/conda-forge']",
+                "numpy[channel='https://repo.prefix.dev/conda-forge']"
+            ),
+            Err(IntersectMatchSpecError::Conflict {
+                field: "channel",
+                ..
+            })
+        ));
+        assert!(matches!(
+            intersect("numpy[track_features='']", "numpy[track_features=debug]"),
+            Err(IntersectMatchSpecError::Conflict {
+                field: "track_features",
+                ..
+            })
+        ));
+        assert!(matches!(
+            intersect("numpy", "scipy"),
+            Err(IntersectMatchSpecError::Conflict { field: "name", .. })
+        ));
+        assert!(matches!(
+            intersect("numpy[build_number='>3']", "numpy[build_number='<2']"),
+            Err(IntersectMatchSpecError::Conflict {
+                field: "build number",
+                ..
+            })
+        ));
+        assert!(matches!(
+            intersect("numpy * py311*", "numpy * *_0"),
+            Err(IntersectMatchSpecError::Unsupported { field: "build", .. })
+        ));
+    }
+
+    #[test]
+    fn test_merge() {
+        let merged = MatchSpec::merge(
+            ["python >=3.8", "python <3.12", "conda-forge::python"]
+                .into_iter()
+                .map(|spec| MatchSpec::from_str(spec, ParseStrictness::Strict).unwrap()),
+        )
+        .unwrap();
+        assert_eq!(merged.to_string(), "conda-forge::python >=3.8,<3.12");
+        assert_eq!(MatchSpec::merge([]).unwrap(), MatchSpec::default());
     }
 }
@@ -2,15 +2,17 @@ use super::matcher::{StringMatcher, StringMatcherParseError};
 use super::MatchSpec;
 use crate::build_spec::{BuildNumberSpec, ParseBuildNumberSpecError};
 use crate::package::ArchiveType;
+use crate::repo_data::split_features;
 use crate::version_spec::version_tree::{recognize_constraint, recognize_version};
-use crate::version_spec::{is_start_of_version_constraint, ParseVersionSpecError};
+use crate::version_spec::{is_start_of_version_constraint, strip_quirks, ParseVersionSpecError};
 use crate::{
-    InvalidPackageNameError, NamelessMatchSpec, PackageName, ParseChannelError, VersionSpec,
+    InvalidPackageNameError, NamelessMatchSpec, PackageName, ParseChannelError, ParseStrictness,
+    VersionSpec,
 };
 use nom::branch::alt;
 use nom::bytes::complete::{tag, take_till1, take_until, take_while, take_while1};
 use nom::character::complete::{char, multispace0, one_of};
-use nom::combinator::{opt, recognize};
+use nom::combinator::{eof, not, opt, recognize};
 use nom::error::{context, ContextError, ParseError};
 use nom::multi::{separated_list0, separated_list1};
 use nom::sequence::{delimited, preceded, separated_pair, terminated};
@@ -83,7 +85,19 @@ impl FromStr for MatchSpec {
     type Err = ParseMatchSpecError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        parse(s)
+        MatchSpec::from_str(s, ParseStrictness::Lenient)
+    }
+}
+
+impl MatchSpec {
+    /// Parses a match spec. With [`ParseStrictness::Lenient`] known quirks found in existing
+    /// repodata are normalized, with [`ParseStrictness::Strict`] they result in an error. See
+    /// [`VersionSpec::from_str`].
+    pub fn from_str(
+        source: &str,
+        strictness: ParseStrictness,
+    ) -> Result<Self, ParseMatchSpecError> {
+        parse(source, strictness)
     }
 }
 
@@ -197,13 +211,14 @@ fn strip_brackets(input: &str) -> Result<(Cow<'_, str>, BracketVec), ParseMatchS
 fn parse_bracket_vec_into_components(
     bracket: BracketVec,
     match_spec: NamelessMatchSpec,
+    strictness: ParseStrictness,
 ) -> Result<NamelessMatchSpec, ParseMatchSpecError> {
     let mut match_spec = match_spec;
 
     for elem in bracket {
         let (key, value) = elem;
         match key {
-            "version" => match_spec.version = Some(VersionSpec::from_str(value)?),
+            "version" => match_spec.version = Some(VersionSpec::from_str(value, strictness)?),
             "build" => match_spec.build = Some(StringMatcher::from_str(value)?),
             "build_number" => match_spec.build_number = Some(BuildNumberSpec::from_str(value)?),
             "sha256" => {
@@ -219,6 +234,22 @@ fn parse_bracket_vec_into_components(
                 )
             }
             "fn" => match_spec.file_name = Some(value.to_string()),
+            "features" => {
+                match_spec.features = Some(split_features(value).map(ToOwned::to_owned).collect())
+            }
+            "track_features" => {
+                match_spec.track_features =
+                    Some(split_features(value).map(ToOwned::to_owned).collect())
+            }
+            "url" => {
+                match_spec.url = Some(
+                    Url::parse(value).map_err(|_| ParseMatchSpecError::InvalidPackagePathOrUrl)?,
+                )
+            }
+            "license" => match_spec.license = Some(value.to_string()),
+            "license_family" => match_spec.license_family = Some(value.to_string()),
+            "channel" => match_spec.channel = Some(value.to_string()),
+            "subdir" => match_spec.subdir = Some(value.to_string()),
             _ => Err(ParseMatchSpecError::InvalidBracketKey(key.to_owned()))?,
         }
     }
@@ -236,6 +267,15 @@ fn strip_package_name(input: &str) -> Result<(PackageName, &str), ParseMatchSpec
     }
 }
 
+/// Removes all whitespace from the version part of a match spec, e.g. `>=1.0 , <2`.
+fn strip_whitespace(version_str: &str) -> Cow<'_, str> {
+    if version_str.find(char::is_whitespace).is_some() {
+        Cow::Owned(version_str.replace(char::is_whitespace, ""))
+    } else {
+        Cow::Borrowed(version_str)
+    }
+}
+
 /// Splits a string into version and build constraints.
 fn split_version_and_build(input: &str) -> Result<(&str, Option<&str>), ParseMatchSpecError> {
     fn parse_version_constraint_or_group<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
@@ -250,9 +290,14 @@ fn split_version_and_build(input: &str) -> Result<(&str, Option<&str>), ParseMat
     fn parse_version_group<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
         input: &'a str,
     ) -> IResult<&'a str, &'a str, E> {
-        recognize(separated_list1(
-            whitespace_enclosed(one_of(",|")),
-            parse_version_constraint_or_group,
+        // A trailing operator is accepted here, whether it is allowed is decided when parsing the
+        // version spec.
+        recognize(terminated(
+            separated_list1(
+                terminated(whitespace_enclosed(one_of(",|")), not(eof)),
+                parse_version_constraint_or_group,
+            ),
+            opt(whitespace_enclosed(one_of(",|"))),
         ))(input)
     }
 
@@ -309,9 +354,17 @@ impl FromStr for NamelessMatchSpec {
     type Err = ParseMatchSpecError;
 
     fn from_str(input: &str) -> Result<Self, Self::Err> {
+        NamelessMatchSpec::from_str(input, ParseStrictness::Lenient)
+    }
+}
+
+impl NamelessMatchSpec {
+    /// Parses a match spec without a package name, see [`MatchSpec::from_str`].
+    pub fn from_str(input: &str, strictness: ParseStrictness) -> Result<Self, ParseMatchSpecError> {
         // Strip off brackets portion
         let (input, brackets) = strip_brackets(input.trim())?;
-        let mut match_spec = parse_bracket_vec_into_components(brackets, Default::default())?;
+        let mut match_spec =
+            parse_bracket_vec_into_components(brackets, Default::default(), strictness)?;
 
         // Get the version and optional build string
         let input = input.trim();
@@ -321,21 +374,16 @@ impl FromStr for NamelessMatchSpec {
             }
 
             let (version_str, build_str) = split_version_and_build(input)?;
+            let version_str = strip_whitespace(strip_quirks(version_str, strictness)?);
 
-            let version_str = if version_str.find(char::is_whitespace).is_some() {
-                Cow::Owned(version_str.replace(char::is_whitespace, ""))
-            } else {
-                Cow::Borrowed(version_str)
-            };
-
-            // Parse the version spec
-            match_spec.version = Some(
-                VersionSpec::from_str(version_str.as_ref())
-                    .map_err(ParseMatchSpecError::InvalidVersionSpec)?,
-            );
+            // Parse the version spec, a version given inside the brackets takes precedence
+            let version = VersionSpec::from_str(version_str.as_ref(), strictness)
+                .map_err(ParseMatchSpecError::InvalidVersionSpec)?;
+            match_spec.version = match_spec.version.or(Some(version));
 
             if let Some(build) = build_str {
-                match_spec.build = Some(StringMatcher::from_str(build)?);
+                let build = StringMatcher::from_str(build)?;
+                match_spec.build = match_spec.build.or(Some(build));
             }
         }
 
@@ -345,7 +393,7 @@ impl FromStr for NamelessMatchSpec {
 
 /// Parses a conda match spec.
 /// This is based on: https://github.com/conda/conda/blob/master/conda/models/match_spec.py#L569
-fn parse(input: &str) -> Result<MatchSpec, ParseMatchSpecError> {
+fn parse(input: &str, strictness: ParseStrictness) -> Result<MatchSpec, ParseMatchSpecError> {
     // Step 1. Strip '#' and `if` statement
     let (input, _comment) = strip_comment(input);
     let (input, _if_clause) = strip_if(input);
@@ -370,7 +418,8 @@ fn parse(input: &str) -> Result<MatchSpec, ParseMatchSpecError> {
 
     // 3. Strip off brackets portion
     let (input, brackets) = strip_brackets(input.trim())?;
-    let mut nameless_match_spec = parse_bracket_vec_into_components(brackets, Default::default())?;
+    let mut nameless_match_spec =
+        parse_bracket_vec_into_components(brackets, Default::default(), strictness)?;
 
     // 4. Strip off parens portion
     // TODO: What is this? I've never seen in
@@ -392,15 +441,21 @@ fn parse(input: &str) -> Result<MatchSpec, ParseMatchSpecError> {
     };
 
     nameless_match_spec.namespace = namespace
+        .filter(|namespace| !namespace.is_empty())
         .map(ToOwned::to_owned)
         .or(nameless_match_spec.namespace);
 
+    // Any key given inside the brackets overrides the value given outside the brackets.
     if let Some(channel_str) = channel_str {
-        if let Some((channel, subdir)) = channel_str.rsplit_once('/') {
+        let (channel, subdir) = match channel_str.rsplit_once('/') {
+            Some((channel, subdir)) => (channel, Some(subdir)),
+            None => (channel_str, None),
+        };
+        if nameless_match_spec.channel.is_none() {
             nameless_match_spec.channel = Some(channel.to_string());
-            nameless_match_spec.subdir = Some(subdir.to_string());
-        } else {
-            nameless_match_spec.channel = Some(channel_str.to_string());
+        }
+        if nameless_match_spec.subdir.is_none() {
+            nameless_match_spec.subdir = subdir.map(ToString::to_string);
         }
     }
 
@@ -416,12 +471,7 @@ fn parse(input: &str) -> Result<MatchSpec, ParseMatchSpecError> {
         }
 
         let (version_str, build_str) = split_version_and_build(input)?;
-
-        let version_str = if version_str.find(char::is_whitespace).is_some() {
-            Cow::Owned(version_str.replace(char::is_whitespace, ""))
-        } else {
-            Cow::Borrowed(version_str)
-        };
+        let version_str = strip_whitespace(strip_quirks(version_str, strictness)?);
 
         // Special case handling for version strings that start with `=`.
         let version_str = if let (Some(version_str), true) =
@@ -447,14 +497,14 @@ fn parse(input: &str) -> Result<MatchSpec, ParseMatchSpecError> {
             version_str
         };
 
-        // Parse the version spec
-        match_spec.version = Some(
-            VersionSpec::from_str(version_str.as_ref())
-                .map_err(ParseMatchSpecError::InvalidVersionSpec)?,
-        );
+        // Parse the version spec, a version given inside the brackets takes precedence
+        let version = VersionSpec::from_str(version_str.as_ref(), strictness)
+            .map_err(ParseMatchSpecError::InvalidVersionSpec)?;
+        match_spec.version = match_spec.version.or(Some(version));
 
         if let Some(build) = build_str {
-            match_spec.build = Some(StringMatcher::from_str(build)?);
+            let build = StringMatcher::from_str(build)?;
+            match_spec.build = match_spec.build.or(Some(build));
         }
     }
 
@@ -473,7 +523,7 @@ mod tests {
         split_version_and_build, strip_brackets, BracketVec, MatchSpec, ParseMatchSpecError,
     };
     use crate::match_spec::parse::parse_bracket_list;
-    use crate::{BuildNumberSpec, NamelessMatchSpec, VersionSpec};
+    use crate::{BuildNumberSpec, NamelessMatchSpec, ParseStrictness, VersionSpec};
     use smallvec::smallvec;
 
     #[test]
@@ -552,9 +602,9 @@ mod tests {
     #[test]
     fn test_nameless_match_spec() {
         insta::assert_yaml_snapshot!([
-            NamelessMatchSpec::from_str("3.8.* *_cpython").unwrap(),
-            NamelessMatchSpec::from_str("1.0 py27_0[fn=\"bla\"]").unwrap(),
-            NamelessMatchSpec::from_str("=1.0 py27_0").unwrap(),
+            NamelessMatchSpec::from_str("3.8.* *_cpython", ParseStrictness::Strict).unwrap(),
+            NamelessMatchSpec::from_str("1.0 py27_0[fn=\"bla\"]", ParseStrictness::Strict).unwrap(),
+            NamelessMatchSpec::from_str("=1.0 py27_0", ParseStrictness::Strict).unwrap(),
         ],
         @r###"
         ---
@@ -570,20 +620,37 @@ mod tests {
 
     #[test]
     fn test_match_spec_more() {
-        let spec = MatchSpec::from_str("conda-forge::foo[version=\"1.0.*\"]").unwrap();
+        let spec = MatchSpec::from_str(
+            "conda-forge::foo[version=\"1.0.*\"]",
+            ParseStrictness::Strict,
+        )
+        .unwrap();
         assert_eq!(spec.name, Some("foo".parse().unwrap()));
-        assert_eq!(spec.version, Some(VersionSpec::from_str("1.0.*").unwrap()));
+        assert_eq!(
+            spec.version,
+            Some(VersionSpec::from_str("1.0.*", ParseStrictness::Strict).unwrap())
+        );
         assert_eq!(spec.channel, Some("conda-forge".to_string()));
 
-        let spec = MatchSpec::from_str("conda-forge::foo[version=1.0.*]").unwrap();
+        let spec = MatchSpec::from_str("conda-forge::foo[version=1.0.*]", ParseStrictness::Strict)
+            .unwrap();
         assert_eq!(spec.name, Some("foo".parse().unwrap()));
-        assert_eq!(spec.version, Some(VersionSpec::from_str("1.0.*").unwrap()));
+        assert_eq!(
+            spec.version,
+            Some(VersionSpec::from_str("1.0.*", ParseStrictness::Strict).unwrap())
+        );
         assert_eq!(spec.channel, Some("conda-forge".to_string()));
 
-        let spec =
-            MatchSpec::from_str(r#"conda-forge::foo[version=1.0.*, build_number=">6"]"#).unwrap();
+        let spec = MatchSpec::from_str(
+            r#"conda-forge::foo[version=1.0.*, build_number=">6"]"#,
+            ParseStrictness::Strict,
+        )
+        .unwrap();
         assert_eq!(spec.name, Some("foo".parse().unwrap()));
-        assert_eq!(spec.version, Some(VersionSpec::from_str("1.0.*").unwrap()));
+        assert_eq!(
+            spec.version,
+            Some(VersionSpec::from_str("1.0.*", ParseStrictness::Strict).unwrap())
+        );
         assert_eq!(spec.channel, Some("conda-forge".to_string()));
         assert_eq!(
             spec.build_number,
@@ -593,13 +660,16 @@ mod tests {
 
     #[test]
     fn test_hash_spec() {
-        let spec = MatchSpec::from_str("conda-forge::foo[md5=1234567890]");
+        let spec = MatchSpec::from_str("conda-forge::foo[md5=1234567890]", ParseStrictness::Strict);
         assert_matches!(spec, Err(ParseMatchSpecError::InvalidHashDigest));
 
-        let spec = MatchSpec::from_str("conda-forge::foo[sha256=1234567890]");
+        let spec = MatchSpec::from_str(
+            "conda-forge::foo[sha256=1234567890]",
+            ParseStrictness::Strict,
+        );
         assert_matches!(spec, Err(ParseMatchSpecError::InvalidHashDigest));
 
-        let spec = MatchSpec::from_str("conda-forge::foo[sha256=315f5bdb76d078c43b8ac0064e4a0164612b1fce77c869345bfc94c75894edd3]").unwrap();
+        let spec = MatchSpec::from_str("conda-forge::foo[sha256=315f5bdb76d078c43b8ac0064e4a0164612b1fce77c869345bfc94c75894edd3]", ParseStrictness::Strict).unwrap();
         assert_eq!(
             spec.sha256,
             Some(
@@ -610,8 +680,11 @@ mod tests {
             )
         );
 
-        let spec =
-            MatchSpec::from_str("conda-forge::foo[md5=8b1a9953c4611296a827abf8c47804d7]").unwrap();
+        let spec = MatchSpec::from_str(
+            "conda-forge::foo[md5=8b1a9953c4611296a827abf8c47804d7]",
+            ParseStrictness::Strict,
+        )
+        .unwrap();
         assert_eq!(
             spec.md5,
             Some(parse_digest_from_hex::<Md5>("8b1a9953c4611296a827abf8c47804d7").unwrap())
@@ -695,7 +768,7 @@ mod tests {
             .map(|spec| {
                 (
                     spec,
-                    MatchSpec::from_str(spec)
+                    MatchSpec::from_str(spec, ParseStrictness::Strict)
                         .map(MatchSpecOrError::MatchSpec)
                         .unwrap_or_else(|err| MatchSpecOrError::Error {
                             error: err.to_string(),
@@ -705,4 +778,25 @@ mod tests {
             .collect();
         insta::assert_yaml_snapshot!("parsed matchspecs", evaluated);
     }
+
+    #[test]
+    fn test_strictness() {
+        for (lenient, strict) in [
+            ("python >=3.6 ,<4", "python >=3.6,<4"),
+            ("python >=3.6 , <4", "python >=3.6,<4"),
+            ("foo 1.0.*.*", "foo 1.0.*"),
+            ("numpy >=1.11.*", "numpy >=1.11"),
+            ("numpy >=1.11,", "numpy >=1.11"),
+        ] {
+            assert_eq!(
+                MatchSpec::from_str(lenient, ParseStrictness::Lenient).unwrap(),
+                MatchSpec::from_str(strict, ParseStrictness::Strict).unwrap(),
+                "{lenient}"
+            );
+            assert!(
+                MatchSpec::from_str(lenient, ParseStrictness::Strict).is_err(),
+                "{lenient}"
+            );
+        }
+    }
 }
@@ -0,0 +1,19 @@
+//! Defines [`ParseStrictness`] which controls how parsers deal with the quirks found in
+//! real-world conda metadata.
+
+/// Defines how strict a parser should behave.
+///
+/// Over the years conda has accepted a number of odd specs that are technically malformed but
+/// which can still be found in existing repodata, e.g. `python >=3.6 ,<4` or `numpy >=1.11.*`.
+/// When parsing existing metadata it is usually best to use [`ParseStrictness::Lenient`] while
+/// user input should be validated with [`ParseStrictness::Strict`].
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ParseStrictness {
+    /// Normalizes known conda quirks to their intended meaning and emits a warning for each of
+    /// them.
+    #[default]
+    Lenient,
+
+    /// Rejects anything that is not well-formed.
+    Strict,
+}
@@ -0,0 +1,474 @@
+//! Conversions between conda [`Version`]s and [`VersionSpec`]s and their
+//! [PEP 440](https://peps.python.org/pep-0440/) counterparts.
+//!
+//! The grammars of both formats overlap for most versions found in practice but they are not the
+//! same. PEP 440 normalizes versions (e.g. `1.0c1` becomes `1.0rc1`) while conda compares the
+//! literal components. Conversions in this module are lossless: a conversion either results in an
+//! equivalent version or it returns a [`Pep440ConversionError`].
+//!
+//! PEP 440 specifiers do not always compare versions the way conda does. E.g. `<2.0` does not
+//! match pre-releases of `2.0` and `==2.0` also matches local versions like `2.0+abc`. Specifiers
+//! are converted to a conda spec that matches the same versions or, if conda cannot express that,
+//! a [`Pep440ConversionError::NotEquivalentSpecifier`] is returned. The global pre-release
+//! filtering of PEP 440 (excluding pre-releases unless explicitly requested) is not part of a
+//! specifier and is therefore not applied.
+
+use crate::version_spec::{EqualityOperator, LogicalOperator, RangeOperator, StrictRangeOperator};
+use crate::{PackageRecord, ParseVersionError, StrictVersion, Version, VersionSpec};
+use itertools::Itertools;
+use pep440_rs::{Operator, PreRelease, VersionSpecifier, VersionSpecifiers};
+use std::fmt::Write;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// An error that can occur when converting between conda and PEP 440 versions.
+#[derive(Debug, Clone, Eq, PartialEq, Error)]
+pub enum Pep440ConversionError {
+    /// The conda version is not a valid PEP 440 version
+    #[error("'{version}' is not a valid PEP 440 version: {reason}")]
+    InvalidPep440Version {
+        /// The conda version
+        version: String,
+        /// The reason why the version could not be parsed
+        reason: String,
+    },
+
+    /// The PEP 440 version could not be parsed as a conda version.
+    #[error(transparent)]
+    InvalidCondaVersion(#[from] ParseVersionError),
+
+    /// The version is valid in both formats but conda and PEP 440 interpret it differently. E.g.
+    /// `1.0c1` is normalized to `1.0rc1` by PEP 440 but these versions are not equal in conda.
+    #[error("'{version}' is interpreted as '{pep440}' by PEP 440 which is not equivalent")]
+    NotEquivalent {
+        /// The conda version
+        version: String,
+        /// The version as interpreted by PEP 440
+        pep440: String,
+    },
+
+    /// The PEP 440 operator has no conda equivalent.
+    #[error("the PEP 440 operator '{0}' has no conda equivalent")]
+    UnsupportedOperator(String),
+
+    /// The PEP 440 specifier matches versions that no conda version spec can express. E.g. `>2.0`
+    /// excludes post-releases of `2.0` which conda orders after `2.0`.
+    #[error("the PEP 440 specifier '{0}' has no equivalent conda version spec")]
+    NotEquivalentSpecifier(String),
+
+    /// The version spec cannot be expressed as PEP 440 specifiers. E.g. PEP 440 has no notion of
+    /// a logical OR.
+    #[error("'{0}' cannot be expressed as PEP 440 version specifiers")]
+    UnsupportedVersionSpec(String),
+
+    /// The version is not allowed in combination with the operator by PEP 440.
+    #[error("invalid PEP 440 version specifier: {0}")]
+    InvalidSpecifier(String),
+}
+
+impl Version {
+    /// Converts a PEP 440 version to a conda version that is ordered the same way relative to
+    /// other converted versions.
+    ///
+    /// A development release of a final release (e.g. `1.0.dev1`) is converted to `1.0dev1`
+    /// because conda orders `1.0.dev1` after `1.0rc1` whereas PEP 440 orders it before any
+    /// pre-release.
+    pub fn from_pep440(version: &pep440_rs::Version) -> Result<Version, Pep440ConversionError> {
+        let mut result = String::new();
+        if version.epoch != 0 {
+            write!(result, "{}!", version.epoch).unwrap();
+        }
+        write!(result, "{}", version.release.iter().format(".")).unwrap();
+        if let Some((kind, number)) = &version.pre {
+            let kind = match kind {
+                PreRelease::Alpha => "a",
+                PreRelease::Beta => "b",
+                PreRelease::Rc => "rc",
+            };
+            write!(result, "{kind}{number}").unwrap();
+        }
+        if let Some(post) = version.post {
+            write!(result, ".post{post}").unwrap();
+        }
+        if let Some(dev) = version.dev {
+            if version.pre.is_none() && version.post.is_none() {
+                write!(result, "dev{dev}").unwrap();
+            } else {
+                write!(result, ".dev{dev}").unwrap();
+            }
+        }
+        if let Some(local) = &version.local {
+            write!(result, "+{}", local.iter().format(".")).unwrap();
+        }
+        Ok(Version::from_str(&result)?)
+    }
+
+    /// Converts this version to a PEP 440 version. Returns an error if the version cannot be
+    /// parsed by PEP 440 or if the PEP 440 interpretation of the version differs from the conda
+    /// interpretation.
+    pub fn to_pep440(&self) -> Result<pep440_rs::Version, Pep440ConversionError> {
+        let version = self.to_string();
+        let pep440 = pep440_rs::Version::from_str(&version).map_err(|reason| {
+            Pep440ConversionError::InvalidPep440Version {
+                version: version.clone(),
+                reason,
+            }
+        })?;
+        if &Version::from_pep440(&pep440)? != self {
+            return Err(Pep440ConversionError::NotEquivalent {
+                version,
+                pep440: pep440.to_string(),
+            });
+        }
+        Ok(pep440)
+    }
+}
+
+impl VersionSpec {
+    /// Converts PEP 440 version specifiers to a conda version spec. The specifiers are combined
+    /// with a logical AND, empty specifiers match any version.
+    pub fn from_pep440(
+        specifiers: &VersionSpecifiers,
+    ) -> Result<VersionSpec, Pep440ConversionError> {
+        let mut specs = specifiers
+            .iter()
+            .map(version_spec_from_pep440)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(match specs.len() {
+            0 => VersionSpec::Any,
+            1 => specs.pop().unwrap(),
+            _ => VersionSpec::Group(LogicalOperator::And, specs),
+        })
+    }
+
+    /// Converts this version spec to PEP 440 version specifiers. Only specs that consist of
+    /// constraints combined with a logical AND can be converted.
+    ///
+    /// The operators are mapped one-to-one so the specifiers are subject to the PEP 440 matching
+    /// rules, e.g. `<2.0` no longer matches `2.0rc1`.
+    pub fn to_pep440(&self) -> Result<VersionSpecifiers, Pep440ConversionError> {
+        let mut specifiers = Vec::new();
+        collect_pep440_specifiers(self, &mut specifiers)?;
+        Ok(specifiers.into_iter().collect())
+    }
+}
+
+/// Returns true if the version of the conda `python` record satisfies the `requires_python`
+/// specifiers of a PyPI package. The specifiers are evaluated with PEP 440 semantics.
+pub fn python_matches_requires_python(
+    python: &PackageRecord,
+    requires_python: &VersionSpecifiers,
+) -> Result<bool, Pep440ConversionError> {
+    let version = python.version.version().to_pep440()?;
+    Ok(requires_python.contains(&version))
+}
+
+/// Converts a single PEP 440 specifier to a conda version spec that matches the same versions.
+fn version_spec_from_pep440(
+    specifier: &VersionSpecifier,
+) -> Result<VersionSpec, Pep440ConversionError> {
+    let pep440 = specifier.version();
+    let not_equivalent = || Pep440ConversionError::NotEquivalentSpecifier(specifier.to_string());
+    let version = Version::from_pep440(pep440)?;
+    Ok(match specifier.operator() {
+        // A public version matches candidates regardless of their local version, e.g. `==2.0`
+        // matches `2.0+abc`. Conda only ignores the local version when comparing prefixes.
+        Operator::Equal | Operator::NotEqual if pep440.local.is_none() => {
+            return Err(not_equivalent())
+        }
+        Operator::Equal => VersionSpec::Exact(EqualityOperator::Equals, version),
+        Operator::NotEqual => VersionSpec::Exact(EqualityOperator::NotEquals, version),
+        // PEP 440 pads the release of the candidate with zeros, e.g. `==2.0.*` matches `2`. Conda
+        // only matches prefixes of the literal version.
+        Operator::EqualStar | Operator::NotEqualStar if pep440.release.last() == Some(&0) => {
+            return Err(not_equivalent())
+        }
+        Operator::EqualStar => {
+            VersionSpec::StrictRange(StrictRangeOperator::StartsWith, StrictVersion(version))
+        }
+        Operator::NotEqualStar => {
+            VersionSpec::StrictRange(StrictRangeOperator::NotStartsWith, StrictVersion(version))
+        }
+        // `~=V.N` means `>=V.N, ==V.*` so the prefix `V` is padded the same way, e.g. `~=1.0.0`
+        // matches `1`. A shorter candidate can only reach the lower bound if `N` is zero as well.
+        Operator::TildeEqual if matches!(pep440.release.as_slice(), [_, .., 0, 0]) => {
+            return Err(not_equivalent())
+        }
+        Operator::TildeEqual => {
+            VersionSpec::StrictRange(StrictRangeOperator::Compatible, StrictVersion(version))
+        }
+        // `<V` does not match pre-releases of `V` unless `V` itself is a pre-release. The lowest
+        // pre-release of a release is its first development release which allows expressing the
+        // bound in conda. Trailing zeros are stripped because conda orders `2rc1` before `2.0dev0`.
+        Operator::LessThan if pep440.any_prerelease() => {
+            VersionSpec::Range(RangeOperator::Less, version)
+        }
+        Operator::LessThan if pep440.is_post() => return Err(not_equivalent()),
+        Operator::LessThan => {
+            let mut lowest = pep440.clone();
+            let release_len = lowest
+                .release
+                .iter()
+                .rposition(|&segment| segment != 0)
+                .map_or(1, |idx| idx + 1);
+            lowest.release.truncate(release_len);
+            lowest.dev = Some(0);
+            VersionSpec::Range(RangeOperator::Less, Version::from_pep440(&lowest)?)
+        }
+        // `<=V` also matches local versions of `V` which conda orders after `V`.
+        Operator::LessThanEqual => return Err(not_equivalent()),
+        // `>V` does not match post-releases or local versions of `V` but there is no lowest
+        // release that follows them.
+        Operator::GreaterThan => return Err(not_equivalent()),
+        Operator::GreaterThanEqual => VersionSpec::Range(RangeOperator::GreaterEquals, version),
+        // Arbitrary equality compares the literal strings which conda cannot express.
+        operator @ Operator::ExactEqual => {
+            return Err(Pep440ConversionError::UnsupportedOperator(
+                operator.to_string(),
+            ))
+        }
+    })
+}
+
+/// Recursively collects the PEP 440 specifiers that together are equivalent to `spec`.
+fn collect_pep440_specifiers(
+    spec: &VersionSpec,
+    specifiers: &mut Vec<VersionSpecifier>,
+) -> Result<(), Pep440ConversionError> {
+    let (operator, version, star) = match spec {
+        VersionSpec::Any => return Ok(()),
+        VersionSpec::Group(LogicalOperator::And, specs) => {
+            return specs
+                .iter()
+                .try_for_each(|spec| collect_pep440_specifiers(spec, specifiers));
+        }
+        VersionSpec::Range(op, version) => {
+            let operator = match op {
+                RangeOperator::Greater => Operator::GreaterThan,
+                RangeOperator::GreaterEquals => Operator::GreaterThanEqual,
+                RangeOperator::Less => Operator::LessThan,
+                RangeOperator::LessEquals => Operator::LessThanEqual,
+            };
+            (operator, version, false)
+        }
+        VersionSpec::Exact(EqualityOperator::Equals, version) => (Operator::Equal, version, false),
+        VersionSpec::Exact(EqualityOperator::NotEquals, version) => {
+            (Operator::NotEqual, version, false)
+        }
+        VersionSpec::StrictRange(StrictRangeOperator::StartsWith, version) => {
+            (Operator::Equal, &version.0, true)
+        }
+        VersionSpec::StrictRange(StrictRangeOperator::NotStartsWith, version) => {
+            (Operator::NotEqual, &version.0, true)
+        }
+        VersionSpec::StrictRange(StrictRangeOperator::Compatible, version) => {
+            (Operator::TildeEqual, &version.0, false)
+        }
+        VersionSpec::StrictRange(StrictRangeOperator::NotCompatible, _)
+        | VersionSpec::Group(LogicalOperator::Or, _)
+        | VersionSpec::None => {
+            return Err(Pep440ConversionError::UnsupportedVersionSpec(
+                spec.to_string(),
+            ))
+        }
+    };
+
+    let specifier = VersionSpecifier::new(operator, version.to_pep440()?, star)
+        .map_err(Pep440ConversionError::InvalidSpecifier)?;
+    specifiers.push(specifier);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{python_matches_requires_python, Pep440ConversionError};
+    use crate::version_spec::StrictRangeOperator;
+    use crate::{PackageName, PackageRecord, ParseStrictness, StrictVersion, Version, VersionSpec};
+    use pep440_rs::VersionSpecifiers;
+    use rstest::rstest;
+    use std::str::FromStr;
+
+    #[rstest]
+    #[case("1.0", "1.0")]
+    #[case("1!2.0", "1!2.0")]
+    #[case("1.0a1", "1.0a1")]
+    #[case("1.0c1", "1.0rc1")]
+    #[case("1.0.post1", "1.0.post1")]
+    #[case("1.0-1", "1.0.post1")]
+    #[case("1.0.dev1", "1.0dev1")]
+    #[case("1.0a1.dev1", "1.0a1.dev1")]
+    #[case("1.0.post1.dev1", "1.0.post1.dev1")]
+    #[case("1.0+abc.5", "1.0+abc.5")]
+    fn test_version_from_pep440(#[case] pep440: &str, #[case] conda: &str) {
+        let pep440 = pep440_rs::Version::from_str(pep440).unwrap();
+        let version = Version::from_pep440(&pep440).unwrap();
+        assert_eq!(version.to_string(), conda);
+        assert_eq!(version.to_pep440().unwrap(), pep440);
+    }
+
+    #[test]
+    fn test_version_ordering() {
+        // PEP 440 ordering, see https://peps.python.org/pep-0440/#summary-of-permitted-suffixes-and-relative-ordering
+        let versions = [
+            "1.0.dev456",
+            "1.0a1",
+            "1.0a2.dev456",
+            "1.0a12.dev456",
+            "1.0a12",
+            "1.0b1.dev456",
+            "1.0b2",
+            "1.0b2.post345.dev456",
+            "1.0b2.post345",
+            "1.0rc1.dev456",
+            "1.0rc1",
+            "1.0",
+            "1.0.post456.dev34",
+            "1.0.post456",
+            "1.1.dev1",
+        ];
+        let converted = versions
+            .iter()
+            .map(|v| Version::from_pep440(&pep440_rs::Version::from_str(v).unwrap()).unwrap())
+            .collect::<Vec<_>>();
+        let mut sorted = converted.clone();
+        sorted.sort();
+        assert_eq!(converted, sorted);
+    }
+
+    #[rstest]
+    #[case("1.0.foo")]
+    #[case("1.0_1")]
+    #[case("1.0c1")]
+    #[case("1.0.dev1")]
+    fn test_version_to_pep440_error(#[case] version: &str) {
+        let version = Version::from_str(version).unwrap();
+        assert!(version.to_pep440().is_err(), "{version}");
+    }
+
+    #[rstest]
+    #[case(">=3.8", ">=3.8")]
+    #[case(">=3.8, <4.0.dev0", ">=3.8,<4.0dev0")]
+    #[case("==3.8.*", "3.8.*")]
+    #[case("!=3.8.*", "!=3.8.*")]
+    #[case("~=3.8", "~=3.8")]
+    #[case("~=0.5", "~=0.5")]
+    #[case("~=1.0", "~=1.0")]
+    #[case("~=2.0.1", "~=2.0.1")]
+    #[case("<1.0rc1", "<1.0rc1")]
+    #[case("==1.0+abc.5", "==1.0+abc.5")]
+    #[case("!=1.0.dev1+abc", "!=1.0dev1+abc")]
+    fn test_version_spec_from_pep440(#[case] pep440: &str, #[case] conda: &str) {
+        let specifiers = VersionSpecifiers::from_str(pep440).unwrap();
+        let spec = VersionSpec::from_pep440(&specifiers).unwrap();
+        assert_eq!(
+            spec,
+            VersionSpec::from_str(conda, ParseStrictness::Strict).unwrap()
+        );
+        assert_eq!(spec.to_pep440().unwrap(), specifiers);
+    }
+
+    #[rstest]
+    #[case("<4", "<4dev0")]
+    #[case("<4.0.0", "<4dev0")]
+    #[case("<1!0.0", "<1!0dev0")]
+    #[case("<3.10", "<3.10dev0")]
+    fn test_version_spec_from_pep440_less_than(#[case] pep440: &str, #[case] conda: &str) {
+        let specifiers = VersionSpecifiers::from_str(pep440).unwrap();
+        let spec = VersionSpec::from_pep440(&specifiers).unwrap();
+        assert_eq!(spec.to_string(), conda);
+    }
+
+    #[rstest]
+    #[case("3.9", true)]
+    #[case("3.10rc1", true)]
+    #[case("4.dev0", false)]
+    #[case("4rc1", false)]
+    #[case("4.0.0a1", false)]
+    #[case("4", false)]
+    fn test_less_than_excludes_pre_releases(#[case] version: &str, #[case] expected: bool) {
+        let specifiers = VersionSpecifiers::from_str("<4.0").unwrap();
+        let spec = VersionSpec::from_pep440(&specifiers).unwrap();
+        let version = pep440_rs::Version::from_str(version).unwrap();
+        assert_eq!(specifiers.contains(&version), expected);
+        assert_eq!(
+            spec.matches(&Version::from_pep440(&version).unwrap()),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_any_version_spec() {
+        let specifiers = VersionSpec::Any.to_pep440().unwrap();
+        assert!(specifiers.is_empty());
+        assert_eq!(
+            VersionSpec::from_pep440(&specifiers).unwrap(),
+            VersionSpec::Any
+        );
+    }
+
+    #[test]
+    fn test_version_spec_pep440_errors() {
+        let specifiers = VersionSpecifiers::from_str("===1.0").unwrap();
+        assert_eq!(
+            VersionSpec::from_pep440(&specifiers),
+            Err(Pep440ConversionError::UnsupportedOperator(String::from(
+                "==="
+            )))
+        );
+
+        for specifier in [
+            "==1.0",
+            "!=1.0",
+            "<=1.0",
+            ">1.0",
+            ">1.0rc1",
+            "<1.0.post1",
+            "==1.0.*",
+            "!=2.0.*",
+            "~=1.0.0",
+            "~=1.5.0.0",
+        ] {
+            let specifiers = VersionSpecifiers::from_str(specifier).unwrap();
+            assert!(
+                matches!(
+                    VersionSpec::from_pep440(&specifiers),
+                    Err(Pep440ConversionError::NotEquivalentSpecifier(_))
+                ),
+                "{specifier}"
+            );
+        }
+
+        for spec in ["3.8.*|>=3.10", "1.0c1"] {
+            let spec = VersionSpec::from_str(spec, ParseStrictness::Strict).unwrap();
+            assert!(spec.to_pep440().is_err(), "{spec}");
+        }
+
+        let not_compatible = VersionSpec::StrictRange(
+            StrictRangeOperator::NotCompatible,
+            StrictVersion(Version::from_str("3.8").unwrap()),
+        );
+        assert!(not_compatible.to_pep440().is_err());
+    }
+
+    #[rstest]
+    #[case("3.11.5", ">=3.8", true)]
+    #[case("3.11.5", ">=3.8,<3.11", false)]
+    #[case("3.7.12", "!=3.7.*", false)]
+    #[case("3.12.0rc3", ">=3.12", false)]
+    fn test_python_matches_requires_python(
+        #[case] python: &str,
+        #[case] requires_python: &str,
+        #[case] expected: bool,
+    ) {
+        let record = PackageRecord::new(
+            PackageName::new_unchecked("python"),
+            Version::from_str(python).unwrap(),
+            String::from("0"),
+        );
+        let requires_python = VersionSpecifiers::from_str(requires_python).unwrap();
+        assert_eq!(
+            python_matches_requires_python(&record, &requires_python).unwrap(),
+            expected
+        );
+    }
+}
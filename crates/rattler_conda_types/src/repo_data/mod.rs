@@ -188,7 +188,7 @@ impl RepoData {
     /// data.
     pub fn into_repo_data_records(self, channel: &Channel) -> Vec<RepoDataRecord> {
         let mut records = Vec::with_capacity(self.packages.len() + self.conda_packages.len());
-        let channel_name = channel.canonical_name();
+        let channel_name = channel.base_url().to_string();
         let base_url = self.base_url().map(ToOwned::to_owned);
 
         // Determine the base_url of the channel
@@ -291,6 +291,27 @@ impl PackageRecord {
     pub fn sort_topologically<T: AsRef<PackageRecord> + Clone>(records: Vec<T>) -> Vec<T> {
         topological_sort::sort_topologically(records)
     }
+
+    /// Returns the individual features of the package. The `features` field contains the features
+    /// as a single string separated by commas and/or whitespace.
+    pub fn feature_names(&self) -> impl Iterator<Item = &str> + '_ {
+        split_features(self.features.as_deref().unwrap_or_default())
+    }
+
+    /// Returns the individual features tracked by the package. Every entry of the
+    /// `track_features` field may contain multiple features separated by commas and/or whitespace.
+    pub fn track_feature_names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.track_features
+            .iter()
+            .flat_map(|features| split_features(features))
+    }
+}
+
+/// Splits a string of features separated by commas and/or whitespace into the individual features.
+pub(crate) fn split_features(features: &str) -> impl Iterator<Item = &str> + '_ {
+    features
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|feature| !feature.is_empty())
 }
 
 /// An error that can occur when parsing a platform from a string.
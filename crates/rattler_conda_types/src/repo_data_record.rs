@@ -30,3 +30,9 @@ impl AsRef<PackageRecord> for RepoDataRecord {
         &self.package_record
     }
 }
+
+impl AsRef<RepoDataRecord> for RepoDataRecord {
+    fn as_ref(&self) -> &RepoDataRecord {
+        self
+    }
+}
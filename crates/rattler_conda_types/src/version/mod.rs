@@ -508,28 +508,48 @@ impl Version {
     }
 }
 
-/// Returns true if the specified segments are considered to start with the other segments.
+/// Returns true if the specified segments are considered to start with the other segments. Like
+/// in conda, all but the last of the other segments have to be equal to the corresponding segments
+/// (ignoring trailing zeros) and the last of the other segments has to be a prefix of the
+/// corresponding segment. E.g. `1.2.3` starts with `1.2` but `1.2a` does not start with `1.2.3`.
 fn segments_starts_with<
     'a,
     'b,
     A: Iterator<Item = SegmentIter<'a>> + 'a,
     B: Iterator<Item = SegmentIter<'b>> + 'b,
 >(
-    a: A,
+    mut a: A,
     b: B,
 ) -> bool {
-    for ranges in a.zip_longest(b) {
-        let (left, right) = match ranges {
-            EitherOrBoth::Both(left, right) => (left, right),
-            EitherOrBoth::Left(_) => return true,
-            EitherOrBoth::Right(_) => return false,
-        };
-        for values in left.components().zip_longest(right.components()) {
-            if !match values {
-                EitherOrBoth::Both(a, b) => a == b,
-                EitherOrBoth::Left(_) => return true,
-                EitherOrBoth::Right(_) => return false,
-            } {
+    let default = Component::default();
+    let mut b = b.peekable();
+    while let Some(right) = b.next() {
+        let left = a.next();
+        if b.peek().is_none() {
+            let Some(left) = left else {
+                return false;
+            };
+            return left
+                .components()
+                .zip_longest(right.components())
+                .all(|values| match values {
+                    EitherOrBoth::Both(a, b) => a == b,
+                    EitherOrBoth::Left(_) => true,
+                    EitherOrBoth::Right(_) => false,
+                });
+        }
+
+        for values in left
+            .iter()
+            .flat_map(SegmentIter::components)
+            .zip_longest(right.components())
+        {
+            let (left_component, right_component) = match values {
+                EitherOrBoth::Left(l) => (l, &default),
+                EitherOrBoth::Right(r) => (&default, r),
+                EitherOrBoth::Both(l, r) => (l, r),
+            };
+            if left_component != right_component {
                 return false;
             }
         }
@@ -1219,9 +1239,19 @@ mod test {
 
     #[test]
     fn starts_with() {
-        assert!(Version::from_str("1.2.3")
-            .unwrap()
-            .starts_with(&Version::from_str("1.2").unwrap()));
+        let starts_with = |a: &str, b: &str| {
+            Version::from_str(a)
+                .unwrap()
+                .starts_with(&Version::from_str(b).unwrap())
+        };
+        assert!(starts_with("1.2.3", "1.2"));
+        assert!(starts_with("1.2.0", "1.2.0"));
+        assert!(starts_with("1.2a", "1.2"));
+        assert!(starts_with("1.2.3", "1.2.3"));
+        assert!(!starts_with("1", "1.0"));
+        assert!(!starts_with("1.2a", "1.2.3"));
+        assert!(!starts_with("1.2a.3", "1.2.3"));
+        assert!(!starts_with("1.3", "1.2"));
     }
 
     fn get_hash(spec: &impl Hash) -> u64 {
@@ -2,7 +2,7 @@ use super::ParseConstraintError;
 use super::RangeOperator;
 use crate::version_spec::parse::constraint_parser;
 use crate::version_spec::{EqualityOperator, StrictRangeOperator};
-use crate::Version;
+use crate::{ParseStrictness, Version};
 use std::str::FromStr;
 
 /// A single version constraint (e.g. `>3.4.5` or `1.2.*`)
@@ -31,7 +31,14 @@ impl FromStr for Constraint {
     type Err = ParseConstraintError;
 
     fn from_str(input: &str) -> Result<Self, Self::Err> {
-        match constraint_parser(input) {
+        Constraint::parse(input, ParseStrictness::Lenient)
+    }
+}
+
+impl Constraint {
+    /// Parses a single constraint with the given strictness.
+    pub fn parse(input: &str, strictness: ParseStrictness) -> Result<Self, ParseConstraintError> {
+        match constraint_parser(input, strictness) {
             Ok(("", version)) => Ok(version),
             Ok((_, _)) => Err(ParseConstraintError::ExpectedEof),
             Err(nom::Err::Failure(e) | nom::Err::Error(e)) => Err(e),
@@ -44,8 +51,10 @@ impl FromStr for Constraint {
 mod test {
     use super::Constraint;
     use crate::version_spec::constraint::ParseConstraintError;
-    use crate::version_spec::{EqualityOperator, RangeOperator, StrictRangeOperator};
-    use crate::Version;
+    use crate::version_spec::{
+        EqualityOperator, RangeOperator, StrictRangeOperator, VersionOperators,
+    };
+    use crate::{ParseStrictness, Version};
     use std::str::FromStr;
 
     #[test]
@@ -213,6 +222,38 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_glob_op_strict() {
+        assert_eq!(
+            Constraint::parse(">=1.2.*", ParseStrictness::Strict),
+            Err(ParseConstraintError::GlobVersionIncompatibleWithOperator(
+                RangeOperator::GreaterEquals
+            ))
+        );
+        assert_eq!(
+            Constraint::parse("==1.2.*", ParseStrictness::Strict),
+            Err(
+                ParseConstraintError::GlobVersionIncompatibleWithVersionOperator(
+                    VersionOperators::Exact(EqualityOperator::Equals)
+                )
+            )
+        );
+        assert_eq!(
+            Constraint::parse("!=1.2.*", ParseStrictness::Strict),
+            Ok(Constraint::StrictComparison(
+                StrictRangeOperator::NotStartsWith,
+                Version::from_str("1.2").unwrap()
+            ))
+        );
+        assert_eq!(
+            Constraint::parse("=1.2.*", ParseStrictness::Strict),
+            Ok(Constraint::StrictComparison(
+                StrictRangeOperator::StartsWith,
+                Version::from_str("1.2").unwrap()
+            ))
+        );
+    }
+
     #[test]
     fn test_starts_with() {
         assert_eq!(
@@ -223,9 +264,16 @@ mod test {
             ))
         );
         assert_eq!(
-            Constraint::from_str("1.2.*.*"),
+            Constraint::parse("1.2.*.*", ParseStrictness::Strict),
             Err(ParseConstraintError::RegexConstraintsNotSupported)
         );
+        assert_eq!(
+            Constraint::parse("1.2.*.*", ParseStrictness::Lenient),
+            Ok(Constraint::StrictComparison(
+                StrictRangeOperator::StartsWith,
+                Version::from_str("1.2").unwrap()
+            ))
+        );
     }
 
     #[test]
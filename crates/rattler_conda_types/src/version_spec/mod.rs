@@ -3,10 +3,12 @@
 
 mod constraint;
 pub(crate) mod parse;
+pub mod range;
+mod set;
 pub(crate) mod version_tree;
 
 use crate::version_spec::version_tree::ParseVersionTreeError;
-use crate::{ParseVersionError, Version};
+use crate::{ParseStrictness, ParseVersionError, Version};
 use constraint::Constraint;
 use serde::{Deserialize, Serialize, Serializer};
 use std::convert::TryFrom;
@@ -141,6 +143,12 @@ pub enum ParseVersionSpecError {
 
     #[error("invalid version constraint: {0}")]
     InvalidConstraint(#[source] ParseConstraintError),
+
+    #[error("the version spec ends with a '{0}' operator")]
+    TrailingOperator(LogicalOperator),
+
+    #[error("unexpected whitespace before a '{0}' operator")]
+    UnexpectedWhitespace(LogicalOperator),
 }
 
 impl From<Constraint> for VersionSpec {
@@ -160,25 +168,88 @@ impl FromStr for VersionSpec {
     type Err = ParseVersionSpecError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        VersionSpec::from_str(s, ParseStrictness::Lenient)
+    }
+}
+
+impl VersionSpec {
+    /// Parses a version spec. With [`ParseStrictness::Lenient`] known quirks found in existing
+    /// repodata (e.g. `>=1.11.*` or a trailing `,`) are normalized, with
+    /// [`ParseStrictness::Strict`] they result in an error.
+    pub fn from_str(
+        source: &str,
+        strictness: ParseStrictness,
+    ) -> Result<Self, ParseVersionSpecError> {
+        let source = strip_quirks(source, strictness)?;
         let version_tree =
-            VersionTree::try_from(s).map_err(ParseVersionSpecError::InvalidVersionTree)?;
+            VersionTree::try_from(source).map_err(ParseVersionSpecError::InvalidVersionTree)?;
 
-        fn parse_tree(tree: VersionTree) -> Result<VersionSpec, ParseVersionSpecError> {
+        fn parse_tree(
+            tree: VersionTree,
+            strictness: ParseStrictness,
+        ) -> Result<VersionSpec, ParseVersionSpecError> {
             match tree {
-                VersionTree::Term(str) => Ok(Constraint::from_str(str)
+                VersionTree::Term(str) => Ok(Constraint::parse(str, strictness)
                     .map_err(ParseVersionSpecError::InvalidConstraint)?
                     .into()),
                 VersionTree::Group(op, groups) => Ok(VersionSpec::Group(
                     op,
                     groups
                         .into_iter()
-                        .map(parse_tree)
+                        .map(|group| parse_tree(group, strictness))
                         .collect::<Result<_, ParseVersionSpecError>>()?,
                 )),
             }
         }
 
-        parse_tree(version_tree)
+        parse_tree(version_tree, strictness)
+    }
+}
+
+/// Checks the version spec for known quirks that conda accepts, like a trailing `,` or whitespace
+/// in front of a `,`. In lenient mode the quirks are removed, in strict mode an error is returned.
+pub(crate) fn strip_quirks(
+    source: &str,
+    strictness: ParseStrictness,
+) -> Result<&str, ParseVersionSpecError> {
+    let mut source = source.trim();
+
+    // Trailing logical operators, e.g. `>=1.0,`
+    while let Some(op) = source.chars().last().and_then(logical_operator) {
+        if strictness == ParseStrictness::Strict {
+            return Err(ParseVersionSpecError::TrailingOperator(op));
+        }
+        tracing::warn!("The version spec '{source}' ends with a '{op}' operator, it is ignored.");
+        source = source[..source.len() - 1].trim_end();
+    }
+
+    // Whitespace in front of a logical operator, e.g. `>=3.6 ,<4`
+    let op_after_whitespace = source
+        .char_indices()
+        .filter(|(_, c)| c.is_whitespace())
+        .find_map(|(idx, _)| {
+            source[idx..]
+                .trim_start()
+                .chars()
+                .next()
+                .and_then(logical_operator)
+        });
+    if let Some(op) = op_after_whitespace {
+        if strictness == ParseStrictness::Strict {
+            return Err(ParseVersionSpecError::UnexpectedWhitespace(op));
+        }
+        tracing::warn!("The version spec '{source}' contains whitespace before a '{op}' operator.");
+    }
+
+    Ok(source)
+}
+
+/// Returns the logical operator represented by the character.
+fn logical_operator(c: char) -> Option<LogicalOperator> {
+    match c {
+        ',' => Some(LogicalOperator::And),
+        '|' => Some(LogicalOperator::Or),
+        _ => None,
     }
 }
 
@@ -213,6 +284,16 @@ impl Display for EqualityOperator {
     }
 }
 
+impl Display for VersionOperators {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VersionOperators::Range(op) => write!(f, "{op}"),
+            VersionOperators::StrictRange(op) => write!(f, "{op}"),
+            VersionOperators::Exact(op) => write!(f, "{op}"),
+        }
+    }
+}
+
 impl Display for LogicalOperator {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -224,7 +305,11 @@ impl Display for LogicalOperator {
 
 impl Display for VersionSpec {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        fn write(spec: &VersionSpec, f: &mut Formatter<'_>, part_of_or: bool) -> std::fmt::Result {
+        fn write(
+            spec: &VersionSpec,
+            f: &mut Formatter<'_>,
+            parent: Option<LogicalOperator>,
+        ) -> std::fmt::Result {
             match spec {
                 VersionSpec::Any => write!(f, "*"),
                 VersionSpec::StrictRange(op, version) => match op {
@@ -239,7 +324,7 @@ impl Display for VersionSpec {
                     write!(f, "{}{}", op, version)
                 }
                 VersionSpec::Group(op, group) => {
-                    let requires_parenthesis = *op == LogicalOperator::And && part_of_or;
+                    let requires_parenthesis = parent.is_some_and(|parent| parent != *op);
                     if requires_parenthesis {
                         write!(f, "(")?;
                     }
@@ -247,7 +332,7 @@ impl Display for VersionSpec {
                         if i > 0 {
                             write!(f, "{}", op)?;
                         }
-                        write(spec, f, *op == LogicalOperator::Or)?;
+                        write(spec, f, Some(*op))?;
                     }
                     if requires_parenthesis {
                         write!(f, ")")?;
@@ -258,7 +343,7 @@ impl Display for VersionSpec {
             }
         }
 
-        write(self, f, false)
+        write(self, f, None)
     }
 }
 
@@ -308,20 +393,20 @@ impl VersionSpec {
 #[cfg(test)]
 mod tests {
     use crate::version_spec::{EqualityOperator, LogicalOperator, RangeOperator};
-    use crate::{Version, VersionSpec};
+    use crate::{ParseStrictness, Version, VersionSpec};
     use std::str::FromStr;
 
     #[test]
     fn test_simple() {
         assert_eq!(
-            VersionSpec::from_str("1.2.3"),
+            VersionSpec::from_str("1.2.3", ParseStrictness::Strict),
             Ok(VersionSpec::Exact(
                 EqualityOperator::Equals,
                 Version::from_str("1.2.3").unwrap()
             ))
         );
         assert_eq!(
-            VersionSpec::from_str(">=1.2.3"),
+            VersionSpec::from_str(">=1.2.3", ParseStrictness::Strict),
             Ok(VersionSpec::Range(
                 RangeOperator::GreaterEquals,
                 Version::from_str("1.2.3").unwrap()
@@ -332,7 +417,7 @@ mod tests {
     #[test]
     fn test_group() {
         assert_eq!(
-            VersionSpec::from_str(">=1.2.3,<2.0.0"),
+            VersionSpec::from_str(">=1.2.3,<2.0.0", ParseStrictness::Strict),
             Ok(VersionSpec::Group(
                 LogicalOperator::And,
                 vec![
@@ -345,7 +430,7 @@ mod tests {
             ))
         );
         assert_eq!(
-            VersionSpec::from_str(">=1.2.3|<1.0.0"),
+            VersionSpec::from_str(">=1.2.3|<1.0.0", ParseStrictness::Strict),
             Ok(VersionSpec::Group(
                 LogicalOperator::Or,
                 vec![
@@ -358,7 +443,7 @@ mod tests {
             ))
         );
         assert_eq!(
-            VersionSpec::from_str("((>=1.2.3)|<1.0.0)"),
+            VersionSpec::from_str("((>=1.2.3)|<1.0.0)", ParseStrictness::Strict),
             Ok(VersionSpec::Group(
                 LogicalOperator::Or,
                 vec![
@@ -375,10 +460,10 @@ mod tests {
     #[test]
     fn test_matches() {
         let v1 = Version::from_str("1.2.0").unwrap();
-        let vs1 = VersionSpec::from_str(">=1.2.3,<2.0.0").unwrap();
+        let vs1 = VersionSpec::from_str(">=1.2.3,<2.0.0", ParseStrictness::Strict).unwrap();
         assert!(!vs1.matches(&v1));
 
-        let vs2 = VersionSpec::from_str("1.2").unwrap();
+        let vs2 = VersionSpec::from_str("1.2", ParseStrictness::Strict).unwrap();
         assert!(vs2.matches(&v1));
 
         let v2 = Version::from_str("1.2.3").unwrap();
@@ -391,18 +476,29 @@ mod tests {
         assert!(!vs1.matches(&v3));
         assert!(!vs2.matches(&v3));
 
-        let vs3 = VersionSpec::from_str(">=1!1.2,<1!2").unwrap();
+        let vs3 = VersionSpec::from_str(">=1!1.2,<1!2", ParseStrictness::Strict).unwrap();
         assert!(vs3.matches(&v3));
     }
 
+    #[test]
+    fn test_display_nested_groups() {
+        for str in [">=1,(<2|>3)", "(>=1,<2)|>3", ">=1,<2|>3,<4"] {
+            let spec = VersionSpec::from_str(str, ParseStrictness::Strict).unwrap();
+            assert_eq!(
+                VersionSpec::from_str(&spec.to_string(), ParseStrictness::Strict).unwrap(),
+                spec
+            );
+        }
+    }
+
     #[test]
     fn issue_204() {
-        assert!(VersionSpec::from_str(">=3.8<3.9").is_err());
+        assert!(VersionSpec::from_str(">=3.8<3.9", ParseStrictness::Strict).is_err());
     }
 
     #[test]
     fn issue_225() {
-        let spec = VersionSpec::from_str("~=2.4").unwrap();
+        let spec = VersionSpec::from_str("~=2.4", ParseStrictness::Strict).unwrap();
         assert!(!spec.matches(&Version::from_str("3.1").unwrap()));
         assert!(spec.matches(&Version::from_str("2.4").unwrap()));
         assert!(spec.matches(&Version::from_str("2.5").unwrap()));
@@ -412,8 +508,30 @@ mod tests {
     #[test]
     fn issue_235() {
         assert_eq!(
-            VersionSpec::from_str(">2.10*").unwrap(),
-            VersionSpec::from_str(">=2.10").unwrap()
+            VersionSpec::from_str(">2.10*", ParseStrictness::Lenient).unwrap(),
+            VersionSpec::from_str(">=2.10", ParseStrictness::Strict).unwrap()
         );
+        assert!(VersionSpec::from_str(">2.10*", ParseStrictness::Strict).is_err());
+    }
+
+    #[test]
+    fn test_strictness() {
+        for (lenient, strict) in [
+            (">=3.6 ,<4", ">=3.6,<4"),
+            (">=1.0,", ">=1.0"),
+            ("1.0.*|", "1.0.*"),
+            ("1.2.*.*", "1.2.*"),
+            (">=1.11.*", ">=1.11"),
+        ] {
+            assert_eq!(
+                VersionSpec::from_str(lenient, ParseStrictness::Lenient).unwrap(),
+                VersionSpec::from_str(strict, ParseStrictness::Strict).unwrap(),
+                "{lenient}"
+            );
+            assert!(
+                VersionSpec::from_str(lenient, ParseStrictness::Strict).is_err(),
+                "{lenient}"
+            );
+        }
     }
 }
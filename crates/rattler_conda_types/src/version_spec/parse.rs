@@ -1,7 +1,7 @@
 use crate::version::parse::version_parser;
 use crate::version_spec::constraint::Constraint;
 use crate::version_spec::{EqualityOperator, RangeOperator, StrictRangeOperator, VersionOperators};
-use crate::{ParseVersionError, ParseVersionErrorKind};
+use crate::{ParseStrictness, ParseVersionError, ParseVersionErrorKind};
 use nom::{
     branch::alt,
     bytes::complete::{tag, take_while, take_while1},
@@ -51,8 +51,11 @@ fn operator_parser(input: &str) -> IResult<&str, VersionOperators, ParseVersionO
 
 #[derive(Debug, Clone, Error, Eq, PartialEq)]
 pub enum ParseConstraintError {
-    #[error("'.' is incompatible with '{0}' operator'")]
+    #[error("'.*' is incompatible with '{0}' operator")]
     GlobVersionIncompatibleWithOperator(RangeOperator),
+    /// A glob was used with an operator that is not a range operator, e.g. `==1.2.*`
+    #[error("'.*' is incompatible with '{0}' operator")]
+    GlobVersionIncompatibleWithVersionOperator(VersionOperators),
     #[error("regex constraints are not supported")]
     RegexConstraintsNotSupported,
     #[error("unterminated unsupported regular expression")]
@@ -100,7 +103,10 @@ fn any_constraint_parser(input: &str) -> IResult<&str, Constraint, ParseConstrai
 }
 
 /// Parses a constraint with an operator in front of it.
-fn logical_constraint_parser(input: &str) -> IResult<&str, Constraint, ParseConstraintError> {
+fn logical_constraint_parser(
+    input: &str,
+    strictness: ParseStrictness,
+) -> IResult<&str, Constraint, ParseConstraintError> {
     // Parse the optional preceding operator
     let (input, op) = match operator_parser(input) {
         Err(
@@ -138,6 +144,23 @@ fn logical_constraint_parser(input: &str) -> IResult<&str, Constraint, ParseCons
         })
     })?;
 
+    // Conda used to accept repeated globs (e.g. `1.0.*.*`) which are equivalent to a single one.
+    let version_rest = if version_rest.len() > 2 && is_repeated_glob(version_rest) {
+        match strictness {
+            ParseStrictness::Strict => {
+                return Err(nom::Err::Failure(
+                    ParseConstraintError::RegexConstraintsNotSupported,
+                ))
+            }
+            ParseStrictness::Lenient => {
+                tracing::warn!("The version constraint '{version_str}' contains a repeated glob, it is interpreted as a single '.*'.");
+                ".*"
+            }
+        }
+    } else {
+        version_rest
+    };
+
     // Convert the operator and the wildcard to something understandable
     let op = match (version_rest, op) {
         // The version was successfully parsed
@@ -148,18 +171,25 @@ fn logical_constraint_parser(input: &str) -> IResult<&str, Constraint, ParseCons
         ("*" | ".*", Some(VersionOperators::StrictRange(StrictRangeOperator::StartsWith))) => {
             VersionOperators::StrictRange(StrictRangeOperator::StartsWith)
         }
-        ("*" | ".*", Some(VersionOperators::Range(RangeOperator::GreaterEquals))) => {
-            VersionOperators::Range(RangeOperator::GreaterEquals)
-        }
-        ("*" | ".*", Some(VersionOperators::Range(RangeOperator::Greater))) => {
-            VersionOperators::Range(RangeOperator::GreaterEquals)
-        }
         ("*" | ".*", Some(VersionOperators::Exact(EqualityOperator::NotEquals))) => {
             VersionOperators::StrictRange(StrictRangeOperator::NotStartsWith)
         }
+        ("*" | ".*", Some(op)) if strictness == ParseStrictness::Strict => {
+            return Err(nom::Err::Failure(match op {
+                VersionOperators::Range(op) => {
+                    ParseConstraintError::GlobVersionIncompatibleWithOperator(op)
+                }
+                op => ParseConstraintError::GlobVersionIncompatibleWithVersionOperator(op),
+            }));
+        }
         (glob @ "*" | glob @ ".*", Some(op)) => {
             tracing::warn!("Using {glob} with relational operator is superfluous and deprecated and will be removed in a future version of conda.");
-            op
+            match op {
+                VersionOperators::Range(RangeOperator::Greater) => {
+                    VersionOperators::Range(RangeOperator::GreaterEquals)
+                }
+                op => op,
+            }
         }
         ("*" | ".*", None) => VersionOperators::StrictRange(StrictRangeOperator::StartsWith),
 
@@ -188,13 +218,26 @@ fn logical_constraint_parser(input: &str) -> IResult<&str, Constraint, ParseCons
     }
 }
 
+/// Returns true if the string only consists of version globs (`.*` or `*`).
+fn is_repeated_glob(input: &str) -> bool {
+    let mut rest = input;
+    while !rest.is_empty() {
+        rest = match rest.strip_prefix(".*").or_else(|| rest.strip_prefix('*')) {
+            Some(rest) => rest,
+            None => return false,
+        };
+    }
+    true
+}
+
 /// Parses a version constraint.
-pub fn constraint_parser(input: &str) -> IResult<&str, Constraint, ParseConstraintError> {
-    alt((
-        regex_constraint_parser,
-        any_constraint_parser,
-        logical_constraint_parser,
-    ))(input)
+pub fn constraint_parser(
+    input: &str,
+    strictness: ParseStrictness,
+) -> IResult<&str, Constraint, ParseConstraintError> {
+    alt((regex_constraint_parser, any_constraint_parser, |input| {
+        logical_constraint_parser(input, strictness)
+    }))(input)
 }
 
 #[cfg(test)]
@@ -283,7 +326,7 @@ mod test {
     #[test]
     fn parse_logical_constraint() {
         assert_eq!(
-            logical_constraint_parser("3.1"),
+            logical_constraint_parser("3.1", ParseStrictness::Lenient),
             Ok((
                 "",
                 Constraint::Exact(EqualityOperator::Equals, Version::from_str("3.1").unwrap())
@@ -291,7 +334,7 @@ mod test {
         );
 
         assert_eq!(
-            logical_constraint_parser(">3.1"),
+            logical_constraint_parser(">3.1", ParseStrictness::Lenient),
             Ok((
                 "",
                 Constraint::Comparison(RangeOperator::Greater, Version::from_str("3.1").unwrap())
@@ -299,7 +342,7 @@ mod test {
         );
 
         assert_eq!(
-            logical_constraint_parser("3.1*"),
+            logical_constraint_parser("3.1*", ParseStrictness::Lenient),
             Ok((
                 "",
                 Constraint::StrictComparison(
@@ -310,7 +353,7 @@ mod test {
         );
 
         assert_eq!(
-            logical_constraint_parser("3.1.*"),
+            logical_constraint_parser("3.1.*", ParseStrictness::Lenient),
             Ok((
                 "",
                 Constraint::StrictComparison(
@@ -321,7 +364,7 @@ mod test {
         );
 
         assert_eq!(
-            logical_constraint_parser("~=3.1"),
+            logical_constraint_parser("~=3.1", ParseStrictness::Lenient),
             Ok((
                 "",
                 Constraint::StrictComparison(
@@ -332,7 +375,7 @@ mod test {
         );
 
         assert_eq!(
-            logical_constraint_parser(">=3.1*"),
+            logical_constraint_parser(">=3.1*", ParseStrictness::Lenient),
             Ok((
                 "",
                 Constraint::Comparison(
@@ -347,23 +390,29 @@ mod test {
     fn parse_constraint() {
         // Regular expressions
         assert_eq!(
-            constraint_parser("^1.2.3$"),
+            constraint_parser("^1.2.3$", ParseStrictness::Lenient),
             Err(nom::Err::Failure(
                 ParseConstraintError::RegexConstraintsNotSupported
             ))
         );
         assert_eq!(
-            constraint_parser("^1.2.3"),
+            constraint_parser("^1.2.3", ParseStrictness::Lenient),
             Err(nom::Err::Failure(ParseConstraintError::UnterminatedRegex))
         );
 
         // Any constraints
-        assert_eq!(constraint_parser("*"), Ok(("", Constraint::Any)));
-        assert_eq!(constraint_parser("*.*"), Ok(("", Constraint::Any)));
+        assert_eq!(
+            constraint_parser("*", ParseStrictness::Lenient),
+            Ok(("", Constraint::Any))
+        );
+        assert_eq!(
+            constraint_parser("*.*", ParseStrictness::Lenient),
+            Ok(("", Constraint::Any))
+        );
     }
 
     #[test]
     fn pixi_issue_278() {
-        assert!(VersionSpec::from_str("1.8.1+g6b29558").is_ok());
+        assert!(VersionSpec::from_str("1.8.1+g6b29558", ParseStrictness::Strict).is_ok());
     }
 }
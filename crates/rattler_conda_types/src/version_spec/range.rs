@@ -4,14 +4,15 @@
 //! representable as the concatenation, union, and complement
 //! of the ranges building blocks.
 
-use pubgrub::version_set::VersionSet;
 use smallvec::{smallvec, SmallVec};
 use std::cmp::Ordering;
-use std::fmt::{Debug, Display, Formatter};
+use std::fmt::{Display, Formatter};
 use std::ops::Bound::{self, Excluded, Included, Unbounded};
 
-type Interval<V> = (Bound<V>, Bound<V>);
+/// An interval between a lower and an upper bound.
+pub type Interval<V> = (Bound<V>, Bound<V>);
 
+/// A set of versions represented as a sorted list of disjoint intervals.
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct Range<V> {
     segments: SmallVec<[Interval<V>; 2]>,
@@ -80,6 +81,24 @@ impl<V: Clone> Range<V> {
             segments: smallvec![(Included(v1), Excluded(v2))],
         }
     }
+
+    /// Set of versions between the two bounds. The caller must ensure that `lower` is not greater
+    /// than `upper`.
+    pub fn from_bounds(lower: Bound<V>, upper: Bound<V>) -> Self {
+        Self {
+            segments: smallvec![(lower, upper)],
+        }
+    }
+
+    /// Returns true if this set does not contain any version.
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Returns the disjoint intervals that make up this set in ascending order.
+    pub fn intervals(&self) -> impl Iterator<Item = &Interval<V>> + '_ {
+        self.segments.iter()
+    }
 }
 
 impl<V: Clone> Range<V> {
@@ -166,6 +185,11 @@ impl<V: Ord + Clone> Range<V> {
         self.negate().intersection(&other.negate()).negate()
     }
 
+    /// Returns true if all the versions in this set are also contained in `other`.
+    pub fn is_subset(&self, other: &Self) -> bool {
+        self.intersection(&other.negate()).is_empty()
+    }
+
     /// Computes the intersection of two sets of versions.
     pub fn intersection(&self, other: &Self) -> Self {
         let mut segments: SmallVec<[Interval<V>; 2]> = Default::default();
@@ -173,134 +197,124 @@ impl<V: Ord + Clone> Range<V> {
         let mut right_iter = other.segments.iter();
         let mut left = left_iter.next();
         let mut right = right_iter.next();
-        loop {
-            match (left, right) {
-                (Some((left_lower, left_upper)), Some((right_lower, right_upper))) => {
-                    // Check if the left range completely smaller than the right range.
-                    if let (
-                        Included(left_upper_version) | Excluded(left_upper_version),
-                        Included(right_lower_version) | Excluded(right_lower_version),
-                    ) = (left_upper, right_lower)
-                    {
-                        match left_upper_version.cmp(right_lower_version) {
-                            Ordering::Less => {
-                                // Left range is disjoint from the right range.
-                                left = left_iter.next();
-                                continue;
-                            }
-                            Ordering::Equal => {
-                                if !matches!((left_upper, right_lower), (Included(_), Included(_)))
-                                {
-                                    // Left and right are overlapping exactly, but one of the bounds is exclusive, therefor the ranges are disjoint
-                                    left = left_iter.next();
-                                    continue;
-                                }
-                            }
-                            Ordering::Greater => {
-                                // Left upper bound is greater than right lower bound, so the lower bound is the right lower bound
-                            }
+        while let (Some((left_lower, left_upper)), Some((right_lower, right_upper))) = (left, right)
+        {
+            // Check if the left range completely smaller than the right range.
+            if let (
+                Included(left_upper_version) | Excluded(left_upper_version),
+                Included(right_lower_version) | Excluded(right_lower_version),
+            ) = (left_upper, right_lower)
+            {
+                match left_upper_version.cmp(right_lower_version) {
+                    Ordering::Less => {
+                        // Left range is disjoint from the right range.
+                        left = left_iter.next();
+                        continue;
+                    }
+                    Ordering::Equal => {
+                        if !matches!((left_upper, right_lower), (Included(_), Included(_))) {
+                            // Left and right are overlapping exactly, but one of the bounds is exclusive, therefor the ranges are disjoint
+                            left = left_iter.next();
+                            continue;
                         }
                     }
-                    // Check if the right range completely smaller than the left range.
-                    if let (
-                        Included(left_lower_version) | Excluded(left_lower_version),
-                        Included(right_upper_version) | Excluded(right_upper_version),
-                    ) = (left_lower, right_upper)
-                    {
-                        match right_upper_version.cmp(left_lower_version) {
-                            Ordering::Less => {
-                                // Right range is disjoint from the left range.
-                                right = right_iter.next();
-                                continue;
-                            }
-                            Ordering::Equal => {
-                                if !matches!((right_upper, left_lower), (Included(_), Included(_)))
-                                {
-                                    // Left and right are overlapping exactly, but one of the bounds is exclusive, therefor the ranges are disjoint
-                                    right = right_iter.next();
-                                    continue;
-                                }
-                            }
-                            Ordering::Greater => {
-                                // Right upper bound is greater than left lower bound, so the lower bound is the left lower bound
-                            }
+                    Ordering::Greater => {
+                        // Left upper bound is greater than right lower bound, so the lower bound is the right lower bound
+                    }
+                }
+            }
+            // Check if the right range completely smaller than the left range.
+            if let (
+                Included(left_lower_version) | Excluded(left_lower_version),
+                Included(right_upper_version) | Excluded(right_upper_version),
+            ) = (left_lower, right_upper)
+            {
+                match right_upper_version.cmp(left_lower_version) {
+                    Ordering::Less => {
+                        // Right range is disjoint from the left range.
+                        right = right_iter.next();
+                        continue;
+                    }
+                    Ordering::Equal => {
+                        if !matches!((right_upper, left_lower), (Included(_), Included(_))) {
+                            // Left and right are overlapping exactly, but one of the bounds is exclusive, therefor the ranges are disjoint
+                            right = right_iter.next();
+                            continue;
                         }
                     }
+                    Ordering::Greater => {
+                        // Right upper bound is greater than left lower bound, so the lower bound is the left lower bound
+                    }
+                }
+            }
 
-                    // At this point we know there is an overlap between the versions, find the lowest bound
-                    let lower = match (left_lower, right_lower) {
-                        (Unbounded, Included(_) | Excluded(_)) => right_lower.clone(),
-                        (Included(_) | Excluded(_), Unbounded) => left_lower.clone(),
-                        (Unbounded, Unbounded) => Unbounded,
-                        (Included(l) | Excluded(l), Included(r) | Excluded(r)) => match l.cmp(r) {
-                            Ordering::Less => right_lower.clone(),
-                            Ordering::Equal => match (left_lower, right_lower) {
-                                (Included(_), Excluded(v)) => Excluded(v.clone()),
-                                (Excluded(_), Excluded(v)) => Excluded(v.clone()),
-                                (Excluded(v), Included(_)) => Excluded(v.clone()),
-                                (Included(_), Included(v)) => Included(v.clone()),
-                                _ => unreachable!(),
-                            },
-                            Ordering::Greater => left_lower.clone(),
-                        },
-                    };
+            // At this point we know there is an overlap between the versions, find the lowest bound
+            let lower = match (left_lower, right_lower) {
+                (Unbounded, Included(_) | Excluded(_)) => right_lower.clone(),
+                (Included(_) | Excluded(_), Unbounded) => left_lower.clone(),
+                (Unbounded, Unbounded) => Unbounded,
+                (Included(l) | Excluded(l), Included(r) | Excluded(r)) => match l.cmp(r) {
+                    Ordering::Less => right_lower.clone(),
+                    Ordering::Equal => match (left_lower, right_lower) {
+                        (Included(_), Excluded(v)) => Excluded(v.clone()),
+                        (Excluded(_), Excluded(v)) => Excluded(v.clone()),
+                        (Excluded(v), Included(_)) => Excluded(v.clone()),
+                        (Included(_), Included(v)) => Included(v.clone()),
+                        _ => unreachable!(),
+                    },
+                    Ordering::Greater => left_lower.clone(),
+                },
+            };
 
-                    // At this point we know there is an overlap between the versions, find the lowest bound
-                    let upper = match (left_upper, right_upper) {
-                        (Unbounded, Included(_) | Excluded(_)) => {
+            // At this point we know there is an overlap between the versions, find the lowest bound
+            let upper = match (left_upper, right_upper) {
+                (Unbounded, Included(_) | Excluded(_)) => {
+                    right = right_iter.next();
+                    right_upper.clone()
+                }
+                (Included(_) | Excluded(_), Unbounded) => {
+                    left = left_iter.next();
+                    left_upper.clone()
+                }
+                (Unbounded, Unbounded) => {
+                    left = left_iter.next();
+                    right = right_iter.next();
+                    Unbounded
+                }
+                (Included(l) | Excluded(l), Included(r) | Excluded(r)) => match l.cmp(r) {
+                    Ordering::Less => {
+                        left = left_iter.next();
+                        left_upper.clone()
+                    }
+                    Ordering::Equal => match (left_upper, right_upper) {
+                        (Included(_), Excluded(v)) => {
+                            right = right_iter.next();
+                            Excluded(v.clone())
+                        }
+                        (Excluded(_), Excluded(v)) => {
+                            left = left_iter.next();
                             right = right_iter.next();
-                            right_upper.clone()
+                            Excluded(v.clone())
                         }
-                        (Included(_) | Excluded(_), Unbounded) => {
+                        (Excluded(v), Included(_)) => {
                             left = left_iter.next();
-                            left_upper.clone()
+                            Excluded(v.clone())
                         }
-                        (Unbounded, Unbounded) => {
+                        (Included(_), Included(v)) => {
                             left = left_iter.next();
                             right = right_iter.next();
-                            Unbounded
+                            Included(v.clone())
                         }
-                        (Included(l) | Excluded(l), Included(r) | Excluded(r)) => match l.cmp(r) {
-                            Ordering::Less => {
-                                left = left_iter.next();
-                                left_upper.clone()
-                            }
-                            Ordering::Equal => match (left_upper, right_upper) {
-                                (Included(_), Excluded(v)) => {
-                                    right = right_iter.next();
-                                    Excluded(v.clone())
-                                }
-                                (Excluded(_), Excluded(v)) => {
-                                    left = left_iter.next();
-                                    right = right_iter.next();
-                                    Excluded(v.clone())
-                                }
-                                (Excluded(v), Included(_)) => {
-                                    left = left_iter.next();
-                                    Excluded(v.clone())
-                                }
-                                (Included(_), Included(v)) => {
-                                    left = left_iter.next();
-                                    right = right_iter.next();
-                                    Included(v.clone())
-                                }
-                                _ => unreachable!(),
-                            },
-                            Ordering::Greater => {
-                                right = right_iter.next();
-                                right_upper.clone()
-                            }
-                        },
-                    };
-
-                    segments.push((lower, upper));
-                }
+                        _ => unreachable!(),
+                    },
+                    Ordering::Greater => {
+                        right = right_iter.next();
+                        right_upper.clone()
+                    }
+                },
+            };
 
-                // Left or right has ended
-                (None, _) | (_, None) => {
-                    break;
-                }
-            }
+            segments.push((lower, upper));
         }
 
         Self { segments }
@@ -339,38 +353,6 @@ impl<V: Display + Eq> Display for Range<V> {
     }
 }
 
-impl<T: Debug + Display + Clone + Eq + Ord> VersionSet for Range<T> {
-    type V = T;
-
-    fn empty() -> Self {
-        Range::none()
-    }
-
-    fn singleton(v: Self::V) -> Self {
-        Range::equal(v)
-    }
-
-    fn complement(&self) -> Self {
-        Range::negate(self)
-    }
-
-    fn intersection(&self, other: &Self) -> Self {
-        Range::intersection(self, other)
-    }
-
-    fn contains(&self, v: &Self::V) -> bool {
-        Range::contains(self, v)
-    }
-
-    fn full() -> Self {
-        Range::any()
-    }
-
-    fn union(&self, other: &Self) -> Self {
-        Range::union(self, other)
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::Range as R;
@@ -0,0 +1,818 @@
+//! Set operations on [`VersionSpec`]s. A spec is converted into a [`Range`] of disjoint intervals
+//! which can be intersected, merged and compared, and which can be converted back into a
+//! simplified spec.
+
+use super::{
+    range::Range, EqualityOperator, LogicalOperator, RangeOperator, StrictRangeOperator,
+    VersionSpec,
+};
+use crate::{
+    version::{Component, SegmentIter, StrictVersion},
+    Version,
+};
+use std::cmp::Ordering;
+use std::ops::Bound::{self, Excluded, Included, Unbounded};
+
+/// A point on the ordered line of versions. A bound always lies in between versions, this includes
+/// the points directly before and directly after the versions that are equal to a certain version
+/// and the points directly before and directly after all the versions that start with a certain
+/// prefix. This allows expressing conda's `1.2.*` and `~=1.2.3` constraints as intervals.
+///
+/// Versions that are equal but that are written with a different number of components (e.g. `1`
+/// and `1.0`) are ordered by their number of components, because `1.0.*` matches `1.0` but not
+/// `1`. Since a bound never coincides with a version, the lower bound of an interval is always
+/// [`Included`] and the upper bound is always [`Excluded`].
+#[derive(Debug, Clone)]
+enum VersionBound {
+    /// Directly before all the versions that are equal to the version
+    Before(Version),
+
+    /// Directly after all the versions that are equal to the version
+    After(Version),
+
+    /// Directly before the versions that are equal to the prefix and that also start with it
+    PrefixEqual(Version),
+
+    /// Directly before all the versions that start with the prefix
+    PrefixStart(Version),
+
+    /// Directly after all the versions that start with the prefix
+    PrefixEnd(Version),
+}
+
+/// The position of a bound among the versions that are equal to each other.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum EqualPosition {
+    /// Directly before the versions with at least this number of components per segment
+    Before(Vec<usize>),
+
+    /// Directly after all the versions
+    After,
+}
+
+impl VersionBound {
+    fn version(&self) -> &Version {
+        match self {
+            VersionBound::Before(version)
+            | VersionBound::After(version)
+            | VersionBound::PrefixEqual(version)
+            | VersionBound::PrefixStart(version)
+            | VersionBound::PrefixEnd(version) => version,
+        }
+    }
+
+    /// Returns the position of the bound among the versions that are equal to its version. Only
+    /// meaningful for bounds that do not refer to a prefix.
+    fn equal_position(&self) -> EqualPosition {
+        match self {
+            VersionBound::Before(version) => EqualPosition::Before(shortest_shape(version)),
+            VersionBound::PrefixEqual(prefix) => EqualPosition::Before(shape(prefix)),
+            VersionBound::After(_) | VersionBound::PrefixStart(_) | VersionBound::PrefixEnd(_) => {
+                EqualPosition::After
+            }
+        }
+    }
+}
+
+impl Ord for VersionBound {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (VersionBound::PrefixStart(a), VersionBound::PrefixStart(b)) => {
+                compare_prefixes(a, b, Ordering::Less)
+            }
+            (VersionBound::PrefixEnd(a), VersionBound::PrefixEnd(b)) => {
+                compare_prefixes(a, b, Ordering::Greater)
+            }
+            (VersionBound::PrefixStart(a), VersionBound::PrefixEnd(b)) => {
+                // If one of the prefixes contains the other the start of one always comes before
+                // the end of the other, otherwise the versions do not overlap at all.
+                if a.starts_with(b) || b.starts_with(a) || a <= b {
+                    Ordering::Less
+                } else {
+                    Ordering::Greater
+                }
+            }
+            (VersionBound::PrefixEnd(_), VersionBound::PrefixStart(_)) => other.cmp(self).reverse(),
+            (VersionBound::PrefixStart(prefix), bound) => {
+                // All the versions that are equal to the prefix come after the start of the
+                // prefix, even the ones that do not start with it.
+                let version = bound.version();
+                if version >= prefix || version.starts_with(prefix) {
+                    Ordering::Less
+                } else {
+                    Ordering::Greater
+                }
+            }
+            (VersionBound::PrefixEnd(prefix), bound) => {
+                let version = bound.version();
+                if version <= prefix || version.starts_with(prefix) {
+                    Ordering::Greater
+                } else {
+                    Ordering::Less
+                }
+            }
+            (_, VersionBound::PrefixStart(_) | VersionBound::PrefixEnd(_)) => {
+                other.cmp(self).reverse()
+            }
+            (a, b) => a
+                .version()
+                .cmp(b.version())
+                .then_with(|| a.equal_position().cmp(&b.equal_position())),
+        }
+    }
+}
+
+impl PartialOrd for VersionBound {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for VersionBound {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for VersionBound {}
+
+/// Compares two prefix bounds of the same kind. `nested` is returned if the versions that start
+/// with `b` are a strict subset of the versions that start with `a`.
+fn compare_prefixes(a: &Version, b: &Version, nested: Ordering) -> Ordering {
+    match (b.starts_with(a), a.starts_with(b)) {
+        (true, true) => Ordering::Equal,
+        (true, false) => nested,
+        (false, true) => nested.reverse(),
+        (false, false) => a.cmp(b),
+    }
+}
+
+/// Returns true if both versions describe the same prefix.
+fn is_same_prefix(a: &Version, b: &Version) -> bool {
+    a.starts_with(b) && b.starts_with(a)
+}
+
+/// Returns the number of components of each segment of the version.
+fn shape(version: &Version) -> Vec<usize> {
+    version
+        .segments()
+        .map(|segment| segment.components().count())
+        .collect()
+}
+
+/// Returns the shape of the shortest version that is equal to the version, which is the version
+/// without trailing zeros (e.g. `1` for `1.0`).
+fn shortest_shape(version: &Version) -> Vec<usize> {
+    let default = Component::default();
+    let significant_components = |segment: &SegmentIter<'_>| {
+        let components = segment.components().collect::<Vec<_>>();
+        components
+            .iter()
+            .rposition(|component| **component != default)
+            .map(|index| index + 1)
+    };
+    let mut shape = version
+        .segments()
+        .map(|segment| significant_components(&segment))
+        .collect::<Vec<_>>();
+    while shape.len() > 1 && shape.last() == Some(&None) {
+        shape.pop();
+    }
+    shape.into_iter().map(|count| count.unwrap_or(1)).collect()
+}
+
+/// Returns true if there are versions that are equal to the prefix but that do not start with it,
+/// e.g. `1` for the prefix `1.0`.
+fn has_trailing_zeros(prefix: &Version) -> bool {
+    shape(prefix) != shortest_shape(prefix)
+}
+
+impl VersionSpec {
+    /// Converts the spec into the set of versions it matches.
+    fn to_range(&self) -> Range<VersionBound> {
+        match self {
+            VersionSpec::None => Range::none(),
+            VersionSpec::Any => Range::any(),
+            VersionSpec::Exact(EqualityOperator::Equals, version) => equal_range(version),
+            VersionSpec::Exact(EqualityOperator::NotEquals, version) => {
+                equal_range(version).negate()
+            }
+            VersionSpec::Range(op, version) => match op {
+                RangeOperator::Greater => {
+                    Range::greater_equal(VersionBound::After(version.clone()))
+                }
+                RangeOperator::GreaterEquals => {
+                    Range::greater_equal(VersionBound::Before(version.clone()))
+                }
+                RangeOperator::Less => Range::less(VersionBound::Before(version.clone())),
+                RangeOperator::LessEquals => Range::less(VersionBound::After(version.clone())),
+            },
+            VersionSpec::StrictRange(op, StrictVersion(version)) => match op {
+                StrictRangeOperator::StartsWith => starts_with_range(version),
+                StrictRangeOperator::NotStartsWith => starts_with_range(version).negate(),
+                StrictRangeOperator::Compatible => compatible_range(version),
+                StrictRangeOperator::NotCompatible => compatible_range(version).negate(),
+            },
+            VersionSpec::Group(LogicalOperator::And, specs) => {
+                specs.iter().fold(Range::any(), |range, spec| {
+                    range.intersection(&spec.to_range())
+                })
+            }
+            VersionSpec::Group(LogicalOperator::Or, specs) => specs
+                .iter()
+                .fold(Range::none(), |range, spec| range.union(&spec.to_range())),
+        }
+    }
+
+    /// Converts a set of versions back into a spec.
+    fn from_range(range: &Range<VersionBound>) -> VersionSpec {
+        // Intervals that are only separated by a single version or prefix are combined into a
+        // single interval with a hole, e.g. `>=1,!=1.5`.
+        let intervals = range.intervals().collect::<Vec<_>>();
+        let mut parts: Vec<(Bound<VersionBound>, Bound<VersionBound>, Vec<VersionSpec>)> =
+            Vec::new();
+        let mut index = 0;
+        while let Some((lower, upper)) = intervals.get(index) {
+            index += 1;
+            if let Some((_, last_upper, holes)) = parts.last_mut() {
+                if let Some(hole) = hole_between(last_upper, lower) {
+                    holes.extend(hole);
+                    *last_upper = upper.clone();
+                    continue;
+                }
+
+                // The versions that start with a prefix with trailing zeros leave two holes
+                // around the versions that are equal to the prefix but that do not start with it.
+                if let Some((next_lower, next_upper)) = intervals.get(index) {
+                    if let Some(prefix) = split_prefix_between(last_upper, lower, upper, next_lower)
+                    {
+                        holes.push(not_starts_with(prefix));
+                        *last_upper = next_upper.clone();
+                        index += 1;
+                        continue;
+                    }
+                }
+            }
+            parts.push((lower.clone(), upper.clone(), Vec::new()));
+        }
+
+        let mut specs = parts
+            .into_iter()
+            .map(|(lower, upper, mut holes)| {
+                let prefix = enclosing_prefix(&lower, &upper, &holes);
+                let mut specs = interval_specs(&lower, &upper, prefix);
+                if let Some(prefix) = prefix {
+                    // The versions that are equal to the prefix but that do not start with it
+                    // are already excluded by the prefix.
+                    let excluded = not_starts_with_equal(prefix);
+                    holes.retain(|hole| *hole != excluded);
+                }
+                specs.extend(holes);
+                group(LogicalOperator::And, specs).unwrap_or(VersionSpec::Any)
+            })
+            .collect::<Vec<_>>();
+
+        match specs.len() {
+            0 => VersionSpec::None,
+            1 => specs.pop().unwrap(),
+            _ => VersionSpec::Group(LogicalOperator::Or, specs),
+        }
+    }
+
+    /// Returns a spec that matches exactly the same versions as this spec but with redundant
+    /// constraints removed, e.g. `>=1,>=1.5,<3,<2.5` becomes `>=1.5,<2.5`.
+    pub fn simplify(&self) -> VersionSpec {
+        Self::from_range(&self.to_range())
+    }
+
+    /// Returns a simplified spec that matches the versions that are matched by both specs.
+    pub fn intersection(&self, other: &VersionSpec) -> VersionSpec {
+        Self::from_range(&self.to_range().intersection(&other.to_range()))
+    }
+
+    /// Returns a simplified spec that matches the versions that are matched by either spec.
+    pub fn union(&self, other: &VersionSpec) -> VersionSpec {
+        Self::from_range(&self.to_range().union(&other.to_range()))
+    }
+
+    /// Returns true if all the versions matched by this spec are also matched by `other`.
+    pub fn is_subset(&self, other: &VersionSpec) -> bool {
+        self.to_range().is_subset(&other.to_range())
+    }
+
+    /// Returns true if the spec cannot match any version, e.g. `>2,<1`.
+    pub fn is_empty(&self) -> bool {
+        self.to_range().is_empty()
+    }
+}
+
+/// The versions that are equal to `version`.
+fn equal_range(version: &Version) -> Range<VersionBound> {
+    Range::between(
+        VersionBound::Before(version.clone()),
+        VersionBound::After(version.clone()),
+    )
+}
+
+/// The versions that start with `prefix`. If the prefix has trailing zeros the versions that are
+/// equal to the prefix but that do not start with it are excluded, e.g. `1` for `1.0.*`.
+fn starts_with_range(prefix: &Version) -> Range<VersionBound> {
+    if has_trailing_zeros(prefix) {
+        Range::between(
+            VersionBound::PrefixStart(prefix.clone()),
+            VersionBound::Before(prefix.clone()),
+        )
+        .union(&Range::between(
+            VersionBound::PrefixEqual(prefix.clone()),
+            VersionBound::PrefixEnd(prefix.clone()),
+        ))
+    } else {
+        Range::between(
+            VersionBound::PrefixStart(prefix.clone()),
+            VersionBound::PrefixEnd(prefix.clone()),
+        )
+    }
+}
+
+/// The versions that are compatible with `version` (`~=`): greater or equal to the version and
+/// starting with all but the last segment of it.
+fn compatible_range(version: &Version) -> Range<VersionBound> {
+    let lower = Range::greater_equal(VersionBound::Before(version.clone()));
+    match version.pop_segments(1) {
+        Some(prefix) => lower.intersection(&starts_with_range(&prefix)),
+        None => lower,
+    }
+}
+
+/// Returns the specs for the versions that are excluded between two adjacent intervals if that is
+/// a single version or all the versions with a single prefix.
+fn hole_between(
+    upper: &Bound<VersionBound>,
+    lower: &Bound<VersionBound>,
+) -> Option<Vec<VersionSpec>> {
+    match (upper, lower) {
+        (Excluded(VersionBound::Before(a)), Included(VersionBound::After(b))) if a == b => Some(
+            vec![VersionSpec::Exact(EqualityOperator::NotEquals, a.clone())],
+        ),
+        (Excluded(VersionBound::PrefixStart(a)), Included(VersionBound::PrefixEnd(b)))
+            if is_same_prefix(a, b) =>
+        {
+            let mut holes = vec![not_starts_with(a)];
+            if has_trailing_zeros(a) {
+                holes.push(VersionSpec::Exact(EqualityOperator::NotEquals, a.clone()));
+            }
+            Some(holes)
+        }
+        (Excluded(VersionBound::Before(a)), Included(VersionBound::PrefixEqual(b))) if a == b => {
+            Some(vec![not_starts_with_equal(b)])
+        }
+        _ => None,
+    }
+}
+
+/// Returns the prefix if the versions between the first upper bound and the next lower bound are
+/// all the versions that start with the prefix, except for the interval in between them which
+/// contains the versions that are equal to the prefix but that do not start with it.
+fn split_prefix_between<'a>(
+    upper: &'a Bound<VersionBound>,
+    lower: &Bound<VersionBound>,
+    next_upper: &Bound<VersionBound>,
+    next_lower: &Bound<VersionBound>,
+) -> Option<&'a Version> {
+    match (upper, lower, next_upper, next_lower) {
+        (
+            Excluded(VersionBound::PrefixStart(prefix)),
+            Included(VersionBound::Before(version)),
+            Excluded(VersionBound::PrefixEqual(equal_prefix)),
+            Included(VersionBound::PrefixEnd(end_prefix)),
+        ) if version == prefix
+            && is_same_prefix(prefix, equal_prefix)
+            && is_same_prefix(prefix, end_prefix) =>
+        {
+            Some(prefix)
+        }
+        _ => None,
+    }
+}
+
+/// Returns a prefix if all the versions in the interval, except for the holes, start with the
+/// prefix. Only the prefixes of the bounds themselves are considered.
+fn enclosing_prefix<'a>(
+    lower: &'a Bound<VersionBound>,
+    upper: &'a Bound<VersionBound>,
+    holes: &[VersionSpec],
+) -> Option<&'a Version> {
+    let (Included(lower), Excluded(upper)) = (lower, upper) else {
+        return None;
+    };
+    let candidates = [lower, upper].into_iter().filter_map(|bound| match bound {
+        VersionBound::PrefixStart(prefix)
+        | VersionBound::PrefixEqual(prefix)
+        | VersionBound::PrefixEnd(prefix) => Some(prefix),
+        VersionBound::Before(_) | VersionBound::After(_) => None,
+    });
+    for prefix in candidates {
+        if *lower < VersionBound::PrefixStart(prefix.clone())
+            || *upper > VersionBound::PrefixEnd(prefix.clone())
+        {
+            continue;
+        }
+
+        // The interval should not contain the versions that are equal to the prefix but that do
+        // not start with it.
+        let contains_equal = has_trailing_zeros(prefix)
+            && *lower < VersionBound::PrefixEqual(prefix.clone())
+            && *upper > VersionBound::Before(prefix.clone())
+            && !holes.contains(&not_starts_with_equal(prefix));
+        if !contains_equal {
+            return Some(prefix);
+        }
+    }
+    None
+}
+
+/// Returns the specs that together describe the interval between the two bounds. If all the
+/// versions in the interval start with `prefix` the spec is expressed in terms of that prefix.
+fn interval_specs(
+    lower: &Bound<VersionBound>,
+    upper: &Bound<VersionBound>,
+    prefix: Option<&Version>,
+) -> Vec<VersionSpec> {
+    let is_prefix = |other: &Version| prefix.is_some_and(|prefix| is_same_prefix(prefix, other));
+    match (lower, upper) {
+        (Included(VersionBound::Before(a)), Excluded(VersionBound::After(b))) if a == b => {
+            return vec![VersionSpec::Exact(EqualityOperator::Equals, a.clone())];
+        }
+        (Included(VersionBound::PrefixStart(a)), Excluded(VersionBound::PrefixEnd(b)))
+            if is_same_prefix(a, b) =>
+        {
+            return if is_prefix(a) {
+                vec![starts_with(a)]
+            } else {
+                vec![any_of(vec![
+                    starts_with(a),
+                    VersionSpec::Exact(EqualityOperator::Equals, a.clone()),
+                ])]
+            };
+        }
+        (
+            Included(VersionBound::Before(version)),
+            Excluded(VersionBound::PrefixEnd(end_prefix)),
+        ) if is_prefix(end_prefix)
+            && version.pop_segments(1).is_some_and(|compatible_prefix| {
+                is_same_prefix(&compatible_prefix, end_prefix)
+            }) =>
+        {
+            return vec![VersionSpec::StrictRange(
+                StrictRangeOperator::Compatible,
+                StrictVersion(version.clone()),
+            )];
+        }
+        _ => {}
+    }
+
+    let mut specs = Vec::new();
+    match lower {
+        Unbounded => {}
+        Included(VersionBound::Before(version)) | Excluded(VersionBound::Before(version)) => specs
+            .push(VersionSpec::Range(
+                RangeOperator::GreaterEquals,
+                version.clone(),
+            )),
+        Included(VersionBound::After(version)) | Excluded(VersionBound::After(version)) => {
+            specs.push(VersionSpec::Range(RangeOperator::Greater, version.clone()))
+        }
+        Included(VersionBound::PrefixStart(start_prefix))
+        | Excluded(VersionBound::PrefixStart(start_prefix)) => {
+            if is_prefix(start_prefix) {
+                specs.push(starts_with(start_prefix));
+            } else {
+                specs.push(any_of(vec![
+                    starts_with(start_prefix),
+                    VersionSpec::Range(RangeOperator::GreaterEquals, start_prefix.clone()),
+                ]));
+            }
+        }
+        Included(VersionBound::PrefixEqual(equal_prefix))
+        | Excluded(VersionBound::PrefixEqual(equal_prefix)) => {
+            specs.push(VersionSpec::Range(
+                RangeOperator::GreaterEquals,
+                equal_prefix.clone(),
+            ));
+            if is_prefix(equal_prefix) {
+                specs.push(starts_with(equal_prefix));
+            } else {
+                specs.push(any_of(vec![
+                    VersionSpec::Range(RangeOperator::Greater, equal_prefix.clone()),
+                    starts_with(equal_prefix),
+                ]));
+            }
+        }
+        Included(VersionBound::PrefixEnd(end_prefix))
+        | Excluded(VersionBound::PrefixEnd(end_prefix)) => {
+            specs.push(VersionSpec::Range(
+                RangeOperator::Greater,
+                end_prefix.clone(),
+            ));
+            specs.push(not_starts_with(end_prefix));
+        }
+    }
+    match upper {
+        Unbounded => {}
+        Included(VersionBound::Before(version)) | Excluded(VersionBound::Before(version)) => {
+            specs.push(VersionSpec::Range(RangeOperator::Less, version.clone()))
+        }
+        Included(VersionBound::After(version)) | Excluded(VersionBound::After(version)) => specs
+            .push(VersionSpec::Range(
+                RangeOperator::LessEquals,
+                version.clone(),
+            )),
+        Included(VersionBound::PrefixStart(start_prefix))
+        | Excluded(VersionBound::PrefixStart(start_prefix)) => {
+            specs.push(VersionSpec::Range(
+                RangeOperator::Less,
+                start_prefix.clone(),
+            ));
+            specs.push(not_starts_with(start_prefix));
+        }
+        Included(VersionBound::PrefixEqual(equal_prefix))
+        | Excluded(VersionBound::PrefixEqual(equal_prefix)) => {
+            specs.push(any_of(vec![
+                VersionSpec::Range(RangeOperator::Less, equal_prefix.clone()),
+                VersionSpec::Group(
+                    LogicalOperator::And,
+                    vec![
+                        VersionSpec::Exact(EqualityOperator::Equals, equal_prefix.clone()),
+                        not_starts_with(equal_prefix),
+                    ],
+                ),
+            ]));
+        }
+        Included(VersionBound::PrefixEnd(end_prefix))
+        | Excluded(VersionBound::PrefixEnd(end_prefix)) => {
+            if is_prefix(end_prefix) {
+                specs.push(starts_with(end_prefix));
+            } else {
+                specs.push(any_of(vec![
+                    VersionSpec::Range(RangeOperator::LessEquals, end_prefix.clone()),
+                    starts_with(end_prefix),
+                ]));
+            }
+        }
+    }
+
+    // The interval is enclosed by the prefix but neither of the bounds refers to it
+    if let Some(prefix) = prefix {
+        if !specs.iter().any(|spec| is_starts_with(spec, prefix)) {
+            specs.push(starts_with(prefix));
+        }
+    }
+    specs.dedup();
+    specs
+}
+
+fn starts_with(prefix: &Version) -> VersionSpec {
+    VersionSpec::StrictRange(
+        StrictRangeOperator::StartsWith,
+        StrictVersion(prefix.clone()),
+    )
+}
+
+fn not_starts_with(prefix: &Version) -> VersionSpec {
+    VersionSpec::StrictRange(
+        StrictRangeOperator::NotStartsWith,
+        StrictVersion(prefix.clone()),
+    )
+}
+
+/// Returns true if the spec matches exactly the versions that start with the prefix.
+fn is_starts_with(spec: &VersionSpec, prefix: &Version) -> bool {
+    matches!(
+        spec,
+        VersionSpec::StrictRange(StrictRangeOperator::StartsWith, StrictVersion(other))
+            if is_same_prefix(prefix, other)
+    )
+}
+
+/// The spec that excludes the versions that are equal to the prefix but that do not start with it.
+fn not_starts_with_equal(prefix: &Version) -> VersionSpec {
+    any_of(vec![
+        VersionSpec::Exact(EqualityOperator::NotEquals, prefix.clone()),
+        starts_with(prefix),
+    ])
+}
+
+/// Combines the specs with a logical OR.
+fn any_of(specs: Vec<VersionSpec>) -> VersionSpec {
+    group(LogicalOperator::Or, specs).unwrap_or(VersionSpec::None)
+}
+
+/// Combines the specs with the operator, returns `None` if there are no specs.
+fn group(op: LogicalOperator, mut specs: Vec<VersionSpec>) -> Option<VersionSpec> {
+    match specs.len() {
+        0 => None,
+        1 => specs.pop(),
+        _ => Some(VersionSpec::Group(op, specs)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::version::StrictVersion;
+    use crate::version_spec::{
+        EqualityOperator, LogicalOperator, RangeOperator, StrictRangeOperator,
+    };
+    use crate::{ParseStrictness, Version, VersionSpec};
+    use proptest::prelude::*;
+    use rstest::rstest;
+    use std::str::FromStr;
+
+    fn spec(str: &str) -> VersionSpec {
+        VersionSpec::from_str(str, ParseStrictness::Strict).unwrap()
+    }
+
+    #[rstest]
+    #[case(">=1,>=1.5,<3,<2.5", ">=1.5,<2.5")]
+    #[case(">=1,<=1", "==1")]
+    #[case(">=1|>=2", ">=1")]
+    #[case("<1|>=1", "*")]
+    #[case(">2,<1", "!")]
+    #[case(">=1,!=1.5,<2", ">=1,<2,!=1.5")]
+    #[case("1.2.*", "1.2.*")]
+    #[case("1.2.*,1.*", "1.2.*")]
+    #[case("1.2.*|1.*", "1.*")]
+    #[case(">=1,!=1.2.*", ">=1,!=1.2.*")]
+    #[case("1.2.*,>=1.2.5", "~=1.2.5")]
+    #[case("1.2.*,>=1.2.5.1", ">=1.2.5.1,1.2.*")]
+    #[case("~=1.2.3", "~=1.2.3")]
+    #[case("~=1.2.3,>=1.2", "~=1.2.3")]
+    #[case(">=1.2.3,1.2.*", "~=1.2.3")]
+    #[case("<1|(>=2,<3)", "<1|(>=2,<3)")]
+    #[case(">=1,(<2|>3)", "(>=1,<2)|>3")]
+    #[case("1.0.*", "1.0.*")]
+    #[case("1.0.*,<1.0.5", "1.0.*,<1.0.5")]
+    #[case("1.0.*,>=1.0", ">=1.0,1.0.*")]
+    #[case("~=1.0|1.0.*", "(1.0.*|>=1.0),1.*")]
+    #[case("1.0.*|>=1.0a", "1.0.*|>=1.0")]
+    #[case(">=1.0a|1.0.*", "1.0.*|>=1.0")]
+    #[case("!=1.0.*", "!=1.0.*")]
+    fn test_simplify(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(spec(input).simplify().to_string(), expected);
+    }
+
+    #[rstest]
+    #[case(">=1.2,<2", ">=1", true)]
+    #[case(">=1", ">=1.2,<2", false)]
+    #[case("1.2.*", ">=1.1,<1.3", true)]
+    #[case("1.2.*", ">=1.2,<1.3", false)]
+    #[case("~=1.2.3", "1.2.*", true)]
+    #[case("1.*", "~=1.2", false)]
+    #[case("==1.2.3", "1.2.*", true)]
+    #[case("==1.3", "1.2.*", false)]
+    #[case(">2,<1", "==5", true)]
+    fn test_is_subset(#[case] a: &str, #[case] b: &str, #[case] is_subset: bool) {
+        assert_eq!(spec(a).is_subset(&spec(b)), is_subset);
+    }
+
+    #[test]
+    fn test_is_empty() {
+        assert!(spec(">2,<1").is_empty());
+        assert!(spec("1.2.*,!=1.2.*").is_empty());
+        assert!(spec("==1.2,>1.2").is_empty());
+        assert!(spec("1.2.*,>=1.3").is_empty());
+        assert!(!spec(">=1,<=1").is_empty());
+        assert!(!spec("1.2.*,>=1.2.9").is_empty());
+    }
+
+    #[test]
+    fn test_intersection_and_union() {
+        assert_eq!(spec(">=1").intersection(&spec("<2")).to_string(), ">=1,<2");
+        assert_eq!(
+            spec(">=1,<2").union(&spec(">=1.5,<3")).to_string(),
+            ">=1,<3"
+        );
+        assert_eq!(spec("1.*").union(&spec("2.*")).to_string(), "1.*|2.*");
+        assert!(spec("==1").intersection(&spec("==2")).is_empty());
+    }
+
+    #[test]
+    fn test_matches_consistent() {
+        let versions = [
+            "0.9", "1", "1.2.dev0", "1.2a", "1.0", "1.1", "1.2", "1.2.0", "1.2.3", "1.2.5",
+            "1.2.9", "1.3", "1.10", "2", "2.5", "3", "1!1.2",
+        ]
+        .map(|v| Version::from_str(v).unwrap());
+        for str in [
+            ">=1,>=1.5,<3,<2.5",
+            "1.2.*",
+            "!=1.2.*",
+            "~=1.2.3",
+            "<1.2|>=2",
+            "1.2.*,>=1.2.5",
+            ">=1,!=1.2.*",
+            "<1|(>=2,<3)",
+            "1.*|2.*,!=2.5",
+        ] {
+            let spec = spec(str);
+            let simplified = spec.simplify();
+            let reparsed =
+                VersionSpec::from_str(&simplified.to_string(), ParseStrictness::Strict).unwrap();
+            for version in &versions {
+                assert_eq!(
+                    spec.matches(version),
+                    simplified.matches(version),
+                    "{str} ({simplified}) {version}"
+                );
+                assert_eq!(
+                    spec.matches(version),
+                    reparsed.matches(version),
+                    "{str} ({reparsed}) {version}"
+                );
+            }
+        }
+    }
+
+    /// Versions with up to three segments and an optional suffix, e.g. `1.0`, `2.1.0rc1`.
+    fn version_strategy() -> impl Strategy<Value = Version> {
+        (
+            prop::collection::vec(0u64..3, 1..4),
+            prop::option::of(prop::sample::select(vec!["a", "rc", "dev", "post"])),
+            prop::option::of(0u64..2),
+        )
+            .prop_map(|(segments, suffix, number)| {
+                let mut version = segments
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(".");
+                if let Some(suffix) = suffix {
+                    version.push_str(suffix);
+                    if let Some(number) = number {
+                        version.push_str(&number.to_string());
+                    }
+                }
+                Version::from_str(&version).unwrap()
+            })
+    }
+
+    fn spec_strategy() -> impl Strategy<Value = VersionSpec> {
+        let leaf = (0..10usize, version_strategy()).prop_map(|(op, version)| match op {
+            0 => VersionSpec::Exact(EqualityOperator::Equals, version),
+            1 => VersionSpec::Exact(EqualityOperator::NotEquals, version),
+            2 => VersionSpec::Range(RangeOperator::Greater, version),
+            3 => VersionSpec::Range(RangeOperator::GreaterEquals, version),
+            4 => VersionSpec::Range(RangeOperator::Less, version),
+            5 => VersionSpec::Range(RangeOperator::LessEquals, version),
+            6 => VersionSpec::StrictRange(StrictRangeOperator::StartsWith, StrictVersion(version)),
+            7 => {
+                VersionSpec::StrictRange(StrictRangeOperator::NotStartsWith, StrictVersion(version))
+            }
+            8 => VersionSpec::StrictRange(StrictRangeOperator::Compatible, StrictVersion(version)),
+            _ => {
+                VersionSpec::StrictRange(StrictRangeOperator::NotCompatible, StrictVersion(version))
+            }
+        });
+        leaf.prop_recursive(2, 12, 3, |inner| {
+            (any::<bool>(), prop::collection::vec(inner, 2..4)).prop_map(|(and, specs)| {
+                let op = if and {
+                    LogicalOperator::And
+                } else {
+                    LogicalOperator::Or
+                };
+                VersionSpec::Group(op, specs)
+            })
+        })
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(2000))]
+
+        #[test]
+        fn set_operations_preserve_matches(
+            a in spec_strategy(),
+            b in spec_strategy(),
+            versions in prop::collection::vec(version_strategy(), 1..20),
+        ) {
+            let simplified = a.simplify();
+            let intersection = a.intersection(&b);
+            let union = a.union(&b);
+            for version in &versions {
+                prop_assert_eq!(
+                    simplified.matches(version),
+                    a.matches(version),
+                    "{} simplified to {} for {}", a, simplified, version
+                );
+                prop_assert_eq!(
+                    intersection.matches(version),
+                    a.matches(version) && b.matches(version),
+                    "{} and {} intersect to {} for {}", a, b, intersection, version
+                );
+                prop_assert_eq!(
+                    union.matches(version),
+                    a.matches(version) || b.matches(version),
+                    "{} or {} unite to {} for {}", a, b, union, version
+                );
+            }
+        }
+    }
+}
@@ -122,7 +122,7 @@ pub(crate) fn recognize_constraint<'a, E: ParseError<&'a str> + ContextError<&'a
                 )),
                 cut(context("version", recognize_version)),
             ),
-            opt(alt((tag(".*"), tag("*")))),
+            many0(alt((tag(".*"), tag("*")))),
         )),
     ))(input)
 }
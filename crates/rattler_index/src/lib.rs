@@ -0,0 +1,705 @@
+#![deny(missing_docs)]
+
+//! This crate provides the ability to index a conda channel on the local filesystem.
+//!
+//! A channel is a directory that contains a subdirectory for every platform (e.g. `linux-64` or
+//! `noarch`) with `.conda` and `.tar.bz2` packages. [`index`] writes a `repodata.json` (and
+//! compressed `repodata.json.zst` and `repodata.json.bz2` copies) to every subdirectory and a
+//! `channeldata.json` to the root of the channel. The resulting directory can be served as a
+//! channel by any static file server.
+//!
+//! Indexing is incremental. The size and modification time of every package is recorded in a
+//! cache file next to the `repodata.json`. Packages that did not change since the previous run
+//! are not read again, their entries are copied from the existing `repodata.json`.
+
+use fxhash::FxHashMap;
+use rattler_conda_types::{
+    package::{
+        AboutJson, ArchiveType, FileMode, IndexJson, PackageFile, PathsJson, RunExportsJson,
+    },
+    ChannelData, ChannelDataPackage, ChannelInfo, ConvertSubdirError, PackageRecord, Platform,
+    RepoData,
+};
+use rattler_digest::{HashingReader, Md5, Md5Hash, Sha256, Sha256Hash};
+use rattler_package_streaming::{read::stream_tar_bz2, seek::stream_conda_info, ExtractError};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::SystemTime,
+};
+use thiserror::Error;
+
+/// The name of the file in a subdirectory that stores the size and modification time of the
+/// packages that were indexed.
+const CACHE_FILE_NAME: &str = ".index-cache.json";
+
+/// The version of the `repodata.json` format that is written.
+const REPODATA_VERSION: u64 = 1;
+
+/// The version of the `channeldata.json` format that is written.
+const CHANNELDATA_VERSION: u32 = 1;
+
+/// An error that can occur when indexing a channel.
+#[derive(Debug, Error)]
+pub enum IndexError {
+    /// An IO error occurred while reading or writing the channel.
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+
+    /// A package could not be read.
+    #[error("failed to read the package '{0}'")]
+    InvalidPackage(PathBuf, #[source] ExtractError),
+
+    /// A package does not contain an `info/index.json` file.
+    #[error("the package '{0}' does not contain an info/index.json file")]
+    MissingIndexJson(PathBuf),
+
+    /// The subdir of a package could not be determined from its `info/index.json`.
+    #[error("failed to determine the subdir of the package '{0}'")]
+    InvalidSubdir(PathBuf, #[source] ConvertSubdirError),
+
+    /// The subdir in the `info/index.json` of a package differs from the subdirectory that
+    /// contains the package.
+    #[error("the package '{0}' belongs to the '{1}' subdir but is stored in '{2}'")]
+    SubdirMismatch(PathBuf, String, String),
+}
+
+/// Indexes all the subdirectories of the channel at `channel_dir` and writes the
+/// `channeldata.json` of the channel. Only directories that are named after a [`Platform`] are
+/// considered subdirectories. A `noarch` subdirectory is always written because conda clients
+/// expect it to exist.
+///
+/// Packages that cannot be read or that belong to a different subdir are skipped with a warning.
+pub fn index(channel_dir: &Path) -> Result<ChannelData, IndexError> {
+    let mut subdirs = vec![Platform::NoArch.to_string()];
+    for entry in std::fs::read_dir(channel_dir)? {
+        let entry = entry?;
+        let Some(name) = entry.file_name().to_str().map(ToOwned::to_owned) else {
+            continue;
+        };
+        if entry.file_type()?.is_dir()
+            && name != Platform::NoArch.as_str()
+            && Platform::from_str(&name).is_ok()
+        {
+            subdirs.push(name);
+        }
+    }
+    subdirs.sort();
+
+    let mut indexed_subdirs = Vec::with_capacity(subdirs.len());
+    for subdir in subdirs {
+        let subdir_path = channel_dir.join(&subdir);
+        std::fs::create_dir_all(&subdir_path)?;
+        let indexed = index_subdir_impl(&subdir_path, &subdir)?;
+        indexed_subdirs.push((subdir, indexed));
+    }
+
+    let channel_data = build_channel_data(channel_dir, &indexed_subdirs);
+    write_json(&channel_dir.join("channeldata.json"), &channel_data)?;
+    Ok(channel_data)
+}
+
+/// Indexes a single subdirectory of a channel and writes its `repodata.json`,
+/// `repodata.json.zst` and `repodata.json.bz2`. The name of the directory is used as the subdir of
+/// the repodata, packages that belong to a different subdir are skipped.
+pub fn index_subdir(subdir_path: &Path) -> Result<RepoData, IndexError> {
+    let subdir = subdir_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(Platform::NoArch.as_str())
+        .to_owned();
+    Ok(index_subdir_impl(subdir_path, &subdir)?.repodata)
+}
+
+/// The size and modification time of a package when it was last indexed.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+struct FileStat {
+    size: u64,
+    modified: SystemTime,
+}
+
+impl FileStat {
+    fn from_metadata(metadata: &std::fs::Metadata) -> std::io::Result<Self> {
+        Ok(Self {
+            size: metadata.len(),
+            modified: metadata.modified()?,
+        })
+    }
+}
+
+/// The metadata that is read from the info section of a package.
+#[derive(Default)]
+struct PackageInfo {
+    index: Option<IndexJson>,
+    about: Option<AboutJson>,
+    run_exports: Option<RunExportsJson>,
+    paths: Option<PathsJson>,
+}
+
+/// The result of indexing a single subdirectory.
+struct IndexedSubdir {
+    repodata: RepoData,
+
+    /// The metadata of the packages that were read during this run, indexed by filename.
+    package_info: HashMap<String, PackageInfo>,
+}
+
+fn index_subdir_impl(subdir_path: &Path, subdir: &str) -> Result<IndexedSubdir, IndexError> {
+    let previous_repodata = read_json::<RepoData>(&subdir_path.join("repodata.json"));
+    let previous_stats =
+        read_json::<BTreeMap<String, FileStat>>(&subdir_path.join(CACHE_FILE_NAME))
+            .unwrap_or_default();
+
+    let mut repodata = RepoData {
+        info: Some(ChannelInfo {
+            subdir: subdir.to_owned(),
+            base_url: None,
+        }),
+        packages: Default::default(),
+        conda_packages: Default::default(),
+        removed: previous_repodata
+            .as_ref()
+            .map(|repodata| repodata.removed.clone())
+            .unwrap_or_default(),
+        version: Some(REPODATA_VERSION),
+    };
+    let mut stats = BTreeMap::new();
+    let mut package_info = HashMap::new();
+
+    for entry in std::fs::read_dir(subdir_path)? {
+        let entry = entry?;
+        let Some(file_name) = entry.file_name().to_str().map(ToOwned::to_owned) else {
+            continue;
+        };
+        let Some((_, archive_type)) = ArchiveType::split_str(&file_name) else {
+            continue;
+        };
+        let metadata = entry.metadata()?;
+        if !metadata.is_file() {
+            continue;
+        }
+
+        let stat = FileStat::from_metadata(&metadata)?;
+        let previous_record = previous_repodata
+            .as_ref()
+            .and_then(|repodata| packages_of_type(repodata, archive_type).get(&file_name))
+            .filter(|_| previous_stats.get(&file_name) == Some(&stat));
+
+        let record = match previous_record {
+            Some(record) => record.clone(),
+            None => {
+                tracing::debug!("indexing {}/{}", subdir, file_name);
+                let path = entry.path();
+                match read_package_record(&path, subdir, archive_type, stat.size) {
+                    Ok((record, info)) => {
+                        package_info.insert(file_name.clone(), info);
+                        record
+                    }
+                    Err(err) => {
+                        // The stat is not recorded so the package is read again on the next run.
+                        tracing::warn!("skipping {}: {err}", path.display());
+                        continue;
+                    }
+                }
+            }
+        };
+
+        let packages = match archive_type {
+            ArchiveType::TarBz2 => &mut repodata.packages,
+            ArchiveType::Conda => &mut repodata.conda_packages,
+        };
+        packages.insert(file_name.clone(), record);
+        stats.insert(file_name, stat);
+    }
+
+    write_repodata(subdir_path, &repodata)?;
+    write_json(&subdir_path.join(CACHE_FILE_NAME), &stats)?;
+
+    Ok(IndexedSubdir {
+        repodata,
+        package_info,
+    })
+}
+
+/// Returns the packages of the repodata that are stored in the specified archive format.
+fn packages_of_type(
+    repodata: &RepoData,
+    archive_type: ArchiveType,
+) -> &FxHashMap<String, PackageRecord> {
+    match archive_type {
+        ArchiveType::TarBz2 => &repodata.packages,
+        ArchiveType::Conda => &repodata.conda_packages,
+    }
+}
+
+/// Reads the metadata of the package at `path` and computes its hashes. Returns an error if the
+/// package does not belong to `subdir`.
+fn read_package_record(
+    path: &Path,
+    subdir: &str,
+    archive_type: ArchiveType,
+    size: u64,
+) -> Result<(PackageRecord, PackageInfo), IndexError> {
+    let mut info = read_package_info(path, archive_type)?;
+    let index = info
+        .index
+        .take()
+        .ok_or_else(|| IndexError::MissingIndexJson(path.to_path_buf()))?;
+    let (sha256, md5) = compute_hashes(path)?;
+    let record = PackageRecord::from_index_json(index, Some(size), Some(sha256), Some(md5))
+        .map_err(|err| IndexError::InvalidSubdir(path.to_path_buf(), err))?;
+    if record.subdir != subdir {
+        return Err(IndexError::SubdirMismatch(
+            path.to_path_buf(),
+            record.subdir,
+            subdir.to_owned(),
+        ));
+    }
+    Ok((record, info))
+}
+
+/// Reads the files from the info section of a package that are required to index it.
+fn read_package_info(path: &Path, archive_type: ArchiveType) -> Result<PackageInfo, IndexError> {
+    let file = File::open(path)?;
+    let result = match archive_type {
+        ArchiveType::TarBz2 => read_info_entries(stream_tar_bz2(file)),
+        ArchiveType::Conda => stream_conda_info(file).and_then(read_info_entries),
+    };
+    result.map_err(|err| IndexError::InvalidPackage(path.to_path_buf(), err))
+}
+
+fn read_info_entries(mut archive: tar::Archive<impl Read>) -> Result<PackageInfo, ExtractError> {
+    let mut info = PackageInfo::default();
+    for entry in archive.entries()? {
+        let entry = entry?;
+        let path = entry.path()?.into_owned();
+        if path == IndexJson::package_path() {
+            info.index = Some(IndexJson::from_reader(entry)?);
+        } else if path == AboutJson::package_path() {
+            info.about = Some(AboutJson::from_reader(entry)?);
+        } else if path == RunExportsJson::package_path() {
+            info.run_exports = Some(RunExportsJson::from_reader(entry)?);
+        } else if path == PathsJson::package_path() {
+            info.paths = Some(PathsJson::from_reader(entry)?);
+        }
+
+        // Stop early to avoid decompressing the rest of a `.tar.bz2` archive.
+        if info.index.is_some()
+            && info.about.is_some()
+            && info.run_exports.is_some()
+            && info.paths.is_some()
+        {
+            break;
+        }
+    }
+    Ok(info)
+}
+
+/// Computes the sha256 and md5 hash of a file in a single pass.
+fn compute_hashes(path: &Path) -> std::io::Result<(Sha256Hash, Md5Hash)> {
+    let reader = HashingReader::<_, Md5>::new(File::open(path)?);
+    let mut reader = HashingReader::<_, Sha256>::new(reader);
+    std::io::copy(&mut reader, &mut std::io::sink())?;
+    let (reader, sha256) = reader.finalize();
+    let (_, md5) = reader.finalize();
+    Ok((sha256, md5))
+}
+
+/// Writes the `repodata.json` and its compressed variants.
+fn write_repodata(subdir_path: &Path, repodata: &RepoData) -> std::io::Result<()> {
+    let bytes = serde_json::to_vec(repodata)?;
+    std::fs::write(subdir_path.join("repodata.json"), &bytes)?;
+
+    let compressed = zstd::encode_all(bytes.as_slice(), zstd::DEFAULT_COMPRESSION_LEVEL)?;
+    std::fs::write(subdir_path.join("repodata.json.zst"), compressed)?;
+
+    let mut encoder = bzip2::write::BzEncoder::new(
+        File::create(subdir_path.join("repodata.json.bz2"))?,
+        bzip2::Compression::default(),
+    );
+    encoder.write_all(&bytes)?;
+    encoder.finish()?;
+
+    Ok(())
+}
+
+/// Builds the `channeldata.json` from the indexed subdirectories. Entries of an existing
+/// `channeldata.json` are reused if the latest version of the package did not change.
+fn build_channel_data(channel_dir: &Path, subdirs: &[(String, IndexedSubdir)]) -> ChannelData {
+    let mut previous = read_json::<ChannelData>(&channel_dir.join("channeldata.json"))
+        .map(|channel_data| channel_data.packages)
+        .unwrap_or_default();
+
+    // Find the latest record of every package and the subdirs that contain the package.
+    let mut latest: BTreeMap<&str, (&str, &IndexedSubdir, &str, &PackageRecord)> = BTreeMap::new();
+    let mut package_subdirs: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for (subdir, indexed) in subdirs {
+        let repodata = &indexed.repodata;
+        for (file_name, record) in repodata.packages.iter().chain(&repodata.conda_packages) {
+            let name = record.name.as_normalized();
+            let is_newer = latest.get(name).map_or(true, |(_, _, _, current)| {
+                (&record.version, record.build_number, record.timestamp)
+                    > (&current.version, current.build_number, current.timestamp)
+            });
+            if is_newer {
+                latest.insert(name, (subdir, indexed, file_name, record));
+            }
+            let subdirs = package_subdirs.entry(name).or_default();
+            if !subdirs.contains(subdir) {
+                subdirs.push(subdir.clone());
+            }
+        }
+    }
+
+    let mut packages = HashMap::with_capacity(latest.len());
+    for (name, (subdir, indexed, file_name, record)) in latest {
+        let subdirs = package_subdirs.remove(name).unwrap_or_default();
+        let timestamp = record
+            .timestamp
+            .and_then(|timestamp| u64::try_from(timestamp.timestamp()).ok());
+
+        let package = match previous.remove(name) {
+            Some(mut package)
+                if package.version.as_ref() == Some(record.version.version())
+                    && package.timestamp == timestamp =>
+            {
+                package.subdirs = subdirs;
+                package
+            }
+            _ => {
+                // The metadata is only kept for packages that were read during this run, other
+                // packages have to be read again. If that fails the entry is written without the
+                // metadata from the info section.
+                let read_info;
+                let info = match indexed.package_info.get(file_name) {
+                    Some(info) => info,
+                    None => {
+                        let path = channel_dir.join(subdir).join(file_name);
+                        let archive_type = ArchiveType::try_from(&path)
+                            .expect("the repodata only contains package archives");
+                        read_info = read_package_info(&path, archive_type).unwrap_or_else(|err| {
+                            tracing::warn!(
+                                "failed to read the metadata of {}: {err}",
+                                path.display()
+                            );
+                            PackageInfo::default()
+                        });
+                        &read_info
+                    }
+                };
+                channel_data_package(record, info, subdirs, timestamp)
+            }
+        };
+        packages.insert(name.to_owned(), package);
+    }
+
+    ChannelData {
+        channeldata_version: CHANNELDATA_VERSION,
+        packages,
+        subdirs: subdirs.iter().map(|(subdir, _)| subdir.clone()).collect(),
+    }
+}
+
+/// Constructs the `channeldata.json` entry of a package from its latest record.
+fn channel_data_package(
+    record: &PackageRecord,
+    info: &PackageInfo,
+    subdirs: Vec<String>,
+    timestamp: Option<u64>,
+) -> ChannelDataPackage {
+    let about = info.about.as_ref();
+    let paths = info.paths.as_ref().map_or(&[][..], |paths| &paths.paths);
+    let has_path = |predicate: &dyn Fn(&Path) -> bool| {
+        paths.iter().any(|entry| predicate(&entry.relative_path))
+    };
+    let has_script = |action: &str| {
+        let file_name = format!(".{}-{action}", record.name.as_normalized());
+        has_path(&|path| {
+            (path.starts_with("bin") || path.starts_with("Scripts"))
+                && path
+                    .file_stem()
+                    .is_some_and(|stem| stem.to_string_lossy() == file_name)
+        })
+    };
+    let has_prefix = |mode: FileMode| {
+        paths.iter().any(|entry| {
+            entry
+                .prefix_placeholder
+                .as_ref()
+                .is_some_and(|placeholder| placeholder.file_mode == mode)
+        })
+    };
+
+    ChannelDataPackage {
+        has_activate_scripts: has_path(&|path| path.starts_with("etc/conda/activate.d")),
+        has_deactivate_scripts: has_path(&|path| path.starts_with("etc/conda/deactivate.d")),
+        binary_prefix: has_prefix(FileMode::Binary),
+        text_prefix: has_prefix(FileMode::Text),
+        has_post_link_scripts: has_script("post-link"),
+        has_pre_link_scripts: has_script("pre-link"),
+        has_pre_unlink_scripts: has_script("pre-unlink"),
+        description: about.and_then(|about| about.description.clone()),
+        summary: about.and_then(|about| about.summary.clone()),
+        dev_url: about.map(|about| about.dev_url.clone()).unwrap_or_default(),
+        doc_url: about.map(|about| about.doc_url.clone()).unwrap_or_default(),
+        home: about.map(|about| about.home.clone()).unwrap_or_default(),
+        source_url: about
+            .and_then(|about| about.source_url.clone())
+            .into_iter()
+            .collect(),
+        license: record
+            .license
+            .clone()
+            .or_else(|| about.and_then(|about| about.license.clone())),
+        run_exports: info
+            .run_exports
+            .iter()
+            .map(|run_exports| (record.version.version().clone(), run_exports.clone()))
+            .collect(),
+        subdirs,
+        timestamp,
+        version: Some(record.version.version().clone()),
+    }
+}
+
+/// Reads and deserializes a json file. Returns `None` if the file does not exist or cannot be
+/// parsed, in which case the file is regenerated from scratch.
+fn read_json<T: DeserializeOwned>(path: &Path) -> Option<T> {
+    let contents = std::fs::read(path).ok()?;
+    match serde_json::from_slice(&contents) {
+        Ok(value) => Some(value),
+        Err(err) => {
+            tracing::warn!("ignoring invalid {}: {err}", path.display());
+            None
+        }
+    }
+}
+
+fn write_json(path: &Path, value: &impl Serialize) -> std::io::Result<()> {
+    std::fs::write(path, serde_json::to_vec_pretty(value)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{index, CACHE_FILE_NAME};
+    use rattler_conda_types::{package::ArchiveType, RepoData};
+    use rattler_digest::{compute_file_digest, Sha256};
+    use rattler_package_streaming::write::{
+        write_conda_package, write_tar_bz2_package, CompressionLevel,
+    };
+    use std::{
+        fs::File,
+        io::Read,
+        path::{Path, PathBuf},
+    };
+
+    /// Writes a minimal package to `channel_dir/subdir` and returns its path.
+    fn write_package(
+        channel_dir: &Path,
+        subdir: &str,
+        name: &str,
+        version: &str,
+        archive_type: ArchiveType,
+    ) -> PathBuf {
+        let source = tempfile::tempdir().unwrap();
+        let files = [
+            (
+                "info/index.json",
+                serde_json::json!({
+                    "name": name,
+                    "version": version,
+                    "build": "0",
+                    "build_number": 0,
+                    "subdir": subdir,
+                    "license": "MIT",
+                })
+                .to_string(),
+            ),
+            (
+                "info/about.json",
+                serde_json::json!({ "summary": format!("The {name} package") }).to_string(),
+            ),
+            (
+                "info/paths.json",
+                serde_json::json!({
+                    "paths": [{
+                        "_path": "etc/conda/activate.d/activate.sh",
+                        "path_type": "hardlink",
+                    }],
+                    "paths_version": 1,
+                })
+                .to_string(),
+            ),
+            ("etc/conda/activate.d/activate.sh", String::from("echo hi")),
+        ];
+        let mut paths = Vec::new();
+        for (path, contents) in files {
+            let path = source.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, contents).unwrap();
+            paths.push(path);
+        }
+
+        let out_name = format!("{name}-{version}-0");
+        let package_path = channel_dir
+            .join(subdir)
+            .join(format!("{out_name}{}", archive_type.extension()));
+        std::fs::create_dir_all(package_path.parent().unwrap()).unwrap();
+        let file = File::create(&package_path).unwrap();
+        match archive_type {
+            ArchiveType::TarBz2 => {
+                write_tar_bz2_package(file, source.path(), &paths, CompressionLevel::Default, None)
+            }
+            ArchiveType::Conda => write_conda_package(
+                file,
+                source.path(),
+                &paths,
+                CompressionLevel::Default,
+                &out_name,
+                None,
+            ),
+        }
+        .unwrap();
+        package_path
+    }
+
+    fn read_repodata(channel_dir: &Path, subdir: &str) -> RepoData {
+        let contents = std::fs::read(channel_dir.join(subdir).join("repodata.json")).unwrap();
+        serde_json::from_slice(&contents).unwrap()
+    }
+
+    #[test]
+    fn test_index() {
+        let channel = tempfile::tempdir().unwrap();
+        let foo = write_package(channel.path(), "noarch", "foo", "1.0", ArchiveType::TarBz2);
+        write_package(channel.path(), "linux-64", "foo", "2.0", ArchiveType::Conda);
+        write_package(channel.path(), "linux-64", "bar", "1.0", ArchiveType::Conda);
+        std::fs::create_dir(channel.path().join("not-a-platform")).unwrap();
+
+        let channel_data = index(channel.path()).unwrap();
+        assert_eq!(channel_data.subdirs, vec!["linux-64", "noarch"]);
+
+        let noarch = read_repodata(channel.path(), "noarch");
+        assert_eq!(noarch.info.unwrap().subdir, "noarch");
+        assert!(noarch.conda_packages.is_empty());
+        let record = &noarch.packages["foo-1.0-0.tar.bz2"];
+        assert_eq!(record.name.as_normalized(), "foo");
+        assert_eq!(record.size, Some(std::fs::metadata(&foo).unwrap().len()));
+        assert_eq!(
+            record.sha256,
+            Some(compute_file_digest::<Sha256>(&foo).unwrap())
+        );
+        assert!(record.md5.is_some());
+
+        let linux = read_repodata(channel.path(), "linux-64");
+        assert!(linux.packages.is_empty());
+        assert_eq!(linux.conda_packages.len(), 2);
+
+        // The compressed variants must contain the same repodata.
+        let subdir = channel.path().join("linux-64");
+        let json = std::fs::read(subdir.join("repodata.json")).unwrap();
+        let zst = std::fs::read(subdir.join("repodata.json.zst")).unwrap();
+        assert_eq!(zstd::decode_all(zst.as_slice()).unwrap(), json);
+        let mut bz2 = Vec::new();
+        bzip2::read::BzDecoder::new(File::open(subdir.join("repodata.json.bz2")).unwrap())
+            .read_to_end(&mut bz2)
+            .unwrap();
+        assert_eq!(bz2, json);
+
+        let foo = &channel_data.packages["foo"];
+        assert_eq!(foo.version.as_ref().unwrap().to_string(), "2.0");
+        assert_eq!(foo.subdirs, vec!["linux-64", "noarch"]);
+        assert_eq!(foo.summary.as_deref(), Some("The foo package"));
+        assert_eq!(foo.license.as_deref(), Some("MIT"));
+        assert!(foo.has_activate_scripts);
+        assert!(!foo.has_deactivate_scripts);
+        assert_eq!(channel_data.packages["bar"].subdirs, vec!["linux-64"]);
+        assert!(channel.path().join("channeldata.json").is_file());
+    }
+
+    #[test]
+    fn test_incremental_index() {
+        let channel = tempfile::tempdir().unwrap();
+        let foo = write_package(channel.path(), "linux-64", "foo", "1.0", ArchiveType::Conda);
+        index(channel.path()).unwrap();
+        assert!(channel.path().join("noarch/repodata.json").is_file());
+        assert!(channel
+            .path()
+            .join("linux-64")
+            .join(CACHE_FILE_NAME)
+            .is_file());
+
+        // Modify the existing entry, it should be reused because the package did not change.
+        let repodata_path = channel.path().join("linux-64/repodata.json");
+        let mut repodata = read_repodata(channel.path(), "linux-64");
+        repodata
+            .conda_packages
+            .get_mut("foo-1.0-0.conda")
+            .unwrap()
+            .license = Some(String::from("reused"));
+        std::fs::write(&repodata_path, serde_json::to_vec(&repodata).unwrap()).unwrap();
+        index(channel.path()).unwrap();
+        let repodata = read_repodata(channel.path(), "linux-64");
+        assert_eq!(
+            repodata.conda_packages["foo-1.0-0.conda"]
+                .license
+                .as_deref(),
+            Some("reused")
+        );
+
+        // Overwriting the package with different contents invalidates the entry.
+        std::fs::remove_file(&foo).unwrap();
+        let source = write_package(
+            channel.path(),
+            "linux-64",
+            "foo",
+            "1.0.1",
+            ArchiveType::Conda,
+        );
+        std::fs::rename(source, &foo).unwrap();
+        index(channel.path()).unwrap();
+        let repodata = read_repodata(channel.path(), "linux-64");
+        let record = &repodata.conda_packages["foo-1.0-0.conda"];
+        assert_eq!(record.license.as_deref(), Some("MIT"));
+        assert_eq!(record.version.to_string(), "1.0.1");
+
+        // Removed packages are removed from the index.
+        std::fs::remove_file(&foo).unwrap();
+        let channel_data = index(channel.path()).unwrap();
+        assert!(read_repodata(channel.path(), "linux-64")
+            .conda_packages
+            .is_empty());
+        assert!(channel_data.packages.is_empty());
+    }
+
+    #[test]
+    fn test_skip_invalid_packages() {
+        let channel = tempfile::tempdir().unwrap();
+        write_package(channel.path(), "linux-64", "foo", "1.0", ArchiveType::Conda);
+        std::fs::write(channel.path().join("linux-64/broken-1.0-0.conda"), "broken").unwrap();
+        std::fs::create_dir(channel.path().join("noarch")).unwrap();
+        std::fs::write(channel.path().join("noarch/broken-1.0-0.tar.bz2"), "broken").unwrap();
+
+        // A package that is stored in the wrong subdirectory.
+        let misplaced = write_package(channel.path(), "linux-64", "bar", "1.0", ArchiveType::Conda);
+        std::fs::rename(&misplaced, channel.path().join("noarch/bar-1.0-0.conda")).unwrap();
+
+        let channel_data = index(channel.path()).unwrap();
+        let linux = read_repodata(channel.path(), "linux-64");
+        assert_eq!(
+            linux.conda_packages.keys().collect::<Vec<_>>(),
+            vec!["foo-1.0-0.conda"]
+        );
+        let noarch = read_repodata(channel.path(), "noarch");
+        assert!(noarch.packages.is_empty());
+        assert!(noarch.conda_packages.is_empty());
+        assert_eq!(
+            channel_data.packages.keys().collect::<Vec<_>>(),
+            vec!["foo"]
+        );
+    }
+}
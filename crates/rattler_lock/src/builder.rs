@@ -434,12 +434,12 @@ pub struct PipLockedDependencyBuilder {
 #[cfg(test)]
 mod tests {
     use chrono::Utc;
-    use std::str::FromStr;
 
     use crate::builder::{CondaLockedDependencyBuilder, LockFileBuilder, LockedPackagesBuilder};
     use crate::PackageHashes;
     use rattler_conda_types::{
-        ChannelConfig, MatchSpec, NoArchType, PackageName, Platform, RepoDataRecord,
+        ChannelConfig, MatchSpec, NoArchType, PackageName, ParseStrictness, Platform,
+        RepoDataRecord,
     };
     use rattler_digest::parse_digest_from_hex;
 
@@ -449,7 +449,7 @@ mod tests {
         let lock = LockFileBuilder::new(
             ["conda_forge"],
             [Platform::Osx64],
-            [MatchSpec::from_str("python =3.11.0").unwrap()]
+            [MatchSpec::from_str("python =3.11.0", ParseStrictness::Strict).unwrap()]
         )
             .add_locked_packages(LockedPackagesBuilder::new(Platform::Osx64)
                 .with_locked_package(CondaLockedDependencyBuilder {
@@ -120,16 +120,14 @@ pub fn calculate_content_hash(
 
 #[cfg(test)]
 mod tests {
-    use std::str::FromStr;
-
     use crate::content_hash;
-    use rattler_conda_types::{MatchSpec, Platform};
+    use rattler_conda_types::{MatchSpec, ParseStrictness, Platform};
 
     #[test]
     fn test_content_data() {
         let output = content_hash::calculate_content_data(
             &Platform::Osx64,
-            &[MatchSpec::from_str("python =3.11.0").unwrap()],
+            &[MatchSpec::from_str("python =3.11.0", ParseStrictness::Strict).unwrap()],
             &["conda-forge".into()],
         );
 
@@ -149,7 +147,7 @@ mod tests {
     fn test_content_hash() {
         let output = content_hash::calculate_content_hash(
             &Platform::Osx64,
-            &[MatchSpec::from_str("python =3.11.0").unwrap()],
+            &[MatchSpec::from_str("python =3.11.0", ParseStrictness::Strict).unwrap()],
             &["conda-forge".into()],
         )
         .unwrap();
@@ -21,7 +21,7 @@ mod utils;
 use crate::conda::ConversionError;
 pub use conda::CondaLockedDependency;
 pub use hash::PackageHashes;
-pub use pip::PipLockedDependency;
+pub use pip::{PipLockedDependency, RequiresPythonError};
 
 /// Represents the conda-lock file
 /// Contains the metadata regarding the lock files
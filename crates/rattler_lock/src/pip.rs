@@ -1,7 +1,10 @@
 use crate::PackageHashes;
+use pep440_rs::{Pep440Error, VersionSpecifiers};
+use rattler_conda_types::{python_matches_requires_python, PackageRecord, Pep440ConversionError};
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, skip_serializing_none};
-use std::collections::HashSet;
+use std::{collections::HashSet, str::FromStr};
+use thiserror::Error;
 use url::Url;
 
 /// A pinned Pip package
@@ -33,3 +36,66 @@ pub struct PipLockedDependency {
     /// Build string
     pub build: Option<String>,
 }
+
+/// An error that can occur when checking the `requires_python` of a [`PipLockedDependency`].
+#[derive(Debug, Error)]
+pub enum RequiresPythonError {
+    /// The `requires_python` field does not contain valid PEP 440 version specifiers.
+    #[error(transparent)]
+    InvalidSpecifiers(#[from] Pep440Error),
+
+    /// The version of the python record cannot be compared with PEP 440 specifiers.
+    #[error(transparent)]
+    ConversionError(#[from] Pep440ConversionError),
+}
+
+impl PipLockedDependency {
+    /// Returns true if the version of the conda `python` record satisfies the `requires_python`
+    /// of this package. A package without `requires_python` is compatible with any python.
+    pub fn is_compatible_with_python(
+        &self,
+        python: &PackageRecord,
+    ) -> Result<bool, RequiresPythonError> {
+        let Some(requires_python) = &self.requires_python else {
+            return Ok(true);
+        };
+        let requires_python = VersionSpecifiers::from_str(requires_python)?;
+        Ok(python_matches_requires_python(python, &requires_python)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::PipLockedDependency;
+    use rattler_conda_types::{PackageName, PackageRecord, Version};
+    use std::str::FromStr;
+    use url::Url;
+
+    #[test]
+    fn test_is_compatible_with_python() {
+        let mut dependency = PipLockedDependency {
+            requires_dist: vec![],
+            requires_python: None,
+            extras: Default::default(),
+            url: Url::parse("https://files.pythonhosted.org/foo-1.0-py3-none-any.whl").unwrap(),
+            hash: None,
+            source: None,
+            build: None,
+        };
+        let python = PackageRecord::new(
+            PackageName::new_unchecked("python"),
+            Version::from_str("3.9.18").unwrap(),
+            String::from("h0755675_0_cpython"),
+        );
+        assert!(dependency.is_compatible_with_python(&python).unwrap());
+
+        dependency.requires_python = Some(String::from(">=3.8"));
+        assert!(dependency.is_compatible_with_python(&python).unwrap());
+
+        dependency.requires_python = Some(String::from(">=3.10, <4"));
+        assert!(!dependency.is_compatible_with_python(&python).unwrap());
+
+        dependency.requires_python = Some(String::from(">=>3.10"));
+        assert!(dependency.is_compatible_with_python(&python).is_err());
+    }
+}
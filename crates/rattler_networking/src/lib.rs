@@ -3,12 +3,15 @@
 //! Networking utilities for Rattler, specifically authenticating requests
 
 use std::path::{Path, PathBuf};
-use std::sync::OnceLock;
+use std::sync::{Arc, OnceLock};
 
 pub use authentication_storage::{authentication::Authentication, storage::AuthenticationStorage};
+pub use mirrors::MirrorMap;
 use reqwest::{Client, IntoUrl, Method, Url};
 
 pub mod authentication_storage;
+pub mod mirrors;
+pub mod oci;
 pub mod retry_policies;
 
 /// A client that can be used to make authenticated requests, based on the [`reqwest::Client`].
@@ -20,6 +23,12 @@ pub struct AuthenticatedClient {
 
     /// The authentication storage
     auth_storage: AuthenticationStorage,
+
+    /// The mirrors that are used by [`AuthenticatedClient::send_with_mirrors`]
+    mirrors: Arc<MirrorMap>,
+
+    /// The tokens that have been obtained from OCI registries
+    oci_tokens: Arc<oci::TokenCache>,
 }
 
 /// Returns the default auth storage directory used by rattler.
@@ -49,8 +58,23 @@ impl AuthenticatedClient {
         AuthenticatedClient {
             client,
             auth_storage,
+            mirrors: Arc::default(),
+            oci_tokens: Arc::default(),
         }
     }
+
+    /// Sets the mirrors that are used by [`AuthenticatedClient::send_with_mirrors`].
+    pub fn with_mirrors(self, mirrors: MirrorMap) -> AuthenticatedClient {
+        AuthenticatedClient {
+            mirrors: Arc::new(mirrors),
+            ..self
+        }
+    }
+
+    /// Returns the mirrors that are used by this client.
+    pub fn mirrors(&self) -> &MirrorMap {
+        &self.mirrors
+    }
 }
 
 impl AuthenticatedClient {
@@ -69,6 +93,59 @@ impl AuthenticatedClient {
         self.request(Method::HEAD, url)
     }
 
+    /// Sends a request for the given URL to the mirrors of the URL (see [`MirrorMap`]) or to the
+    /// URL itself if it is not mirrored. If a mirror cannot be reached or responds with a server
+    /// error the request is sent to the next mirror. The `configure` function is called for the
+    /// request to every mirror and can be used to add headers.
+    ///
+    /// URLs with the `oci` scheme are requested from an OCI registry (see [`oci`]).
+    ///
+    /// Returns the URL that the request was sent to and the response of that mirror.
+    pub async fn send_with_mirrors(
+        &self,
+        method: Method,
+        url: &Url,
+        configure: impl Fn(reqwest::RequestBuilder) -> reqwest::RequestBuilder,
+    ) -> Result<(Url, reqwest::Response), reqwest::Error> {
+        let candidates = self.mirrors.candidates(url);
+        let (last, others) = candidates
+            .split_last()
+            .expect("there is always at least one candidate");
+        for candidate in others {
+            let result = self.send_to(method.clone(), candidate, &configure).await;
+            if !mirrors::should_fail_over(result.as_ref().map(reqwest::Response::status)) {
+                return result.map(|response| (candidate.clone(), response));
+            }
+            match &result {
+                Ok(response) => tracing::warn!(
+                    "'{candidate}' responded with {}, trying the next mirror",
+                    response.status()
+                ),
+                Err(err) => {
+                    tracing::warn!("failed to reach '{candidate}': {err}, trying the next mirror")
+                }
+            }
+        }
+
+        // The last mirror (or the original url if it is not mirrored) is the final attempt.
+        self.send_to(method, last, &configure)
+            .await
+            .map(|response| (last.clone(), response))
+    }
+
+    /// Sends a request to a single URL.
+    async fn send_to(
+        &self,
+        method: Method,
+        url: &Url,
+        configure: &impl Fn(reqwest::RequestBuilder) -> reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, reqwest::Error> {
+        if url.scheme() == "oci" {
+            return oci::send(self, method, url, configure).await;
+        }
+        configure(self.request(method, url.clone())).send().await
+    }
+
     /// Create a request builder for the given URL (see also [`reqwest::Client::request`])
     pub fn request<U: IntoUrl>(&self, method: Method, url: U) -> reqwest::RequestBuilder {
         let url_clone = url.as_str().to_string();
@@ -135,6 +212,12 @@ pub struct AuthenticatedClientBlocking {
 
     /// The authentication storage
     auth_storage: AuthenticationStorage,
+
+    /// The mirrors that are used by [`AuthenticatedClientBlocking::send_with_mirrors`]
+    mirrors: MirrorMap,
+
+    /// The bearer tokens that have been obtained from OCI registries
+    oci_tokens: oci::TokenCache,
 }
 
 #[cfg(feature = "blocking")]
@@ -147,8 +230,20 @@ impl AuthenticatedClientBlocking {
         AuthenticatedClientBlocking {
             client,
             auth_storage,
+            mirrors: MirrorMap::default(),
+            oci_tokens: oci::TokenCache::default(),
         }
     }
+
+    /// Sets the mirrors that are used by [`AuthenticatedClientBlocking::send_with_mirrors`].
+    pub fn with_mirrors(self, mirrors: MirrorMap) -> AuthenticatedClientBlocking {
+        AuthenticatedClientBlocking { mirrors, ..self }
+    }
+
+    /// Returns the mirrors that are used by this client.
+    pub fn mirrors(&self) -> &MirrorMap {
+        &self.mirrors
+    }
 }
 
 #[cfg(feature = "blocking")]
@@ -168,6 +263,49 @@ impl AuthenticatedClientBlocking {
         self.request(Method::HEAD, url)
     }
 
+    /// Sends a request for the given URL to the mirrors of the URL, see
+    /// [`AuthenticatedClient::send_with_mirrors`].
+    ///
+    /// URLs with the `oci` scheme are requested from an OCI registry (see [`oci`]). Unlike the
+    /// async client, the blocking client reads the content of a blob completely to verify it
+    /// before the response is returned.
+    pub fn send_with_mirrors(
+        &self,
+        method: Method,
+        url: &Url,
+        configure: impl Fn(reqwest::blocking::RequestBuilder) -> reqwest::blocking::RequestBuilder,
+    ) -> Result<(Url, reqwest::blocking::Response), reqwest::Error> {
+        let candidates = self.mirrors.candidates(url);
+        let (last, others) = candidates
+            .split_last()
+            .expect("there is always at least one candidate");
+        for candidate in others {
+            let result = self.send_to(method.clone(), candidate, &configure);
+            if !mirrors::should_fail_over(result.as_ref().map(reqwest::blocking::Response::status))
+            {
+                return result.map(|response| (candidate.clone(), response));
+            }
+            tracing::warn!("failed to fetch '{candidate}', trying the next mirror");
+        }
+
+        // The last mirror (or the original url if it is not mirrored) is the final attempt.
+        self.send_to(method, last, &configure)
+            .map(|response| (last.clone(), response))
+    }
+
+    /// Sends a request to a single URL.
+    fn send_to(
+        &self,
+        method: Method,
+        url: &Url,
+        configure: &impl Fn(reqwest::blocking::RequestBuilder) -> reqwest::blocking::RequestBuilder,
+    ) -> Result<reqwest::blocking::Response, reqwest::Error> {
+        if url.scheme() == "oci" {
+            return oci::send_blocking(self, method, url, configure);
+        }
+        configure(self.request(method, url.clone())).send()
+    }
+
     /// Create a request builder for the given URL (see also [`reqwest::blocking::Client::request`])
     pub fn request<U: IntoUrl>(&self, method: Method, url: U) -> reqwest::blocking::RequestBuilder {
         let url_clone = url.as_str().to_string();
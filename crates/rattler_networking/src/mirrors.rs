@@ -0,0 +1,166 @@
+//! Support for serving channels from one or more mirrors.
+//!
+//! A [`MirrorMap`] maps url prefixes (e.g. `https://conda.anaconda.org/conda-forge/`) to an
+//! ordered list of mirrors. Requests that are sent through
+//! [`crate::AuthenticatedClient::send_with_mirrors`] are rewritten to the first mirror and fail
+//! over to the next mirror if a mirror cannot be reached or responds with a server error.
+//!
+//! Because the authentication information is looked up by the host of the rewritten url, every
+//! mirror can use its own credentials.
+
+use reqwest::Url;
+
+/// Maps url prefixes to an ordered list of mirrors.
+#[derive(Debug, Clone, Default)]
+pub struct MirrorMap {
+    /// The prefixes and their mirrors. All urls end with a slash.
+    entries: Vec<(Url, Vec<Url>)>,
+}
+
+impl MirrorMap {
+    /// Constructs an empty map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers mirrors for all urls that start with `prefix`. The mirrors are tried in the
+    /// specified order. The original url is not used, unless it is also part of `mirrors` or no
+    /// valid url can be constructed for any of the mirrors (see [`MirrorMap::candidates`]).
+    ///
+    /// Registering mirrors for a prefix that already has mirrors replaces them. An empty list of
+    /// mirrors removes the prefix.
+    pub fn insert(&mut self, prefix: Url, mirrors: Vec<Url>) {
+        let prefix = add_trailing_slash(prefix);
+        self.entries.retain(|(existing, _)| existing != &prefix);
+        if !mirrors.is_empty() {
+            let mirrors = mirrors.into_iter().map(add_trailing_slash).collect();
+            self.entries.push((prefix, mirrors));
+        }
+    }
+
+    /// Returns true if no mirrors have been registered.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the mirrors of the longest prefix that matches `url`, if any.
+    pub fn mirrors_for(&self, url: &Url) -> Option<(&Url, &[Url])> {
+        self.entries
+            .iter()
+            .filter(|(prefix, _)| url.as_str().starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.as_str().len())
+            .map(|(prefix, mirrors)| (prefix, mirrors.as_slice()))
+    }
+
+    /// Returns the urls that should be tried, in order, to request `url`. Mirrors for which the
+    /// combination with the remainder of `url` is not a valid url are skipped. If there are no
+    /// mirrors for the url, or none of them result in a valid url, the result only contains `url`
+    /// itself.
+    pub fn candidates(&self, url: &Url) -> Vec<Url> {
+        let Some((prefix, mirrors)) = self.mirrors_for(url) else {
+            return vec![url.clone()];
+        };
+
+        let remainder = &url.as_str()[prefix.as_str().len()..];
+        let candidates: Vec<_> = mirrors
+            .iter()
+            .filter_map(|mirror| match Url::parse(&format!("{mirror}{remainder}")) {
+                Ok(candidate) => Some(candidate),
+                Err(err) => {
+                    tracing::warn!("skipping mirror '{mirror}' for '{url}': {err}");
+                    None
+                }
+            })
+            .collect();
+        if candidates.is_empty() {
+            vec![url.clone()]
+        } else {
+            candidates
+        }
+    }
+}
+
+/// Returns true if a request that resulted in `status` should be retried on the next mirror. This
+/// is the case if the mirror could not be reached or if it responded with a server error.
+pub(crate) fn should_fail_over(status: Result<reqwest::StatusCode, &reqwest::Error>) -> bool {
+    match status {
+        Ok(status) => status.is_server_error(),
+        Err(err) => err.is_connect() || err.is_timeout(),
+    }
+}
+
+fn add_trailing_slash(mut url: Url) -> Url {
+    if !url.path().ends_with('/') {
+        url.set_path(&format!("{}/", url.path()));
+    }
+    url
+}
+
+#[cfg(test)]
+mod test {
+    use super::MirrorMap;
+    use reqwest::Url;
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    #[test]
+    fn test_candidates() {
+        let mut mirrors = MirrorMap::new();
+        mirrors.insert(
+            url("https://conda.anaconda.org/conda-forge"),
+            vec![
+                url("https://mirror-a.example.com/conda-forge"),
+                url("https://mirror-b.example.com/mirrors/conda-forge/"),
+            ],
+        );
+        mirrors.insert(
+            url("https://conda.anaconda.org/conda-forge/label/dev/"),
+            vec![url("https://dev.example.com/")],
+        );
+
+        assert_eq!(
+            mirrors.candidates(&url(
+                "https://conda.anaconda.org/conda-forge/linux-64/repodata.json"
+            )),
+            vec![
+                url("https://mirror-a.example.com/conda-forge/linux-64/repodata.json"),
+                url("https://mirror-b.example.com/mirrors/conda-forge/linux-64/repodata.json"),
+            ]
+        );
+
+        // The longest prefix wins
+        assert_eq!(
+            mirrors.candidates(&url(
+                "https://conda.anaconda.org/conda-forge/label/dev/noarch/repodata.json"
+            )),
+            vec![url("https://dev.example.com/noarch/repodata.json")]
+        );
+
+        // Prefixes only match complete path segments
+        assert_eq!(
+            mirrors.candidates(&url(
+                "https://conda.anaconda.org/conda-forge-extra/noarch/repodata.json"
+            )),
+            vec![url(
+                "https://conda.anaconda.org/conda-forge-extra/noarch/repodata.json"
+            )]
+        );
+
+        // Removing the mirrors
+        mirrors.insert(
+            url("https://conda.anaconda.org/conda-forge/label/dev"),
+            vec![],
+        );
+        assert_eq!(
+            mirrors.candidates(&url(
+                "https://conda.anaconda.org/conda-forge/label/dev/noarch/repodata.json"
+            )),
+            vec![
+                url("https://mirror-a.example.com/conda-forge/label/dev/noarch/repodata.json"),
+                url("https://mirror-b.example.com/mirrors/conda-forge/label/dev/noarch/repodata.json"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,905 @@
+//! Support for channels that are stored as artifacts in an OCI registry.
+//!
+//! A channel url like `oci://ghcr.io/channel-mirrors/conda-forge` refers to the
+//! `channel-mirrors/conda-forge` namespace of the `ghcr.io` registry. Every file of the channel is
+//! stored as a layer of an artifact in its own repository:
+//!
+//! | File                                       | Repository                           | Tag                 |
+//! |--------------------------------------------|--------------------------------------|---------------------|
+//! | `{subdir}/repodata.json[.zst\|.bz2]`       | `{namespace}/{subdir}/repodata.json` | `latest`            |
+//! | `{subdir}/{name}-{version}-{build}.conda`  | `{namespace}/{subdir}/{name}`        | `{version}-{build}` |
+//!
+//! The layer that contains the file is selected by its media type. Characters that are not
+//! allowed in tags are escaped (`+` as `__p__`, `!` as `__e__` and `=` as `__eq__`) and package
+//! names that start with an underscore are prefixed with `zzz` because repository names have to
+//! start with an alphanumeric character. Other files of a channel are reported as not found.
+//!
+//! Registries are accessed over https, except for registries on the local machine which are
+//! accessed over http. Most registries require a bearer token, even for public repositories. If a
+//! registry responds with `401 Unauthorized` a token is requested from the realm of the
+//! `WWW-Authenticate` challenge. The credentials that are stored for the host of the realm are
+//! used to request the token, which is then cached for the lifetime of the client.
+//!
+//! The content of a blob is verified against the digest from the manifest while it is read.
+
+use crate::AuthenticatedClient;
+#[cfg(feature = "blocking")]
+use crate::AuthenticatedClientBlocking;
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+#[cfg(feature = "blocking")]
+use reqwest::blocking;
+use reqwest::{header, Method, RequestBuilder, Response, StatusCode, Url};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    pin::Pin,
+    sync::Mutex,
+    task::{ready, Context, Poll},
+};
+
+/// The media type of an OCI image manifest.
+pub const OCI_MANIFEST_MEDIA_TYPE: &str = "application/vnd.oci.image.manifest.v1+json";
+
+/// The media type of the layer that contains a `repodata.json` file.
+pub const REPODATA_MEDIA_TYPE: &str = "application/vnd.conda.repodata.v1+json";
+
+/// The media type of the layer that contains a `repodata.json.zst` file.
+pub const REPODATA_ZST_MEDIA_TYPE: &str = "application/vnd.conda.repodata.v1+json+zst";
+
+/// The media type of the layer that contains a `repodata.json.bz2` file.
+pub const REPODATA_BZ2_MEDIA_TYPE: &str = "application/vnd.conda.repodata.v1+json+bzip2";
+
+/// The media type of the layer that contains a `.tar.bz2` package.
+pub const TAR_BZ2_PACKAGE_MEDIA_TYPE: &str = "application/vnd.conda.package.v1";
+
+/// The media type of the layer that contains a `.conda` package.
+pub const CONDA_PACKAGE_MEDIA_TYPE: &str = "application/vnd.conda.package.v2";
+
+/// The location of a file of a channel in an OCI registry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OciReference {
+    /// The url of the registry, e.g. `https://ghcr.io/`
+    pub registry: Url,
+
+    /// The repository that contains the file, e.g. `channel-mirrors/conda-forge/noarch/repodata.json`
+    pub repository: String,
+
+    /// The tag of the artifact that contains the file
+    pub tag: String,
+
+    /// The media type of the layer that contains the file
+    pub media_type: &'static str,
+}
+
+impl OciReference {
+    /// Determines the location of the file that is referred to by an `oci://` url. Returns `None`
+    /// if the url does not use the `oci` scheme or if the file cannot be stored in a registry.
+    pub fn from_url(url: &Url) -> Option<Self> {
+        if url.scheme() != "oci" {
+            return None;
+        }
+
+        let host = url.host_str()?;
+        let is_local = host == "localhost"
+            || host
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .parse::<IpAddr>()
+                .is_ok_and(|ip| ip.is_loopback());
+        let scheme = if is_local { "http" } else { "https" };
+        let registry = match url.port() {
+            Some(port) => Url::parse(&format!("{scheme}://{host}:{port}/")),
+            None => Url::parse(&format!("{scheme}://{host}/")),
+        }
+        .ok()?;
+
+        let (namespace, file_name) = url.path().trim_start_matches('/').rsplit_once('/')?;
+        if namespace.is_empty() {
+            return None;
+        }
+
+        let repodata = |media_type| {
+            Some((
+                format!("{namespace}/repodata.json"),
+                String::from("latest"),
+                media_type,
+            ))
+        };
+        let (repository, tag, media_type) = match file_name {
+            "repodata.json" => repodata(REPODATA_MEDIA_TYPE),
+            "repodata.json.zst" => repodata(REPODATA_ZST_MEDIA_TYPE),
+            "repodata.json.bz2" => repodata(REPODATA_BZ2_MEDIA_TYPE),
+            _ => {
+                let (stem, media_type) = if let Some(stem) = file_name.strip_suffix(".conda") {
+                    (stem, CONDA_PACKAGE_MEDIA_TYPE)
+                } else if let Some(stem) = file_name.strip_suffix(".tar.bz2") {
+                    (stem, TAR_BZ2_PACKAGE_MEDIA_TYPE)
+                } else {
+                    return None;
+                };
+                let mut parts = stem.rsplitn(3, '-');
+                let (build, version, name) = (parts.next()?, parts.next()?, parts.next()?);
+                let name = if name.starts_with('_') {
+                    format!("zzz{name}")
+                } else {
+                    name.to_owned()
+                };
+                Some((
+                    format!("{namespace}/{name}"),
+                    escape_tag(&format!("{version}-{build}")),
+                    media_type,
+                ))
+            }
+        }?;
+
+        Some(Self {
+            registry,
+            repository,
+            tag,
+            media_type,
+        })
+    }
+
+    /// Returns the url of the manifest of the artifact that contains the file.
+    pub fn manifest_url(&self) -> Url {
+        self.registry
+            .join(&format!("v2/{}/manifests/{}", self.repository, self.tag))
+            .expect("repository and tag form a valid path")
+    }
+
+    /// Returns the key under which the token for the repository that contains the file is cached.
+    fn token_cache_key(&self) -> String {
+        format!("{}{}", self.registry, self.repository)
+    }
+
+    /// Returns the url of a blob in the repository that contains the file.
+    pub fn blob_url(&self, digest: &str) -> Url {
+        self.registry
+            .join(&format!("v2/{}/blobs/{digest}", self.repository))
+            .expect("repository and digest form a valid path")
+    }
+}
+
+/// Replaces the characters of a conda version and build string that are not allowed in tags.
+fn escape_tag(tag: &str) -> String {
+    tag.replace('+', "__p__")
+        .replace('!', "__e__")
+        .replace('=', "__eq__")
+}
+
+/// The bearer tokens that have been obtained from registries, by registry and repository.
+#[derive(Debug, Default)]
+pub(crate) struct TokenCache(Mutex<HashMap<String, String>>);
+
+impl TokenCache {
+    fn get(&self, key: &str) -> Option<String> {
+        self.0
+            .lock()
+            .expect("token cache lock was poisoned")
+            .get(key)
+            .cloned()
+    }
+
+    fn insert(&self, key: String, token: String) {
+        self.0
+            .lock()
+            .expect("token cache lock was poisoned")
+            .insert(key, token);
+    }
+}
+
+/// An OCI image manifest, only the fields that are used are deserialized.
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    layers: Vec<Descriptor>,
+}
+
+impl Manifest {
+    /// Returns the layer that contains the file that is referred to by `reference`.
+    fn layer(self, reference: &OciReference) -> Option<Descriptor> {
+        self.layers
+            .into_iter()
+            .find(|layer| layer.media_type == reference.media_type)
+    }
+}
+
+/// Describes a blob in a registry.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Descriptor {
+    media_type: String,
+    digest: String,
+    size: u64,
+}
+
+impl Descriptor {
+    /// Constructs the response to a `HEAD` request for the blob.
+    fn head_response<R: From<http::Response<Vec<u8>>>>(&self) -> R {
+        http::Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_LENGTH, self.size)
+            .body(Vec::new())
+            .expect("response is valid")
+            .into()
+    }
+}
+
+/// The response of the token endpoint of a registry. Registries use either of the fields.
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    token: Option<String>,
+    access_token: Option<String>,
+}
+
+/// Error that is returned when reading a blob whose content does not match its digest.
+#[derive(Debug, thiserror::Error)]
+#[error("the content of the blob does not match its digest '{0}'")]
+pub struct DigestMismatchError(pub String);
+
+/// Sends a request for a file in an OCI registry. Only `GET` and `HEAD` requests are supported.
+///
+/// Files that do not exist, or cannot be stored in a registry, result in a `404 Not Found`
+/// response. The `configure` function is applied to the request for the blob that contains the
+/// file.
+pub(crate) async fn send(
+    client: &AuthenticatedClient,
+    method: Method,
+    url: &Url,
+    configure: &impl Fn(RequestBuilder) -> RequestBuilder,
+) -> Result<Response, reqwest::Error> {
+    let Some(reference) = OciReference::from_url(url) else {
+        tracing::debug!("'{url}' cannot be stored in an OCI registry");
+        return Ok(empty_response(StatusCode::NOT_FOUND));
+    };
+
+    let response = send_authorized(client, &reference, reference.manifest_url(), &|builder| {
+        builder.header(header::ACCEPT, OCI_MANIFEST_MEDIA_TYPE)
+    })
+    .await?;
+    if !response.status().is_success() {
+        return Ok(response);
+    }
+
+    let manifest: Manifest = response.json().await?;
+    let Some(layer) = manifest.layer(&reference) else {
+        return Ok(empty_response(StatusCode::NOT_FOUND));
+    };
+    if method == Method::HEAD {
+        return Ok(layer.head_response());
+    }
+
+    let response = send_authorized(
+        client,
+        &reference,
+        reference.blob_url(&layer.digest),
+        configure,
+    )
+    .await?;
+    if !response.status().is_success() {
+        return Ok(response);
+    }
+    Ok(verify_digest(response, &layer.digest))
+}
+
+/// Sends a `GET` request to the registry. If the registry requires a token, the token is
+/// requested and the request is sent again.
+async fn send_authorized(
+    client: &AuthenticatedClient,
+    reference: &OciReference,
+    url: Url,
+    configure: &impl Fn(RequestBuilder) -> RequestBuilder,
+) -> Result<Response, reqwest::Error> {
+    let key = reference.token_cache_key();
+    let token = client.oci_tokens.get(&key);
+    let response = send_with_token(client, url.clone(), token.as_deref(), configure).await?;
+    if response.status() != StatusCode::UNAUTHORIZED {
+        return Ok(response);
+    }
+
+    let Some(token_url) = response
+        .headers()
+        .get(header::WWW_AUTHENTICATE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| token_url(value, reference))
+    else {
+        return Ok(response);
+    };
+
+    let token_response = client.get(token_url.clone()).send().await?;
+    if !token_response.status().is_success() {
+        tracing::warn!(
+            "failed to obtain a token from '{token_url}': {}",
+            token_response.status()
+        );
+        return Ok(response);
+    }
+    let TokenResponse {
+        token,
+        access_token,
+    } = token_response.json().await?;
+    let Some(token) = token.or(access_token) else {
+        tracing::warn!("'{token_url}' did not provide a token");
+        return Ok(response);
+    };
+
+    client.oci_tokens.insert(key, token.clone());
+    send_with_token(client, url, Some(&token), configure).await
+}
+
+/// Sends a `GET` request with the given bearer token. Without a token the credentials that are
+/// stored for the registry are used.
+async fn send_with_token(
+    client: &AuthenticatedClient,
+    url: Url,
+    token: Option<&str>,
+    configure: &impl Fn(RequestBuilder) -> RequestBuilder,
+) -> Result<Response, reqwest::Error> {
+    let builder = match token {
+        Some(token) => client.client.get(url).bearer_auth(token),
+        None => client.get(url),
+    };
+    configure(builder).send().await
+}
+
+/// Sends a request for a file in an OCI registry with a blocking client, see [`send`].
+///
+/// The blocking client cannot verify the content of a blob while it is read. Instead the blob is
+/// read completely and the body of the returned response fails to read if the content does not
+/// match the digest.
+#[cfg(feature = "blocking")]
+pub(crate) fn send_blocking(
+    client: &AuthenticatedClientBlocking,
+    method: Method,
+    url: &Url,
+    configure: &impl Fn(blocking::RequestBuilder) -> blocking::RequestBuilder,
+) -> Result<blocking::Response, reqwest::Error> {
+    let Some(reference) = OciReference::from_url(url) else {
+        tracing::debug!("'{url}' cannot be stored in an OCI registry");
+        return Ok(empty_response(StatusCode::NOT_FOUND));
+    };
+
+    let response =
+        send_authorized_blocking(client, &reference, reference.manifest_url(), &|builder| {
+            builder.header(header::ACCEPT, OCI_MANIFEST_MEDIA_TYPE)
+        })?;
+    if !response.status().is_success() {
+        return Ok(response);
+    }
+
+    let manifest: Manifest = response.json()?;
+    let Some(layer) = manifest.layer(&reference) else {
+        return Ok(empty_response(StatusCode::NOT_FOUND));
+    };
+    if method == Method::HEAD {
+        return Ok(layer.head_response());
+    }
+
+    let response = send_authorized_blocking(
+        client,
+        &reference,
+        reference.blob_url(&layer.digest),
+        configure,
+    )?;
+    if !response.status().is_success() {
+        return Ok(response);
+    }
+    verify_digest_blocking(response, &layer.digest)
+}
+
+/// Sends a `GET` request to the registry with a blocking client, see [`send_authorized`].
+#[cfg(feature = "blocking")]
+fn send_authorized_blocking(
+    client: &AuthenticatedClientBlocking,
+    reference: &OciReference,
+    url: Url,
+    configure: &impl Fn(blocking::RequestBuilder) -> blocking::RequestBuilder,
+) -> Result<blocking::Response, reqwest::Error> {
+    let send_with_token = |token: Option<&str>| {
+        let builder = match token {
+            Some(token) => client.client.get(url.clone()).bearer_auth(token),
+            None => client.get(url.clone()),
+        };
+        configure(builder).send()
+    };
+
+    let key = reference.token_cache_key();
+    let token = client.oci_tokens.get(&key);
+    let response = send_with_token(token.as_deref())?;
+    if response.status() != StatusCode::UNAUTHORIZED {
+        return Ok(response);
+    }
+
+    let Some(token_url) = response
+        .headers()
+        .get(header::WWW_AUTHENTICATE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| token_url(value, reference))
+    else {
+        return Ok(response);
+    };
+
+    let token_response = client.get(token_url.clone()).send()?;
+    if !token_response.status().is_success() {
+        tracing::warn!(
+            "failed to obtain a token from '{token_url}': {}",
+            token_response.status()
+        );
+        return Ok(response);
+    }
+    let TokenResponse {
+        token,
+        access_token,
+    } = token_response.json()?;
+    let Some(token) = token.or(access_token) else {
+        tracing::warn!("'{token_url}' did not provide a token");
+        return Ok(response);
+    };
+
+    client.oci_tokens.insert(key, token.clone());
+    send_with_token(Some(&token))
+}
+
+/// Reads the body of the response and verifies it against the digest. If the content does not
+/// match, reading the body of the returned response fails.
+#[cfg(feature = "blocking")]
+fn verify_digest_blocking(
+    response: blocking::Response,
+    digest: &str,
+) -> Result<blocking::Response, reqwest::Error> {
+    let Some(expected) = digest
+        .strip_prefix("sha256:")
+        .and_then(|hash| hex::decode(hash).ok())
+    else {
+        tracing::warn!("cannot verify the unsupported digest '{digest}'");
+        return Ok(response);
+    };
+
+    let mut builder = http::Response::builder()
+        .status(response.status())
+        .version(response.version());
+    for (name, value) in response.headers() {
+        builder = builder.header(name, value);
+    }
+    let content = response.bytes()?;
+    let body = if Sha256::digest(&content).as_slice() == expected.as_slice() {
+        reqwest::Body::from(content)
+    } else {
+        let err = DigestMismatchError(digest.to_owned());
+        reqwest::Body::wrap_stream(futures_util::stream::once(futures_util::future::ready(
+            Err::<Bytes, _>(err),
+        )))
+    };
+    Ok(builder.body(body).expect("response is valid").into())
+}
+
+/// Parses a `Bearer` challenge from a `WWW-Authenticate` header and returns the url from which a
+/// token can be requested.
+fn token_url(challenge: &str, reference: &OciReference) -> Option<Url> {
+    let (scheme, mut rest) = challenge.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("bearer") {
+        return None;
+    }
+
+    let mut params = HashMap::new();
+    while !rest.is_empty() {
+        let (key, tail) = rest.split_once('=')?;
+        let (value, tail) = match tail.strip_prefix('"') {
+            Some(tail) => {
+                let end = tail.find('"')?;
+                (&tail[..end], &tail[end + 1..])
+            }
+            None => tail.split_at(tail.find(',').unwrap_or(tail.len())),
+        };
+        params.insert(key.trim().to_ascii_lowercase(), value);
+        rest = tail.trim_start().trim_start_matches(',').trim_start();
+    }
+
+    let mut url = Url::parse(params.get("realm")?).ok()?;
+    {
+        let mut query = url.query_pairs_mut();
+        if let Some(service) = params.get("service") {
+            query.append_pair("service", service);
+        }
+        match params.get("scope") {
+            Some(scope) => query.append_pair("scope", scope),
+            None => query.append_pair(
+                "scope",
+                &format!("repository:{}:pull", reference.repository),
+            ),
+        };
+    }
+    Some(url)
+}
+
+/// Constructs a response without a body.
+fn empty_response<R: From<http::Response<Vec<u8>>>>(status: StatusCode) -> R {
+    http::Response::builder()
+        .status(status)
+        .body(Vec::new())
+        .expect("response is valid")
+        .into()
+}
+
+/// Wraps the body of the response so that reading the body fails if its content does not match
+/// the digest.
+fn verify_digest(response: Response, digest: &str) -> Response {
+    let Some(expected) = digest
+        .strip_prefix("sha256:")
+        .and_then(|hash| hex::decode(hash).ok())
+    else {
+        tracing::warn!("cannot verify the unsupported digest '{digest}'");
+        return response;
+    };
+
+    let mut builder = http::Response::builder()
+        .status(response.status())
+        .version(response.version());
+    for (name, value) in response.headers() {
+        builder = builder.header(name, value);
+    }
+    let body = VerifyDigest {
+        inner: Box::pin(response.bytes_stream()),
+        hasher: Some(Sha256::new()),
+        expected,
+        digest: digest.to_owned(),
+    };
+    builder
+        .body(reqwest::Body::wrap_stream(body))
+        .expect("response is valid")
+        .into()
+}
+
+/// A stream of bytes that computes the hash of the bytes and fails at the end of the stream if
+/// the hash does not match the expected hash.
+struct VerifyDigest {
+    inner: Pin<Box<dyn Stream<Item = reqwest::Result<Bytes>> + Send + Sync>>,
+    hasher: Option<Sha256>,
+    expected: Vec<u8>,
+    digest: String,
+}
+
+impl Stream for VerifyDigest {
+    type Item = Result<Bytes, Box<dyn std::error::Error + Send + Sync>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // The hasher is removed once the stream has ended
+        if self.hasher.is_none() {
+            return Poll::Ready(None);
+        }
+        match ready!(self.inner.poll_next_unpin(cx)) {
+            Some(Ok(chunk)) => {
+                if let Some(hasher) = self.hasher.as_mut() {
+                    hasher.update(&chunk);
+                }
+                Poll::Ready(Some(Ok(chunk)))
+            }
+            Some(Err(err)) => {
+                self.hasher = None;
+                Poll::Ready(Some(Err(err.into())))
+            }
+            None => {
+                let hasher = self.hasher.take().expect("hasher is only taken once");
+                if hasher.finalize().as_slice() == self.expected.as_slice() {
+                    Poll::Ready(None)
+                } else {
+                    Poll::Ready(Some(Err(DigestMismatchError(self.digest.clone()).into())))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{OciReference, CONDA_PACKAGE_MEDIA_TYPE, TAR_BZ2_PACKAGE_MEDIA_TYPE};
+    use crate::{AuthenticatedClient, MirrorMap};
+    use axum::{
+        extract::{Query, State},
+        http::{header, HeaderMap, StatusCode, Uri},
+        response::{IntoResponse, Response},
+        routing::get,
+        Router,
+    };
+    use reqwest::{Method, Url};
+    use sha2::{Digest, Sha256};
+    use std::{
+        collections::HashMap,
+        net::{SocketAddr, TcpListener},
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    const TOKEN: &str = "registry-token";
+
+    #[derive(Default)]
+    struct Registry {
+        /// The content of the manifests and blobs by path
+        files: HashMap<String, Vec<u8>>,
+        /// The number of tokens that have been handed out
+        tokens_issued: AtomicUsize,
+        addr: Option<SocketAddr>,
+    }
+
+    impl Registry {
+        /// Adds an artifact with a single layer.
+        fn push(&mut self, repository: &str, tag: &str, media_type: &str, content: &[u8]) {
+            let digest = format!("sha256:{:x}", Sha256::digest(content));
+            let manifest = serde_json::json!({
+                "schemaVersion": 2,
+                "mediaType": super::OCI_MANIFEST_MEDIA_TYPE,
+                "layers": [{ "mediaType": media_type, "digest": digest, "size": content.len() }],
+            });
+            self.files.insert(
+                format!("/v2/{repository}/manifests/{tag}"),
+                serde_json::to_vec(&manifest).unwrap(),
+            );
+            self.files
+                .insert(format!("/v2/{repository}/blobs/{digest}"), content.to_vec());
+        }
+    }
+
+    async fn token(
+        State(registry): State<Arc<Registry>>,
+        Query(query): Query<HashMap<String, String>>,
+    ) -> Response {
+        assert_eq!(query.get("service").map(String::as_str), Some("test"));
+        assert!(query["scope"].starts_with("repository:channel/"));
+        registry.tokens_issued.fetch_add(1, Ordering::SeqCst);
+        axum::Json(serde_json::json!({ "token": TOKEN })).into_response()
+    }
+
+    async fn registry(
+        State(registry): State<Arc<Registry>>,
+        headers: HeaderMap,
+        uri: Uri,
+    ) -> Response {
+        if headers
+            .get(header::AUTHORIZATION)
+            .map(|value| value.as_bytes())
+            != Some(format!("Bearer {TOKEN}").as_bytes())
+        {
+            let challenge = format!(
+                "Bearer realm=\"http://{}/token\",service=\"test\"",
+                registry.addr.unwrap()
+            );
+            return (
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, challenge)],
+            )
+                .into_response();
+        }
+        match registry.files.get(uri.path()) {
+            Some(content) => content.clone().into_response(),
+            None => StatusCode::NOT_FOUND.into_response(),
+        }
+    }
+
+    /// Starts a registry on a random port, `push` is called to add files to the registry.
+    fn serve(push: impl FnOnce(&mut Registry)) -> Arc<Registry> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut state = Registry {
+            addr: Some(listener.local_addr().unwrap()),
+            ..Registry::default()
+        };
+        push(&mut state);
+        let state = Arc::new(state);
+        let router = Router::new()
+            .route("/token", get(token))
+            .route("/v2/*path", get(registry))
+            .with_state(state.clone());
+        let server = axum::Server::from_tcp(listener)
+            .unwrap()
+            .serve(router.into_make_service());
+        tokio::spawn(server);
+        state
+    }
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    #[test]
+    fn test_reference_from_url() {
+        let reference = OciReference::from_url(&url(
+            "oci://ghcr.io/channel-mirrors/conda-forge/linux-64/_libgcc_mutex-0.1-conda_forge.tar.bz2",
+        ))
+        .unwrap();
+        assert_eq!(reference.registry, url("https://ghcr.io/"));
+        assert_eq!(
+            reference.repository,
+            "channel-mirrors/conda-forge/linux-64/zzz_libgcc_mutex"
+        );
+        assert_eq!(reference.tag, "0.1-conda_forge");
+        assert_eq!(reference.media_type, TAR_BZ2_PACKAGE_MEDIA_TYPE);
+        assert_eq!(
+            reference.manifest_url(),
+            url("https://ghcr.io/v2/channel-mirrors/conda-forge/linux-64/zzz_libgcc_mutex/manifests/0.1-conda_forge")
+        );
+
+        let reference = OciReference::from_url(&url(
+            "oci://localhost:5000/channel/noarch/foo-bar-1.0+local!1-py_0.conda",
+        ))
+        .unwrap();
+        assert_eq!(reference.registry, url("http://localhost:5000/"));
+        assert_eq!(reference.repository, "channel/noarch/foo-bar");
+        assert_eq!(reference.tag, "1.0__p__local__e__1-py_0");
+        assert_eq!(reference.media_type, CONDA_PACKAGE_MEDIA_TYPE);
+
+        let reference =
+            OciReference::from_url(&url("oci://ghcr.io/channel/noarch/repodata.json.zst")).unwrap();
+        assert_eq!(reference.repository, "channel/noarch/repodata.json");
+        assert_eq!(reference.tag, "latest");
+
+        for unsupported in [
+            "https://ghcr.io/channel/noarch/repodata.json",
+            "oci://ghcr.io/channel/noarch/repodata.jlap",
+            "oci://ghcr.io/repodata.json",
+        ] {
+            assert_eq!(OciReference::from_url(&url(unsupported)), None);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_from_registry() {
+        let registry = serve(|registry| {
+            registry.push(
+                "channel/noarch/repodata.json",
+                "latest",
+                super::REPODATA_MEDIA_TYPE,
+                b"{}",
+            );
+            registry.push(
+                "channel/noarch/foo",
+                "1.0-0",
+                CONDA_PACKAGE_MEDIA_TYPE,
+                b"package",
+            );
+        });
+        let channel = format!("oci://{}/channel", registry.addr.unwrap());
+        let client = AuthenticatedClient::default();
+
+        let (_, response) = client
+            .send_with_mirrors(
+                Method::GET,
+                &url(&format!("{channel}/noarch/repodata.json")),
+                |builder| builder,
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.bytes().await.unwrap().as_ref(), b"{}");
+
+        let (_, response) = client
+            .send_with_mirrors(
+                Method::GET,
+                &url(&format!("{channel}/noarch/foo-1.0-0.conda")),
+                |builder| builder,
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.bytes().await.unwrap().as_ref(), b"package");
+
+        // Only layers with the matching media type are found
+        for (method, file, status) in [
+            (Method::HEAD, "repodata.json", StatusCode::OK),
+            (Method::HEAD, "repodata.json.zst", StatusCode::NOT_FOUND),
+            (Method::GET, "foo-1.0-0.tar.bz2", StatusCode::NOT_FOUND),
+            (Method::GET, "foo-2.0-0.conda", StatusCode::NOT_FOUND),
+            (Method::GET, "repodata.jlap", StatusCode::NOT_FOUND),
+        ] {
+            let (_, response) = client
+                .send_with_mirrors(
+                    method,
+                    &url(&format!("{channel}/noarch/{file}")),
+                    |builder| builder,
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), status, "{file}");
+        }
+
+        // A token is requested once per repository
+        assert_eq!(registry.tokens_issued.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_digest_mismatch() {
+        let registry = serve(|registry| {
+            registry.push(
+                "channel/noarch/repodata.json",
+                "latest",
+                super::REPODATA_MEDIA_TYPE,
+                b"{}",
+            );
+            for content in registry.files.values_mut() {
+                if content.as_slice() == b"{}" {
+                    *content = b"{\"tampered\": true}".to_vec();
+                }
+            }
+        });
+
+        // The registry is used as a mirror of a regular channel
+        let mut mirrors = MirrorMap::new();
+        mirrors.insert(
+            url("https://conda.anaconda.org/channel"),
+            vec![url(&format!("oci://{}/channel", registry.addr.unwrap()))],
+        );
+        let client = AuthenticatedClient::default().with_mirrors(mirrors);
+        let (_, response) = client
+            .send_with_mirrors(
+                Method::GET,
+                &url("https://conda.anaconda.org/channel/noarch/repodata.json"),
+                |builder| builder,
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.bytes().await.is_err());
+    }
+
+    #[cfg(feature = "blocking")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_fetch_from_registry_blocking() {
+        let registry = serve(|registry| {
+            registry.push(
+                "channel/noarch/repodata.json",
+                "latest",
+                super::REPODATA_MEDIA_TYPE,
+                b"{}",
+            );
+            registry.push(
+                "channel/noarch/foo",
+                "1.0-0",
+                CONDA_PACKAGE_MEDIA_TYPE,
+                b"package",
+            );
+            for (path, content) in registry.files.iter_mut() {
+                if path.contains("/foo/blobs/") {
+                    *content = b"tampered".to_vec();
+                }
+            }
+        });
+        let channel = format!("oci://{}/channel", registry.addr.unwrap());
+
+        tokio::task::spawn_blocking(move || {
+            let client = crate::AuthenticatedClientBlocking::default();
+            let send = |method: Method, file: &str| {
+                let (_, response) = client
+                    .send_with_mirrors(
+                        method,
+                        &url(&format!("{channel}/noarch/{file}")),
+                        |builder| builder,
+                    )
+                    .unwrap();
+                response
+            };
+
+            let response = send(Method::GET, "repodata.json");
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.bytes().unwrap().as_ref(), b"{}");
+
+            let response = send(Method::HEAD, "repodata.json");
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers().get(header::CONTENT_LENGTH).unwrap(), "2");
+
+            assert_eq!(
+                send(Method::GET, "foo-2.0-0.conda").status(),
+                StatusCode::NOT_FOUND
+            );
+
+            // The content of the blob does not match its digest
+            let response = send(Method::GET, "foo-1.0-0.conda");
+            assert_eq!(response.status(), StatusCode::OK);
+            assert!(response.bytes().is_err());
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(registry.tokens_issued.load(Ordering::SeqCst), 2);
+    }
+}
@@ -4,9 +4,19 @@ use crate::{ExtractError, ExtractResult};
 use rattler_conda_types::package::ArchiveType;
 use rattler_networking::AuthenticatedClientBlocking;
 use reqwest::blocking::Response;
-use reqwest::IntoUrl;
+use reqwest::{IntoUrl, Method};
 use std::path::Path;
 
+/// Sends the request for the file, if the channel is mirrored this fails over to the next mirror
+/// if a mirror is unavailable.
+fn get(client: &AuthenticatedClientBlocking, url: impl IntoUrl) -> Result<Response, ExtractError> {
+    let url = url.into_url().map_err(ExtractError::ReqwestError)?;
+    client
+        .send_with_mirrors(Method::GET, &url, |builder| builder)
+        .and_then(|(_, response)| response.error_for_status())
+        .map_err(ExtractError::ReqwestError)
+}
+
 /// Extracts the contents a `.tar.bz2` package archive from the specified remote location.
 ///
 /// ```rust,no_run
@@ -25,12 +35,7 @@ pub fn extract_tar_bz2(
     url: impl IntoUrl,
     destination: &Path,
 ) -> Result<ExtractResult, ExtractError> {
-    // Send the request for the file
-    let response = client
-        .get(url)
-        .send()
-        .and_then(Response::error_for_status)
-        .map_err(ExtractError::ReqwestError)?;
+    let response = get(&client, url)?;
 
     // The `response` is used to stream in the package data
     crate::read::extract_tar_bz2(response, destination)
@@ -54,12 +59,7 @@ pub fn extract_conda(
     url: impl IntoUrl,
     destination: &Path,
 ) -> Result<ExtractResult, ExtractError> {
-    // Send the request for the file
-    let response = client
-        .get(url)
-        .send()
-        .and_then(Response::error_for_status)
-        .map_err(ExtractError::ReqwestError)?;
+    let response = get(&client, url)?;
 
     // The `response` is used to stream in the package data
     crate::read::extract_conda(response, destination)
@@ -5,7 +5,7 @@ use crate::{ExtractError, ExtractResult};
 use futures_util::stream::TryStreamExt;
 use rattler_conda_types::package::ArchiveType;
 use rattler_networking::AuthenticatedClient;
-use reqwest::Response;
+use reqwest::Method;
 use std::path::Path;
 use tokio::io::BufReader;
 use tokio_util::either::Either;
@@ -23,12 +23,14 @@ async fn get_reader(
 
         Ok(Either::Left(BufReader::new(file)))
     } else {
-        // Send the request for the file
-        let response = client
-            .get(url.clone())
-            .send()
+        // Send the request for the file, if the channel is mirrored this fails over to the next
+        // mirror if a mirror is unavailable.
+        let (_, response) = client
+            .send_with_mirrors(Method::GET, &url, |builder| builder)
             .await
-            .and_then(Response::error_for_status)
+            .map_err(ExtractError::ReqwestError)?;
+        let response = response
+            .error_for_status()
             .map_err(ExtractError::ReqwestError)?;
 
         // Get the response as a stream
@@ -1,9 +1,11 @@
+use cache_control::{Cachability, CacheControl};
 use reqwest::{
     header,
     header::{HeaderMap, HeaderValue},
     Response,
 };
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 /// Extracted HTTP response headers that enable caching the repodata.json files.
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -77,4 +79,21 @@ impl CacheHeaders {
             headers.insert(header::IF_MODIFIED_SINCE, last_modified);
         }
     }
+
+    /// Returns true if the `Cache-Control` header allows using a response that was received `age`
+    /// ago without revalidating it with the server.
+    pub fn is_fresh(&self, age: Duration) -> bool {
+        match self
+            .cache_control
+            .as_deref()
+            .and_then(CacheControl::from_value)
+        {
+            Some(CacheControl {
+                cachability: Some(Cachability::Public),
+                max_age: Some(max_age),
+                ..
+            }) => age <= max_age,
+            _ => false,
+        }
+    }
 }
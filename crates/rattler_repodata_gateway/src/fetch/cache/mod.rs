@@ -15,6 +15,13 @@ pub struct RepoDataState {
     /// NOT include the final filename.
     pub url: Url,
 
+    /// The mirror from which the repodata was downloaded if the channel is mirrored (see
+    /// [`rattler_networking::MirrorMap`]). This is the URL that the request for [`Self::url`] was
+    /// actually sent to. Cache states that were written before mirrors were supported do not
+    /// contain this field and are read as `None`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mirror_url: Option<Url>,
+
     /// The HTTP cache headers send along with the last response.
     #[serde(flatten)]
     pub cache_headers: CacheHeaders,
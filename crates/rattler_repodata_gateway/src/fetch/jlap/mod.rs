@@ -83,7 +83,7 @@ use rattler_digest::{
 use rattler_networking::AuthenticatedClient;
 use reqwest::{
     header::{HeaderMap, HeaderValue},
-    Response, StatusCode,
+    Method, Response, StatusCode,
 };
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
@@ -441,7 +441,10 @@ async fn fetch_jlap(
     client: &AuthenticatedClient,
     range: &str,
 ) -> Result<Response, reqwest::Error> {
-    let request_builder = client.get(url);
+    let Ok(parsed_url) = Url::parse(url) else {
+        // Forward the error of the invalid url to the caller
+        return client.get(url).send().await;
+    };
     let mut headers = HeaderMap::default();
 
     headers.insert(
@@ -449,7 +452,12 @@ async fn fetch_jlap(
         HeaderValue::from_str(range).unwrap(),
     );
 
-    request_builder.headers(headers).send().await
+    client
+        .send_with_mirrors(Method::GET, &parsed_url, |builder| {
+            builder.headers(headers.clone())
+        })
+        .await
+        .map(|(_, response)| response)
 }
 
 /// Fetches the JLAP response but also retries in the case of a RANGE_NOT_SATISFIABLE error
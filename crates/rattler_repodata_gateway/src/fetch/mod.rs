@@ -5,11 +5,13 @@ use cache::{CacheHeaders, Expiring, RepoDataState};
 use cache_control::{Cachability, CacheControl};
 use futures::{future::ready, FutureExt, TryStreamExt};
 use humansize::{SizeFormatter, DECIMAL};
+#[cfg(feature = "condarc")]
+use rattler_conda_types::CondaRc;
 use rattler_digest::{compute_file_digest, Blake2b256, HashingWriter};
 use rattler_networking::AuthenticatedClient;
 use reqwest::{
     header::{HeaderMap, HeaderValue},
-    Response, StatusCode,
+    Method, Response, StatusCode,
 };
 use std::{
     io::ErrorKind,
@@ -21,7 +23,7 @@ use tokio_util::io::StreamReader;
 use tracing::instrument;
 use url::Url;
 
-mod cache;
+pub(crate) mod cache;
 pub mod jlap;
 
 /// Type alias for function to report progress while downloading repodata
@@ -162,6 +164,23 @@ impl Default for FetchRepoDataOptions {
     }
 }
 
+#[cfg(feature = "condarc")]
+impl From<&CondaRc> for FetchRepoDataOptions {
+    /// Constructs the options from a `.condarc` configuration. When `offline` is enabled only the
+    /// cache is used, regardless of whether it is up to date.
+    fn from(condarc: &CondaRc) -> Self {
+        let cache_action = if condarc.offline == Some(true) {
+            CacheAction::ForceCacheOnly
+        } else {
+            CacheAction::default()
+        };
+        Self {
+            cache_action,
+            ..Self::default()
+        }
+    }
+}
+
 /// A struct that provides information about download progress.
 #[derive(Debug, Clone)]
 pub struct DownloadProgress {
@@ -226,6 +245,7 @@ async fn repodata_from_file(
     // create a dummy cache state
     let new_cache_state = RepoDataState {
         url: subdir_url.clone(),
+        mirror_url: None,
         cache_size: tokio::fs::metadata(&out_path)
             .await
             .map_err(FetchRepoDataError::FailedToDownloadRepoData)?
@@ -455,8 +475,6 @@ pub async fn fetch_repo_data(
 
     // Construct the HTTP request
     tracing::debug!("fetching '{}'", &repo_data_url);
-    let request_builder = client.get(repo_data_url.clone());
-
     let mut headers = HeaderMap::default();
 
     // We can handle g-zip encoding which is often used. We could also set this option on the
@@ -476,18 +494,24 @@ pub async fn fetch_repo_data(
     if let Some(cache_headers) = cache_state.as_ref().map(|state| &state.cache_headers) {
         cache_headers.add_to_request(&mut headers)
     }
-    // Send the request and wait for a reply
-    let response = match request_builder.headers(headers).send().await {
-        Ok(response) if response.status() == StatusCode::NOT_FOUND => {
+    // Send the request (possibly to one of the mirrors of the channel) and wait for a reply
+    let (response_url, response) = match client
+        .send_with_mirrors(Method::GET, &repo_data_url, |builder| {
+            builder.headers(headers.clone())
+        })
+        .await
+    {
+        Ok((_, response)) if response.status() == StatusCode::NOT_FOUND => {
             return Err(FetchRepoDataError::NotFound(
                 RepoDataNotFoundError::HttpError(response.error_for_status().unwrap_err()),
             ));
         }
-        Ok(response) => response.error_for_status()?,
+        Ok((response_url, response)) => (response_url, response.error_for_status()?),
         Err(e) => {
             return Err(FetchRepoDataError::HttpError(e));
         }
     };
+    let mirror_url = (response_url != repo_data_url).then_some(response_url);
 
     // If the content didn't change, simply return whatever we have on disk.
     if response.status() == StatusCode::NOT_MODIFIED {
@@ -496,6 +520,7 @@ pub async fn fetch_repo_data(
         // Update the cache on disk with any new findings.
         let cache_state = RepoDataState {
             url: repo_data_url,
+            mirror_url,
             has_zst: variant_availability.has_zst,
             has_bz2: variant_availability.has_bz2,
             has_jlap: variant_availability.has_jlap,
@@ -555,6 +580,7 @@ pub async fn fetch_repo_data(
     let had_cache = cache_state.is_some();
     let new_cache_state = RepoDataState {
         url: repo_data_url,
+        mirror_url,
         cache_headers,
         cache_last_modified: repo_data_json_metadata
             .modified()
@@ -812,8 +838,11 @@ async fn check_valid_download_target(url: &Url, client: &AuthenticatedClient) ->
         exists
     } else {
         // Otherwise, perform a HEAD request to determine whether the url seems valid.
-        match client.head(url.clone()).send().await {
-            Ok(response) => {
+        match client
+            .send_with_mirrors(Method::HEAD, url, |builder| builder)
+            .await
+        {
+            Ok((_, response)) => {
                 if response.status().is_success() {
                     tracing::debug!("'{url}' seems to be available");
                     true
@@ -1020,7 +1049,7 @@ mod test {
     use crate::utils::Encoding;
     use assert_matches::assert_matches;
     use hex_literal::hex;
-    use rattler_networking::{AuthenticatedClient, AuthenticationStorage};
+    use rattler_networking::{AuthenticatedClient, AuthenticationStorage, MirrorMap};
     use reqwest::Client;
     use std::path::Path;
     use std::sync::atomic::{AtomicU64, Ordering};
@@ -1134,6 +1163,143 @@ mod test {
         );
     }
 
+    #[tracing_test::traced_test]
+    #[tokio::test]
+    pub async fn test_mirror_failover() {
+        // Create a mirror that is down
+        let down_server = SimpleChannelServer::new(TempDir::new().unwrap().path());
+        let down_url = down_server.url();
+        drop(down_server);
+
+        // Create a mirror that responds with a server error to every request
+        let failing_server = axum::Server::bind(&([127, 0, 0, 1], 0).into()).serve(
+            axum::Router::new()
+                .fallback(|| async { reqwest::StatusCode::INTERNAL_SERVER_ERROR })
+                .into_make_service(),
+        );
+        let failing_url = Url::parse(&format!(
+            "http://localhost:{}",
+            failing_server.local_addr().port()
+        ))
+        .unwrap();
+        let failing_server = tokio::spawn(failing_server);
+
+        // Create a mirror that serves the repodata
+        let subdir_path = TempDir::new().unwrap();
+        std::fs::write(subdir_path.path().join("repodata.json"), FAKE_REPO_DATA).unwrap();
+        write_encoded(
+            FAKE_REPO_DATA.as_bytes(),
+            &subdir_path.path().join("repodata.json.zst"),
+            Encoding::Zst,
+        )
+        .await
+        .unwrap();
+        let server = SimpleChannelServer::new(subdir_path.path());
+
+        // The original channel is never contacted, only its mirrors.
+        let channel_url = Url::parse("http://channel.invalid/conda-forge/noarch/").unwrap();
+        let mut mirrors = MirrorMap::new();
+        mirrors.insert(
+            channel_url.clone(),
+            vec![down_url, failing_url, server.url()],
+        );
+        let client = AuthenticatedClient::default().with_mirrors(mirrors);
+
+        let cache_dir = TempDir::new().unwrap();
+        let result = fetch_repo_data(
+            channel_url.clone(),
+            client,
+            cache_dir.path().to_owned(),
+            Default::default(),
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(result.repo_data_json_path).unwrap(),
+            FAKE_REPO_DATA
+        );
+        assert_eq!(
+            result.cache_state.url,
+            channel_url.join("repodata.json.zst").unwrap()
+        );
+        assert_eq!(
+            result.cache_state.mirror_url,
+            Some(server.url().join("repodata.json.zst").unwrap())
+        );
+
+        failing_server.abort();
+    }
+
+    #[tracing_test::traced_test]
+    #[tokio::test]
+    pub async fn test_fetch_from_oci_registry() {
+        // Create a registry that contains an artifact with only the repodata.json layer
+        let content = FAKE_REPO_DATA.as_bytes();
+        let digest = format!(
+            "sha256:{:x}",
+            rattler_digest::compute_bytes_digest::<rattler_digest::Sha256>(content)
+        );
+        let manifest = serde_json::json!({
+            "schemaVersion": 2,
+            "mediaType": rattler_networking::oci::OCI_MANIFEST_MEDIA_TYPE,
+            "layers": [{
+                "mediaType": rattler_networking::oci::REPODATA_MEDIA_TYPE,
+                "digest": digest,
+                "size": content.len(),
+            }],
+        });
+        let files = Arc::new(std::collections::HashMap::from([
+            (
+                "/v2/channel/noarch/repodata.json/manifests/latest".to_owned(),
+                serde_json::to_vec(&manifest).unwrap(),
+            ),
+            (
+                format!("/v2/channel/noarch/repodata.json/blobs/{digest}"),
+                content.to_vec(),
+            ),
+        ]));
+        let registry = axum::Server::bind(&([127, 0, 0, 1], 0).into()).serve(
+            axum::Router::new()
+                .fallback(move |uri: axum::http::Uri| async move {
+                    match files.get(uri.path()) {
+                        Some(content) => Ok(content.clone()),
+                        None => Err(reqwest::StatusCode::NOT_FOUND),
+                    }
+                })
+                .into_make_service(),
+        );
+        let channel_url = Url::parse(&format!(
+            "oci://127.0.0.1:{}/channel/noarch/",
+            registry.local_addr().port()
+        ))
+        .unwrap();
+        let registry = tokio::spawn(registry);
+
+        let cache_dir = TempDir::new().unwrap();
+        let result = fetch_repo_data(
+            channel_url.clone(),
+            AuthenticatedClient::default(),
+            cache_dir.path().to_owned(),
+            Default::default(),
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(result.repo_data_json_path).unwrap(),
+            FAKE_REPO_DATA
+        );
+        assert_eq!(
+            result.cache_state.url,
+            channel_url.join("repodata.json").unwrap()
+        );
+
+        registry.abort();
+    }
+
     #[tracing_test::traced_test]
     #[tokio::test]
     pub async fn test_cache_works() {
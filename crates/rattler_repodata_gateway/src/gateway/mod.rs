@@ -0,0 +1,768 @@
+//! This module provides the [`Gateway`] which is a high-level interface to query the records of
+//! packages from one or more channels.
+
+mod sharded_subdir;
+
+use crate::{
+    fetch::{fetch_repo_data, FetchRepoDataError, FetchRepoDataOptions, Variant},
+    sparse::SparseRepoData,
+};
+use futures::future::try_join_all;
+use rattler_conda_types::{
+    Channel, MatchSpec, PackageName, PackageRecord, Platform, RepoDataRecord,
+};
+use rattler_networking::AuthenticatedClient;
+use sharded_subdir::ShardedSubdir;
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::OnceCell;
+use url::Url;
+
+/// An error that can occur when querying records through a [`Gateway`].
+#[derive(Debug, thiserror::Error)]
+pub enum GatewayError {
+    /// The repodata of a subdirectory could not be fetched.
+    #[error("failed to fetch repodata from {0}")]
+    FetchRepoDataError(Url, #[source] Box<FetchRepoDataError>),
+
+    /// The repodata of a subdirectory or the records within could not be read.
+    #[error(transparent)]
+    IoError(#[from] io::Error),
+
+    /// A file could not be downloaded.
+    #[error("failed to download {0}")]
+    HttpError(Url, #[source] reqwest::Error),
+
+    /// The index of a sharded subdirectory or one of its shards could not be parsed.
+    #[error("failed to parse sharded repodata from {0}")]
+    InvalidShardedRepodata(Url, #[source] rmp_serde::decode::Error),
+
+    /// The index of a sharded subdirectory refers to a shard that does not exist.
+    #[error("the shard {0} does not exist")]
+    ShardNotFound(Url),
+
+    /// A shard is not cached and the cache action does not allow downloading it.
+    #[error("the shard {0} is not cached")]
+    ShardNotCached(Url),
+
+    /// The hash of a downloaded shard does not match the hash in the index.
+    #[error(transparent)]
+    ShardHashMismatch(Box<ShardHashMismatchError>),
+
+    /// The operation was cancelled.
+    #[error("the operation was cancelled")]
+    Cancelled,
+}
+
+/// The hash of a downloaded shard does not match the hash in the index.
+#[derive(Debug, thiserror::Error)]
+#[error("the hash of {url} does not match, expected {expected} but got {actual}")]
+pub struct ShardHashMismatchError {
+    /// The url of the shard
+    pub url: Url,
+    /// The hash recorded in the index
+    pub expected: String,
+    /// The hash of the downloaded shard
+    pub actual: String,
+}
+
+impl From<tokio::task::JoinError> for GatewayError {
+    fn from(err: tokio::task::JoinError) -> Self {
+        // Rethrow any panic
+        if let Ok(panic) = err.try_into_panic() {
+            std::panic::resume_unwind(panic);
+        }
+
+        // Otherwise it the operation has been cancelled
+        GatewayError::Cancelled
+    }
+}
+
+/// Additional knobs that allow you to tweak the behavior of a [`Gateway`].
+#[derive(Clone)]
+pub struct GatewayOptions {
+    /// The options used when fetching the `repodata.json` of a subdirectory.
+    pub fetch_options: FetchRepoDataOptions,
+
+    /// When enabled, the records of a package are only taken from the first channel that
+    /// contains the package. Otherwise the records of all channels are returned.
+    pub strict_channel_priority: bool,
+
+    /// A function that can be used to patch the package records after they have been parsed
+    /// (e.g. to add `pip` to `python`).
+    pub patch_record_fn: Option<fn(&mut PackageRecord)>,
+
+    /// When enabled, sharded repodata is used for subdirectories that provide it. Only the shards
+    /// of the packages that are queried are downloaded instead of the full `repodata.json`. When
+    /// a subdirectory does not provide sharded repodata the full `repodata.json` is used.
+    pub sharded_repodata_enabled: bool,
+}
+
+impl Default for GatewayOptions {
+    fn default() -> Self {
+        Self {
+            fetch_options: FetchRepoDataOptions::default(),
+            strict_channel_priority: true,
+            patch_record_fn: None,
+            sharded_repodata_enabled: true,
+        }
+    }
+}
+
+/// A [`Gateway`] provides access to the records of packages from different channels.
+///
+/// The repodata of every subdirectory that is queried is downloaded (and cached on disk) only
+/// once and kept in memory for subsequent queries. If a subdirectory provides sharded repodata
+/// only the shards of the required packages are downloaded, see [`GatewayOptions`]. Concurrent
+/// queries that require the same subdirectory share a single request. Cloning a [`Gateway`] is
+/// cheap and the clones share the same in-memory cache.
+#[derive(Clone)]
+pub struct Gateway {
+    inner: Arc<GatewayInner>,
+}
+
+struct GatewayInner {
+    /// The client used to download the repodata.
+    client: AuthenticatedClient,
+
+    /// The directory in which the downloaded repodata is cached.
+    cache_dir: PathBuf,
+
+    /// Options that influence how repodata is fetched and records are loaded.
+    options: GatewayOptions,
+
+    /// The subdirectories that have been loaded or are currently being loaded, indexed by the url
+    /// of the subdirectory. A subdirectory that does not exist is stored as `None`.
+    subdirs: Mutex<HashMap<Url, SubdirCell>>,
+}
+
+/// A subdirectory that has been loaded or is being loaded, `None` if the subdirectory does not
+/// exist.
+type SubdirCell = Arc<OnceCell<Option<Arc<Subdir>>>>;
+
+/// The repodata of a single subdirectory of a channel.
+enum Subdir {
+    /// The full `repodata.json` of the subdirectory.
+    Sparse(Arc<SparseRepoData>),
+
+    /// Sharded repodata, shards are downloaded when the records of a package are requested.
+    Sharded(Box<ShardedSubdir>),
+}
+
+impl Subdir {
+    /// Returns all the records for the specified package name.
+    async fn load_records(
+        &self,
+        package_name: &PackageName,
+    ) -> Result<Vec<RepoDataRecord>, GatewayError> {
+        match self {
+            Subdir::Sparse(repo_data) => {
+                let repo_data = repo_data.clone();
+                let package_name = package_name.clone();
+                tokio::task::spawn_blocking(move || Ok(repo_data.load_records(&package_name)?))
+                    .await?
+            }
+            Subdir::Sharded(sharded) => sharded.load_records(package_name).await,
+        }
+    }
+}
+
+impl Gateway {
+    /// Constructs a new gateway that uses the specified client to download repodata and stores
+    /// it in `cache_dir`.
+    pub fn new(client: AuthenticatedClient, cache_dir: impl Into<PathBuf>) -> Self {
+        Self::with_options(client, cache_dir, GatewayOptions::default())
+    }
+
+    /// Constructs a new gateway with custom [`GatewayOptions`].
+    pub fn with_options(
+        client: AuthenticatedClient,
+        cache_dir: impl Into<PathBuf>,
+        options: GatewayOptions,
+    ) -> Self {
+        Self {
+            inner: Arc::new(GatewayInner {
+                client,
+                cache_dir: cache_dir.into(),
+                options,
+                subdirs: Mutex::default(),
+            }),
+        }
+    }
+
+    /// Returns the records of all packages that match the names of the specified specs, as well
+    /// as the records of all the packages they (recursively) depend on.
+    ///
+    /// The repodata of all `platforms` of the `channels` is fetched concurrently. The `noarch`
+    /// subdirectory is always included, other subdirectories that do not exist in a channel are
+    /// skipped. The result contains a list of records for every channel, in the order in which the
+    /// channels were specified. Specs without a name are ignored.
+    pub async fn query<'c, 's>(
+        &self,
+        channels: impl IntoIterator<Item = &'c Channel>,
+        platforms: impl IntoIterator<Item = Platform>,
+        specs: impl IntoIterator<Item = &'s MatchSpec>,
+    ) -> Result<Vec<Vec<RepoDataRecord>>, GatewayError> {
+        let channels: Vec<_> = channels.into_iter().collect();
+        let mut platforms: Vec<_> = platforms.into_iter().collect();
+        if !platforms.contains(&Platform::NoArch) {
+            platforms.push(Platform::NoArch);
+        }
+
+        // Fetch the repodata of all subdirectories at the same time.
+        let subdirs = try_join_all(channels.iter().enumerate().flat_map(|(idx, channel)| {
+            platforms.iter().map(move |&platform| async move {
+                Ok::<_, GatewayError>((idx, self.subdir(channel, platform).await?))
+            })
+        }))
+        .await?;
+        let subdirs: Vec<_> = subdirs
+            .into_iter()
+            .filter_map(|(idx, subdir)| Some((idx, subdir?)))
+            .collect();
+
+        // Construct a set of packages that we have seen and a list of packages that still need to
+        // be processed.
+        let mut seen: HashSet<PackageName> = specs
+            .into_iter()
+            .filter_map(|spec| spec.name.clone())
+            .collect();
+        let mut pending: Vec<_> = seen.iter().cloned().collect();
+
+        // Load the records of all pending packages at the same time and find their dependencies
+        // until no new packages are discovered.
+        let strict_channel_priority = self.inner.options.strict_channel_priority;
+        let mut result = vec![Vec::new(); channels.len()];
+        while !pending.is_empty() {
+            let subdirs = &subdirs;
+            let loaded = try_join_all(pending.drain(..).map(|package_name| async move {
+                let records = try_join_all(
+                    subdirs
+                        .iter()
+                        .map(|(_, subdir)| subdir.load_records(&package_name)),
+                )
+                .await?;
+                Ok::<_, GatewayError>(records)
+            }))
+            .await?;
+
+            for records_per_subdir in loaded {
+                let mut found_in_channel = None;
+                for ((idx, _), records) in subdirs.iter().zip(records_per_subdir) {
+                    // If the package was found in another channel, skip this subdirectory
+                    if found_in_channel.is_some_and(|channel_idx| channel_idx != *idx) {
+                        continue;
+                    }
+                    if strict_channel_priority && !records.is_empty() {
+                        found_in_channel = Some(*idx);
+                    }
+
+                    for record in &records {
+                        for dependency in &record.package_record.depends {
+                            let dependency_name = PackageName::new_unchecked(
+                                dependency.split_once(' ').unwrap_or((dependency, "")).0,
+                            );
+                            if seen.insert(dependency_name.clone()) {
+                                pending.push(dependency_name);
+                            }
+                        }
+                    }
+
+                    result[*idx].extend(records);
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Returns the repodata of a single subdirectory of a channel or `None` if the subdirectory
+    /// does not exist. The repodata is fetched if it has not been loaded before.
+    async fn subdir(
+        &self,
+        channel: &Channel,
+        platform: Platform,
+    ) -> Result<Option<Arc<Subdir>>, GatewayError> {
+        let url = channel.platform_url(platform);
+        let cell = self
+            .inner
+            .subdirs
+            .lock()
+            .expect("subdir cache lock was poisoned")
+            .entry(url.clone())
+            .or_default()
+            .clone();
+
+        // If another query is already fetching this subdirectory this waits for it to complete.
+        // If that fails the cell remains empty and the next caller tries again.
+        cell.get_or_try_init(|| self.fetch_subdir(channel.clone(), platform, url))
+            .await
+            .cloned()
+    }
+
+    /// Fetches the index of a sharded subdirectory or, if the subdirectory does not provide
+    /// sharded repodata, downloads the full `repodata.json` and constructs a [`SparseRepoData`]
+    /// from it.
+    async fn fetch_subdir(
+        &self,
+        channel: Channel,
+        platform: Platform,
+        url: Url,
+    ) -> Result<Option<Arc<Subdir>>, GatewayError> {
+        let options = &self.inner.options;
+        if options.sharded_repodata_enabled
+            && options.fetch_options.variant == Variant::AfterPatches
+        {
+            if let Some(sharded) = ShardedSubdir::new(
+                channel.clone(),
+                platform,
+                self.inner.client.clone(),
+                &self.inner.cache_dir,
+                options.fetch_options.cache_action,
+                options.patch_record_fn,
+            )
+            .await?
+            {
+                return Ok(Some(Arc::new(Subdir::Sharded(Box::new(sharded)))));
+            }
+        }
+
+        let cached = match fetch_repo_data(
+            url.clone(),
+            self.inner.client.clone(),
+            self.inner.cache_dir.clone(),
+            self.inner.options.fetch_options.clone(),
+            None,
+        )
+        .await
+        {
+            Ok(cached) => cached,
+            Err(FetchRepoDataError::NotFound(_)) if platform != Platform::NoArch => {
+                tracing::debug!("no repodata found for {url}, skipping");
+                return Ok(None);
+            }
+            Err(err) => return Err(GatewayError::FetchRepoDataError(url, Box::new(err))),
+        };
+
+        let patch_record_fn = self.inner.options.patch_record_fn;
+        let repo_data = tokio::task::spawn_blocking(move || {
+            // Keep the lock on the cache until the file has been memory mapped.
+            let repo_data = SparseRepoData::from_cached_repo_data(
+                channel,
+                platform.to_string(),
+                &cached,
+                patch_record_fn,
+            );
+            drop(cached);
+            repo_data
+        })
+        .await??;
+
+        Ok(Some(Arc::new(Subdir::Sparse(Arc::new(repo_data)))))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        sharded_subdir::{Shard, ShardedRepodata, ShardedSubdirInfo, SHARDS_INDEX_FILE_NAME},
+        Gateway, GatewayError, GatewayOptions,
+    };
+    use crate::{
+        fetch::CacheAction,
+        utils::{simple_channel_server::SimpleChannelServer, url_to_cache_filename},
+    };
+    use assert_matches::assert_matches;
+    use rattler_conda_types::{
+        Channel, ChannelConfig, MatchSpec, PackageName, PackageRecord, ParseStrictness, Platform,
+        RepoData, Version,
+    };
+    use rattler_digest::{compute_bytes_digest, Sha256};
+    use rattler_networking::AuthenticatedClient;
+    use std::{collections::HashMap, path::Path, str::FromStr};
+    use url::Url;
+
+    fn record(name: &str, version: &str, depends: &[&str]) -> PackageRecord {
+        let mut record = PackageRecord::new(
+            PackageName::new_unchecked(name),
+            Version::from_str(version).unwrap(),
+            String::from("0"),
+        );
+        record.depends = depends.iter().map(ToString::to_string).collect();
+        record
+    }
+
+    fn write_subdir(channel_dir: &Path, subdir: &str, records: Vec<PackageRecord>) {
+        let packages = records
+            .into_iter()
+            .map(|record| {
+                let file_name = format!(
+                    "{}-{}-0.tar.bz2",
+                    record.name.as_normalized(),
+                    record.version
+                );
+                (file_name, record)
+            })
+            .collect();
+        let repo_data = RepoData {
+            info: None,
+            packages,
+            conda_packages: Default::default(),
+            removed: Default::default(),
+            version: Some(1),
+        };
+        let subdir_path = channel_dir.join(subdir);
+        std::fs::create_dir_all(&subdir_path).unwrap();
+        std::fs::write(
+            subdir_path.join("repodata.json"),
+            serde_json::to_string(&repo_data).unwrap(),
+        )
+        .unwrap();
+    }
+
+    /// Writes the records as sharded repodata, the shards are stored in a `shards` directory. The
+    /// `removed` file names are marked as removed in the shards that contain them.
+    fn write_sharded_subdir(
+        channel_dir: &Path,
+        subdir: &str,
+        records: Vec<PackageRecord>,
+        removed: &[&str],
+    ) {
+        let shards_path = channel_dir.join(subdir).join("shards");
+        std::fs::create_dir_all(&shards_path).unwrap();
+
+        let mut shards: HashMap<String, Shard> = HashMap::new();
+        for record in records {
+            let file_name = format!(
+                "{}-{}-0.tar.bz2",
+                record.name.as_normalized(),
+                record.version
+            );
+            shards
+                .entry(record.name.as_normalized().to_owned())
+                .or_default()
+                .packages
+                .insert(file_name, record);
+        }
+
+        let mut index = ShardedRepodata {
+            info: ShardedSubdirInfo {
+                subdir: subdir.to_owned(),
+                base_url: String::from("./"),
+                shards_base_url: String::from("./shards/"),
+            },
+            shards: HashMap::new(),
+        };
+        for (name, mut shard) in shards {
+            shard.removed = shard
+                .packages
+                .keys()
+                .filter(|file_name| removed.contains(&file_name.as_str()))
+                .cloned()
+                .collect();
+            let bytes =
+                zstd::encode_all(rmp_serde::to_vec_named(&shard).unwrap().as_slice(), 0).unwrap();
+            let hash: [u8; 32] = compute_bytes_digest::<Sha256>(&bytes).into();
+            std::fs::write(
+                shards_path.join(format!("{}.msgpack.zst", hex::encode(hash))),
+                bytes,
+            )
+            .unwrap();
+            index.shards.insert(name, hash);
+        }
+
+        let bytes =
+            zstd::encode_all(rmp_serde::to_vec_named(&index).unwrap().as_slice(), 0).unwrap();
+        std::fs::write(channel_dir.join(subdir).join(SHARDS_INDEX_FILE_NAME), bytes).unwrap();
+    }
+
+    fn spec(spec: &str) -> MatchSpec {
+        MatchSpec::from_str(spec, ParseStrictness::Strict).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_query() {
+        let channel_dir = tempfile::tempdir().unwrap();
+        write_subdir(
+            channel_dir.path(),
+            "noarch",
+            vec![
+                record("foo", "1.0", &["bar >=1"]),
+                record("baz", "1.0", &[]),
+            ],
+        );
+        write_subdir(
+            channel_dir.path(),
+            "linux-64",
+            vec![
+                record("bar", "1.0", &[]),
+                record("bar", "2.0", &["qux"]),
+                record("qux", "1.0", &[]),
+            ],
+        );
+        let server = SimpleChannelServer::new(channel_dir.path());
+        let channel = Channel::from_str(server.url().as_str(), &ChannelConfig::default()).unwrap();
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let gateway = Gateway::new(AuthenticatedClient::default(), cache_dir.path());
+
+        // Run two queries at the same time, both require the same subdirectories.
+        let specs = [spec("foo")];
+        let (first, second) = tokio::join!(
+            gateway.query([&channel], [Platform::Linux64, Platform::Osx64], &specs),
+            gateway.query([&channel], [Platform::Linux64], &specs),
+        );
+        let first = first.unwrap();
+        assert_eq!(first, second.unwrap());
+
+        let mut file_names = first[0]
+            .iter()
+            .map(|record| record.file_name.as_str())
+            .collect::<Vec<_>>();
+        file_names.sort();
+        assert_eq!(
+            file_names,
+            vec![
+                "bar-1.0-0.tar.bz2",
+                "bar-2.0-0.tar.bz2",
+                "foo-1.0-0.tar.bz2",
+                "qux-1.0-0.tar.bz2"
+            ]
+        );
+
+        // Once loaded, the repodata is kept in memory so querying works without the server.
+        drop(server);
+        let records = gateway
+            .query([&channel], [Platform::Linux64], [&spec("baz")])
+            .await
+            .unwrap();
+        assert_eq!(records[0].len(), 1);
+        assert_eq!(records[0][0].file_name, "baz-1.0-0.tar.bz2");
+    }
+
+    #[tokio::test]
+    async fn test_channel_priority() {
+        let first_dir = tempfile::tempdir().unwrap();
+        write_subdir(first_dir.path(), "noarch", vec![record("foo", "1.0", &[])]);
+        let second_dir = tempfile::tempdir().unwrap();
+        write_subdir(
+            second_dir.path(),
+            "noarch",
+            vec![record("foo", "2.0", &[]), record("bar", "1.0", &[])],
+        );
+        let first_server = SimpleChannelServer::new(first_dir.path());
+        let second_server = SimpleChannelServer::new(second_dir.path());
+        let channels = [first_server.url(), second_server.url()]
+            .map(|url| Channel::from_str(url.as_str(), &ChannelConfig::default()).unwrap());
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let gateway = Gateway::new(AuthenticatedClient::default(), cache_dir.path());
+        let records = gateway
+            .query(&channels, [], [&spec("foo"), &spec("bar")])
+            .await
+            .unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].len(), 1);
+        assert_eq!(records[0][0].file_name, "foo-1.0-0.tar.bz2");
+        assert_eq!(records[1].len(), 1);
+        assert_eq!(records[1][0].file_name, "bar-1.0-0.tar.bz2");
+    }
+
+    #[tokio::test]
+    async fn test_sharded_query() {
+        let channel_dir = tempfile::tempdir().unwrap();
+        write_sharded_subdir(
+            channel_dir.path(),
+            "noarch",
+            vec![
+                record("foo", "1.0", &["bar >=1"]),
+                record("bar", "1.0", &[]),
+                record("bar", "2.0", &[]),
+                record("bar", "3.0", &[]),
+                record("baz", "1.0", &[]),
+            ],
+            &["bar-3.0-0.tar.bz2"],
+        );
+        let server = SimpleChannelServer::new(channel_dir.path());
+        let channel = Channel::from_str(server.url().as_str(), &ChannelConfig::default()).unwrap();
+
+        let cache_dir = tempfile::tempdir().unwrap();
+        let gateway = Gateway::new(AuthenticatedClient::default(), cache_dir.path());
+        let records = gateway
+            .query([&channel], [Platform::Linux64], [&spec("foo")])
+            .await
+            .unwrap();
+
+        let mut file_names = records[0]
+            .iter()
+            .map(|record| record.file_name.as_str())
+            .collect::<Vec<_>>();
+        file_names.sort();
+        assert_eq!(
+            file_names,
+            vec![
+                "bar-1.0-0.tar.bz2",
+                "bar-2.0-0.tar.bz2",
+                "foo-1.0-0.tar.bz2"
+            ]
+        );
+        assert_eq!(
+            records[0][0].url,
+            server
+                .url()
+                .join(&format!("noarch/{}", records[0][0].file_name))
+                .unwrap()
+        );
+
+        // Only the shards of the requested packages have been downloaded.
+        assert_eq!(
+            std::fs::read_dir(cache_dir.path().join("shards-v1"))
+                .unwrap()
+                .count(),
+            2
+        );
+
+        // The index and the shards are cached on disk.
+        drop(server);
+        let mut options = GatewayOptions::default();
+        options.fetch_options.cache_action = CacheAction::ForceCacheOnly;
+        let gateway =
+            Gateway::with_options(AuthenticatedClient::default(), cache_dir.path(), options);
+        let records = gateway.query([&channel], [], [&spec("foo")]).await.unwrap();
+        assert_eq!(records[0].len(), 3);
+
+        // The shard of a package that has not been queried before is not cached.
+        let result = gateway.query([&channel], [], [&spec("baz")]).await;
+        assert_matches!(result, Err(GatewayError::ShardNotCached(_)));
+    }
+
+    #[tokio::test]
+    async fn test_sharded_fallback() {
+        let channel_dir = tempfile::tempdir().unwrap();
+        write_subdir(
+            channel_dir.path(),
+            "noarch",
+            vec![record("foo", "1.0", &[])],
+        );
+
+        // Buckets respond with 403 for keys that do not exist.
+        let server = axum::Server::bind(&([127, 0, 0, 1], 0).into()).serve(
+            axum::Router::new()
+                .route(
+                    &format!("/noarch/{SHARDS_INDEX_FILE_NAME}"),
+                    axum::routing::get(|| async { reqwest::StatusCode::FORBIDDEN }),
+                )
+                .fallback_service(tower_http::services::ServeDir::new(channel_dir.path()))
+                .into_make_service(),
+        );
+        let url = format!("http://localhost:{}", server.local_addr().port());
+        let server = tokio::spawn(server);
+        let channel = Channel::from_str(&url, &ChannelConfig::default()).unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let gateway = Gateway::new(AuthenticatedClient::default(), cache_dir.path());
+        let records = gateway.query([&channel], [], [&spec("foo")]).await.unwrap();
+        assert_eq!(records[0].len(), 1);
+        server.abort();
+
+        // An index that cannot be parsed is ignored as well.
+        std::fs::write(
+            channel_dir
+                .path()
+                .join("noarch")
+                .join(SHARDS_INDEX_FILE_NAME),
+            b"invalid",
+        )
+        .unwrap();
+        let channel = Channel::from_str(
+            Url::from_directory_path(channel_dir.path())
+                .unwrap()
+                .as_str(),
+            &ChannelConfig::default(),
+        )
+        .unwrap();
+        let gateway = Gateway::new(AuthenticatedClient::default(), cache_dir.path());
+        let records = gateway.query([&channel], [], [&spec("foo")]).await.unwrap();
+        assert_eq!(records[0].len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_sharded_index_revalidation() {
+        let channel_dir = tempfile::tempdir().unwrap();
+        write_sharded_subdir(
+            channel_dir.path(),
+            "noarch",
+            vec![record("foo", "1.0", &[])],
+            &[],
+        );
+        let server = SimpleChannelServer::new(channel_dir.path());
+        let channel = Channel::from_str(server.url().as_str(), &ChannelConfig::default()).unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let gateway = Gateway::new(AuthenticatedClient::default(), cache_dir.path());
+        let records = gateway.query([&channel], [], [&spec("foo")]).await.unwrap();
+        assert_eq!(records[0].len(), 1);
+
+        // Replace the cached index with an empty one. The server reports that the index did not
+        // change so the cached index is used.
+        let index_url = server
+            .url()
+            .join(&format!("noarch/{SHARDS_INDEX_FILE_NAME}"))
+            .unwrap();
+        let index_cache_path = cache_dir
+            .path()
+            .join(format!("{}.msgpack.zst", url_to_cache_filename(&index_url)));
+        let empty_index = ShardedRepodata {
+            info: ShardedSubdirInfo::default(),
+            shards: HashMap::new(),
+        };
+        std::fs::write(
+            &index_cache_path,
+            zstd::encode_all(rmp_serde::to_vec_named(&empty_index).unwrap().as_slice(), 0).unwrap(),
+        )
+        .unwrap();
+        let gateway = Gateway::new(AuthenticatedClient::default(), cache_dir.path());
+        let records = gateway.query([&channel], [], [&spec("foo")]).await.unwrap();
+        assert!(records[0].is_empty());
+
+        // Once the index changes on the server it is downloaded again. The `Last-Modified` header
+        // only has a resolution of a second.
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        write_sharded_subdir(
+            channel_dir.path(),
+            "noarch",
+            vec![record("foo", "1.0", &[]), record("foo", "2.0", &[])],
+            &[],
+        );
+        let gateway = Gateway::new(AuthenticatedClient::default(), cache_dir.path());
+        let records = gateway.query([&channel], [], [&spec("foo")]).await.unwrap();
+        assert_eq!(records[0].len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_sharded_hash_mismatch() {
+        let channel_dir = tempfile::tempdir().unwrap();
+        write_sharded_subdir(
+            channel_dir.path(),
+            "noarch",
+            vec![record("foo", "1.0", &[])],
+            &[],
+        );
+
+        // Tamper with the shard
+        let shards_path = channel_dir.path().join("noarch/shards");
+        for entry in std::fs::read_dir(&shards_path).unwrap() {
+            std::fs::write(entry.unwrap().path(), b"tampered").unwrap();
+        }
+
+        let server = SimpleChannelServer::new(channel_dir.path());
+        let channel = Channel::from_str(server.url().as_str(), &ChannelConfig::default()).unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let gateway = Gateway::new(AuthenticatedClient::default(), cache_dir.path());
+        let result = gateway.query([&channel], [], [&spec("foo")]).await;
+        assert_matches!(result, Err(GatewayError::ShardHashMismatch(_)));
+    }
+}
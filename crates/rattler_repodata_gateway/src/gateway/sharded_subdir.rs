@@ -0,0 +1,439 @@
+//! Support for sharded repodata.
+//!
+//! Instead of a single `repodata.json` file a sharded subdirectory provides a small index file
+//! (`repodata_shards.msgpack.zst`) that maps every package name to the SHA256 hash of a shard.
+//! A shard is a zstd compressed msgpack file that contains all the records of a single package.
+//! Because shards are content-addressed they can be cached indefinitely, the index is cached
+//! according to the HTTP cache headers of the server.
+
+use super::{GatewayError, ShardHashMismatchError};
+use crate::{
+    fetch::{cache::CacheHeaders, CacheAction},
+    utils::url_to_cache_filename,
+};
+use rattler_conda_types::{
+    compute_package_url, Channel, PackageName, PackageRecord, Platform, RepoDataRecord,
+};
+use rattler_digest::{compute_bytes_digest, Sha256};
+use rattler_networking::AuthenticatedClient;
+use reqwest::{header::HeaderMap, Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, Bytes};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+use tokio::sync::OnceCell;
+use url::Url;
+
+/// The file name of the index of a sharded subdirectory.
+pub const SHARDS_INDEX_FILE_NAME: &str = "repodata_shards.msgpack.zst";
+
+/// The index of a sharded subdirectory, stored as `repodata_shards.msgpack.zst`.
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShardedRepodata {
+    /// Information about the subdirectory and where to find its packages and shards.
+    pub info: ShardedSubdirInfo,
+
+    /// The SHA256 hash of the shard of every package in the subdirectory.
+    #[serde_as(as = "HashMap<_, Bytes>")]
+    pub shards: HashMap<String, [u8; 32]>,
+}
+
+/// Information about a sharded subdirectory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ShardedSubdirInfo {
+    /// The name of the subdirectory
+    pub subdir: String,
+
+    /// The url at which the packages of the subdirectory are stored. This can be relative to the
+    /// url of the subdirectory.
+    #[serde(default)]
+    pub base_url: String,
+
+    /// The url at which the shards of the subdirectory are stored. This can be relative to the
+    /// url of the subdirectory.
+    #[serde(default)]
+    pub shards_base_url: String,
+}
+
+/// The contents of a single shard, the records of a single package.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Shard {
+    /// The tar.bz2 packages contained in the shard
+    #[serde(default)]
+    pub packages: BTreeMap<String, PackageRecord>,
+
+    /// The conda packages contained in the shard
+    #[serde(default, rename = "packages.conda")]
+    pub conda_packages: BTreeMap<String, PackageRecord>,
+
+    /// The file names of packages that have been removed
+    #[serde(default)]
+    pub removed: Vec<String>,
+}
+
+/// A subdirectory of a channel that provides sharded repodata. Shards are downloaded on demand
+/// and the records they contain are kept in memory.
+pub(crate) struct ShardedSubdir {
+    channel: Channel,
+    subdir: String,
+    client: AuthenticatedClient,
+
+    /// The url of the subdirectory
+    subdir_url: Url,
+
+    /// The directory in which downloaded shards are stored
+    shards_cache_dir: PathBuf,
+
+    /// Determines whether shards are read from the cache or downloaded
+    cache_action: CacheAction,
+
+    /// The index of the subdirectory
+    index: ShardedRepodata,
+
+    /// A function that can be used to patch the package record after it has been parsed.
+    patch_record_fn: Option<fn(&mut PackageRecord)>,
+
+    /// The records of the packages that have been loaded (or are being loaded).
+    records: Mutex<HashMap<PackageName, Arc<OnceCell<Vec<RepoDataRecord>>>>>,
+}
+
+impl ShardedSubdir {
+    /// Fetches the index of a sharded subdirectory. Returns `None` if the subdirectory does not
+    /// provide sharded repodata, that is if the index cannot be downloaded or cannot be parsed.
+    ///
+    /// The index is stored in `cache_dir` together with the HTTP cache headers of the response.
+    /// Like the `repodata.json`, a cached index is used as long as the `Cache-Control` header
+    /// allows it and is revalidated with the server afterwards.
+    pub async fn new(
+        channel: Channel,
+        platform: Platform,
+        client: AuthenticatedClient,
+        cache_dir: &Path,
+        cache_action: CacheAction,
+        patch_record_fn: Option<fn(&mut PackageRecord)>,
+    ) -> Result<Option<Self>, GatewayError> {
+        let subdir_url = channel.platform_url(platform);
+        let index_url = subdir_url
+            .join(SHARDS_INDEX_FILE_NAME)
+            .expect("file name is valid");
+
+        // Local files are always read directly, there is no need to cache them.
+        let bytes = if index_url.scheme() == "file" {
+            fetch_bytes(&client, &index_url).await?
+        } else {
+            fetch_index(&client, &index_url, cache_dir, cache_action).await?
+        };
+        let Some(bytes) = bytes else {
+            return Ok(None);
+        };
+
+        let index: ShardedRepodata = match decode_msgpack_zst(&index_url, bytes).await {
+            Ok(index) => index,
+            Err(err) => {
+                tracing::debug!("ignoring invalid sharded repodata index {index_url}: {err}");
+                return Ok(None);
+            }
+        };
+        tracing::debug!(
+            "found sharded repodata for {subdir_url} with {} packages",
+            index.shards.len()
+        );
+
+        Ok(Some(Self {
+            channel,
+            subdir: platform.to_string(),
+            client,
+            subdir_url,
+            shards_cache_dir: cache_dir.join("shards-v1"),
+            cache_action,
+            index,
+            patch_record_fn,
+            records: Mutex::default(),
+        }))
+    }
+
+    /// Returns all the records for the specified package name. The shard of the package is
+    /// downloaded if it has not been loaded before.
+    pub async fn load_records(
+        &self,
+        package_name: &PackageName,
+    ) -> Result<Vec<RepoDataRecord>, GatewayError> {
+        let Some(hash) = self.index.shards.get(package_name.as_normalized()) else {
+            return Ok(Vec::new());
+        };
+
+        let cell = self
+            .records
+            .lock()
+            .expect("shard cache lock was poisoned")
+            .entry(package_name.clone())
+            .or_default()
+            .clone();
+        cell.get_or_try_init(|| self.fetch_shard(hash))
+            .await
+            .cloned()
+    }
+
+    /// Reads the shard with the given hash from the cache or downloads it, and converts its
+    /// contents into records. The cache action determines whether the shard may be read from the
+    /// cache and whether it may be downloaded.
+    async fn fetch_shard(&self, hash: &[u8; 32]) -> Result<Vec<RepoDataRecord>, GatewayError> {
+        let file_name = format!("{}.msgpack.zst", hex::encode(hash));
+        let shard_url = compute_package_url(
+            &self.subdir_url,
+            Some(&self.index.info.shards_base_url),
+            &file_name,
+        );
+        let cache_path = self.shards_cache_dir.join(&file_name);
+
+        // Shards are content-addressed, a cached shard never has to be revalidated.
+        let cached = if self.cache_action == CacheAction::NoCache {
+            Err(ErrorKind::NotFound.into())
+        } else {
+            tokio::fs::read(&cache_path).await
+        };
+        let bytes = match cached {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                if matches!(
+                    self.cache_action,
+                    CacheAction::UseCacheOnly | CacheAction::ForceCacheOnly
+                ) {
+                    return Err(GatewayError::ShardNotCached(shard_url));
+                }
+                let bytes = fetch_bytes(&self.client, &shard_url)
+                    .await?
+                    .ok_or_else(|| GatewayError::ShardNotFound(shard_url.clone()))?;
+                let actual = compute_bytes_digest::<Sha256>(&bytes);
+                if actual.as_slice() != hash {
+                    return Err(GatewayError::ShardHashMismatch(Box::new(
+                        ShardHashMismatchError {
+                            url: shard_url,
+                            expected: hex::encode(hash),
+                            actual: format!("{actual:x}"),
+                        },
+                    )));
+                }
+                write_atomic(&cache_path, &bytes).await?;
+                bytes
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let shard: Shard = decode_msgpack_zst(&shard_url, bytes).await?;
+        Ok(self.records_from_shard(shard))
+    }
+
+    /// Converts the contents of a shard into records. Packages that have been removed are skipped.
+    fn records_from_shard(&self, shard: Shard) -> Vec<RepoDataRecord> {
+        let channel_name = self.channel.base_url().to_string();
+        let removed: HashSet<_> = shard.removed.into_iter().collect();
+        shard
+            .packages
+            .into_iter()
+            .chain(shard.conda_packages)
+            .filter(|(file_name, _)| !removed.contains(file_name))
+            .map(|(file_name, mut package_record)| {
+                // Overwrite subdir if its empty
+                if package_record.subdir.is_empty() {
+                    package_record.subdir = self.subdir.clone();
+                }
+                if let Some(patch_fn) = self.patch_record_fn {
+                    patch_fn(&mut package_record);
+                }
+                RepoDataRecord {
+                    url: compute_package_url(
+                        &self.subdir_url,
+                        Some(&self.index.info.base_url),
+                        &file_name,
+                    ),
+                    channel: channel_name.clone(),
+                    package_record,
+                    file_name,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Returns the contents of the index of a sharded subdirectory from the cache or downloads it if
+/// the cache is missing or out of date. Returns `None` if the server does not successfully respond
+/// with the index or if it is not cached and the cache action forbids fetching it.
+async fn fetch_index(
+    client: &AuthenticatedClient,
+    index_url: &Url,
+    cache_dir: &Path,
+    cache_action: CacheAction,
+) -> Result<Option<Vec<u8>>, GatewayError> {
+    let cache_key = url_to_cache_filename(index_url);
+    let index_cache_path = cache_dir.join(format!("{cache_key}.msgpack.zst"));
+    let cache_state_path = cache_dir.join(format!("{cache_key}.info.json"));
+
+    let cached = if cache_action == CacheAction::NoCache {
+        None
+    } else {
+        read_cached_index(&index_cache_path, &cache_state_path).await?
+    };
+    let cached = match (cached, cache_action) {
+        (Some(cached), CacheAction::ForceCacheOnly) => return Ok(Some(cached.bytes)),
+        (Some(cached), _) if cached.is_fresh() => return Ok(Some(cached.bytes)),
+        (_, CacheAction::UseCacheOnly | CacheAction::ForceCacheOnly) => return Ok(None),
+        (cached, _) => cached,
+    };
+
+    let mut headers = HeaderMap::default();
+    if let Some(cached) = &cached {
+        cached.cache_headers.add_to_request(&mut headers);
+    }
+    let http_error = |err| GatewayError::HttpError(index_url.clone(), err);
+    let (_, response) = client
+        .send_with_mirrors(Method::GET, index_url, |builder| {
+            builder.headers(headers.clone())
+        })
+        .await
+        .map_err(http_error)?;
+    match response.status() {
+        StatusCode::NOT_MODIFIED if cached.is_some() => {
+            tracing::debug!("sharded repodata index {index_url} was unmodified");
+            return Ok(cached.map(|cached| cached.bytes));
+        }
+        status if !status.is_success() => {
+            // Servers that do not provide sharded repodata respond with all kinds of errors (e.g.
+            // buckets respond with 403 for missing keys), so this is not treated as a failure.
+            tracing::debug!("no sharded repodata index at {index_url} ({status})");
+            return Ok(None);
+        }
+        _ => {}
+    }
+
+    let cache_headers = CacheHeaders::from(&response);
+    let bytes = response.bytes().await.map_err(http_error)?.to_vec();
+
+    // The index is written before its state so the state never refers to an older index.
+    write_atomic(&index_cache_path, &bytes).await?;
+    let state = serde_json::to_vec(&cache_headers).map_err(std::io::Error::from)?;
+    write_atomic(&cache_state_path, &state).await?;
+
+    Ok(Some(bytes))
+}
+
+/// The index of a sharded subdirectory that is stored in the cache.
+struct CachedIndex {
+    bytes: Vec<u8>,
+
+    /// The cache headers of the response that contained the index.
+    cache_headers: CacheHeaders,
+
+    /// The time at which the index was stored.
+    modified: SystemTime,
+}
+
+impl CachedIndex {
+    /// Returns true if the cached index can be used without revalidating it with the server.
+    fn is_fresh(&self) -> bool {
+        SystemTime::now()
+            .duration_since(self.modified)
+            .is_ok_and(|age| self.cache_headers.is_fresh(age))
+    }
+}
+
+/// Reads a cached index and its cache headers. Returns `None` if either of them is missing or the
+/// cache headers cannot be parsed.
+async fn read_cached_index(
+    index_cache_path: &Path,
+    cache_state_path: &Path,
+) -> Result<Option<CachedIndex>, GatewayError> {
+    let (bytes, state) = match futures::try_join!(
+        tokio::fs::read(index_cache_path),
+        tokio::fs::read(cache_state_path)
+    ) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let cache_headers = match serde_json::from_slice(&state) {
+        Ok(cache_headers) => cache_headers,
+        Err(err) => {
+            tracing::warn!(
+                "invalid sharded repodata cache state '{}': {err}. Ignoring cached index...",
+                cache_state_path.display()
+            );
+            return Ok(None);
+        }
+    };
+    let modified = tokio::fs::metadata(index_cache_path).await?.modified()?;
+    Ok(Some(CachedIndex {
+        bytes,
+        cache_headers,
+        modified,
+    }))
+}
+
+/// Downloads the contents of a url. Returns `None` if the file does not exist.
+async fn fetch_bytes(
+    client: &AuthenticatedClient,
+    url: &Url,
+) -> Result<Option<Vec<u8>>, GatewayError> {
+    if url.scheme() == "file" {
+        let path = url.to_file_path().expect("file url is a valid path");
+        return match tokio::fs::read(path).await {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        };
+    }
+
+    let http_error = |err| GatewayError::HttpError(url.clone(), err);
+    let (_, response) = client
+        .send_with_mirrors(Method::GET, url, |builder| builder)
+        .await
+        .map_err(http_error)?;
+    if response.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let bytes = response
+        .error_for_status()
+        .map_err(http_error)?
+        .bytes()
+        .await
+        .map_err(http_error)?;
+    Ok(Some(bytes.to_vec()))
+}
+
+/// Decompresses and deserializes a zstd compressed msgpack file.
+async fn decode_msgpack_zst<T: serde::de::DeserializeOwned + Send + 'static>(
+    url: &Url,
+    bytes: Vec<u8>,
+) -> Result<T, GatewayError> {
+    let url = url.clone();
+    tokio::task::spawn_blocking(move || {
+        let decompressed = zstd::decode_all(bytes.as_slice())?;
+        rmp_serde::from_slice(&decompressed)
+            .map_err(|err| GatewayError::InvalidShardedRepodata(url, err))
+    })
+    .await?
+}
+
+/// Writes the contents to a temporary file first and then moves it into place so other processes
+/// never observe a partially written file.
+async fn write_atomic(path: &Path, bytes: &[u8]) -> Result<(), GatewayError> {
+    let path = path.to_path_buf();
+    let bytes = bytes.to_vec();
+    tokio::task::spawn_blocking(move || {
+        let dir = path
+            .parent()
+            .expect("cache files are stored in a directory");
+        std::fs::create_dir_all(dir)?;
+        let mut file = tempfile::NamedTempFile::new_in(dir)?;
+        file.write_all(&bytes)?;
+        file.persist(&path).map_err(|err| err.error)?;
+        Ok(())
+    })
+    .await?
+}
@@ -4,8 +4,8 @@
 //! repodata. It currently provides functionality to download and cache `repodata.json` files
 //! through the [`fetch::fetch_repo_data`] function.
 //!
-//! In the future this crate will also provide more high-level functionality to query information
-//! about specific packages from different sources.
+//! With the `sparse` feature enabled, the [`gateway::Gateway`] provides more high-level
+//! functionality to query the records of specific packages from different channels.
 //!
 //! # Install
 //! Add the following to your *Cargo.toml*:
@@ -61,6 +61,8 @@
 
 pub mod fetch;
 #[cfg(feature = "sparse")]
+pub mod gateway;
+#[cfg(feature = "sparse")]
 pub mod sparse;
 
 mod utils;
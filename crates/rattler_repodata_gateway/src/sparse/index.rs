@@ -0,0 +1,350 @@
+//! A persistent binary index of a `repodata.json` file.
+//!
+//! Constructing a [`LazyRepoData`] requires scanning the entire `repodata.json` file. For large
+//! channels this takes seconds. The index stores the location of the filename and the record of
+//! every package in the file (already sorted by package name) so a [`LazyRepoData`] can be
+//! reconstructed without parsing the json. Records are only deserialized when they are requested.
+//!
+//! The index is only valid for a specific version of the `repodata.json` file. It stores a key
+//! that is derived from the [`RepoDataState`] of the file and the index is ignored if the key no
+//! longer matches.
+//!
+//! The layout of the file is (all integers are little-endian):
+//!
+//! ```text
+//! magic        8 bytes
+//! key          u32 length followed by utf8 bytes
+//! json size    u64
+//! info         u32 length followed by the json encoded `info` field
+//! packages     u64 count followed by (u64 offset, u32 length) pairs of the filename and record
+//! conda pkgs   same as packages
+//! ```
+
+use super::{LazyRepoData, PackageFilename};
+use crate::fetch::cache::RepoDataState;
+use std::{
+    io::{self, Write},
+    path::Path,
+    time::UNIX_EPOCH,
+};
+
+/// Identifies the file format and its version.
+const MAGIC: &[u8; 8] = b"RSPIDX01";
+
+/// Returns a key that identifies the version of a cached `repodata.json` file. This is the blake2
+/// hash of the file if it is known, otherwise the modification time and size of the file are used.
+pub(super) fn index_key(state: &RepoDataState) -> String {
+    match &state.blake2_hash {
+        Some(hash) => format!("blake2:{hash:x}"),
+        None => {
+            let mtime = state
+                .cache_last_modified
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_nanos())
+                .unwrap_or_default();
+            format!("mtime:{mtime}:{}", state.cache_size)
+        }
+    }
+}
+
+/// Writes the index of `repo_data` which was parsed from `memory_map` to `path`.
+pub(super) fn write_index(
+    path: &Path,
+    key: &str,
+    memory_map: &[u8],
+    repo_data: &LazyRepoData<'_>,
+) -> io::Result<()> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(MAGIC);
+    write_bytes(&mut buf, key.as_bytes())?;
+    buf.extend_from_slice(&(memory_map.len() as u64).to_le_bytes());
+    write_bytes(&mut buf, &serde_json::to_vec(&repo_data.info)?)?;
+    for entries in [&repo_data.packages, &repo_data.conda_packages] {
+        buf.extend_from_slice(&(entries.len() as u64).to_le_bytes());
+        for (filename, raw_record) in entries {
+            write_range(&mut buf, memory_map, filename.filename.as_bytes())?;
+            write_range(&mut buf, memory_map, raw_record)?;
+        }
+    }
+
+    // Write to a temporary file first so other processes never read a partially written index.
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut file = tempfile::NamedTempFile::new_in(dir)?;
+    file.write_all(&buf)?;
+    file.persist(path).map_err(|err| err.error)?;
+    Ok(())
+}
+
+/// Reconstructs a [`LazyRepoData`] from an index. Returns `None` if the index was created for
+/// another version of the `repodata.json` file or if the index is corrupt. An index is considered
+/// corrupt if its entries do not refer to json strings and objects in `memory_map` or if they are
+/// not sorted by package name.
+pub(super) fn read_index<'i>(
+    index: &[u8],
+    key: &str,
+    memory_map: &'i [u8],
+) -> Option<LazyRepoData<'i>> {
+    let mut reader = Reader(index);
+    if reader.take(MAGIC.len())? != MAGIC
+        || reader.bytes()? != key.as_bytes()
+        || reader.u64()? != memory_map.len() as u64
+    {
+        return None;
+    }
+    let info = serde_json::from_slice(reader.bytes()?).ok()?;
+    let packages = reader.entries(memory_map)?;
+    let conda_packages = reader.entries(memory_map)?;
+    if !reader.0.is_empty() || !is_sorted(&packages) || !is_sorted(&conda_packages) {
+        return None;
+    }
+
+    Some(LazyRepoData {
+        info,
+        packages,
+        conda_packages,
+    })
+}
+
+/// Returns true if the entries are sorted by package name, records are looked up with a binary
+/// search.
+fn is_sorted(entries: &[(PackageFilename<'_>, &[u8])]) -> bool {
+    entries
+        .windows(2)
+        .all(|pair| pair[0].0.package <= pair[1].0.package)
+}
+
+/// Writes a length-prefixed byte string.
+fn write_bytes(buf: &mut Vec<u8>, bytes: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(bytes.len()).map_err(|_| invalid_data("value is too large"))?;
+    buf.extend_from_slice(&len.to_le_bytes());
+    buf.extend_from_slice(bytes);
+    Ok(())
+}
+
+/// Writes the location of `slice` within `memory_map`.
+fn write_range(buf: &mut Vec<u8>, memory_map: &[u8], slice: &[u8]) -> io::Result<()> {
+    let offset = (slice.as_ptr() as usize)
+        .checked_sub(memory_map.as_ptr() as usize)
+        .filter(|offset| offset + slice.len() <= memory_map.len())
+        .ok_or_else(|| invalid_data("entry does not refer to the repodata"))?;
+    let len = u32::try_from(slice.len()).map_err(|_| invalid_data("entry is too large"))?;
+    buf.extend_from_slice(&(offset as u64).to_le_bytes());
+    buf.extend_from_slice(&len.to_le_bytes());
+    Ok(())
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// A cursor over the bytes of an index.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Some(head)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    fn bytes(&mut self) -> Option<&'a [u8]> {
+        let len = self.u32()?;
+        self.take(len as usize)
+    }
+
+    /// Reads the location of a slice of `memory_map` and returns the slice including `padding`
+    /// bytes on both sides.
+    fn range<'i>(&mut self, memory_map: &'i [u8], padding: usize) -> Option<&'i [u8]> {
+        let offset = usize::try_from(self.u64()?).ok()?;
+        let len = self.u32()? as usize;
+        let end = offset.checked_add(len)?.checked_add(padding)?;
+        memory_map.get(offset.checked_sub(padding)?..end)
+    }
+
+    /// Reads the location of the contents of a json string in `memory_map`.
+    fn string<'i>(&mut self, memory_map: &'i [u8]) -> Option<&'i str> {
+        let quoted = self.range(memory_map, 1)?;
+        let contents = quoted.strip_prefix(b"\"")?.strip_suffix(b"\"")?;
+        std::str::from_utf8(contents).ok()
+    }
+
+    /// Reads the location of a json object in `memory_map`.
+    fn object<'i>(&mut self, memory_map: &'i [u8]) -> Option<&'i [u8]> {
+        let object = self.range(memory_map, 0)?;
+        (object.first() == Some(&b'{') && object.last() == Some(&b'}')).then_some(object)
+    }
+
+    fn entries<'i>(
+        &mut self,
+        memory_map: &'i [u8],
+    ) -> Option<Vec<(PackageFilename<'i>, &'i [u8])>> {
+        let count = usize::try_from(self.u64()?).ok()?;
+
+        // Each entry takes 24 bytes, this protects against allocating a huge vector for a corrupt
+        // index.
+        let mut entries = Vec::with_capacity(count.min(self.0.len() / 24));
+        for _ in 0..count {
+            let filename = self.string(memory_map)?;
+            let raw_record = self.object(memory_map)?;
+            entries.push((PackageFilename::try_from(filename).ok()?, raw_record));
+        }
+        Some(entries)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{read_index, write_index};
+    use crate::sparse::SparseRepoData;
+    use rattler_conda_types::{Channel, ChannelConfig, PackageName};
+
+    const REPO_DATA: &str = r#"{
+        "info": {"subdir": "linux-64"},
+        "packages": {
+            "foo-1.0-0.tar.bz2": {"name": "foo", "version": "1.0", "build": "0", "build_number": 0, "depends": ["bar"]},
+            "bar-2.0-0.tar.bz2": {"name": "bar", "version": "2.0", "build": "0", "build_number": 0, "depends": []}
+        },
+        "packages.conda": {
+            "foo-2.0-0.conda": {"name": "foo", "version": "2.0", "build": "0", "build_number": 0, "depends": []}
+        }
+    }"#;
+
+    fn load(dir: &std::path::Path, key: &str) -> SparseRepoData {
+        SparseRepoData::new_with_index(
+            Channel::from_str("conda-forge", &ChannelConfig::default()).unwrap(),
+            "linux-64",
+            &dir.join("repodata.json"),
+            &dir.join("repodata.sparse-index"),
+            key,
+            None,
+        )
+        .unwrap()
+    }
+
+    fn file_names(repo_data: &SparseRepoData, name: &str) -> Vec<String> {
+        repo_data
+            .load_records(&PackageName::new_unchecked(name))
+            .unwrap()
+            .into_iter()
+            .map(|record| record.file_name)
+            .collect()
+    }
+
+    #[test]
+    fn test_index_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("repodata.json"), REPO_DATA).unwrap();
+
+        // The first load creates the index
+        let repo_data = load(dir.path(), "blake2:abc");
+        let index = std::fs::read(dir.path().join("repodata.sparse-index")).unwrap();
+        let memory_map = REPO_DATA.as_bytes();
+        let from_index = read_index(&index, "blake2:abc", memory_map).unwrap();
+        assert_eq!(from_index.packages.len(), 2);
+        assert_eq!(from_index.conda_packages.len(), 1);
+        assert_eq!(from_index.info.unwrap().subdir, "linux-64");
+        assert!(read_index(&index, "blake2:def", memory_map).is_none());
+        assert!(read_index(&index[..index.len() - 1], "blake2:abc", memory_map).is_none());
+        assert!(read_index(&index, "blake2:abc", &memory_map[1..]).is_none());
+
+        // Loading again uses the index and results in the same records
+        let from_index = load(dir.path(), "blake2:abc");
+        for name in ["foo", "bar", "baz"] {
+            assert_eq!(file_names(&repo_data, name), file_names(&from_index, name));
+        }
+        assert_eq!(
+            file_names(&from_index, "foo"),
+            vec!["foo-1.0-0.tar.bz2", "foo-2.0-0.conda"]
+        );
+        assert_eq!(
+            from_index.package_names().collect::<Vec<_>>(),
+            vec!["bar", "foo"]
+        );
+    }
+
+    /// Returns the position of the first entry of the `packages` in the index.
+    fn packages_position(index: &[u8], key: &str) -> usize {
+        let info_position = 8 + 4 + key.len() + 8;
+        let info_len = u32::from_le_bytes(index[info_position..][..4].try_into().unwrap());
+        info_position + 4 + info_len as usize + 8
+    }
+
+    #[test]
+    fn test_corrupt_index_is_rebuilt() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("repodata.json"), REPO_DATA).unwrap();
+        load(dir.path(), "key");
+        let index_path = dir.path().join("repodata.sparse-index");
+        let index = std::fs::read(&index_path).unwrap();
+        let memory_map = REPO_DATA.as_bytes();
+        let position = packages_position(&index, "key");
+
+        // Swapping the entries breaks the ordering by package name.
+        let mut unsorted = index.clone();
+        unsorted[position..position + 48].rotate_left(24);
+        assert!(read_index(&unsorted, "key", memory_map).is_none());
+
+        // Shifting the filename no longer refers to a json string.
+        let mut shifted = index.clone();
+        shifted[position] += 1;
+        assert!(read_index(&shifted, "key", memory_map).is_none());
+
+        // Shifting the record no longer refers to a json object.
+        let mut shifted = index.clone();
+        shifted[position + 12] += 1;
+        assert!(read_index(&shifted, "key", memory_map).is_none());
+
+        // A corrupt index is replaced by a valid one.
+        std::fs::write(&index_path, &unsorted).unwrap();
+        let repo_data = load(dir.path(), "key");
+        assert_eq!(
+            file_names(&repo_data, "foo"),
+            vec!["foo-1.0-0.tar.bz2", "foo-2.0-0.conda"]
+        );
+        assert_eq!(std::fs::read(&index_path).unwrap(), index);
+    }
+
+    #[test]
+    fn test_stale_index_is_rebuilt() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("repodata.json"), REPO_DATA).unwrap();
+        load(dir.path(), "mtime:1:2");
+
+        // Change the repodata, the index was created for another version.
+        let updated = REPO_DATA.replace("\"bar-2.0-0.tar.bz2\"", "\"baz-2.0-0.tar.bz2\"");
+        std::fs::write(dir.path().join("repodata.json"), &updated).unwrap();
+        let repo_data = load(dir.path(), "mtime:3:4");
+        assert_eq!(file_names(&repo_data, "bar"), Vec::<String>::new());
+        assert_eq!(file_names(&repo_data, "baz"), vec!["baz-2.0-0.tar.bz2"]);
+
+        let index = std::fs::read(dir.path().join("repodata.sparse-index")).unwrap();
+        assert!(read_index(&index, "mtime:3:4", updated.as_bytes()).is_some());
+    }
+
+    #[test]
+    fn test_write_index_rejects_foreign_slices() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("repodata.json"), REPO_DATA).unwrap();
+        let repo_data = load(dir.path(), "key");
+        let result = repo_data.inner.with(|fields| {
+            write_index(
+                &dir.path().join("other.sparse-index"),
+                "key",
+                b"not the repodata",
+                fields.repo_data,
+            )
+        });
+        assert!(result.is_err());
+    }
+}
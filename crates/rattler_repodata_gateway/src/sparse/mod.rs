@@ -1,6 +1,7 @@
 //! This module provides the [`SparseRepoData`] which is a struct to enable only sparsely loading records
 //! from a `repodata.json` file.
 
+use crate::fetch::CachedRepoData;
 use futures::{stream, StreamExt, TryFutureExt, TryStreamExt};
 use itertools::Itertools;
 use rattler_conda_types::{
@@ -19,6 +20,8 @@ use std::{
 };
 use superslice::Ext;
 
+mod index;
+
 /// A struct to enable loading records from a `repodata.json` file on demand. Since most of the time you
 /// don't need all the records from the `repodata.json` this can help provide some significant speedups.
 pub struct SparseRepoData {
@@ -75,6 +78,83 @@ impl SparseRepoData {
         })
     }
 
+    /// Construct an instance of self from the result of [`crate::fetch::fetch_repo_data`].
+    ///
+    /// Building the index of the package names requires scanning the entire `repodata.json` file
+    /// which can take a while for large channels. The index is therefore persisted next to the
+    /// cached `repodata.json` file and reused as long as the cache state of the repodata did not
+    /// change.
+    pub fn from_cached_repo_data(
+        channel: Channel,
+        subdir: impl Into<String>,
+        cached: &CachedRepoData,
+        patch_function: Option<fn(&mut PackageRecord)>,
+    ) -> Result<Self, io::Error> {
+        Self::new_with_index(
+            channel,
+            subdir,
+            &cached.repo_data_json_path,
+            &cached.repo_data_json_path.with_extension("sparse-index"),
+            &index::index_key(&cached.cache_state),
+            patch_function,
+        )
+    }
+
+    /// Construct an instance of self from a file on disk and use the index stored at `index_path`
+    /// if it was created for the same `key`. Otherwise the index is rebuilt and stored at
+    /// `index_path`.
+    fn new_with_index(
+        channel: Channel,
+        subdir: impl Into<String>,
+        path: &Path,
+        index_path: &Path,
+        key: &str,
+        patch_function: Option<fn(&mut PackageRecord)>,
+    ) -> Result<Self, io::Error> {
+        let file = std::fs::File::open(path)?;
+        let memory_map = unsafe { memmap2::Mmap::map(&file) }?;
+        let index = match std::fs::read(index_path) {
+            Ok(index) => Some(index),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => {
+                tracing::warn!("failed to read {}: {err}", index_path.display());
+                None
+            }
+        };
+
+        let mut index_used = false;
+        let inner = SparseRepoDataInnerTryBuilder {
+            memory_map,
+            repo_data_builder: |memory_map| {
+                let repo_data = index
+                    .as_deref()
+                    .and_then(|index| index::read_index(index, key, memory_map));
+                if let Some(repo_data) = repo_data {
+                    index_used = true;
+                    return Ok(repo_data);
+                }
+                serde_json::from_slice(memory_map.as_ref())
+            },
+        }
+        .try_build()?;
+
+        if !index_used {
+            let result = inner.with(|fields| {
+                index::write_index(index_path, key, fields.memory_map, fields.repo_data)
+            });
+            if let Err(err) = result {
+                tracing::warn!("failed to write {}: {err}", index_path.display());
+            }
+        }
+
+        Ok(SparseRepoData {
+            inner,
+            subdir: subdir.into(),
+            channel,
+            patch_record_fn: patch_function,
+        })
+    }
+
     /// Returns an iterator over all package names in this repodata file.
     ///
     /// This works by iterating over all elements in the `packages` and `conda_packages` fields of
@@ -212,31 +292,31 @@ struct LazyRepoData<'i> {
     /// The tar.bz2 packages contained in the repodata.json file
     #[serde(borrow)]
     #[serde(deserialize_with = "deserialize_filename_and_raw_record")]
-    packages: Vec<(PackageFilename<'i>, &'i RawValue)>,
+    packages: Vec<(PackageFilename<'i>, &'i [u8])>,
 
     /// The conda packages contained in the repodata.json file (under a different key for
     /// backwards compatibility with previous conda versions)
     #[serde(borrow, rename = "packages.conda")]
     #[serde(deserialize_with = "deserialize_filename_and_raw_record")]
-    conda_packages: Vec<(PackageFilename<'i>, &'i RawValue)>,
+    conda_packages: Vec<(PackageFilename<'i>, &'i [u8])>,
 }
 
 /// Parse the records for the specified package from the raw index
 fn parse_records<'i>(
     package_name: &PackageName,
-    packages: &[(PackageFilename<'i>, &'i RawValue)],
+    packages: &[(PackageFilename<'i>, &'i [u8])],
     base_url: Option<&str>,
     channel: &Channel,
     subdir: &str,
     patch_function: Option<fn(&mut PackageRecord)>,
 ) -> io::Result<Vec<RepoDataRecord>> {
-    let channel_name = channel.canonical_name();
+    let channel_name = channel.base_url().to_string();
 
     let package_indices =
         packages.equal_range_by(|(package, _)| package.package.cmp(package_name.as_normalized()));
     let mut result = Vec::with_capacity(package_indices.len());
     for (key, raw_json) in &packages[package_indices] {
-        let mut package_record: PackageRecord = serde_json::from_str(raw_json.get())?;
+        let mut package_record: PackageRecord = serde_json::from_slice(raw_json)?;
         // Overwrite subdir if its empty
         if package_record.subdir.is_empty() {
             package_record.subdir = subdir.to_owned();
@@ -303,9 +383,13 @@ pub async fn load_repo_data_recursively(
 
 fn deserialize_filename_and_raw_record<'d, D: Deserializer<'d>>(
     deserializer: D,
-) -> Result<Vec<(PackageFilename<'d>, &'d RawValue)>, D::Error> {
-    let mut entries: Vec<(PackageFilename<'d>, &'d RawValue)> =
+) -> Result<Vec<(PackageFilename<'d>, &'d [u8])>, D::Error> {
+    let entries: Vec<(PackageFilename<'d>, &'d RawValue)> =
         deserializer.deserialize_map(MapVisitor(PhantomData))?;
+    let mut entries: Vec<_> = entries
+        .into_iter()
+        .map(|(filename, raw_json)| (filename, raw_json.get().as_bytes()))
+        .collect();
 
     // Although in general the filenames are sorted in repodata.json this doesnt necessarily mean
     // that the records are also sorted by package name.
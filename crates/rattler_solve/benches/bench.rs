@@ -1,8 +1,7 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion, SamplingMode};
-use rattler_conda_types::{Channel, ChannelConfig, MatchSpec};
+use rattler_conda_types::{Channel, ChannelConfig, MatchSpec, ParseStrictness};
 use rattler_repodata_gateway::sparse::SparseRepoData;
-use rattler_solve::{SolverImpl, SolverTask};
-use std::str::FromStr;
+use rattler_solve::{SolverImpl, SolverTask, SolverTaskSnapshot};
 
 fn conda_json_path() -> String {
     format!(
@@ -39,7 +38,7 @@ fn bench_solve_environment(c: &mut Criterion, specs: Vec<&str>) {
 
     let specs = specs
         .iter()
-        .map(|s| MatchSpec::from_str(s).unwrap())
+        .map(|s| MatchSpec::from_str(s, ParseStrictness::Strict).unwrap())
         .collect::<Vec<MatchSpec>>();
 
     let json_file = conda_json_path();
@@ -59,11 +58,8 @@ fn bench_solve_environment(c: &mut Criterion, specs: Vec<&str>) {
         b.iter(|| {
             rattler_solve::libsolv_c::Solver
                 .solve(black_box(SolverTask {
-                    available_packages: &available_packages,
-                    locked_packages: vec![],
-                    pinned_packages: vec![],
-                    virtual_packages: vec![],
                     specs: specs.clone(),
+                    ..SolverTask::new(&available_packages)
                 }))
                 .unwrap()
         })
@@ -74,11 +70,8 @@ fn bench_solve_environment(c: &mut Criterion, specs: Vec<&str>) {
         b.iter(|| {
             rattler_solve::resolvo::Solver
                 .solve(black_box(SolverTask {
-                    available_packages: &available_packages,
-                    locked_packages: vec![],
-                    pinned_packages: vec![],
-                    virtual_packages: vec![],
                     specs: specs.clone(),
+                    ..SolverTask::new(&available_packages)
                 }))
                 .unwrap()
         })
@@ -87,12 +80,63 @@ fn bench_solve_environment(c: &mut Criterion, specs: Vec<&str>) {
     group.finish();
 }
 
+fn solver_snapshots_path() -> String {
+    format!(
+        "{}/{}",
+        env!("CARGO_MANIFEST_DIR"),
+        "../../test-data/solver-snapshots"
+    )
+}
+
+/// Benchmarks the snapshots (see [`SolverTaskSnapshot`]) in the solver snapshots directory, if
+/// there are any.
+fn bench_solve_snapshots(c: &mut Criterion) {
+    let Ok(entries) = std::fs::read_dir(solver_snapshots_path()) else {
+        return;
+    };
+
+    let mut paths = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    for path in paths {
+        let snapshot = SolverTaskSnapshot::from_path(&path).unwrap();
+        let file_name = path.file_name().unwrap().to_string_lossy();
+        let mut group = c.benchmark_group(format!("solve snapshot {file_name}"));
+
+        group.sampling_mode(SamplingMode::Flat);
+        group.sample_size(20);
+
+        #[cfg(feature = "libsolv_c")]
+        group.bench_function("libsolv_c", |b| {
+            b.iter(|| {
+                rattler_solve::libsolv_c::Solver
+                    .solve(black_box(snapshot.task()))
+                    .unwrap()
+            })
+        });
+
+        #[cfg(feature = "resolvo")]
+        group.bench_function("resolvo", |b| {
+            b.iter(|| {
+                rattler_solve::resolvo::Solver
+                    .solve(black_box(snapshot.task()))
+                    .unwrap()
+            })
+        });
+
+        group.finish();
+    }
+}
+
 fn criterion_benchmark(c: &mut Criterion) {
     bench_solve_environment(c, vec!["python=3.9"]);
     bench_solve_environment(c, vec!["xtensor", "xsimd"]);
     bench_solve_environment(c, vec!["tensorflow"]);
     bench_solve_environment(c, vec!["quetz"]);
     bench_solve_environment(c, vec!["tensorboard=2.1.1", "grpc-cpp=1.39.1"]);
+    bench_solve_snapshots(c);
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -0,0 +1,68 @@
+//! Provides [`PackageExclusion`] which describes records the solver must never select.
+
+use rattler_conda_types::{MatchSpec, RepoDataRecord};
+use rattler_digest::{serde::SerializableHash, Sha256, Sha256Hash};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use std::fmt;
+use url::Url;
+
+/// Identifies records that the solver must never select, see [`crate::SolverTask::exclusions`].
+///
+/// This can be used to avoid specific broken builds without having to modify the repodata of a
+/// channel.
+#[serde_as]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[allow(clippy::large_enum_variant)]
+pub enum PackageExclusion {
+    /// Excludes all the records that match the spec
+    MatchSpec(#[serde_as(as = "DisplayFromStr")] MatchSpec),
+
+    /// Excludes the record with the specified url
+    Url(Url),
+
+    /// Excludes the records with the specified sha256 hash
+    Sha256(#[serde_as(as = "SerializableHash::<Sha256>")] Sha256Hash),
+}
+
+impl PackageExclusion {
+    /// Returns true if the specified record is excluded.
+    pub fn matches(&self, record: &RepoDataRecord) -> bool {
+        match self {
+            PackageExclusion::MatchSpec(spec) => spec.matches_record(record),
+            PackageExclusion::Url(url) => &record.url == url,
+            PackageExclusion::Sha256(sha256) => {
+                record.package_record.sha256.as_ref() == Some(sha256)
+            }
+        }
+    }
+}
+
+impl From<MatchSpec> for PackageExclusion {
+    fn from(spec: MatchSpec) -> Self {
+        PackageExclusion::MatchSpec(spec)
+    }
+}
+
+impl From<Url> for PackageExclusion {
+    fn from(url: Url) -> Self {
+        PackageExclusion::Url(url)
+    }
+}
+
+impl From<Sha256Hash> for PackageExclusion {
+    fn from(sha256: Sha256Hash) -> Self {
+        PackageExclusion::Sha256(sha256)
+    }
+}
+
+impl fmt::Display for PackageExclusion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PackageExclusion::MatchSpec(spec) => write!(f, "{spec}"),
+            PackageExclusion::Url(url) => write!(f, "{url}"),
+            PackageExclusion::Sha256(sha256) => write!(f, "sha256 {sha256:x}"),
+        }
+    }
+}
@@ -8,7 +8,23 @@ pub mod libsolv_c;
 #[cfg(feature = "resolvo")]
 pub mod resolvo;
 
-use rattler_conda_types::{GenericVirtualPackage, MatchSpec, RepoDataRecord};
+mod exclusion;
+mod multi_platform;
+mod snapshot;
+mod solution_graph;
+
+pub use exclusion::PackageExclusion;
+pub use multi_platform::{MultiPlatformSolution, MultiPlatformSolverTask, PlatformSolverTask};
+pub use snapshot::{SolverTaskSnapshot, SolverTaskSnapshotError};
+pub use solution_graph::{DependencyPath, SolutionGraph, SolutionReason};
+
+use rattler_conda_types::{
+    GenericVirtualPackage, MatchSpec, PackageName, ParseMatchSpecError, ParseStrictness,
+    PrefixRecord, RepoDataRecord, Version,
+};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 
 /// Represents a solver implementation, capable of solving [`SolverTask`]s
@@ -26,6 +42,70 @@ pub trait SolverImpl {
         &mut self,
         task: SolverTask<TAvailablePackagesIterator>,
     ) -> Result<Vec<RepoDataRecord>, SolveError>;
+
+    /// Resolve the dependencies and return a [`SolutionGraph`] that contains the
+    /// [`RepoDataRecord`]s that should be present in the environment together with the reason why
+    /// each of them was selected.
+    fn solve_with_graph<
+        'a,
+        R: IntoRepoData<'a, Self::RepoData<'a>>,
+        TAvailablePackagesIterator: IntoIterator<Item = R>,
+    >(
+        &mut self,
+        task: SolverTask<TAvailablePackagesIterator>,
+    ) -> Result<SolutionGraph, SolveError> {
+        // Specs of removed packages are ignored by the solver, they did not cause any record to be
+        // selected.
+        let removed_packages = RemovedPackages::new(
+            &task.remove_specs,
+            task.removal_strategy,
+            &task.locked_packages,
+        );
+        let mut specs = task
+            .specs
+            .iter()
+            .chain(task.update_strategy.update_specs())
+            .filter(|spec| !removed_packages.is_removed_spec(spec))
+            .cloned()
+            .collect::<Vec<_>>();
+        let soft_specs = task
+            .soft_specs
+            .iter()
+            .filter(|spec| !removed_packages.is_removed_spec(spec))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let (records, dropped_soft_specs) = self.solve_with_dropped_soft_specs(task)?;
+        specs.extend(
+            soft_specs
+                .into_iter()
+                .filter(|spec| !dropped_soft_specs.contains(spec)),
+        );
+        Ok(SolutionGraph::new(specs, records))
+    }
+
+    /// Resolve the dependencies like [`SolverImpl::solve`] and additionally return the
+    /// [`SolverTask::soft_specs`] that were dropped because they could not be satisfied.
+    ///
+    /// Backends that know which soft specs they dropped should override this. The default
+    /// implementation is an approximation for backends that cannot report it: it considers every
+    /// soft spec that is not matched by any of the selected records as dropped.
+    fn solve_with_dropped_soft_specs<
+        'a,
+        R: IntoRepoData<'a, Self::RepoData<'a>>,
+        TAvailablePackagesIterator: IntoIterator<Item = R>,
+    >(
+        &mut self,
+        task: SolverTask<TAvailablePackagesIterator>,
+    ) -> Result<(Vec<RepoDataRecord>, Vec<MatchSpec>), SolveError> {
+        let soft_specs = task.soft_specs.clone();
+        let records = self.solve(task)?;
+        let dropped_soft_specs = soft_specs
+            .into_iter()
+            .filter(|spec| !records.iter().any(|record| spec.matches_record(record)))
+            .collect();
+        Ok((records, dropped_soft_specs))
+    }
 }
 
 /// Represents an error when solving the dependencies for a given environment
@@ -39,6 +119,11 @@ pub enum SolveError {
     /// and can be used for error reporting
     UnsupportedOperations(Vec<String>),
 
+    /// The task can only be solved by selecting records that are excluded through
+    /// [`SolverTask::exclusions`]. Each string describes an excluded record that would be selected
+    /// together with the exclusion that matches it.
+    ExcludedPackagesRequired(Vec<String>),
+
     /// Error when converting matchspec
     #[error(transparent)]
     ParseMatchSpecError(#[from] rattler_conda_types::ParseMatchSpecError),
@@ -54,6 +139,13 @@ impl fmt::Display for SolveError {
                     operations.join(", ")
                 )
             }
+            SolveError::ExcludedPackagesRequired(records) => {
+                write!(
+                    f,
+                    "Cannot solve the request without selecting excluded packages: {}",
+                    records.join(", ")
+                )
+            }
             SolveError::UnsupportedOperations(operations) => {
                 write!(f, "Unsupported operations: {}", operations.join(", "))
             }
@@ -93,6 +185,475 @@ pub struct SolverTask<TAvailablePackagesIterator> {
 
     /// The specs we want to solve
     pub specs: Vec<MatchSpec>,
+
+    /// Specs that the solver tries to satisfy but that are dropped if satisfying them would make
+    /// the task unsolvable.
+    ///
+    /// Soft specs are treated like `specs` as long as possible, e.g. a package is installed if it
+    /// is only requested through a soft spec. This can be used to express a preference, like
+    /// `blas=*=openblas`, without failing the solve if the preference cannot be honoured. If soft
+    /// specs conflict with each other, specs earlier in the list take precedence (on a best-effort
+    /// basis for the libsolv backend). Use [`SolverImpl::solve_with_dropped_soft_specs`] to find
+    /// out which soft specs were dropped.
+    pub soft_specs: Vec<MatchSpec>,
+
+    /// Determines how the solver treats the `locked_packages`.
+    pub update_strategy: UpdateStrategy,
+
+    /// Whether the solver is allowed to select a lower version of a package than the version of
+    /// the record in `locked_packages`.
+    ///
+    /// If this is `false` the solver will never select a variant of a package with a version lower
+    /// than that of its locked record, regardless of the `update_strategy`.
+    pub allow_downgrade: bool,
+
+    /// Specs of the packages to remove from the environment.
+    ///
+    /// Every record in `locked_packages` that matches one of these specs is removed. The solver
+    /// only installs the packages required by `specs`, so dependencies of the removed packages
+    /// that are no longer required by any other package are removed as well. To make this work,
+    /// `specs` should contain the specs that were explicitly requested by the user, see
+    /// [`requested_specs`].
+    pub remove_specs: Vec<MatchSpec>,
+
+    /// Determines how the solver treats packages that depend on a package that is removed through
+    /// `remove_specs`.
+    pub removal_strategy: RemovalStrategy,
+
+    /// Records that the solver must never select, not even if they are part of `locked_packages`
+    /// or `pinned_packages`.
+    ///
+    /// If the task is unsolvable only because of the exclusions, the solver returns a
+    /// [`SolveError::ExcludedPackagesRequired`] error.
+    pub exclusions: Vec<PackageExclusion>,
+}
+
+impl<TAvailablePackagesIterator> SolverTask<TAvailablePackagesIterator> {
+    /// Constructs a task that selects from the specified available packages. All other fields are
+    /// empty or set to their default, use the struct update syntax to override them:
+    ///
+    /// ```
+    /// # use rattler_conda_types::RepoDataRecord;
+    /// # use rattler_solve::SolverTask;
+    /// # let repo_data: Vec<RepoDataRecord> = Vec::new();
+    /// let task = SolverTask {
+    ///     specs: vec!["python >=3.10".parse().unwrap()],
+    ///     ..SolverTask::new([&repo_data])
+    /// };
+    /// ```
+    pub fn new(available_packages: TAvailablePackagesIterator) -> Self {
+        Self {
+            available_packages,
+            locked_packages: Vec::new(),
+            pinned_packages: Vec::new(),
+            virtual_packages: Vec::new(),
+            specs: Vec::new(),
+            soft_specs: Vec::new(),
+            update_strategy: UpdateStrategy::default(),
+            allow_downgrade: true,
+            remove_specs: Vec::new(),
+            removal_strategy: RemovalStrategy::default(),
+            exclusions: Vec::new(),
+        }
+    }
+
+    /// Splits the available packages from the rest of the task.
+    #[cfg_attr(not(feature = "resolvo"), allow(dead_code))]
+    pub(crate) fn split_available_packages(self) -> (TAvailablePackagesIterator, SolverTask<()>) {
+        let SolverTask {
+            available_packages,
+            locked_packages,
+            pinned_packages,
+            virtual_packages,
+            specs,
+            soft_specs,
+            update_strategy,
+            allow_downgrade,
+            remove_specs,
+            removal_strategy,
+            exclusions,
+        } = self;
+        (
+            available_packages,
+            SolverTask {
+                available_packages: (),
+                locked_packages,
+                pinned_packages,
+                virtual_packages,
+                specs,
+                soft_specs,
+                update_strategy,
+                allow_downgrade,
+                remove_specs,
+                removal_strategy,
+                exclusions,
+            },
+        )
+    }
+}
+
+impl<TAvailablePackagesIterator: Default> Default for SolverTask<TAvailablePackagesIterator> {
+    fn default() -> Self {
+        Self::new(TAvailablePackagesIterator::default())
+    }
+}
+
+/// Describes how the solver treats packages that depend on a package that is removed through the
+/// `remove_specs` of a [`SolverTask`].
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RemovalStrategy {
+    /// Locked packages that (transitively) depend on a removed package are removed as well. This
+    /// is similar to `conda remove`.
+    #[default]
+    Cascade,
+
+    /// Only the locked packages that match the remove specs are removed. Packages that depend on a
+    /// removed package are kept even though their dependencies are no longer satisfied. This is
+    /// similar to `conda remove --force` and will usually leave the environment in an inconsistent
+    /// state.
+    Force,
+}
+
+/// Returns the specs that were explicitly requested by the user when the specified packages were
+/// installed, as recorded in [`PrefixRecord::requested_spec`].
+///
+/// Packages without a requested spec were installed as a dependency of another package. Passing
+/// the returned specs as the `specs` of a [`SolverTask`] ensures that the solver keeps the
+/// requested packages but drops dependencies that are no longer required.
+pub fn requested_specs<'a>(
+    prefix_records: impl IntoIterator<Item = &'a PrefixRecord>,
+) -> Result<Vec<MatchSpec>, ParseMatchSpecError> {
+    prefix_records
+        .into_iter()
+        .filter_map(|record| record.requested_spec.as_deref())
+        .map(|spec| MatchSpec::from_str(spec, ParseStrictness::Lenient))
+        .collect()
+}
+
+/// Describes how the solver should treat the `locked_packages` of a [`SolverTask`].
+#[serde_as]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateStrategy {
+    /// Locked packages are favored over other variants, but the solver may select another variant
+    /// if that is required to satisfy the specs.
+    #[default]
+    FavorLocked,
+
+    /// The packages that match the specs, and the locked packages they (transitively) depend on,
+    /// are updated to the best available variant. All other locked packages are favored.
+    ///
+    /// The specs are also added as requirements of the task. This is similar to
+    /// `conda update numpy`.
+    Update(#[serde_as(as = "Vec<DisplayFromStr>")] Vec<MatchSpec>),
+
+    /// All packages are updated to the best available variant, the locked packages are not
+    /// favored. This is similar to `conda update --all`.
+    UpdateAll,
+
+    /// Locked packages CANNOT be changed, they are treated as if they were part of the
+    /// `pinned_packages`. Packages that are not locked are solved as usual. This is similar to
+    /// `conda install --freeze-installed`.
+    FreezeLocked,
+}
+
+impl UpdateStrategy {
+    /// Returns the specs of the packages that should be updated.
+    pub(crate) fn update_specs(&self) -> &[MatchSpec] {
+        match self {
+            UpdateStrategy::Update(specs) => specs,
+            _ => &[],
+        }
+    }
+}
+
+/// The locked and pinned packages of a [`SolverTask`] after the [`UpdateStrategy`] and the
+/// downgrade policy have been applied. Backends use this to determine which records to favor,
+/// which to pin and which records can never be selected.
+#[derive(Clone)]
+pub(crate) struct LockedPackages<'a> {
+    /// Records that should be favored over other variants of the same package.
+    pub favored: Vec<&'a RepoDataRecord>,
+
+    /// Records that must be selected if a package with the same name is selected.
+    pub pinned: Vec<&'a RepoDataRecord>,
+
+    /// The minimum allowed version for packages if downgrades are not allowed.
+    minimum_versions: HashMap<&'a str, &'a Version>,
+
+    /// The pinned records indexed by name.
+    pinned_by_name: HashMap<&'a str, Vec<&'a RepoDataRecord>>,
+
+    /// The names of the packages that are removed from the environment.
+    removed_names: HashSet<&'a str>,
+
+    /// The records that can never be selected.
+    exclusions: &'a [PackageExclusion],
+}
+
+impl<'a> LockedPackages<'a> {
+    /// Determines the locked and pinned packages from the corresponding fields of a
+    /// [`SolverTask`].
+    pub fn new(
+        locked_packages: &'a [RepoDataRecord],
+        pinned_packages: &'a [RepoDataRecord],
+        update_strategy: &UpdateStrategy,
+        allow_downgrade: bool,
+        removed_packages: &RemovedPackages<'a>,
+        exclusions: &'a [PackageExclusion],
+    ) -> Self {
+        let removed_names = removed_packages.names.clone();
+        let mut favored = Vec::new();
+        let mut pinned = pinned_packages.iter().collect::<Vec<_>>();
+
+        match update_strategy {
+            UpdateStrategy::FavorLocked => favored.extend(locked_packages.iter()),
+            UpdateStrategy::Update(specs) => {
+                let updated_names = updated_package_names(specs, locked_packages);
+                favored.extend(locked_packages.iter().filter(|record| {
+                    !updated_names.contains(record.package_record.name.as_normalized())
+                }));
+            }
+            UpdateStrategy::UpdateAll => {}
+            UpdateStrategy::FreezeLocked => pinned.extend(locked_packages.iter()),
+        }
+
+        // Removed packages are neither favored nor pinned
+        favored
+            .retain(|record| !removed_names.contains(record.package_record.name.as_normalized()));
+        pinned.retain(|record| !removed_names.contains(record.package_record.name.as_normalized()));
+
+        let minimum_versions = if allow_downgrade {
+            HashMap::new()
+        } else {
+            locked_packages
+                .iter()
+                .map(|record| {
+                    (
+                        record.package_record.name.as_normalized(),
+                        record.package_record.version.version(),
+                    )
+                })
+                .collect()
+        };
+
+        let mut pinned_by_name: HashMap<&str, Vec<&RepoDataRecord>> = HashMap::new();
+        for record in pinned.iter() {
+            pinned_by_name
+                .entry(record.package_record.name.as_normalized())
+                .or_default()
+                .push(record);
+        }
+
+        Self {
+            favored,
+            pinned,
+            minimum_versions,
+            pinned_by_name,
+            removed_names,
+            exclusions,
+        }
+    }
+
+    /// Returns a copy of these locked packages that ignores the exclusions of the task. Used to
+    /// determine whether a task is unsolvable because of its exclusions.
+    pub fn without_exclusions(&self) -> Self {
+        Self {
+            exclusions: &[],
+            ..self.clone()
+        }
+    }
+
+    /// Returns the exclusions of the task.
+    #[cfg_attr(not(feature = "resolvo"), allow(dead_code))]
+    pub fn exclusions(&self) -> &'a [PackageExclusion] {
+        self.exclusions
+    }
+
+    /// Returns true if the task excludes some records.
+    pub fn has_exclusions(&self) -> bool {
+        !self.exclusions.is_empty()
+    }
+
+    /// Returns the exclusion that matches the specified record, if any.
+    pub fn exclusion(&self, record: &RepoDataRecord) -> Option<&'a PackageExclusion> {
+        self.exclusions
+            .iter()
+            .find(|exclusion| exclusion.matches(record))
+    }
+
+    /// Returns the error for a task that can only be solved by selecting the excluded records in
+    /// the specified solution.
+    pub fn excluded_packages_required(&self, solution: &[RepoDataRecord]) -> SolveError {
+        SolveError::ExcludedPackagesRequired(
+            solution
+                .iter()
+                .filter_map(|record| {
+                    self.exclusion(record).map(|exclusion| {
+                        format!("{} (excluded by {})", record.package_record, exclusion)
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    /// Returns true if some of the available records are not allowed to be selected by the solver.
+    #[cfg_attr(not(feature = "libsolv_c"), allow(dead_code))]
+    pub fn restricts_records(&self) -> bool {
+        !self.pinned_by_name.is_empty()
+            || !self.minimum_versions.is_empty()
+            || !self.removed_names.is_empty()
+            || self.has_exclusions()
+    }
+
+    /// Returns the names of the packages for which some records are not allowed to be selected by
+    /// the solver.
+    #[cfg_attr(not(feature = "resolvo"), allow(dead_code))]
+    pub fn restricted_names(&self) -> impl Iterator<Item = &'a str> + '_ {
+        self.pinned_by_name
+            .keys()
+            .chain(self.minimum_versions.keys())
+            .chain(self.removed_names.iter())
+            .copied()
+    }
+
+    /// Returns true if the solver is allowed to select the specified record.
+    ///
+    /// Excluded records and records of removed packages are never allowed. If a package is pinned
+    /// only its pinned records are allowed, otherwise a record is allowed if its version is not
+    /// lower than the minimum version of the package (if any).
+    pub fn is_allowed(&self, record: &RepoDataRecord) -> bool {
+        let name = record.package_record.name.as_normalized();
+        if self.removed_names.contains(name) || self.exclusion(record).is_some() {
+            return false;
+        }
+        if let Some(pinned) = self.pinned_by_name.get(name) {
+            return pinned
+                .iter()
+                .any(|pinned| pinned.package_record == record.package_record);
+        }
+        match self.minimum_versions.get(name) {
+            Some(minimum_version) => record.package_record.version.version() >= *minimum_version,
+            None => true,
+        }
+    }
+}
+
+/// Returns the names of the packages that match the specified update specs together with the names
+/// of all the locked packages they (transitively) depend on.
+fn updated_package_names(
+    specs: &[MatchSpec],
+    locked_packages: &[RepoDataRecord],
+) -> HashSet<String> {
+    let locked_by_name = locked_packages
+        .iter()
+        .map(|record| (record.package_record.name.as_normalized(), record))
+        .collect::<HashMap<_, _>>();
+
+    let mut queue = specs
+        .iter()
+        .filter_map(|spec| spec.name.as_ref())
+        .map(|name| name.as_normalized().to_owned())
+        .collect::<Vec<_>>();
+    let mut names = HashSet::new();
+    while let Some(name) = queue.pop() {
+        if !names.insert(name.clone()) {
+            continue;
+        }
+        let Some(record) = locked_by_name.get(name.as_str()) else {
+            continue;
+        };
+        queue.extend(
+            dependency_names(record).map(|name: PackageName| name.as_normalized().to_owned()),
+        );
+    }
+
+    names
+}
+
+/// The packages that are removed from the environment by the `remove_specs` of a [`SolverTask`]
+/// after the [`RemovalStrategy`] has been applied.
+pub(crate) struct RemovedPackages<'a> {
+    /// The names of the packages that are removed. Records with these names can never be selected
+    /// by the solver.
+    names: HashSet<&'a str>,
+
+    /// Virtual packages that stand in for force-removed packages. These satisfy the dependencies
+    /// of the packages that depend on a force-removed package without pulling in the dependencies
+    /// of the removed package itself.
+    pub stand_ins: Vec<GenericVirtualPackage>,
+}
+
+impl<'a> RemovedPackages<'a> {
+    /// Determines the packages to remove from the corresponding fields of a [`SolverTask`].
+    pub fn new(
+        remove_specs: &[MatchSpec],
+        removal_strategy: RemovalStrategy,
+        locked_packages: &'a [RepoDataRecord],
+    ) -> Self {
+        let removed_records = locked_packages
+            .iter()
+            .filter(|record| remove_specs.iter().any(|spec| spec.matches_record(record)))
+            .collect::<Vec<_>>();
+        let mut names = removed_records
+            .iter()
+            .map(|record| record.package_record.name.as_normalized())
+            .collect::<HashSet<_>>();
+
+        let stand_ins = match removal_strategy {
+            RemovalStrategy::Cascade => {
+                // Keep removing the packages that depend on a removed package until nothing
+                // changes anymore.
+                loop {
+                    let dependents = locked_packages
+                        .iter()
+                        .filter(|record| {
+                            !names.contains(record.package_record.name.as_normalized())
+                        })
+                        .filter(|record| {
+                            dependency_names(record)
+                                .any(|dependency| names.contains(dependency.as_normalized()))
+                        })
+                        .map(|record| record.package_record.name.as_normalized())
+                        .collect::<Vec<_>>();
+                    if dependents.is_empty() {
+                        break;
+                    }
+                    names.extend(dependents);
+                }
+                Vec::new()
+            }
+            RemovalStrategy::Force => removed_records
+                .iter()
+                .map(|record| GenericVirtualPackage {
+                    name: record.package_record.name.clone(),
+                    version: record.package_record.version.version().clone(),
+                    build_string: record.package_record.build.clone(),
+                })
+                .collect(),
+        };
+
+        Self { names, stand_ins }
+    }
+
+    /// Returns true if the spec refers to a package that is removed, the solver should no longer
+    /// try to satisfy such a spec.
+    pub fn is_removed_spec(&self, spec: &MatchSpec) -> bool {
+        spec.name
+            .as_ref()
+            .is_some_and(|name| self.names.contains(name.as_normalized()))
+    }
+}
+
+/// Returns the names of the dependencies of the specified record.
+fn dependency_names(record: &RepoDataRecord) -> impl Iterator<Item = PackageName> + '_ {
+    record
+        .package_record
+        .depends
+        .iter()
+        .filter_map(|dependency| MatchSpec::from_str(dependency, ParseStrictness::Lenient).ok())
+        .filter_map(|spec| spec.name)
 }
 
 /// A representation of a collection of [`RepoDataRecord`] usable by a [`SolverImpl`]
@@ -26,6 +26,27 @@ impl LibcByteSlice {
         LibcByteSlice { ptr, len }
     }
 
+    /// Copies the bytes of a slice into a newly allocated `LibcByteSlice`
+    pub(super) fn from_slice(bytes: &[u8]) -> LibcByteSlice {
+        // Allocate at least a single byte because `malloc(0)` is allowed to return null
+        let ptr = unsafe { libc::malloc(bytes.len().max(1)) };
+        let ptr = NonNull::new(ptr).expect("out of memory");
+
+        // Safe because the allocated chunk of memory is at least `bytes.len()` bytes long
+        unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr.as_ptr().cast(), bytes.len()) };
+
+        LibcByteSlice {
+            ptr,
+            len: bytes.len(),
+        }
+    }
+
+    /// Returns the bytes as a slice
+    pub fn as_slice(&self) -> &[u8] {
+        // Safe because `ptr` points to an allocated chunk of memory of `len` bytes
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr().cast(), self.len) }
+    }
+
     pub(super) fn as_ptr(&self) -> *mut libc::c_void {
         self.ptr.as_ptr()
     }
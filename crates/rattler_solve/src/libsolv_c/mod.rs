@@ -1,24 +1,30 @@
 //! Provides an solver implementation based on the [`rattler_libsolv_c`] crate.
 
-use crate::{IntoRepoData, SolverRepoData};
+use crate::{
+    updated_package_names, IntoRepoData, LockedPackages, RemovedPackages, SolverRepoData,
+    UpdateStrategy,
+};
 use crate::{SolveError, SolverTask};
 pub use input::cache_repodata;
 use input::{add_repodata_records, add_solv_file, add_virtual_packages};
 pub use libc_byte_slice::LibcByteSlice;
 use output::get_required_packages;
-use rattler_conda_types::RepoDataRecord;
+use rattler_conda_types::{MatchSpec, NamelessMatchSpec, PackageName, RepoDataRecord};
+pub use solv_cache::SolvFileCache;
 use std::collections::HashMap;
 use std::ffi::CString;
 use wrapper::{
     flags::SolverFlag,
-    pool::{Pool, Verbosity},
-    repo::Repo,
+    pool::{libsolv_matchspec, Pool, Verbosity},
+    repo::{Repo, RepoId},
+    solvable,
     solve_goal::SolveGoal,
 };
 
 mod input;
 mod libc_byte_slice;
 mod output;
+mod solv_cache;
 mod wrapper;
 
 /// Represents the information required to load available packages into libsolv for a single channel
@@ -90,6 +96,31 @@ impl super::SolverImpl for Solver {
         &mut self,
         task: SolverTask<TAvailablePackagesIterator>,
     ) -> Result<Vec<RepoDataRecord>, SolveError> {
+        // Determine which packages to remove from the environment
+        let removed_packages = RemovedPackages::new(
+            &task.remove_specs,
+            task.removal_strategy,
+            &task.locked_packages,
+        );
+
+        // Determine which locked packages to favor and which packages to pin
+        let locked_packages = LockedPackages::new(
+            &task.locked_packages,
+            &task.pinned_packages,
+            &task.update_strategy,
+            task.allow_downgrade,
+            &removed_packages,
+            &task.exclusions,
+        );
+
+        // Force-removed packages are replaced by virtual packages that stand in for them
+        let virtual_packages = task
+            .virtual_packages
+            .iter()
+            .chain(removed_packages.stand_ins.iter())
+            .cloned()
+            .collect::<Vec<_>>();
+
         // Construct a default libsolv pool
         let pool = Pool::default();
 
@@ -99,13 +130,6 @@ impl super::SolverImpl for Solver {
         });
         pool.set_debug_level(Verbosity::Low);
 
-        // Add virtual packages
-        let repo = Repo::new(&pool, "virtual_packages");
-        add_virtual_packages(&pool, &repo, &task.virtual_packages);
-
-        // Mark the virtual packages as installed.
-        pool.set_installed(&repo);
-
         // Create repos for all channel + platform combinations
         let mut repo_mapping = HashMap::new();
         let mut all_repodata_records = Vec::new();
@@ -131,67 +155,192 @@ impl super::SolverImpl for Solver {
             std::mem::forget(repo);
         }
 
-        // Create a special pool for records that are already installed or locked.
-        let repo = Repo::new(&pool, "locked");
-        let installed_solvables = add_repodata_records(&pool, &repo, &task.locked_packages);
+        // The locked packages that the solver is allowed to keep are installed together with the
+        // virtual packages. This enables libsolv to update them or to lock them in place. An
+        // environment contains a single record per package, so if multiple records of a package
+        // are locked they are only favored.
+        let mut locked_record_count = HashMap::<&str, usize>::new();
+        for record in task.locked_packages.iter() {
+            *locked_record_count
+                .entry(record.package_record.name.as_normalized())
+                .or_default() += 1;
+        }
+        let (installed_records, ambiguous_records): (Vec<_>, Vec<_>) = task
+            .locked_packages
+            .iter()
+            .filter(|record| locked_packages.is_allowed(record))
+            .partition(|record| {
+                locked_record_count[record.package_record.name.as_normalized()] == 1
+            });
+        let repo = Repo::new(&pool, "installed");
+        add_virtual_packages(&pool, &repo, &virtual_packages);
+        let installed_solvables =
+            add_repodata_records(&pool, &repo, installed_records.iter().copied());
+        pool.set_installed(&repo);
 
         // Also add the installed records to the repodata
         repo_mapping.insert(repo.id(), repo_mapping.len());
-        all_repodata_records.push(task.locked_packages.iter().collect());
+        all_repodata_records.push(installed_records.clone());
 
-        // Create a special pool for records that are pinned and cannot be changed.
-        let repo = Repo::new(&pool, "pinned");
-        let pinned_solvables = add_repodata_records(&pool, &repo, &task.pinned_packages);
+        // Create a special pool for the locked records that cannot be installed.
+        let repo = Repo::new(&pool, "locked");
+        let ambiguous_solvables =
+            add_repodata_records(&pool, &repo, ambiguous_records.iter().copied());
 
-        // Also add the installed records to the repodata
+        // Also add the locked records to the repodata
+        repo_mapping.insert(repo.id(), repo_mapping.len());
+        all_repodata_records.push(ambiguous_records);
+
+        // Create a special pool for records that are pinned and cannot be changed. When the
+        // locked packages are frozen they are already part of the installed repo.
+        let repo = Repo::new(&pool, "pinned");
+        let pinned_records = match task.update_strategy {
+            UpdateStrategy::FreezeLocked => task
+                .pinned_packages
+                .iter()
+                .filter(|record| locked_packages.is_allowed(record))
+                .collect(),
+            _ => locked_packages.pinned.clone(),
+        };
+        add_repodata_records(&pool, &repo, pinned_records.iter().copied());
+
+        // Also add the pinned records to the repodata
         repo_mapping.insert(repo.id(), repo_mapping.len());
-        all_repodata_records.push(task.pinned_packages.iter().collect());
+        all_repodata_records.push(pinned_records);
 
         // Create datastructures for solving
         pool.create_whatprovides();
 
-        // Add matchspec to the queue
-        let mut goal = SolveGoal::default();
+        // Returns the record that a solvable was created from, virtual packages have no record
+        let solvable_index_id = pool.intern_str("solvable:repodata_record_index");
+        let solvable_record = |solvable_id: solvable::SolvableId| {
+            let solvable = solvable_id.resolve_raw(&pool);
+
+            // Safe because there are no active mutable borrows of any solvable at this stage
+            let repo_id = RepoId::from_ffi_solvable(unsafe { solvable.as_ref() });
+            let &repo_index = repo_mapping.get(&repo_id)?;
+            let record_index = solvable::lookup_num(solvable.as_ptr(), solvable_index_id)?;
+            Some(all_repodata_records[repo_index][record_index as usize])
+        };
+
+        // Adds a job to install the records that match the spec. libsolv only understands the
+        // name, version and build of a spec, if the spec also has other fields the matching
+        // records are selected explicitly.
+        let install = |goal: &mut SolveGoal, spec: &MatchSpec, optional: bool| {
+            if libsolv_matchspec(spec) == *spec {
+                goal.install(pool.intern_matchspec(spec), optional);
+            } else {
+                let selection = pool.intern_solvable_selection(pool.solvable_ids().filter(|&id| {
+                    solvable_record(id).is_some_and(|record| spec.matches_record(record))
+                }));
+                goal.install_one_of(selection, optional);
+            }
+        };
+
+        // Creates the jobs for the solver, the locked packages determine which records can be
+        // selected
+        let create_goal = |locked_packages: &LockedPackages<'_>| {
+            let mut goal = SolveGoal::default();
+
+            // Favor the locked packages that are not updated and drive the update strategy
+            for &solvable_id in installed_solvables.iter().chain(&ambiguous_solvables) {
+                let is_favored = solvable_record(solvable_id).is_some_and(|record| {
+                    locked_packages
+                        .favored
+                        .iter()
+                        .any(|favored| favored.package_record == record.package_record)
+                });
+                if is_favored {
+                    goal.favor(solvable_id);
+                }
+            }
+            match &task.update_strategy {
+                UpdateStrategy::FavorLocked => {}
+                UpdateStrategy::Update(specs) => {
+                    for name in updated_package_names(specs, &task.locked_packages) {
+                        let spec = MatchSpec::from_nameless(
+                            NamelessMatchSpec::default(),
+                            Some(PackageName::new_unchecked(name)),
+                        );
+                        goal.update(pool.intern_matchspec(&spec));
+                    }
+                }
+                UpdateStrategy::UpdateAll => goal.update_all(),
+                UpdateStrategy::FreezeLocked => {
+                    for &solvable_id in installed_solvables.iter() {
+                        goal.lock(solvable_id);
+                    }
+                }
+            }
 
-        // Favor the currently installed packages
-        for favor_solvable in installed_solvables {
-            goal.favor(favor_solvable);
-        }
+            // Lock all the records that the solver is not allowed to select, e.g. because they
+            // are excluded, because another variant of the package is pinned or because selecting
+            // it would result in a downgrade. None of these records are installed so locking them
+            // ensures they are never selected.
+            if locked_packages.restricts_records() {
+                let selection = pool.intern_solvable_selection(pool.solvable_ids().filter(|&id| {
+                    solvable_record(id).is_some_and(|record| !locked_packages.is_allowed(record))
+                }));
+                goal.lock_one_of(selection);
+            }
 
-        // Lock the currently pinned packages
-        for locked_solvable in pinned_solvables {
-            goal.lock(locked_solvable);
-        }
+            // Specify the matchspec requests
+            for spec in task
+                .specs
+                .iter()
+                .chain(task.update_strategy.update_specs())
+                .filter(|spec| !removed_packages.is_removed_spec(spec))
+            {
+                install(&mut goal, spec, false);
+            }
 
-        // Specify the matchspec requests
-        for spec in task.specs {
-            let id = pool.intern_matchspec(&spec);
-            goal.install(id, false)
-        }
+            // Soft specs are added as weak jobs, libsolv drops them if they make the task
+            // unsolvable
+            for spec in task
+                .soft_specs
+                .iter()
+                .filter(|spec| !removed_packages.is_removed_spec(spec))
+            {
+                install(&mut goal, spec, true);
+            }
+
+            goal
+        };
+
+        // The specs that determine which of the installed packages are still required
+        let required_specs = task
+            .specs
+            .iter()
+            .chain(task.update_strategy.update_specs())
+            .chain(task.soft_specs.iter())
+            .filter(|spec| !removed_packages.is_removed_spec(spec))
+            .collect::<Vec<_>>();
 
         // Construct a solver and solve the problems in the queue
-        let mut solver = pool.create_solver();
-        solver.set_flag(SolverFlag::allow_uninstall(), true);
-        solver.set_flag(SolverFlag::allow_downgrade(), true);
-
-        let transaction = solver.solve(&mut goal).map_err(SolveError::Unsolvable)?;
-
-        let required_records = get_required_packages(
-            &pool,
-            &repo_mapping,
-            &transaction,
-            all_repodata_records.as_slice(),
-        )
-        .map_err(|unsupported_operation_ids| {
-            SolveError::UnsupportedOperations(
-                unsupported_operation_ids
-                    .into_iter()
-                    .map(|id| format!("libsolv operation {id}"))
-                    .collect(),
-            )
-        })?;
-
-        Ok(required_records)
+        let solve = |mut goal: SolveGoal| -> Result<Vec<RepoDataRecord>, SolveError> {
+            let mut solver = pool.create_solver();
+            solver.set_flag(SolverFlag::allow_uninstall(), true);
+            solver.set_flag(SolverFlag::allow_downgrade(), true);
+
+            let transaction = solver.solve(&mut goal).map_err(SolveError::Unsolvable)?;
+
+            Ok(get_required_packages(
+                &transaction,
+                solvable_record,
+                &required_specs,
+            ))
+        };
+
+        match solve(create_goal(&locked_packages)) {
+            Err(SolveError::Unsolvable(problems)) if locked_packages.has_exclusions() => {
+                // Determine whether the task is only unsolvable because of the exclusions
+                match solve(create_goal(&locked_packages.without_exclusions())) {
+                    Ok(records) => Err(locked_packages.excluded_packages_required(&records)),
+                    Err(_) => Err(SolveError::Unsolvable(problems)),
+                }
+            }
+            result => result,
+        }
     }
 }
 
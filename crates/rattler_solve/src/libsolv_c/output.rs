@@ -1,75 +1,52 @@
 //! Contains business logic to retrieve the results from libsolv after attempting to resolve a conda
 //! environment
 
-use super::{
-    wrapper::pool::{Pool, StringId},
-    wrapper::repo::RepoId,
-    wrapper::solvable::SolvableId,
-    wrapper::transaction::Transaction,
-    wrapper::{ffi, solvable},
-};
-use rattler_conda_types::RepoDataRecord;
-use std::collections::HashMap;
+use super::{wrapper::solvable::SolvableId, wrapper::transaction::Transaction};
+use rattler_conda_types::{MatchSpec, RepoDataRecord};
+use std::collections::{HashMap, HashSet};
 
 /// Returns which packages should be installed in the environment
 ///
-/// If the transaction contains libsolv operations that are not "install" an error is returned
-/// containing their ids.
-pub fn get_required_packages(
-    pool: &Pool,
-    repo_mapping: &HashMap<RepoId, usize>,
+/// The locked packages are part of the installed repo and libsolv keeps installed packages even if
+/// nothing depends on them anymore. Only the records that are required by the `specs`, either
+/// directly or through the dependencies of other required records, are returned. Solvables
+/// without a record (e.g. virtual packages) are skipped.
+pub fn get_required_packages<'a>(
     transaction: &Transaction,
-    repodata_records: &[Vec<&RepoDataRecord>],
-) -> Result<Vec<RepoDataRecord>, Vec<ffi::Id>> {
-    let mut required_packages = Vec::new();
-    let mut unsupported_operations = Vec::new();
-
-    let solvable_index_id = pool
-        .find_interned_str("solvable:repodata_record_index")
-        .unwrap();
-
-    // Safe because `transaction.steps` is an active queue
-    let transaction_queue = transaction.get_steps();
-
-    for id in transaction_queue.iter() {
-        let transaction_type = transaction.transaction_type(id);
-
-        // Retrieve the repodata record corresponding to this solvable
-        let (repo_index, solvable_index) =
-            get_solvable_indexes(pool, repo_mapping, solvable_index_id, id);
-        let repodata_record = repodata_records[repo_index][solvable_index];
-
-        match transaction_type as u32 {
-            ffi::SOLVER_TRANSACTION_INSTALL => {
-                required_packages.push(repodata_record.clone());
-            }
-            _ => {
-                unsupported_operations.push(transaction_type);
-            }
+    solvable_record: impl Fn(SolvableId) -> Option<&'a RepoDataRecord>,
+    specs: &[&MatchSpec],
+) -> Vec<RepoDataRecord> {
+    let installed = transaction
+        .installed_result()
+        .into_iter()
+        .filter_map(solvable_record)
+        .collect::<Vec<_>>();
+
+    let installed_by_name = installed
+        .iter()
+        .map(|record| (record.package_record.name.as_normalized(), *record))
+        .collect::<HashMap<_, _>>();
+
+    // Walk the dependencies starting from the records that match the specs
+    let mut queue = installed
+        .iter()
+        .copied()
+        .filter(|record| specs.iter().any(|spec| spec.matches_record(record)))
+        .collect::<Vec<_>>();
+    let mut required_names = HashSet::new();
+    while let Some(record) = queue.pop() {
+        if !required_names.insert(record.package_record.name.as_normalized()) {
+            continue;
         }
+        queue.extend(
+            crate::dependency_names(record)
+                .filter_map(|name| installed_by_name.get(name.as_normalized()).copied()),
+        );
     }
 
-    if !unsupported_operations.is_empty() {
-        return Err(unsupported_operations);
-    }
-
-    Ok(required_packages)
-}
-
-fn get_solvable_indexes(
-    pool: &Pool,
-    repo_mapping: &HashMap<RepoId, usize>,
-    solvable_index_id: StringId,
-    id: SolvableId,
-) -> (usize, usize) {
-    let solvable = id.resolve_raw(pool);
-    let solvable_index =
-        solvable::lookup_num(solvable.as_ptr(), solvable_index_id).unwrap() as usize;
-
-    // Safe because there are no active mutable borrows of any solvable at this stage
-    let repo_id = RepoId::from_ffi_solvable(unsafe { solvable.as_ref() });
-
-    let repo_index = repo_mapping[&repo_id];
-
-    (repo_index, solvable_index)
+    installed
+        .into_iter()
+        .filter(|record| required_names.contains(record.package_record.name.as_normalized()))
+        .cloned()
+        .collect()
 }
@@ -0,0 +1,115 @@
+//! Provides an on-disk cache of `.solv` files, see [`SolvFileCache`].
+
+use super::{input::cache_repodata, libc_byte_slice::LibcByteSlice};
+use rattler_conda_types::RepoDataRecord;
+use rattler_digest::{compute_bytes_digest, Sha256};
+use std::{
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+/// The version of the format of the files in the cache. Files with a different version are
+/// ignored and overwritten.
+const SOLV_CACHE_VERSION: u32 = 1;
+
+/// An on-disk cache of `.solv` files.
+///
+/// Converting the records of a large channel to the internal representation of libsolv takes a
+/// significant amount of time. Like mamba, this cache stores the result of this conversion as a
+/// `.solv` file so it only has to happen once for every version of the repodata of a channel
+/// subdirectory. The returned bytes can be passed to the solver through
+/// [`super::RepoData::solv_file`].
+///
+/// Each entry is identified by the URL of the channel subdirectory and a state key that changes
+/// whenever the repodata changes. Usually the state key is derived from the `RepoDataState` of the
+/// cached `repodata.json` (e.g. its blake2 hash, or its modification time and size). Only a single
+/// entry is kept for every URL, an entry with a different state key is replaced.
+///
+/// A `.solv` file refers to the records by their index, so the records passed to the solver have to
+/// be exactly the records (in the same order) that were used to create the cache entry. This is the
+/// case as long as the state key identifies the content of the repodata.
+#[derive(Debug, Clone)]
+pub struct SolvFileCache {
+    cache_dir: PathBuf,
+}
+
+impl SolvFileCache {
+    /// Constructs a cache that stores its files in the specified directory. The directory is
+    /// created when the first file is written.
+    pub fn new(cache_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            cache_dir: cache_dir.into(),
+        }
+    }
+
+    /// Returns the directory in which the files of the cache are stored.
+    pub fn cache_dir(&self) -> &Path {
+        &self.cache_dir
+    }
+
+    /// Returns the path of the cache file for the specified channel subdirectory url.
+    pub fn cache_path(&self, url: &str) -> PathBuf {
+        let url_hash = compute_bytes_digest::<Sha256>(url);
+        self.cache_dir
+            .join(format!("{}.solv", hex::encode(&url_hash[..8])))
+    }
+
+    /// Loads the `.solv` file for the specified channel subdirectory url and state key from the
+    /// cache. Returns `None` if the cache does not contain an entry for the url or if the entry was
+    /// created for a different state or number of records.
+    pub fn get(
+        &self,
+        url: &str,
+        state_key: &str,
+        records: &[RepoDataRecord],
+    ) -> std::io::Result<Option<LibcByteSlice>> {
+        let contents = match std::fs::read(self.cache_path(url)) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let header = cache_header(url, state_key, records);
+        Ok(contents
+            .strip_prefix(header.as_bytes())
+            .map(LibcByteSlice::from_slice))
+    }
+
+    /// Loads the `.solv` file for the specified channel subdirectory url and state key from the
+    /// cache. If the cache does not contain a matching entry, the `.solv` file is created from the
+    /// records and stored in the cache.
+    pub fn get_or_insert(
+        &self,
+        url: &str,
+        state_key: &str,
+        records: &[RepoDataRecord],
+    ) -> std::io::Result<LibcByteSlice> {
+        if let Some(solv_file) = self.get(url, state_key, records)? {
+            return Ok(solv_file);
+        }
+
+        tracing::debug!("creating .solv file for {url}");
+        let solv_file = cache_repodata(url.to_owned(), records);
+
+        // Write the file to a temporary location first, to make sure another process never
+        // observes a partially written file.
+        std::fs::create_dir_all(&self.cache_dir)?;
+        let mut file = tempfile::NamedTempFile::new_in(&self.cache_dir)?;
+        file.write_all(cache_header(url, state_key, records).as_bytes())?;
+        file.write_all(solv_file.as_slice())?;
+        file.persist(self.cache_path(url)).map_err(|e| e.error)?;
+
+        Ok(solv_file)
+    }
+}
+
+/// Returns the header that precedes the contents of the `.solv` file in a cache file. The header
+/// identifies the entry, an entry is only used if its header matches exactly.
+fn cache_header(url: &str, state_key: &str, records: &[RepoDataRecord]) -> String {
+    let key_hash = compute_bytes_digest::<Sha256>(format!("{url}\n{state_key}"));
+    format!(
+        "rattler-solv-cache {SOLV_CACHE_VERSION} {} {:x}\n",
+        records.len(),
+        key_hash
+    )
+}
@@ -1,6 +1,7 @@
 use super::{
     super::{c_string, wrapper::ffi::Id},
     ffi,
+    queue::Queue,
     repo::Repo,
     solvable::SolvableId,
     solver::Solver,
@@ -166,6 +167,18 @@ impl Pool {
         unsafe { ffi::pool_setdisttype(self.raw_ptr(), disttype as i32) };
     }
 
+    /// Returns the ids of all solvables in the pool that belong to a repo
+    pub fn solvable_ids(&self) -> impl Iterator<Item = SolvableId> + '_ {
+        // The first two solvables are reserved by libsolv
+        (2..self.as_ref().nsolvables).map(SolvableId).filter(|&id| {
+            // Safe because there are no active mutable borrows of any solvable
+            let solvable = unsafe { id.resolve_raw(self).as_ref() };
+
+            // Solvables that have been freed no longer belong to a repo
+            !solvable.repo.is_null()
+        })
+    }
+
     /// Create the solver
     pub fn create_solver(&self) -> Solver {
         let solver = NonNull::new(unsafe { ffi::solver_create(self.raw_ptr()) })
@@ -182,11 +195,35 @@ impl Pool {
         }
     }
 
+    /// Interns a matchspec into the pool.
+    ///
+    /// libsolv only understands the name, version and build of a spec (see
+    /// [`libsolv_matchspec`]), the other fields of the spec, like the `channel` or `md5` bracket
+    /// keys, are left out. Solvables that have to match those fields as well can be selected with
+    /// [`Pool::intern_solvable_selection`] instead.
     pub fn intern_matchspec(&self, match_spec: &MatchSpec) -> MatchSpecId {
-        let c_str = c_string(match_spec.to_string());
+        let c_str = c_string(libsolv_matchspec(match_spec).to_string());
         unsafe { MatchSpecId(ffi::pool_conda_matchspec(self.raw_ptr(), c_str.as_ptr())) }
     }
 
+    /// Interns a selection of solvables into the pool. The returned id provides exactly the
+    /// specified solvables.
+    pub fn intern_solvable_selection(
+        &self,
+        solvables: impl IntoIterator<Item = SolvableId>,
+    ) -> SolvableSelectionId {
+        let mut queue = Queue::<SolvableId>::default();
+        for solvable in solvables {
+            queue.push_id(solvable);
+        }
+        unsafe {
+            SolvableSelectionId(ffi::pool_queuetowhatprovides(
+                self.raw_ptr(),
+                queue.raw_ptr(),
+            ))
+        }
+    }
+
     /// Interns string like types into a `Pool` returning a `StringId`
     pub fn intern_str<T: Into<Vec<u8>>>(&self, str: T) -> StringId {
         let c_str = CString::new(str).expect("the provided string contained a NUL byte");
@@ -273,12 +310,33 @@ impl From<MatchSpecId> for Id {
     }
 }
 
+/// Wrapper for the id of a selection of solvables
+#[derive(Copy, Clone)]
+pub struct SolvableSelectionId(Id);
+
+/// Conversion to [`Id`]
+impl From<SolvableSelectionId> for Id {
+    fn from(id: SolvableSelectionId) -> Self {
+        id.0
+    }
+}
+
+/// Returns the part of the spec that libsolv understands: the name, the version and the build.
+pub fn libsolv_matchspec(match_spec: &MatchSpec) -> MatchSpec {
+    MatchSpec {
+        name: match_spec.name.clone(),
+        version: match_spec.version.clone(),
+        build: match_spec.build.clone(),
+        ..MatchSpec::default()
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use std::{ffi::CString, str::FromStr};
+    use std::ffi::CString;
 
     use super::super::pool::Pool;
-    use rattler_conda_types::MatchSpec;
+    use rattler_conda_types::{MatchSpec, ParseStrictness};
 
     #[test]
     fn test_pool_string_interning() {
@@ -319,13 +377,27 @@ mod test {
     #[test]
     fn test_matchspec_interning() {
         // Create a matchspec
-        let spec = MatchSpec::from_str("foo=1.0=py27_0").unwrap();
+        let spec = MatchSpec::from_str("foo=1.0=py27_0", ParseStrictness::Strict).unwrap();
         // Intern it into the pool
         let pool = Pool::default();
         pool.intern_matchspec(&spec);
         // Don't think libsolv has an API to get it back
     }
 
+    #[test]
+    fn test_libsolv_matchspec() {
+        // Bracket keys that libsolv does not understand are left out
+        let spec = MatchSpec::from_str(
+            "conda-forge::foo 1.0 py27_0[md5=b3af409bb8423187c75e6c7f5b683908, license=MIT]",
+            ParseStrictness::Strict,
+        )
+        .unwrap();
+        assert_eq!(
+            super::libsolv_matchspec(&spec),
+            MatchSpec::from_str("foo 1.0 py27_0", ParseStrictness::Strict).unwrap()
+        );
+    }
+
     #[test]
     fn test_pool_callback() {
         let pool = Pool::default();
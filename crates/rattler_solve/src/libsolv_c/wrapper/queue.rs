@@ -47,7 +47,6 @@ impl<T> Queue<T> {
 
 impl<T: Into<ffi::Id>> Queue<T> {
     /// Pushes a single id to the back of the queue
-    #[allow(dead_code)]
     pub fn push_id(&mut self, id: T) {
         unsafe {
             ffi::queue_insert(self.raw_ptr(), self.queue.count, id.into());
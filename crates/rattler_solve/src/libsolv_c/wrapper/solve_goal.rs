@@ -2,9 +2,10 @@ use super::{
     ffi,
     ffi::{
         SOLVER_DISFAVOR, SOLVER_ERASE, SOLVER_FAVOR, SOLVER_INSTALL, SOLVER_LOCK, SOLVER_SOLVABLE,
-        SOLVER_SOLVABLE_PROVIDES, SOLVER_UPDATE, SOLVER_WEAK,
+        SOLVER_SOLVABLE_ALL, SOLVER_SOLVABLE_ONE_OF, SOLVER_SOLVABLE_PROVIDES, SOLVER_UPDATE,
+        SOLVER_WEAK,
     },
-    pool::MatchSpecId,
+    pool::{MatchSpecId, SolvableSelectionId},
     solvable::SolvableId,
 };
 use std::os::raw::c_int;
@@ -62,6 +63,16 @@ impl SolveGoal {
         self.push_id_with_flags(match_spec, action | SOLVER_SOLVABLE_PROVIDES);
     }
 
+    /// One of the solvables of the specified selection must be installed
+    pub fn install_one_of(&mut self, selection: SolvableSelectionId, optional: bool) {
+        let action = if optional {
+            SOLVER_INSTALL | SOLVER_WEAK
+        } else {
+            SOLVER_INSTALL
+        };
+        self.push_id_with_flags(selection, action | SOLVER_SOLVABLE_ONE_OF);
+    }
+
     /// The specified spec must not be installed.
     pub fn erase(&mut self, match_spec: MatchSpecId) {
         self.push_id_with_flags(match_spec, SOLVER_ERASE | SOLVER_SOLVABLE_PROVIDES);
@@ -72,18 +83,30 @@ impl SolveGoal {
         self.push_id_with_flags(match_spec, SOLVER_UPDATE | SOLVER_SOLVABLE_PROVIDES);
     }
 
+    /// The highest possible version of all installed solvables must be installed
+    pub fn update_all(&mut self) {
+        self.push_id_with_flags(0, SOLVER_UPDATE | SOLVER_SOLVABLE_ALL);
+    }
+
     /// Favor the specified solvable over other variants. This doesnt mean this variant will be
     /// used. To guarantee a solvable is used (if selected) use the `Self::lock` function.
     pub fn favor(&mut self, solvable: SolvableId) {
         self.push_id_with_flags(solvable, SOLVER_SOLVABLE | SOLVER_FAVOR);
     }
 
-    /// Lock the specified solvable over other variants. This implies that not other variant will
-    /// ever be considered.
+    /// Lock the specified solvable in its current state. If the solvable is installed, it will stay
+    /// installed and no other variant will ever be considered. If the solvable is not installed, it
+    /// will never be selected.
     pub fn lock(&mut self, solvable: SolvableId) {
         self.push_id_with_flags(solvable, SOLVER_SOLVABLE | SOLVER_LOCK);
     }
 
+    /// Lock all the solvables of the specified selection in their current state, see
+    /// [`Self::lock`].
+    pub fn lock_one_of(&mut self, selection: SolvableSelectionId) {
+        self.push_id_with_flags(selection, SOLVER_SOLVABLE_ONE_OF | SOLVER_LOCK);
+    }
+
     /// Disfavor the specified variant over other variants. This does not mean it will never be
     /// selected, but other variants are considered first.
     pub fn disfavor(&mut self, solvable: SolvableId) {
@@ -1,4 +1,9 @@
-use super::{ffi, queue::QueueRef, solvable::SolvableId, solver::Solver};
+use super::{
+    ffi,
+    queue::{Queue, QueueRef},
+    solvable::SolvableId,
+    solver::Solver,
+};
 use std::{marker::PhantomData, ptr::NonNull};
 
 /// Wrapper for [`ffi::Transaction`], which is an abstraction over changes that need to be
@@ -63,4 +68,15 @@ impl Transaction<'_> {
         // Safe because the transaction is live and `transaction.steps` is a queue
         unsafe { QueueRef::from_ffi_queue(self, self.as_ref().steps) }
     }
+
+    /// Returns the solvables that are installed after the transaction has been applied. This
+    /// includes the installed solvables that are kept by the transaction.
+    pub fn installed_result(&self) -> Vec<SolvableId> {
+        let mut queue = Queue::<SolvableId>::default();
+
+        // Safe because the transaction is live and the queue is initialized
+        unsafe { ffi::transaction_installedresult(self.raw_ptr(), queue.raw_ptr()) };
+
+        queue.id_iter().map(SolvableId).collect()
+    }
 }
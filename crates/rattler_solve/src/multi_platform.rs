@@ -0,0 +1,135 @@
+//! Provides functionality to solve the same set of specs for multiple platforms in a single call.
+
+use crate::{
+    IntoRepoData, PackageExclusion, RemovalStrategy, SolveError, SolverImpl, SolverTask,
+    UpdateStrategy,
+};
+use rattler_conda_types::{GenericVirtualPackage, MatchSpec, Platform, RepoDataRecord};
+use std::collections::HashMap;
+
+/// The platform specific part of a [`MultiPlatformSolverTask`].
+pub struct PlatformSolverTask<TAvailablePackagesIterator> {
+    /// The platform for which to solve the environment
+    pub platform: Platform,
+
+    /// An iterator over all packages available for this platform.
+    ///
+    /// Usually this contains the records of the platform specific subdirectory and the `noarch`
+    /// subdirectory of every channel. Because records are passed by reference the records of
+    /// shared subdirectories (like `noarch`) only have to be loaded once and can be passed to every
+    /// platform.
+    pub available_packages: TAvailablePackagesIterator,
+
+    /// Records of packages that are previously selected for this platform. See
+    /// [`SolverTask::locked_packages`] for more information.
+    pub locked_packages: Vec<RepoDataRecord>,
+
+    /// Records of packages that are previously selected for this platform and CANNOT be changed.
+    /// See [`SolverTask::pinned_packages`] for more information.
+    pub pinned_packages: Vec<RepoDataRecord>,
+
+    /// Virtual packages considered active for this platform
+    pub virtual_packages: Vec<GenericVirtualPackage>,
+}
+
+/// Represents a dependency resolution task for the same specs on multiple platforms.
+///
+/// Building a multi-platform environment (e.g. a lock-file) requires solving the same specs once
+/// for every platform. This task bundles the information of all platforms together so they can be
+/// solved with a single call to [`MultiPlatformSolverTask::solve`].
+pub struct MultiPlatformSolverTask<TAvailablePackagesIterator> {
+    /// The specs we want to solve for every platform. The specs are parsed once and shared between
+    /// all platforms.
+    pub specs: Vec<MatchSpec>,
+
+    /// Specs that the solver tries to satisfy on every platform. See [`SolverTask::soft_specs`]
+    /// for more information.
+    pub soft_specs: Vec<MatchSpec>,
+
+    /// The platform specific information for each platform to solve for.
+    pub platforms: Vec<PlatformSolverTask<TAvailablePackagesIterator>>,
+
+    /// Determines how the solver treats the locked packages of every platform. See
+    /// [`SolverTask::update_strategy`] for more information.
+    pub update_strategy: UpdateStrategy,
+
+    /// Whether the solver is allowed to downgrade locked packages. See
+    /// [`SolverTask::allow_downgrade`] for more information.
+    pub allow_downgrade: bool,
+
+    /// Specs of the packages to remove on every platform. See [`SolverTask::remove_specs`] for
+    /// more information.
+    pub remove_specs: Vec<MatchSpec>,
+
+    /// Determines how packages that depend on a removed package are treated. See
+    /// [`SolverTask::removal_strategy`] for more information.
+    pub removal_strategy: RemovalStrategy,
+
+    /// Records that the solver must never select on any platform. See
+    /// [`SolverTask::exclusions`] for more information.
+    pub exclusions: Vec<PackageExclusion>,
+}
+
+/// The result of solving a [`MultiPlatformSolverTask`]. Contains the outcome of the solve for every
+/// platform. The failure to solve for one platform does not affect the result of the other
+/// platforms.
+pub type MultiPlatformSolution = HashMap<Platform, Result<Vec<RepoDataRecord>, SolveError>>;
+
+impl<TAvailablePackagesIterator: IntoIterator + Send>
+    MultiPlatformSolverTask<TAvailablePackagesIterator>
+{
+    /// Solves the specs for all platforms using the solver backend `S`.
+    ///
+    /// Each platform is solved on a separate thread with a solver instance of its own, the solver
+    /// backends in this crate do not share any state between solves so the platforms can be solved
+    /// fully in parallel.
+    ///
+    /// If the same platform is specified multiple times only the result of the last occurrence is
+    /// returned.
+    pub fn solve<'a, S: SolverImpl + Default>(self) -> MultiPlatformSolution
+    where
+        TAvailablePackagesIterator::Item: IntoRepoData<'a, S::RepoData<'a>>,
+    {
+        let specs = self.specs;
+        let soft_specs = self.soft_specs;
+        let update_strategy = self.update_strategy;
+        let allow_downgrade = self.allow_downgrade;
+        let remove_specs = self.remove_specs;
+        let removal_strategy = self.removal_strategy;
+        let exclusions = self.exclusions;
+        std::thread::scope(|scope| {
+            let handles = self
+                .platforms
+                .into_iter()
+                .map(|platform_task| {
+                    let platform = platform_task.platform;
+                    let task = SolverTask {
+                        available_packages: platform_task.available_packages,
+                        locked_packages: platform_task.locked_packages,
+                        pinned_packages: platform_task.pinned_packages,
+                        virtual_packages: platform_task.virtual_packages,
+                        specs: specs.clone(),
+                        soft_specs: soft_specs.clone(),
+                        update_strategy: update_strategy.clone(),
+                        allow_downgrade,
+                        remove_specs: remove_specs.clone(),
+                        removal_strategy,
+                        exclusions: exclusions.clone(),
+                    };
+                    let handle = scope.spawn(move || S::default().solve(task));
+                    (platform, handle)
+                })
+                .collect::<Vec<_>>();
+
+            handles
+                .into_iter()
+                .map(|(platform, handle)| {
+                    let result = handle
+                        .join()
+                        .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+                    (platform, result)
+                })
+                .collect()
+        })
+    }
+}
@@ -1,19 +1,20 @@
-use crate::resolvo::{CondaDependencyProvider, SolverMatchSpec};
+use crate::resolvo::{CondaDependencyProvider, SolverMatchSpec, SolverPackageRecord};
 use rattler_conda_types::Version;
 use resolvo::{SolvableId, SolverCache, VersionSetId};
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 /// Returns the order of two candidates based on the order used by conda.
 #[allow(clippy::too_many_arguments)]
 pub(super) fn compare_candidates<'a>(
     a: SolvableId,
     b: SolvableId,
-    solver: &SolverCache<SolverMatchSpec<'a>, String, CondaDependencyProvider<'a>>,
+    solver: &SolverCache<SolverMatchSpec<'a>, String, CondaDependencyProvider<'_, 'a>>,
     match_spec_highest_version: &mut HashMap<
         VersionSetId,
         Option<(rattler_conda_types::Version, bool)>,
     >,
+    enabled_features: &HashSet<String>,
 ) -> Ordering {
     let pool = solver.pool();
 
@@ -23,16 +24,36 @@ pub(super) fn compare_candidates<'a>(
     let a_record = &a_solvable.inner();
     let b_record = &b_solvable.inner();
 
-    // First compare by "tracked_features". If one of the packages has a tracked feature it is
-    // sorted below the one that doesn't have the tracked feature.
-    let a_has_tracked_features = !a_record.track_features().is_empty();
-    let b_has_tracked_features = !b_record.track_features().is_empty();
-    match a_has_tracked_features.cmp(&b_has_tracked_features) {
+    // First compare by the features that are not enabled yet. If one of the packages tracks such a
+    // feature it is sorted below the one that doesn't. Packages that require a legacy feature are
+    // treated the same way because they pull in a package that tracks the feature.
+    let disabled_features = |record: &SolverPackageRecord<'_>| {
+        record
+            .track_feature_names()
+            .chain(record.feature_names())
+            .filter(|feature| !enabled_features.contains(*feature))
+            .count()
+    };
+    match disabled_features(a_record).cmp(&disabled_features(b_record)) {
         Ordering::Less => return Ordering::Less,
         Ordering::Greater => return Ordering::Greater,
         Ordering::Equal => {}
     };
 
+    // Otherwise, select the variant that requires the most enabled features. Like conda, the
+    // featured variant of a package is preferred once its feature is enabled.
+    let enabled_feature_count = |record: &SolverPackageRecord<'_>| {
+        record
+            .feature_names()
+            .filter(|feature| enabled_features.contains(*feature))
+            .count()
+    };
+    match enabled_feature_count(a_record).cmp(&enabled_feature_count(b_record)) {
+        Ordering::Less => return Ordering::Greater,
+        Ordering::Greater => return Ordering::Less,
+        Ordering::Equal => {}
+    };
+
     // Otherwise, select the variant with the highest version
     match a_record.version().cmp(b_record.version()) {
         Ordering::Less => return Ordering::Greater,
@@ -122,7 +143,7 @@ pub(super) fn compare_candidates<'a>(
 
 pub(super) fn find_highest_version<'a>(
     match_spec_id: VersionSetId,
-    solver: &SolverCache<SolverMatchSpec<'a>, String, CondaDependencyProvider<'a>>,
+    solver: &SolverCache<SolverMatchSpec<'a>, String, CondaDependencyProvider<'_, 'a>>,
     match_spec_highest_version: &mut HashMap<
         VersionSetId,
         Option<(rattler_conda_types::Version, bool)>,
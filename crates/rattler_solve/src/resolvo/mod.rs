@@ -1,26 +1,33 @@
 //! Provides an solver implementation based on the [`resolvo`] crate.
 
-use crate::{IntoRepoData, SolveError, SolverRepoData, SolverTask};
+use crate::{
+    IntoRepoData, LockedPackages, PackageExclusion, RemovedPackages, SolveError, SolverRepoData,
+    SolverTask,
+};
 use rattler_conda_types::package::ArchiveType;
 use rattler_conda_types::{
     GenericVirtualPackage, MatchSpec, NamelessMatchSpec, PackageRecord, ParseMatchSpecError,
-    RepoDataRecord,
+    ParseStrictness, RepoDataRecord,
 };
 use resolvo::{
     Candidates, Dependencies, DependencyProvider, NameId, Pool, SolvableDisplay, SolvableId,
     Solver as LibSolvRsSolver, SolverCache, VersionSet, VersionSetId,
 };
 use std::{
+    borrow::Cow,
     cell::RefCell,
     cmp::Ordering,
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt::{Display, Formatter},
     marker::PhantomData,
     ops::Deref,
     str::FromStr,
+    sync::OnceLock,
 };
 
 use itertools::Itertools;
+use rattler_digest::Sha256Hash;
+use url::Url;
 
 mod conda_util;
 
@@ -78,35 +85,41 @@ impl<'a> VersionSet for SolverMatchSpec<'a> {
 
     fn contains(&self, v: &Self::V) -> bool {
         match v {
-            SolverPackageRecord::Record(rec) => self.inner.matches(&rec.package_record),
-            SolverPackageRecord::VirtualPackage(GenericVirtualPackage {
-                version,
-                build_string,
-                ..
-            }) => {
+            SolverPackageRecord::Record(rec) => self.inner.matches_record(rec),
+            SolverPackageRecord::VirtualPackage(virtual_package) => {
                 if let Some(spec) = self.inner.version.as_ref() {
-                    if !spec.matches(version) {
+                    if !spec.matches(&virtual_package.version) {
                         return false;
                     }
                 }
 
                 if let Some(build_match) = self.inner.build.as_ref() {
-                    if !build_match.matches(build_string) {
+                    if !build_match.matches(&virtual_package.build_string) {
                         return false;
                     }
                 }
 
                 true
             }
+            SolverPackageRecord::Feature(..) => true,
         }
     }
 }
 
 /// Wrapper around [`PackageRecord`] so that we can use it in resolvo pool
-#[derive(Ord, PartialOrd, Eq, PartialEq)]
+///
+/// Records from the available packages are borrowed, records that are specific to a single task
+/// (locked, pinned and virtual packages) are owned if they do not outlive the pool.
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[allow(clippy::large_enum_variant)]
 enum SolverPackageRecord<'a> {
-    Record(&'a RepoDataRecord),
-    VirtualPackage(&'a GenericVirtualPackage),
+    Record(Cow<'a, RepoDataRecord>),
+    VirtualPackage(Cow<'a, GenericVirtualPackage>),
+
+    /// A legacy feature that is provided by a record that tracks it. Records that have a feature
+    /// in their `features` depend on a package of the feature, every package of a feature depends
+    /// on the record that tracks it.
+    Feature(String, VersionSetId),
 }
 
 impl<'a> SolverPackageRecord<'a> {
@@ -114,6 +127,10 @@ impl<'a> SolverPackageRecord<'a> {
         match self {
             SolverPackageRecord::Record(rec) => rec.package_record.version.version(),
             SolverPackageRecord::VirtualPackage(rec) => &rec.version,
+            SolverPackageRecord::Feature(..) => {
+                static FEATURE_VERSION: OnceLock<rattler_conda_types::Version> = OnceLock::new();
+                FEATURE_VERSION.get_or_init(|| rattler_conda_types::Version::from_str("0").unwrap())
+            }
         }
     }
 
@@ -121,21 +138,41 @@ impl<'a> SolverPackageRecord<'a> {
         const EMPTY: [String; 0] = [];
         match self {
             SolverPackageRecord::Record(rec) => &rec.package_record.track_features,
-            SolverPackageRecord::VirtualPackage(_rec) => &EMPTY,
+            SolverPackageRecord::VirtualPackage(_) | SolverPackageRecord::Feature(..) => &EMPTY,
+        }
+    }
+
+    /// Returns the legacy features that the record requires.
+    fn feature_names(&self) -> impl Iterator<Item = &str> + '_ {
+        match self {
+            SolverPackageRecord::Record(rec) => Some(rec.package_record.feature_names()),
+            SolverPackageRecord::VirtualPackage(_) | SolverPackageRecord::Feature(..) => None,
         }
+        .into_iter()
+        .flatten()
+    }
+
+    /// Returns the legacy features that the record tracks.
+    fn track_feature_names(&self) -> impl Iterator<Item = &str> + '_ {
+        match self {
+            SolverPackageRecord::Record(rec) => Some(rec.package_record.track_feature_names()),
+            SolverPackageRecord::VirtualPackage(_) | SolverPackageRecord::Feature(..) => None,
+        }
+        .into_iter()
+        .flatten()
     }
 
     fn build_number(&self) -> u64 {
         match self {
             SolverPackageRecord::Record(rec) => rec.package_record.build_number,
-            SolverPackageRecord::VirtualPackage(_rec) => 0,
+            SolverPackageRecord::VirtualPackage(_) | SolverPackageRecord::Feature(..) => 0,
         }
     }
 
     fn timestamp(&self) -> Option<&chrono::DateTime<chrono::Utc>> {
         match self {
             SolverPackageRecord::Record(rec) => rec.package_record.timestamp.as_ref(),
-            SolverPackageRecord::VirtualPackage(_rec) => None,
+            SolverPackageRecord::VirtualPackage(_) | SolverPackageRecord::Feature(..) => None,
         }
     }
 }
@@ -149,43 +186,70 @@ impl<'a> Display for SolverPackageRecord<'a> {
             SolverPackageRecord::VirtualPackage(rec) => {
                 write!(f, "{}", rec)
             }
+            SolverPackageRecord::Feature(feature, _) => {
+                write!(f, "{}@", feature)
+            }
         }
     }
 }
 
-/// Dependency provider for conda
-#[derive(Default)]
-pub(crate) struct CondaDependencyProvider<'a> {
+/// The available packages for the resolvo backend in a form that can be reused between solves.
+///
+/// Preparing the available packages for the solver is expensive: every package name has to be
+/// interned and every dependency has to be parsed. A [`SolverPool`] keeps this information around
+/// so that solving multiple tasks against the same channels only has to process the information
+/// that is specific to each task (specs, locked, pinned and virtual packages). Use
+/// [`Solver::solve_with_pool`] to solve a task using a pool.
+///
+/// The underlying `resolvo` pool can only grow, it never releases memory. Locked and pinned
+/// records that are also part of the available packages (identified by their url) reuse the
+/// solvable of the available record. Other locked, pinned and virtual packages and the specs of a
+/// task are added to the pool once and are reused by subsequent solves that use identical ones. A
+/// long-lived pool therefore only grows with the number of distinct packages and specs that are
+/// not part of the available packages.
+///
+/// The pool uses interior mutability that is not thread-safe, so it is [`Send`] but not [`Sync`].
+/// To solve tasks on multiple threads, either create a pool per thread or wrap the pool in a
+/// [`std::sync::Mutex`] to solve one task at a time.
+pub struct SolverPool<'a> {
     pool: Pool<SolverMatchSpec<'a>, String>,
 
+    /// The candidates of every package in the available records
     records: HashMap<NameId, Candidates>,
 
-    matchspec_to_highest_version:
-        RefCell<HashMap<VersionSetId, Option<(rattler_conda_types::Version, bool)>>>,
+    /// The solvables of the available records by url
+    records_by_url: HashMap<&'a Url, SolvableId>,
+
+    /// The solvables of the available records by sha256 hash
+    records_by_sha256: HashMap<&'a Sha256Hash, Vec<SolvableId>>,
+
+    /// The solvables of the locked, pinned and virtual packages of the solved tasks
+    task_solvables: RefCell<HashMap<SolverPackageRecord<'a>, SolvableId>>,
+
+    /// Caches the parsed dependency specs
+    parse_match_spec_cache: RefCell<HashMap<String, VersionSetId>>,
+
+    /// The solvables of the available records that track a legacy feature, by feature
+    feature_trackers: HashMap<&'a str, Vec<SolvableId>>,
+
+    /// Maps the name of a legacy feature to the spec that requires one of its feature packages
+    feature_specs: RefCell<HashMap<String, VersionSetId>>,
 
-    parse_match_spec_cache: RefCell<HashMap<&'a str, VersionSetId>>,
+    /// The solvables of the feature packages by name
+    feature_solvables: RefCell<HashMap<NameId, Vec<SolvableId>>>,
 }
 
-impl<'a> CondaDependencyProvider<'a> {
-    pub fn from_solver_task(
-        repodata: impl IntoIterator<Item = RepoData<'a>>,
-        favored_records: &'a [RepoDataRecord],
-        locked_records: &'a [RepoDataRecord],
-        virtual_packages: &'a [GenericVirtualPackage],
-    ) -> Self {
+impl<'a> SolverPool<'a> {
+    /// Constructs a new pool from the available packages.
+    pub fn new<R: IntoRepoData<'a, RepoData<'a>>>(repodata: impl IntoIterator<Item = R>) -> Self {
         let pool = Pool::default();
         let mut records: HashMap<NameId, Candidates> = HashMap::default();
-
-        // Add virtual packages to the records
-        for virtual_package in virtual_packages {
-            let name = pool.intern_package_name(virtual_package.name.as_normalized());
-            let solvable =
-                pool.intern_solvable(name, SolverPackageRecord::VirtualPackage(virtual_package));
-            records.entry(name).or_default().candidates.push(solvable);
-        }
+        let mut records_by_url = HashMap::new();
+        let mut records_by_sha256: HashMap<_, Vec<_>> = HashMap::new();
+        let mut feature_trackers: HashMap<_, Vec<_>> = HashMap::new();
 
         // Add additional records
-        for repo_datas in repodata {
+        for repo_datas in repodata.into_iter().map(IntoRepoData::into) {
             // Iterate over all records and dedup records that refer to the same package data but with
             // different archive types. This can happen if you have two variants of the same package but
             // with different extensions. We prefer `.conda` packages over `.tar.bz`.
@@ -233,43 +297,495 @@ impl<'a> CondaDependencyProvider<'a> {
             for record in ordered_repodata {
                 let package_name =
                     pool.intern_package_name(record.package_record.name.as_normalized());
-                let solvable_id =
-                    pool.intern_solvable(package_name, SolverPackageRecord::Record(record));
+                let solvable_id = pool.intern_solvable(
+                    package_name,
+                    SolverPackageRecord::Record(Cow::Borrowed(record)),
+                );
                 let candidates = records.entry(package_name).or_default();
                 candidates.candidates.push(solvable_id);
                 candidates.hint_dependencies_available.push(solvable_id);
+                records_by_url.insert(&record.url, solvable_id);
+                if let Some(sha256) = record.package_record.sha256.as_ref() {
+                    records_by_sha256
+                        .entry(sha256)
+                        .or_default()
+                        .push(solvable_id);
+                }
+                for feature in record.package_record.track_feature_names() {
+                    feature_trackers
+                        .entry(feature)
+                        .or_default()
+                        .push(solvable_id);
+                }
+            }
+        }
+
+        Self {
+            pool,
+            records,
+            records_by_url,
+            records_by_sha256,
+            feature_trackers,
+            task_solvables: Default::default(),
+            parse_match_spec_cache: Default::default(),
+            feature_specs: Default::default(),
+            feature_solvables: Default::default(),
+        }
+    }
+
+    /// Returns the candidates of the specified package in the available records.
+    fn candidates(&self, name: NameId) -> Option<Candidates> {
+        if let Some(candidates) = self.records.get(&name) {
+            return Some(candidates.clone());
+        }
+
+        self.feature_solvables
+            .borrow()
+            .get(&name)
+            .map(|solvables| Candidates {
+                candidates: solvables.clone(),
+                hint_dependencies_available: solvables.clone(),
+                ..Candidates::default()
+            })
+    }
+
+    /// Returns the solvable of a locked, pinned or virtual package of a task. Records that are part
+    /// of the available packages use the solvable of the available record, other solvables are
+    /// reused if the same package was used by a previous task.
+    fn intern_task_solvable(&self, name: &str, record: SolverPackageRecord<'a>) -> SolvableId {
+        if let SolverPackageRecord::Record(rec) = &record {
+            if let Some(&solvable) = self.records_by_url.get(&rec.url) {
+                if matches!(self.pool.resolve_solvable(solvable).inner(),
+                    SolverPackageRecord::Record(available) if available.package_record == rec.package_record)
+                {
+                    return solvable;
+                }
+            }
+        }
+
+        let mut task_solvables = self.task_solvables.borrow_mut();
+        if let Some(&solvable) = task_solvables.get(&record) {
+            return solvable;
+        }
+
+        let name = self.pool.intern_package_name(name);
+        let solvable = self.pool.intern_solvable(name, record.clone());
+        task_solvables.insert(record, solvable);
+        solvable
+    }
+
+    /// Returns the spec that requires a package of a legacy feature, adds the packages if they
+    /// don't exist yet.
+    ///
+    /// Conda models a feature as a package named `<feature>@` which is provided by the records that
+    /// track the feature. A record that has a feature in its `features` depends on this package,
+    /// which makes it selectable only together with a record that tracks the feature. Every
+    /// available record that tracks the feature gets its own feature package that depends on
+    /// exactly that record.
+    fn intern_feature(&self, feature: &str) -> VersionSetId {
+        if let Some(&spec) = self.feature_specs.borrow().get(feature) {
+            return spec;
+        }
+
+        let name = self.pool.intern_package_name(format!("{feature}@"));
+        let solvables = self
+            .feature_trackers
+            .get(feature)
+            .into_iter()
+            .flatten()
+            .filter_map(|&tracker| {
+                let SolverPackageRecord::Record(rec) = self.pool.resolve_solvable(tracker).inner() else { return None };
+                let tracker_spec = NamelessMatchSpec {
+                    url: Some(rec.url.clone()),
+                    ..NamelessMatchSpec::default()
+                };
+                let tracker_spec = self.pool.intern_version_set(
+                    self.pool.resolve_solvable(tracker).name_id(),
+                    tracker_spec.into(),
+                );
+                Some(self.pool.intern_solvable(
+                    name,
+                    SolverPackageRecord::Feature(feature.to_owned(), tracker_spec),
+                ))
+            })
+            .collect();
+        self.feature_solvables.borrow_mut().insert(name, solvables);
+
+        let spec = self
+            .pool
+            .intern_version_set(name, NamelessMatchSpec::default().into());
+        self.feature_specs
+            .borrow_mut()
+            .insert(feature.to_owned(), spec);
+        spec
+    }
+
+    /// Returns the names of the packages that have available records which are excluded by the
+    /// task. Exclusions by url and hash are looked up directly, only match specs without a name
+    /// require checking all the available records.
+    fn excluded_names(&self, locked_packages: &LockedPackages<'_>) -> HashSet<NameId> {
+        let name_of = |solvable: SolvableId| self.pool.resolve_solvable(solvable).name_id();
+        let mut names = HashSet::new();
+        for exclusion in locked_packages.exclusions() {
+            match exclusion {
+                PackageExclusion::MatchSpec(spec) => match &spec.name {
+                    Some(name) => names.extend(
+                        self.pool
+                            .lookup_package_name(&name.as_normalized().to_owned()),
+                    ),
+                    None => names.extend(self.records.iter().filter_map(|(&name, candidates)| {
+                        candidates
+                            .candidates
+                            .iter()
+                            .any(|&solvable| {
+                                matches!(self.pool.resolve_solvable(solvable).inner(),
+                                    SolverPackageRecord::Record(rec) if exclusion.matches(rec))
+                            })
+                            .then_some(name)
+                    })),
+                },
+                PackageExclusion::Url(url) => {
+                    names.extend(self.records_by_url.get(url).copied().map(name_of))
+                }
+                PackageExclusion::Sha256(sha256) => names.extend(
+                    self.records_by_sha256
+                        .get(sha256)
+                        .into_iter()
+                        .flatten()
+                        .copied()
+                        .map(name_of),
+                ),
+            }
+        }
+        names
+    }
+
+    /// Returns the dependencies of the specified solvable.
+    fn dependencies(&self, solvable: SolvableId) -> Dependencies {
+        let rec = match self.pool.resolve_solvable(solvable).inner() {
+            SolverPackageRecord::Record(rec) => rec,
+            SolverPackageRecord::Feature(_, tracker_spec) => {
+                return Dependencies {
+                    requirements: vec![*tracker_spec],
+                    constrains: Vec::new(),
+                }
+            }
+            SolverPackageRecord::VirtualPackage(_) => return Dependencies::default(),
+        };
+
+        let mut parse_match_spec_cache = self.parse_match_spec_cache.borrow_mut();
+        let mut dependencies = Dependencies::default();
+        for depends in rec.package_record.depends.iter() {
+            let version_set_id =
+                parse_match_spec(&self.pool, depends, &mut parse_match_spec_cache).unwrap();
+            dependencies.requirements.push(version_set_id);
+        }
+
+        // Records that require a legacy feature depend on a record that tracks it
+        for feature in rec.package_record.feature_names() {
+            dependencies.requirements.push(self.intern_feature(feature));
+        }
+
+        for constrains in rec.package_record.constrains.iter() {
+            let version_set_id =
+                parse_match_spec(&self.pool, constrains, &mut parse_match_spec_cache).unwrap();
+            dependencies.constrains.push(version_set_id);
+        }
+
+        dependencies
+    }
+
+    /// Solves the task described by the specs and the locked, pinned and virtual packages.
+    ///
+    /// The records and virtual packages of the task are converted with `into_record` and
+    /// `into_virtual_package` before they are added to the pool. This allows borrowing them if
+    /// they outlive the pool.
+    fn solve<'t>(
+        &self,
+        specs: impl IntoIterator<Item = &'t MatchSpec>,
+        locked_packages: &LockedPackages<'t>,
+        virtual_packages: &'t [GenericVirtualPackage],
+        into_record: impl Fn(&'t RepoDataRecord) -> Cow<'a, RepoDataRecord>,
+        into_virtual_package: impl Fn(&'t GenericVirtualPackage) -> Cow<'a, GenericVirtualPackage>,
+    ) -> Result<Vec<RepoDataRecord>, SolveError> {
+        // Construct the requirements that the solver needs to satisfy.
+        let root_requirements = specs
+            .into_iter()
+            .map(|spec| {
+                let (name, spec) = spec.clone().into_nameless();
+                let name = name.expect("cannot use matchspec without a name");
+                let name_id = self.pool.intern_package_name(name.as_normalized());
+                self.pool.intern_version_set(name_id, spec.into())
+            })
+            .collect::<Vec<_>>();
+
+        // Construct a provider that can serve the data.
+        let provider = CondaDependencyProvider::new(
+            self,
+            &root_requirements,
+            locked_packages,
+            virtual_packages,
+            into_record,
+            into_virtual_package,
+        );
+
+        // Construct a solver and solve the problems in the queue
+        let mut solver = LibSolvRsSolver::new(provider);
+        let solvables = solver.solve(root_requirements).map_err(|problem| {
+            SolveError::Unsolvable(vec![problem
+                .display_user_friendly(&solver, &CondaSolvableDisplay)
+                .to_string()])
+        })?;
+
+        // Get the resulting packages from the solver.
+        let required_records = solvables
+            .into_iter()
+            .filter_map(|id| match solver.pool().resolve_solvable(id).inner() {
+                SolverPackageRecord::Record(rec) => Some(rec.deref().clone()),
+                SolverPackageRecord::VirtualPackage(_) | SolverPackageRecord::Feature(..) => None,
+            })
+            .collect();
+
+        Ok(required_records)
+    }
+
+    /// Solves the task like [`Self::solve`]. The soft specs are added to the specs as long as the
+    /// task remains solvable, soft specs earlier in the list take precedence. Returns the records
+    /// together with the soft specs that were dropped.
+    fn solve_with_soft_specs<'t>(
+        &self,
+        specs: Vec<&'t MatchSpec>,
+        soft_specs: Vec<&'t MatchSpec>,
+        locked_packages: &LockedPackages<'t>,
+        virtual_packages: &'t [GenericVirtualPackage],
+        into_record: impl Fn(&'t RepoDataRecord) -> Cow<'a, RepoDataRecord>,
+        into_virtual_package: impl Fn(&'t GenericVirtualPackage) -> Cow<'a, GenericVirtualPackage>,
+    ) -> Result<(Vec<RepoDataRecord>, Vec<MatchSpec>), SolveError> {
+        let solve = |specs: &[&'t MatchSpec]| {
+            self.solve(
+                specs.iter().copied(),
+                locked_packages,
+                virtual_packages,
+                &into_record,
+                &into_virtual_package,
+            )
+        };
+
+        // Usually all the soft specs can be satisfied
+        let all_specs = specs
+            .iter()
+            .chain(soft_specs.iter())
+            .copied()
+            .collect::<Vec<_>>();
+        let result = solve(&all_specs);
+        if result.is_ok() || soft_specs.is_empty() {
+            return result.map(|records| (records, Vec::new()));
+        }
+
+        // Otherwise add the soft specs one by one and drop the ones that make the task unsolvable
+        let mut accepted_specs = specs;
+        let mut dropped_soft_specs = Vec::new();
+        let mut records = solve(&accepted_specs)?;
+        for soft_spec in soft_specs {
+            accepted_specs.push(soft_spec);
+            match solve(&accepted_specs) {
+                Ok(solution) => records = solution,
+                Err(SolveError::Unsolvable(_)) => {
+                    accepted_specs.pop();
+                    dropped_soft_specs.push(soft_spec.clone());
+                }
+                Err(e) => return Err(e),
             }
         }
 
+        Ok((records, dropped_soft_specs))
+    }
+
+    /// Solves the task like [`Self::solve_with_soft_specs`]. If the task is unsolvable, this
+    /// determines whether that is only because of the excluded records of the task.
+    ///
+    /// The task is solved again without exclusions, including the soft specs, so the excluded
+    /// records that are reported are the ones the task would select if it were allowed to.
+    fn solve_task<'t>(
+        &self,
+        specs: Vec<&'t MatchSpec>,
+        soft_specs: Vec<&'t MatchSpec>,
+        locked_packages: &LockedPackages<'t>,
+        virtual_packages: &'t [GenericVirtualPackage],
+        into_record: impl Fn(&'t RepoDataRecord) -> Cow<'a, RepoDataRecord>,
+        into_virtual_package: impl Fn(&'t GenericVirtualPackage) -> Cow<'a, GenericVirtualPackage>,
+    ) -> Result<(Vec<RepoDataRecord>, Vec<MatchSpec>), SolveError> {
+        let result = self.solve_with_soft_specs(
+            specs.clone(),
+            soft_specs.clone(),
+            locked_packages,
+            virtual_packages,
+            &into_record,
+            &into_virtual_package,
+        );
+        match result {
+            Err(SolveError::Unsolvable(problems)) if locked_packages.has_exclusions() => match self
+                .solve_with_soft_specs(
+                    specs,
+                    soft_specs,
+                    &locked_packages.without_exclusions(),
+                    virtual_packages,
+                    &into_record,
+                    &into_virtual_package,
+                ) {
+                Ok((records, _)) => Err(locked_packages.excluded_packages_required(&records)),
+                Err(_) => Err(SolveError::Unsolvable(problems)),
+            },
+            result => result,
+        }
+    }
+}
+
+/// Dependency provider for conda
+pub(crate) struct CondaDependencyProvider<'p, 'a> {
+    pool: &'p SolverPool<'a>,
+
+    /// The candidates of the packages that are affected by the task, these take precedence over
+    /// the candidates in the pool.
+    records: HashMap<NameId, Candidates>,
+
+    matchspec_to_highest_version:
+        RefCell<HashMap<VersionSetId, Option<(rattler_conda_types::Version, bool)>>>,
+
+    /// The legacy features that are enabled by the locked and pinned records or by the specs of
+    /// the task.
+    enabled_features: HashSet<String>,
+}
+
+impl<'p, 'a> CondaDependencyProvider<'p, 'a> {
+    fn new<'t>(
+        pool: &'p SolverPool<'a>,
+        root_requirements: &[VersionSetId],
+        locked_packages: &LockedPackages<'t>,
+        virtual_packages: &'t [GenericVirtualPackage],
+        into_record: impl Fn(&'t RepoDataRecord) -> Cow<'a, RepoDataRecord>,
+        into_virtual_package: impl Fn(&'t GenericVirtualPackage) -> Cow<'a, GenericVirtualPackage>,
+    ) -> Self {
+        let mut records: HashMap<NameId, Candidates> = HashMap::default();
+
+        // Determine the packages for which some records are not allowed to be selected.
+        let mut restricted_names = locked_packages
+            .restricted_names()
+            .filter_map(|name| pool.pool.lookup_package_name(&name.to_owned()))
+            .collect::<HashSet<_>>();
+        if locked_packages.has_exclusions() {
+            restricted_names.extend(pool.excluded_names(locked_packages));
+        }
+
+        // Remove the records that the solver is not allowed to select
+        for name in restricted_names {
+            let Some(mut candidates) = pool.candidates(name) else {
+                continue;
+            };
+            let is_allowed =
+                |solvable: &SolvableId| match pool.pool.resolve_solvable(*solvable).inner() {
+                    SolverPackageRecord::Record(rec) => locked_packages.is_allowed(rec),
+                    SolverPackageRecord::VirtualPackage(_) | SolverPackageRecord::Feature(..) => {
+                        true
+                    }
+                };
+            candidates.candidates.retain(is_allowed);
+            candidates.hint_dependencies_available.retain(is_allowed);
+            records.insert(name, candidates);
+        }
+
+        // Add virtual packages to the records
+        for virtual_package in virtual_packages {
+            let solvable = pool.intern_task_solvable(
+                virtual_package.name.as_normalized(),
+                SolverPackageRecord::VirtualPackage(into_virtual_package(virtual_package)),
+            );
+            let name = pool.pool.resolve_solvable(solvable).name_id();
+            records
+                .entry(name)
+                .or_insert_with(|| pool.candidates(name).unwrap_or_default())
+                .candidates
+                .push(solvable);
+        }
+
         // Add favored packages to the records
-        for favored_record in favored_records {
-            let name = pool.intern_package_name(favored_record.package_record.name.as_normalized());
-            let solvable = pool.intern_solvable(name, SolverPackageRecord::Record(favored_record));
-            let mut candidates = records.entry(name).or_default();
-            candidates.candidates.push(solvable);
+        for &favored_record in locked_packages.favored.iter() {
+            if !locked_packages.is_allowed(favored_record) {
+                continue;
+            }
+
+            let solvable = pool.intern_task_solvable(
+                favored_record.package_record.name.as_normalized(),
+                SolverPackageRecord::Record(into_record(favored_record)),
+            );
+            let name = pool.pool.resolve_solvable(solvable).name_id();
+            let candidates = records
+                .entry(name)
+                .or_insert_with(|| pool.candidates(name).unwrap_or_default());
+            if !candidates.candidates.contains(&solvable) {
+                candidates.candidates.push(solvable);
+            }
             candidates.favored = Some(solvable);
         }
 
-        for locked_record in locked_records {
-            let name = pool.intern_package_name(locked_record.package_record.name.as_normalized());
-            let solvable = pool.intern_solvable(name, SolverPackageRecord::Record(locked_record));
-            let mut candidates = records.entry(name).or_default();
-            candidates.candidates.push(solvable);
+        for &locked_record in locked_packages.pinned.iter() {
+            if !locked_packages.is_allowed(locked_record) {
+                continue;
+            }
+
+            let solvable = pool.intern_task_solvable(
+                locked_record.package_record.name.as_normalized(),
+                SolverPackageRecord::Record(into_record(locked_record)),
+            );
+            let name = pool.pool.resolve_solvable(solvable).name_id();
+            let candidates = records
+                .entry(name)
+                .or_insert_with(|| pool.candidates(name).unwrap_or_default());
+            if !candidates.candidates.contains(&solvable) {
+                candidates.candidates.push(solvable);
+            }
             candidates.locked = Some(solvable);
         }
 
+        // A feature is enabled if a locked or pinned record tracks it, or if all the records that
+        // match one of the specs track it.
+        let mut enabled_features = locked_packages
+            .favored
+            .iter()
+            .chain(locked_packages.pinned.iter())
+            .filter(|record| locked_packages.is_allowed(record))
+            .flat_map(|record| record.package_record.track_feature_names())
+            .map(str::to_owned)
+            .collect::<HashSet<_>>();
+        for &requirement in root_requirements {
+            let name = pool.pool.resolve_version_set_package_name(requirement);
+            let version_set = pool.pool.resolve_version_set(requirement);
+            let candidates = records
+                .get(&name)
+                .cloned()
+                .or_else(|| pool.candidates(name));
+            let tracked_features = candidates
+                .iter()
+                .flat_map(|candidates| candidates.candidates.iter())
+                .map(|&solvable| pool.pool.resolve_solvable(solvable).inner())
+                .filter(|record| version_set.contains(record))
+                .map(|record| record.track_feature_names().collect::<HashSet<_>>())
+                .reduce(|a, b| a.intersection(&b).copied().collect());
+            enabled_features.extend(tracked_features.into_iter().flatten().map(str::to_owned));
+        }
+
         Self {
             pool,
             records,
             matchspec_to_highest_version: Default::default(),
-            parse_match_spec_cache: Default::default(),
+            enabled_features,
         }
     }
 }
 
-impl<'a> DependencyProvider<SolverMatchSpec<'a>> for CondaDependencyProvider<'a> {
+impl<'p, 'a> DependencyProvider<SolverMatchSpec<'a>> for CondaDependencyProvider<'p, 'a> {
     fn pool(&self) -> &Pool<SolverMatchSpec<'a>, String> {
-        &self.pool
+        &self.pool.pool
     }
 
     fn sort_candidates(
@@ -279,32 +795,25 @@ impl<'a> DependencyProvider<SolverMatchSpec<'a>> for CondaDependencyProvider<'a>
     ) {
         let mut highest_version_spec = self.matchspec_to_highest_version.borrow_mut();
         solvables.sort_by(|&p1, &p2| {
-            conda_util::compare_candidates(p1, p2, solver, &mut highest_version_spec)
+            conda_util::compare_candidates(
+                p1,
+                p2,
+                solver,
+                &mut highest_version_spec,
+                &self.enabled_features,
+            )
         });
     }
 
     fn get_candidates(&self, name: NameId) -> Option<Candidates> {
-        self.records.get(&name).cloned()
+        match self.records.get(&name) {
+            Some(candidates) => Some(candidates.clone()),
+            None => self.pool.candidates(name),
+        }
     }
 
     fn get_dependencies(&self, solvable: SolvableId) -> Dependencies {
-        let SolverPackageRecord::Record(rec) = self.pool.resolve_solvable(solvable).inner() else { return Dependencies::default() };
-
-        let mut parse_match_spec_cache = self.parse_match_spec_cache.borrow_mut();
-        let mut dependencies = Dependencies::default();
-        for depends in rec.package_record.depends.iter() {
-            let version_set_id =
-                parse_match_spec(&self.pool, depends, &mut parse_match_spec_cache).unwrap();
-            dependencies.requirements.push(version_set_id);
-        }
-
-        for constrains in rec.package_record.constrains.iter() {
-            let version_set_id =
-                parse_match_spec(&self.pool, constrains, &mut parse_match_spec_cache).unwrap();
-            dependencies.constrains.push(version_set_id);
-        }
-
-        dependencies
+        self.pool.dependencies(solvable)
     }
 }
 
@@ -330,6 +839,28 @@ impl SolvableDisplay<SolverMatchSpec<'_>> for CondaSolvableDisplay {
 #[derive(Default)]
 pub struct Solver;
 
+impl Solver {
+    /// Solves a task using the available packages of a [`SolverPool`]. The pool can be reused
+    /// for subsequent solves, which avoids processing the available packages again.
+    pub fn solve_with_pool(
+        &mut self,
+        task: SolverTask<&SolverPool<'_>>,
+    ) -> Result<Vec<RepoDataRecord>, SolveError> {
+        let (removed_packages, locked_packages, virtual_packages) = prepare_task(&task);
+
+        let (specs, soft_specs) = task_specs(&task, &removed_packages);
+        let (records, _) = task.available_packages.solve_task(
+            specs,
+            soft_specs,
+            &locked_packages,
+            &virtual_packages,
+            |record| Cow::Owned(record.clone()),
+            |virtual_package| Cow::Owned(virtual_package.clone()),
+        )?;
+        Ok(records)
+    }
+}
+
 impl super::SolverImpl for Solver {
     type RepoData<'a> = RepoData<'a>;
 
@@ -341,56 +872,104 @@ impl super::SolverImpl for Solver {
         &mut self,
         task: SolverTask<TAvailablePackagesIterator>,
     ) -> Result<Vec<RepoDataRecord>, SolveError> {
-        // Construct a provider that can serve the data.
-        let provider = CondaDependencyProvider::from_solver_task(
-            task.available_packages.into_iter().map(|r| r.into()),
-            &task.locked_packages,
-            &task.pinned_packages,
-            &task.virtual_packages,
-        );
-
-        // Construct the requirements that the solver needs to satisfy.
-        let root_requirements = task
-            .specs
-            .into_iter()
-            .map(|spec| {
-                let (name, spec) = spec.into_nameless();
-                let name = name.expect("cannot use matchspec without a name");
-                let name_id = provider.pool.intern_package_name(name.as_normalized());
-                provider.pool.intern_version_set(name_id, spec.into())
-            })
-            .collect();
+        let (records, _) = self.solve_with_dropped_soft_specs(task)?;
+        Ok(records)
+    }
 
-        // Construct a solver and solve the problems in the queue
-        let mut solver = LibSolvRsSolver::new(provider);
-        let solvables = solver.solve(root_requirements).map_err(|problem| {
-            SolveError::Unsolvable(vec![problem
-                .display_user_friendly(&solver, &CondaSolvableDisplay)
-                .to_string()])
-        })?;
+    fn solve_with_dropped_soft_specs<
+        'a,
+        R: IntoRepoData<'a, Self::RepoData<'a>>,
+        TAvailablePackagesIterator: IntoIterator<Item = R>,
+    >(
+        &mut self,
+        task: SolverTask<TAvailablePackagesIterator>,
+    ) -> Result<(Vec<RepoDataRecord>, Vec<MatchSpec>), SolveError> {
+        let (available_packages, task) = task.split_available_packages();
+        let (removed_packages, locked_packages, virtual_packages) = prepare_task(&task);
+
+        // The pool is only used for this task so the records of the task can be borrowed.
+        let pool = SolverPool::new(
+            available_packages
+                .into_iter()
+                .map(|repodata| -> RepoData<'_> { repodata.into() }),
+        );
+        let (specs, soft_specs) = task_specs(&task, &removed_packages);
+        pool.solve_task(
+            specs,
+            soft_specs,
+            &locked_packages,
+            &virtual_packages,
+            Cow::Borrowed,
+            Cow::Borrowed,
+        )
+    }
+}
 
-        // Get the resulting packages from the solver.
-        let required_records = solvables
-            .into_iter()
-            .filter_map(|id| match solver.pool().resolve_solvable(id).inner() {
-                SolverPackageRecord::Record(rec) => Some(rec.deref().clone()),
-                SolverPackageRecord::VirtualPackage(_) => None,
-            })
-            .collect();
+/// Determines the removed and locked packages of a task and the virtual packages that are
+/// available to the solver.
+fn prepare_task<T>(
+    task: &SolverTask<T>,
+) -> (
+    RemovedPackages<'_>,
+    LockedPackages<'_>,
+    Vec<GenericVirtualPackage>,
+) {
+    // Determine which packages to remove from the environment
+    let removed_packages = RemovedPackages::new(
+        &task.remove_specs,
+        task.removal_strategy,
+        &task.locked_packages,
+    );
+
+    // Determine which locked packages to favor and which packages to pin
+    let locked_packages = LockedPackages::new(
+        &task.locked_packages,
+        &task.pinned_packages,
+        &task.update_strategy,
+        task.allow_downgrade,
+        &removed_packages,
+        &task.exclusions,
+    );
+
+    // Force-removed packages are replaced by virtual packages that stand in for them
+    let virtual_packages = task
+        .virtual_packages
+        .iter()
+        .chain(removed_packages.stand_ins.iter())
+        .cloned()
+        .collect();
+
+    (removed_packages, locked_packages, virtual_packages)
+}
 
-        Ok(required_records)
-    }
+/// Returns the specs and the soft specs of a task, without the specs of removed packages.
+fn task_specs<'t, T>(
+    task: &'t SolverTask<T>,
+    removed_packages: &RemovedPackages<'_>,
+) -> (Vec<&'t MatchSpec>, Vec<&'t MatchSpec>) {
+    let specs = task
+        .specs
+        .iter()
+        .chain(task.update_strategy.update_specs())
+        .filter(|spec| !removed_packages.is_removed_spec(spec))
+        .collect();
+    let soft_specs = task
+        .soft_specs
+        .iter()
+        .filter(|spec| !removed_packages.is_removed_spec(spec))
+        .collect();
+    (specs, soft_specs)
 }
 
-fn parse_match_spec<'a>(
-    pool: &Pool<SolverMatchSpec<'a>>,
-    spec_str: &'a str,
-    parse_match_spec_cache: &mut HashMap<&'a str, VersionSetId>,
+fn parse_match_spec(
+    pool: &Pool<SolverMatchSpec<'_>>,
+    spec_str: &str,
+    parse_match_spec_cache: &mut HashMap<String, VersionSetId>,
 ) -> Result<VersionSetId, ParseMatchSpecError> {
     Ok(match parse_match_spec_cache.get(spec_str) {
         Some(spec_id) => *spec_id,
         None => {
-            let match_spec = MatchSpec::from_str(spec_str)?;
+            let match_spec = MatchSpec::from_str(spec_str, ParseStrictness::Lenient)?;
             let (name, spec) = match_spec.into_nameless();
             let dependency_name = pool.intern_package_name(
                 name.as_ref()
@@ -398,7 +977,7 @@ fn parse_match_spec<'a>(
                     .as_normalized(),
             );
             let version_set_id = pool.intern_version_set(dependency_name, spec.into());
-            parse_match_spec_cache.insert(spec_str, version_set_id);
+            parse_match_spec_cache.insert(spec_str.to_owned(), version_set_id);
             version_set_id
         }
     })
@@ -0,0 +1,351 @@
+//! Provides [`SolverTaskSnapshot`], a self-contained serializable copy of a [`SolverTask`].
+
+use crate::{
+    dependency_names, PackageExclusion, RemovalStrategy, SolveError, SolverImpl, SolverTask,
+    UpdateStrategy,
+};
+use rattler_conda_types::{GenericVirtualPackage, MatchSpec, PackageName, RepoDataRecord};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+/// A self-contained copy of a [`SolverTask`] that can be stored on disk and solved again later.
+///
+/// A snapshot contains the specs of a task together with all the records the solver could
+/// possibly select, which makes it independent of the channels the task was created from. This is
+/// useful to reproduce a solve from a bug report, or to benchmark the solver backends with a real
+/// world task.
+///
+/// Instead of all the available packages, a snapshot only contains the records that are reachable
+/// from the specs, locked and pinned packages of the task. A record is reachable if its name is
+/// requested by one of the specs or if it is a dependency of another reachable record. The other
+/// records can never be selected by the solver so they do not affect the solution.
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SolverTaskSnapshot {
+    /// The reachable subset of the available packages of the task
+    pub available_packages: Vec<RepoDataRecord>,
+
+    /// See [`SolverTask::locked_packages`]
+    pub locked_packages: Vec<RepoDataRecord>,
+
+    /// See [`SolverTask::pinned_packages`]
+    pub pinned_packages: Vec<RepoDataRecord>,
+
+    /// See [`SolverTask::virtual_packages`]
+    pub virtual_packages: Vec<GenericVirtualPackage>,
+
+    /// See [`SolverTask::specs`]
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    pub specs: Vec<MatchSpec>,
+
+    /// See [`SolverTask::soft_specs`]
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    #[serde(default)]
+    pub soft_specs: Vec<MatchSpec>,
+
+    /// See [`SolverTask::update_strategy`]
+    #[serde(default)]
+    pub update_strategy: UpdateStrategy,
+
+    /// See [`SolverTask::allow_downgrade`]
+    #[serde(default = "default_allow_downgrade")]
+    pub allow_downgrade: bool,
+
+    /// See [`SolverTask::remove_specs`]
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    #[serde(default)]
+    pub remove_specs: Vec<MatchSpec>,
+
+    /// See [`SolverTask::removal_strategy`]
+    #[serde(default)]
+    pub removal_strategy: RemovalStrategy,
+
+    /// See [`SolverTask::exclusions`]
+    #[serde(default)]
+    pub exclusions: Vec<PackageExclusion>,
+}
+
+/// Downgrades are allowed unless a snapshot says otherwise, like in [`SolverTask::new`].
+fn default_allow_downgrade() -> bool {
+    true
+}
+
+/// An error that can occur when reading or writing a [`SolverTaskSnapshot`].
+#[derive(Debug, thiserror::Error)]
+pub enum SolverTaskSnapshotError {
+    /// An IO error occurred
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+
+    /// The snapshot could not be (de)serialized
+    #[error(transparent)]
+    SerializationError(#[from] serde_json::Error),
+}
+
+impl SolverTaskSnapshot {
+    /// Constructs a snapshot from a task. The available packages of the task are only iterated,
+    /// the task itself can still be solved afterwards.
+    pub fn from_task<'a, T, R>(task: &SolverTask<T>) -> Self
+    where
+        T: IntoIterator<Item = R> + Clone,
+        R: IntoIterator<Item = &'a RepoDataRecord>,
+    {
+        // Determine the names of all the packages the solver could possibly select
+        let mut reachable: HashSet<PackageName> = task
+            .specs
+            .iter()
+            .chain(task.soft_specs.iter())
+            .chain(task.update_strategy.update_specs())
+            .filter_map(|spec| spec.name.clone())
+            .chain(
+                task.locked_packages
+                    .iter()
+                    .chain(task.pinned_packages.iter())
+                    .flat_map(|record| {
+                        std::iter::once(record.package_record.name.clone())
+                            .chain(dependency_names(record))
+                    }),
+            )
+            .collect();
+
+        let available_packages = task
+            .available_packages
+            .clone()
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        let mut records_by_name: HashMap<&PackageName, Vec<&RepoDataRecord>> = HashMap::new();
+        for &record in available_packages.iter() {
+            records_by_name
+                .entry(&record.package_record.name)
+                .or_default()
+                .push(record);
+        }
+
+        let mut queue = reachable.iter().cloned().collect::<Vec<_>>();
+        while let Some(name) = queue.pop() {
+            for &record in records_by_name.get(&name).into_iter().flatten() {
+                for dependency in dependency_names(record) {
+                    if reachable.insert(dependency.clone()) {
+                        queue.push(dependency);
+                    }
+                }
+            }
+        }
+
+        Self {
+            available_packages: available_packages
+                .into_iter()
+                .filter(|record| reachable.contains(&record.package_record.name))
+                .cloned()
+                .collect(),
+            locked_packages: task.locked_packages.clone(),
+            pinned_packages: task.pinned_packages.clone(),
+            virtual_packages: task.virtual_packages.clone(),
+            specs: task.specs.clone(),
+            soft_specs: task.soft_specs.clone(),
+            update_strategy: task.update_strategy.clone(),
+            allow_downgrade: task.allow_downgrade,
+            remove_specs: task.remove_specs.clone(),
+            removal_strategy: task.removal_strategy,
+            exclusions: task.exclusions.clone(),
+        }
+    }
+
+    /// Returns a task that can be solved by any of the solver backends.
+    pub fn task(&self) -> SolverTask<[&Vec<RepoDataRecord>; 1]> {
+        SolverTask {
+            available_packages: [&self.available_packages],
+            locked_packages: self.locked_packages.clone(),
+            pinned_packages: self.pinned_packages.clone(),
+            virtual_packages: self.virtual_packages.clone(),
+            specs: self.specs.clone(),
+            soft_specs: self.soft_specs.clone(),
+            update_strategy: self.update_strategy.clone(),
+            allow_downgrade: self.allow_downgrade,
+            remove_specs: self.remove_specs.clone(),
+            removal_strategy: self.removal_strategy,
+            exclusions: self.exclusions.clone(),
+        }
+    }
+
+    /// Solves the task of the snapshot with the solver backend `S`.
+    pub fn solve<S: SolverImpl + Default>(&self) -> Result<Vec<RepoDataRecord>, SolveError> {
+        S::default().solve(self.task())
+    }
+
+    /// Reads a snapshot from a reader. The reader must contain a snapshot in JSON format.
+    pub fn from_reader(mut reader: impl Read) -> Result<Self, SolverTaskSnapshotError> {
+        // Records can only be deserialized from borrowed data, so read everything first
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    /// Writes the snapshot in JSON format to a writer.
+    pub fn to_writer(&self, writer: impl Write) -> Result<(), SolverTaskSnapshotError> {
+        Ok(serde_json::to_writer(writer, self)?)
+    }
+
+    /// Reads a snapshot from a file. Files with the `.zst` extension are decompressed with zstd.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, SolverTaskSnapshotError> {
+        let path = path.as_ref();
+        let reader = BufReader::new(File::open(path)?);
+        if is_zstd_path(path) {
+            Self::from_reader(zstd::Decoder::new(reader)?)
+        } else {
+            Self::from_reader(reader)
+        }
+    }
+
+    /// Writes the snapshot to a file. Files with the `.zst` extension are compressed with zstd.
+    pub fn to_path(&self, path: impl AsRef<Path>) -> Result<(), SolverTaskSnapshotError> {
+        let path = path.as_ref();
+        let mut writer = BufWriter::new(File::create(path)?);
+        if is_zstd_path(path) {
+            let mut encoder = zstd::Encoder::new(writer, 0)?;
+            self.to_writer(&mut encoder)?;
+            writer = encoder.finish()?;
+        } else {
+            self.to_writer(&mut writer)?;
+        }
+        Ok(writer.flush()?)
+    }
+}
+
+/// Returns true if the file at the specified path should be compressed with zstd.
+fn is_zstd_path(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "zst")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rattler_conda_types::{PackageRecord, ParseStrictness, Version};
+    use std::str::FromStr;
+
+    fn record(name: &str, version: &str, depends: &[&str]) -> RepoDataRecord {
+        let mut package_record = PackageRecord::new(
+            PackageName::new_unchecked(name),
+            Version::from_str(version).unwrap(),
+            "0".to_string(),
+        );
+        package_record.depends = depends.iter().map(|s| s.to_string()).collect();
+        RepoDataRecord {
+            url: format!(
+                "https://conda.anaconda.org/conda-forge/linux-64/{name}-{version}-0.tar.bz2"
+            )
+            .parse()
+            .unwrap(),
+            channel: "https://conda.anaconda.org/conda-forge/".to_string(),
+            file_name: format!("{name}-{version}-0.tar.bz2"),
+            package_record,
+        }
+    }
+
+    #[test]
+    fn test_reachable_records() {
+        let repo_data = vec![
+            record("foo", "1.0", &["bar >=1"]),
+            record("bar", "1.0", &[]),
+            record("bar", "2.0", &["baz"]),
+            record("baz", "1.0", &[]),
+            record("unrelated", "1.0", &["foo"]),
+            record("locked-dep", "1.0", &[]),
+        ];
+        let task = SolverTask {
+            locked_packages: vec![record("locked", "1.0", &["locked-dep"])],
+            specs: vec![MatchSpec::from_str("foo", ParseStrictness::Strict).unwrap()],
+            ..SolverTask::new([&repo_data])
+        };
+
+        let snapshot = SolverTaskSnapshot::from_task(&task);
+        let mut names = snapshot
+            .available_packages
+            .iter()
+            .map(|record| record.package_record.to_string())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                "bar=1.0=0",
+                "bar=2.0=0",
+                "baz=1.0=0",
+                "foo=1.0=0",
+                "locked-dep=1.0=0"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let repo_data = vec![record("foo", "1.0", &["bar"]), record("bar", "1.0", &[])];
+        let task = SolverTask {
+            locked_packages: vec![repo_data[1].clone()],
+            virtual_packages: vec![GenericVirtualPackage {
+                name: PackageName::new_unchecked("__unix"),
+                version: Version::from_str("0").unwrap(),
+                build_string: "0".to_string(),
+            }],
+            specs: vec![MatchSpec::from_str("foo >=1.0", ParseStrictness::Strict).unwrap()],
+            soft_specs: vec![MatchSpec::from_str("bar 1.*", ParseStrictness::Strict).unwrap()],
+            exclusions: vec![
+                PackageExclusion::MatchSpec(
+                    MatchSpec::from_str("foo 2.*", ParseStrictness::Strict).unwrap(),
+                ),
+                PackageExclusion::Url(repo_data[0].url.clone()),
+            ],
+            update_strategy: UpdateStrategy::Update(vec![MatchSpec::from_str(
+                "bar",
+                ParseStrictness::Strict,
+            )
+            .unwrap()]),
+            allow_downgrade: false,
+            removal_strategy: RemovalStrategy::Force,
+            ..SolverTask::new([&repo_data])
+        };
+        let snapshot = SolverTaskSnapshot::from_task(&task);
+
+        let dir = tempfile::tempdir().unwrap();
+        for file_name in ["snapshot.json", "snapshot.json.zst"] {
+            let path = dir.path().join(file_name);
+            snapshot.to_path(&path).unwrap();
+            let loaded = SolverTaskSnapshot::from_path(&path).unwrap();
+
+            assert_eq!(loaded.available_packages, snapshot.available_packages);
+            assert_eq!(loaded.locked_packages, snapshot.locked_packages);
+            assert_eq!(loaded.virtual_packages, snapshot.virtual_packages);
+            assert_eq!(loaded.specs, snapshot.specs);
+            assert_eq!(loaded.soft_specs, snapshot.soft_specs);
+            assert_eq!(loaded.update_strategy, snapshot.update_strategy);
+            assert_eq!(loaded.allow_downgrade, snapshot.allow_downgrade);
+            assert_eq!(loaded.removal_strategy, snapshot.removal_strategy);
+            assert_eq!(loaded.exclusions, snapshot.exclusions);
+        }
+    }
+
+    #[test]
+    fn test_optional_fields() {
+        let snapshot = SolverTaskSnapshot::from_reader(
+            r#"{
+                "available_packages": [],
+                "locked_packages": [],
+                "pinned_packages": [],
+                "virtual_packages": [],
+                "specs": ["foo"]
+            }"#
+            .as_bytes(),
+        )
+        .unwrap();
+        assert!(snapshot.allow_downgrade);
+        assert!(snapshot.soft_specs.is_empty());
+        assert!(snapshot.exclusions.is_empty());
+    }
+}
@@ -0,0 +1,221 @@
+//! Provides the [`SolutionGraph`] which describes why each record is part of a solution.
+
+use rattler_conda_types::{MatchSpec, PackageName, ParseStrictness, RepoDataRecord};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+/// Describes why the records of a solution were selected by the solver.
+///
+/// Every record in the solution is either requested directly through one of the specs of the
+/// [`crate::SolverTask`], or it is a dependency of another record in the solution (or both). The
+/// graph can be used to answer questions like "why is openssl 1.1 in my environment?", see
+/// [`SolutionGraph::shortest_path`].
+#[derive(Debug, Clone)]
+pub struct SolutionGraph {
+    /// The specs that were requested
+    specs: Vec<MatchSpec>,
+
+    /// The records of the solution
+    records: Vec<RepoDataRecord>,
+
+    /// For every record the indices of the specs that requested it
+    requested_by: Vec<Vec<usize>>,
+
+    /// For every record the indices of the records that depend on it, together with the
+    /// dependency that was matched.
+    parents: Vec<Vec<(usize, String)>>,
+
+    /// Maps a package name to the index of its record
+    by_name: HashMap<String, usize>,
+}
+
+/// The reason why a record is part of a solution.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SolutionReason<'a> {
+    /// The record was requested through a spec of the task
+    Requested(&'a MatchSpec),
+
+    /// The record is a dependency of another record
+    Dependency {
+        /// The record that depends on the record
+        parent: &'a RepoDataRecord,
+
+        /// The dependency of the parent that matched the record
+        dependency: &'a str,
+    },
+}
+
+/// A chain of records that explains why a record is part of a solution. The chain starts at a
+/// requested spec and ends at the record, each step is the dependency that pulled in the next
+/// record.
+///
+/// The [`fmt::Display`] implementation formats the chain as
+/// `spec -> record -> dependency -> record -> ...`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DependencyPath<'a> {
+    /// The requested spec the chain starts with
+    pub spec: &'a MatchSpec,
+
+    /// The record that was selected for the requested spec
+    pub root: &'a RepoDataRecord,
+
+    /// The dependencies in the chain, each together with the record that was selected for it. The
+    /// last record is the record that is explained by this path.
+    pub steps: Vec<(&'a str, &'a RepoDataRecord)>,
+}
+
+impl<'a> DependencyPath<'a> {
+    /// Returns the record that is explained by this path.
+    pub fn target(&self) -> &'a RepoDataRecord {
+        self.steps.last().map_or(self.root, |(_, record)| record)
+    }
+}
+
+impl fmt::Display for DependencyPath<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} -> {}", self.spec, self.root.package_record)?;
+        for (dependency, record) in self.steps.iter() {
+            write!(f, " -> {} -> {}", dependency, record.package_record)?;
+        }
+        Ok(())
+    }
+}
+
+impl SolutionGraph {
+    /// Constructs a graph from the specs that the solver satisfied and the records it returned.
+    /// Specs that the solver ignored, like the specs of removed packages or dropped soft specs,
+    /// should not be passed because they would show up as reasons for the records they match.
+    ///
+    /// The graph is reconstructed from the solution, not from the internal decisions of the
+    /// solver. The dependencies of every record are matched against the other records in the
+    /// solution. Because a solution contains at most one record per package name, this is the
+    /// record that the solver selected for the dependency. Dependencies that are not matched by any
+    /// record (e.g. dependencies on virtual packages) are not part of the graph. Constraints
+    /// (`constrains`) never result in edges.
+    pub fn new(specs: Vec<MatchSpec>, records: Vec<RepoDataRecord>) -> Self {
+        let by_name = records
+            .iter()
+            .enumerate()
+            .map(|(idx, record)| (record.package_record.name.as_normalized().to_owned(), idx))
+            .collect::<HashMap<_, _>>();
+
+        let requested_by = records
+            .iter()
+            .map(|record| {
+                specs
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, spec)| spec.matches_record(record))
+                    .map(|(idx, _)| idx)
+                    .collect()
+            })
+            .collect();
+
+        let mut parents = vec![Vec::new(); records.len()];
+        for (parent_idx, record) in records.iter().enumerate() {
+            for dependency in record.package_record.depends.iter() {
+                let Ok(spec) = MatchSpec::from_str(dependency, ParseStrictness::Lenient) else {
+                    continue;
+                };
+                let Some(&child_idx) = spec
+                    .name
+                    .as_ref()
+                    .and_then(|name| by_name.get(name.as_normalized()))
+                else {
+                    continue;
+                };
+                if spec.matches_record(&records[child_idx]) {
+                    parents[child_idx].push((parent_idx, dependency.clone()));
+                }
+            }
+        }
+
+        Self {
+            specs,
+            records,
+            requested_by,
+            parents,
+            by_name,
+        }
+    }
+
+    /// Returns the specs from which the records of the solution were reached.
+    pub fn specs(&self) -> &[MatchSpec] {
+        &self.specs
+    }
+
+    /// Returns the records of the solution.
+    pub fn records(&self) -> &[RepoDataRecord] {
+        &self.records
+    }
+
+    /// Consumes the graph and returns the records of the solution.
+    pub fn into_records(self) -> Vec<RepoDataRecord> {
+        self.records
+    }
+
+    /// Returns the record of the package with the given name, if it is part of the solution.
+    pub fn record(&self, name: &PackageName) -> Option<&RepoDataRecord> {
+        self.by_name
+            .get(name.as_normalized())
+            .map(|&idx| &self.records[idx])
+    }
+
+    /// Returns all the reasons why the package with the given name is part of the solution. The
+    /// iterator is empty if the package is not part of the solution.
+    pub fn reasons(&self, name: &PackageName) -> impl Iterator<Item = SolutionReason<'_>> + '_ {
+        let idx = self.by_name.get(name.as_normalized()).copied();
+        let requested = idx
+            .into_iter()
+            .flat_map(|idx| self.requested_by[idx].iter())
+            .map(|&spec_idx| SolutionReason::Requested(&self.specs[spec_idx]));
+        let dependencies = idx
+            .into_iter()
+            .flat_map(|idx| self.parents[idx].iter())
+            .map(|(parent_idx, dependency)| SolutionReason::Dependency {
+                parent: &self.records[*parent_idx],
+                dependency,
+            });
+        requested.chain(dependencies)
+    }
+
+    /// Returns the shortest chain of dependencies from a requested spec to the package with the
+    /// given name. Returns `None` if the package is not part of the solution or if it cannot be
+    /// reached from any of the requested specs.
+    pub fn shortest_path(&self, name: &PackageName) -> Option<DependencyPath<'_>> {
+        let target = *self.by_name.get(name.as_normalized())?;
+
+        // Walk the graph backwards from the target until a requested record is found. For every
+        // visited record we store the edge through which it was reached.
+        let mut reached_through: Vec<Option<(usize, &str)>> = vec![None; self.records.len()];
+        let mut visited = vec![false; self.records.len()];
+        let mut queue = VecDeque::from([target]);
+        visited[target] = true;
+        while let Some(idx) = queue.pop_front() {
+            if let Some(&spec_idx) = self.requested_by[idx].first() {
+                // Reconstruct the path by following the edges back to the target
+                let mut steps = Vec::new();
+                let mut current = idx;
+                while let Some((child, dependency)) = reached_through[current] {
+                    steps.push((dependency, &self.records[child]));
+                    current = child;
+                }
+                return Some(DependencyPath {
+                    spec: &self.specs[spec_idx],
+                    root: &self.records[idx],
+                    steps,
+                });
+            }
+
+            for (parent_idx, dependency) in self.parents[idx].iter() {
+                if !visited[*parent_idx] {
+                    visited[*parent_idx] = true;
+                    reached_through[*parent_idx] = Some((idx, dependency));
+                    queue.push_back(*parent_idx);
+                }
+            }
+        }
+
+        None
+    }
+}
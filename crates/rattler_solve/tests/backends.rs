@@ -1,10 +1,13 @@
 use once_cell::sync::Lazy;
 use rattler_conda_types::{
-    Channel, ChannelConfig, GenericVirtualPackage, MatchSpec, NoArchType, PackageRecord, RepoData,
-    RepoDataRecord, Version,
+    Channel, ChannelConfig, GenericVirtualPackage, MatchSpec, NoArchType, PackageName,
+    PackageRecord, ParseStrictness, RepoData, RepoDataRecord, Version,
 };
 use rattler_repodata_gateway::sparse::SparseRepoData;
-use rattler_solve::{SolveError, SolverImpl, SolverTask};
+use rattler_solve::{
+    MultiPlatformSolverTask, PackageExclusion, PlatformSolverTask, RemovalStrategy, SolutionReason,
+    SolveError, SolverImpl, SolverTask, SolverTaskSnapshot, UpdateStrategy,
+};
 use std::str::FromStr;
 use std::time::Instant;
 use url::Url;
@@ -100,10 +103,194 @@ fn installed_package(
     }
 }
 
+fn dummy_package(
+    subdir: &str,
+    name: &str,
+    version: &str,
+    build: &str,
+    depends: &[&str],
+) -> RepoDataRecord {
+    let file_name = format!("{name}-{version}-{build}.tar.bz2");
+    let mut record = installed_package("conda-forge", subdir, name, version, build, 0);
+    record.url = Url::parse(&format!(
+        "https://conda.anaconda.org/conda-forge/{subdir}/{file_name}"
+    ))
+    .unwrap();
+    record.file_name = file_name;
+    record.package_record.depends = depends.iter().map(|s| s.to_string()).collect();
+    record
+}
+
+fn dummy_virtual_package(name: &str, version: &str) -> GenericVirtualPackage {
+    GenericVirtualPackage {
+        name: rattler_conda_types::PackageName::new_unchecked(name),
+        version: Version::from_str(version).unwrap(),
+        build_string: "0".to_string(),
+    }
+}
+
+fn dummy_update_repo() -> Vec<RepoDataRecord> {
+    vec![
+        dummy_package("linux-64", "foo", "1.0", "0", &["bar"]),
+        dummy_package("linux-64", "foo", "2.0", "0", &["bar"]),
+        dummy_package("linux-64", "bar", "1.0", "0", &[]),
+        dummy_package("linux-64", "bar", "2.0", "0", &[]),
+        dummy_package("linux-64", "baz", "1.0", "0", &[]),
+    ]
+}
+
+fn dummy_removal_repo() -> Vec<RepoDataRecord> {
+    vec![
+        dummy_package("linux-64", "app", "1.0", "0", &["lib"]),
+        dummy_package("linux-64", "lib", "1.0", "0", &["dep"]),
+        dummy_package("linux-64", "dep", "1.0", "0", &[]),
+        dummy_package("linux-64", "tool", "1.0", "0", &[]),
+        dummy_package("linux-64", "plugin", "1.0", "0", &["app"]),
+    ]
+}
+
+/// Removes packages from an environment that contains all the packages of the removal repo, only
+/// `app`, `plugin` and `tool` were explicitly requested.
+fn solve_with_removal<T: SolverImpl + Default>(
+    remove_specs: &[&str],
+    removal_strategy: RemovalStrategy,
+) -> Result<Vec<String>, SolveError> {
+    let repo_data = dummy_removal_repo();
+
+    let task = SolverTask {
+        locked_packages: repo_data.clone(),
+        specs: ["app", "plugin", "tool"]
+            .iter()
+            .map(|s| MatchSpec::from_str(s, ParseStrictness::Strict).unwrap())
+            .collect(),
+        remove_specs: remove_specs
+            .iter()
+            .map(|s| MatchSpec::from_str(s, ParseStrictness::Strict).unwrap())
+            .collect(),
+        removal_strategy,
+        ..SolverTask::new([&repo_data])
+    };
+
+    let mut records = T::default()
+        .solve(task)?
+        .into_iter()
+        .map(|r| r.package_record.to_string())
+        .collect::<Vec<_>>();
+    records.sort();
+    Ok(records)
+}
+
+/// Solves the specs and soft specs against the update repo with the specified exclusions, returns
+/// the records of the solution and the soft specs that were dropped.
+fn solve_with_soft_specs<T: SolverImpl + Default>(
+    specs: &[&str],
+    soft_specs: &[&str],
+    exclusions: Vec<PackageExclusion>,
+) -> Result<(Vec<String>, Vec<String>), SolveError> {
+    let repo_data = dummy_update_repo();
+    let parse_specs = |specs: &[&str]| {
+        specs
+            .iter()
+            .map(|s| MatchSpec::from_str(s, ParseStrictness::Strict).unwrap())
+            .collect()
+    };
+
+    let task = SolverTask {
+        specs: parse_specs(specs),
+        soft_specs: parse_specs(soft_specs),
+        exclusions,
+        ..SolverTask::new([&repo_data])
+    };
+
+    let (records, dropped_soft_specs) = T::default().solve_with_dropped_soft_specs(task)?;
+    let mut records = records
+        .into_iter()
+        .map(|r| r.package_record.to_string())
+        .collect::<Vec<_>>();
+    records.sort();
+    Ok((
+        records,
+        dropped_soft_specs.iter().map(|s| s.to_string()).collect(),
+    ))
+}
+
+/// Solves the specs against the update repo with the specified exclusions, the update repo
+/// records are locked if `locked` is true.
+fn solve_with_exclusions<T: SolverImpl + Default>(
+    specs: &[&str],
+    exclusions: Vec<PackageExclusion>,
+    locked: bool,
+) -> Result<Vec<String>, SolveError> {
+    let repo_data = dummy_update_repo();
+
+    let task = SolverTask {
+        locked_packages: if locked {
+            repo_data.clone()
+        } else {
+            Vec::new()
+        },
+        specs: specs
+            .iter()
+            .map(|s| MatchSpec::from_str(s, ParseStrictness::Strict).unwrap())
+            .collect(),
+        exclusions,
+        ..SolverTask::new([&repo_data])
+    };
+
+    let mut records = T::default()
+        .solve(task)?
+        .into_iter()
+        .map(|r| r.package_record.to_string())
+        .collect::<Vec<_>>();
+    records.sort();
+    Ok(records)
+}
+
+fn solve_with_update_strategy<T: SolverImpl + Default>(
+    locked: &[(&str, &str)],
+    specs: &[&str],
+    update_strategy: UpdateStrategy,
+    allow_downgrade: bool,
+) -> Result<Vec<String>, SolveError> {
+    let repo_data = dummy_update_repo();
+    let locked_packages = locked
+        .iter()
+        .map(|(name, version)| {
+            repo_data
+                .iter()
+                .find(|r| {
+                    r.package_record.name.as_normalized() == *name
+                        && r.package_record.version.to_string() == *version
+                })
+                .unwrap()
+                .clone()
+        })
+        .collect();
+
+    let task = SolverTask {
+        locked_packages,
+        specs: specs
+            .iter()
+            .map(|s| MatchSpec::from_str(s, ParseStrictness::Strict).unwrap())
+            .collect(),
+        update_strategy,
+        allow_downgrade,
+        ..SolverTask::new([&repo_data])
+    };
+
+    let mut records = T::default()
+        .solve(task)?
+        .into_iter()
+        .map(|r| r.package_record.to_string())
+        .collect::<Vec<_>>();
+    records.sort();
+    Ok(records)
+}
+
 fn solve_real_world<T: SolverImpl + Default>(specs: Vec<&str>) -> Vec<String> {
     let specs = specs
         .iter()
-        .map(|s| MatchSpec::from_str(s).unwrap())
+        .map(|s| MatchSpec::from_str(s, ParseStrictness::Strict).unwrap())
         .collect::<Vec<_>>();
 
     let sparse_repo_datas = read_real_world_repo_data();
@@ -113,11 +300,8 @@ fn solve_real_world<T: SolverImpl + Default>(specs: Vec<&str>) -> Vec<String> {
         SparseRepoData::load_records_recursive(sparse_repo_datas, names, None, true).unwrap();
 
     let solver_task = SolverTask {
-        available_packages: &available_packages,
         specs: specs.clone(),
-        locked_packages: Default::default(),
-        pinned_packages: Default::default(),
-        virtual_packages: Default::default(),
+        ..SolverTask::new(&available_packages)
     };
 
     let pkgs1 = match T::default().solve(solver_task) {
@@ -424,6 +608,405 @@ macro_rules! solver_backend_tests {
             assert_eq!("3.0.2", &info.package_record.version.to_string());
         }
 
+        #[test]
+        fn test_solve_multi_platform() {
+            let noarch = vec![dummy_package("noarch", "bar", "1.0", "0", &[])];
+            let linux = vec![
+                dummy_package("linux-64", "foo", "1.0", "0", &["__glibc >=2.17", "bar"]),
+                dummy_package("linux-64", "foo", "2.0", "0", &["__glibc >=2.28", "bar"]),
+            ];
+            let win = vec![dummy_package(
+                "win-64",
+                "foo",
+                "1.0",
+                "0",
+                &["__win", "bar"],
+            )];
+
+            let solution = MultiPlatformSolverTask {
+                specs: vec![MatchSpec::from_str("foo", ParseStrictness::Strict).unwrap()],
+                soft_specs: Vec::new(),
+                exclusions: Vec::new(),
+                platforms: vec![
+                    PlatformSolverTask {
+                        platform: rattler_conda_types::Platform::Linux64,
+                        available_packages: vec![&linux, &noarch],
+                        locked_packages: Vec::new(),
+                        pinned_packages: Vec::new(),
+                        virtual_packages: vec![dummy_virtual_package("__glibc", "2.17")],
+                    },
+                    PlatformSolverTask {
+                        platform: rattler_conda_types::Platform::Win64,
+                        available_packages: vec![&win, &noarch],
+                        locked_packages: Vec::new(),
+                        pinned_packages: Vec::new(),
+                        virtual_packages: vec![dummy_virtual_package("__win", "0")],
+                    },
+                    PlatformSolverTask {
+                        platform: rattler_conda_types::Platform::Osx64,
+                        available_packages: vec![&noarch],
+                        locked_packages: Vec::new(),
+                        pinned_packages: Vec::new(),
+                        virtual_packages: Vec::new(),
+                    },
+                ],
+                update_strategy: Default::default(),
+                allow_downgrade: true,
+                remove_specs: Vec::new(),
+                removal_strategy: Default::default(),
+            }
+            .solve::<$T>();
+
+            assert_eq!(solution.len(), 3);
+
+            let extract = |platform| {
+                let mut records = solution[&platform]
+                    .as_ref()
+                    .unwrap()
+                    .iter()
+                    .map(|r| r.package_record.to_string())
+                    .collect::<Vec<_>>();
+                records.sort();
+                records
+            };
+            assert_eq!(
+                extract(rattler_conda_types::Platform::Linux64),
+                vec!["bar=1.0=0", "foo=1.0=0"]
+            );
+            assert_eq!(
+                extract(rattler_conda_types::Platform::Win64),
+                vec!["bar=1.0=0", "foo=1.0=0"]
+            );
+            assert!(matches!(
+                solution[&rattler_conda_types::Platform::Osx64],
+                Err(SolveError::Unsolvable(_))
+            ));
+        }
+
+        #[test]
+        fn test_solve_update_strategy_favor_locked() {
+            let result = solve_with_update_strategy::<$T>(
+                &[("foo", "1.0"), ("bar", "1.0")],
+                &["foo"],
+                UpdateStrategy::FavorLocked,
+                true,
+            )
+            .unwrap();
+            assert_eq!(result, vec!["bar=1.0=0", "foo=1.0=0"]);
+        }
+
+        #[test]
+        fn test_solve_update_strategy_update_single() {
+            let result = solve_with_update_strategy::<$T>(
+                &[("foo", "1.0"), ("bar", "1.0")],
+                &["foo"],
+                UpdateStrategy::Update(vec![MatchSpec::from_str("bar", ParseStrictness::Strict).unwrap()]),
+                true,
+            )
+            .unwrap();
+            assert_eq!(result, vec!["bar=2.0=0", "foo=1.0=0"]);
+        }
+
+        #[test]
+        fn test_solve_update_strategy_update_with_dependencies() {
+            let result = solve_with_update_strategy::<$T>(
+                &[("foo", "1.0"), ("bar", "1.0"), ("baz", "1.0")],
+                &["foo", "baz"],
+                UpdateStrategy::Update(vec![MatchSpec::from_str("foo", ParseStrictness::Strict).unwrap()]),
+                true,
+            )
+            .unwrap();
+            assert_eq!(result, vec!["bar=2.0=0", "baz=1.0=0", "foo=2.0=0"]);
+        }
+
+        #[test]
+        fn test_solve_update_strategy_update_all() {
+            let result = solve_with_update_strategy::<$T>(
+                &[("foo", "1.0"), ("bar", "1.0")],
+                &["foo"],
+                UpdateStrategy::UpdateAll,
+                true,
+            )
+            .unwrap();
+            assert_eq!(result, vec!["bar=2.0=0", "foo=2.0=0"]);
+        }
+
+        #[test]
+        fn test_solve_update_strategy_freeze_locked() {
+            let result = solve_with_update_strategy::<$T>(
+                &[("foo", "1.0"), ("bar", "1.0")],
+                &["foo", "baz"],
+                UpdateStrategy::FreezeLocked,
+                true,
+            )
+            .unwrap();
+            assert_eq!(result, vec!["bar=1.0=0", "baz=1.0=0", "foo=1.0=0"]);
+
+            let result = solve_with_update_strategy::<$T>(
+                &[("foo", "1.0"), ("bar", "1.0")],
+                &["foo>=2"],
+                UpdateStrategy::FreezeLocked,
+                true,
+            );
+            assert!(matches!(result, Err(SolveError::Unsolvable(_))));
+        }
+
+        #[test]
+        fn test_solve_disallow_downgrade() {
+            let result = solve_with_update_strategy::<$T>(
+                &[("foo", "2.0"), ("bar", "2.0")],
+                &["foo<2"],
+                UpdateStrategy::FavorLocked,
+                true,
+            )
+            .unwrap();
+            assert_eq!(result, vec!["bar=2.0=0", "foo=1.0=0"]);
+
+            let result = solve_with_update_strategy::<$T>(
+                &[("foo", "2.0"), ("bar", "2.0")],
+                &["foo<2"],
+                UpdateStrategy::FavorLocked,
+                false,
+            );
+            assert!(matches!(result, Err(SolveError::Unsolvable(_))));
+        }
+
+        #[test]
+        fn test_solve_remove_cascade() {
+            // Removing `lib` also removes `app` and `plugin` which depend on it, `dep` is no
+            // longer required by anything.
+            let result = solve_with_removal::<$T>(&["lib"], RemovalStrategy::Cascade).unwrap();
+            assert_eq!(result, vec!["tool=1.0=0"]);
+
+            // Removing `app` also removes `plugin`, `lib` and `dep` were only installed as
+            // dependencies of `app`.
+            let result = solve_with_removal::<$T>(&["app"], RemovalStrategy::Cascade).unwrap();
+            assert_eq!(result, vec!["tool=1.0=0"]);
+        }
+
+        #[test]
+        fn test_solve_remove_force() {
+            // Only `lib` is removed, `dep` is no longer required by anything.
+            let result = solve_with_removal::<$T>(&["lib"], RemovalStrategy::Force).unwrap();
+            assert_eq!(result, vec!["app=1.0=0", "plugin=1.0=0", "tool=1.0=0"]);
+        }
+
+        #[test]
+        fn test_solve_remove_not_installed() {
+            let result = solve_with_removal::<$T>(&["lib>=2"], RemovalStrategy::Cascade).unwrap();
+            assert_eq!(
+                result,
+                vec![
+                    "app=1.0=0",
+                    "dep=1.0=0",
+                    "lib=1.0=0",
+                    "plugin=1.0=0",
+                    "tool=1.0=0"
+                ]
+            );
+        }
+
+        #[test]
+        fn test_solve_with_graph() {
+            let repo_data = dummy_removal_repo();
+            let solve = |specs: &[&str]| {
+                let task = SolverTask {
+                    specs: specs
+                        .iter()
+                        .map(|s| MatchSpec::from_str(s, ParseStrictness::Strict).unwrap())
+                        .collect(),
+                    ..SolverTask::new([&repo_data])
+                };
+                <$T>::default().solve_with_graph(task).unwrap()
+            };
+
+            let graph = solve(&["plugin", "tool"]);
+            assert_eq!(graph.records().len(), 5);
+
+            let dep = PackageName::new_unchecked("dep");
+            assert_eq!(
+                graph.shortest_path(&dep).unwrap().to_string(),
+                "plugin -> plugin=1.0=0 -> app -> app=1.0=0 -> lib -> lib=1.0=0 -> dep -> dep=1.0=0"
+            );
+
+            let app = PackageName::new_unchecked("app");
+            let reasons = graph.reasons(&app).collect::<Vec<_>>();
+            assert!(matches!(
+                reasons.as_slice(),
+                [SolutionReason::Dependency { parent, dependency: "app" }]
+                    if parent.package_record.name.as_normalized() == "plugin"
+            ));
+
+            // Requesting `app` directly results in a shorter path
+            let graph = solve(&["plugin", "app"]);
+            assert_eq!(
+                graph.shortest_path(&dep).unwrap().to_string(),
+                "app -> app=1.0=0 -> lib -> lib=1.0=0 -> dep -> dep=1.0=0"
+            );
+            assert_eq!(
+                graph.shortest_path(&app).unwrap().to_string(),
+                "app -> app=1.0=0"
+            );
+            assert!(graph
+                .shortest_path(&PackageName::new_unchecked("tool"))
+                .is_none());
+
+            // Specs of removed packages are not part of the graph
+            let task = SolverTask {
+                locked_packages: repo_data.clone(),
+                specs: ["app", "plugin", "tool"]
+                    .iter()
+                    .map(|s| MatchSpec::from_str(s, ParseStrictness::Strict).unwrap())
+                    .collect(),
+                remove_specs: vec![MatchSpec::from_str("app", ParseStrictness::Strict).unwrap()],
+                ..SolverTask::new([&repo_data])
+            };
+            let graph = <$T>::default().solve_with_graph(task).unwrap();
+            assert_eq!(
+                graph.specs().iter().map(ToString::to_string).collect::<Vec<_>>(),
+                vec!["tool"]
+            );
+            assert_eq!(graph.records().len(), 1);
+            assert_eq!(graph.reasons(&app).count(), 0);
+        }
+
+        #[test]
+        fn test_solve_soft_specs() {
+            // Soft specs are satisfied if possible, also if that means installing a package
+            let (records, dropped) = solve_with_soft_specs::<$T>(&["foo"], &["bar<2", "baz"], Vec::new()).unwrap();
+            assert_eq!(records, vec!["bar=1.0=0", "baz=1.0=0", "foo=2.0=0"]);
+            assert!(dropped.is_empty());
+        }
+
+        #[test]
+        fn test_solve_soft_specs_dropped() {
+            // The soft spec for bar conflicts with the specs and is dropped, the other soft spec is
+            // still satisfied.
+            let (records, dropped) =
+                solve_with_soft_specs::<$T>(&["foo", "bar>=2"], &["bar<2", "baz"], Vec::new()).unwrap();
+            assert_eq!(records, vec!["bar=2.0=0", "baz=1.0=0", "foo=2.0=0"]);
+            assert_eq!(dropped, vec!["bar <2"]);
+
+            // Soft specs never make the task unsolvable, but the specs still can
+            assert!(solve_with_soft_specs::<$T>(&["foo", "bar>=3"], &["baz"], Vec::new()).is_err());
+
+            // A soft spec that can only be satisfied by excluded records is dropped as well
+            let exclusions = vec![PackageExclusion::MatchSpec(
+                MatchSpec::from_str("foo 2.*", ParseStrictness::Strict).unwrap(),
+            )];
+            let (records, dropped) =
+                solve_with_soft_specs::<$T>(&["bar"], &["foo>=2", "baz"], exclusions).unwrap();
+            assert_eq!(records, vec!["bar=2.0=0", "baz=1.0=0"]);
+            assert_eq!(dropped, vec!["foo >=2"]);
+        }
+
+        #[test]
+        fn test_solve_channel_specific() {
+            // Both channels provide foo, the spec determines which channel it is selected from
+            let conda_forge = vec![dummy_package("linux-64", "foo", "2.0", "0", &[])];
+            let bioconda = vec![{
+                let mut record = dummy_package("linux-64", "foo", "1.0", "0", &[]);
+                record.channel = String::from("https://conda.anaconda.org/bioconda/");
+                record.url = Url::parse(
+                    "https://conda.anaconda.org/bioconda/linux-64/foo-1.0-0.tar.bz2",
+                )
+                .unwrap();
+                record
+            }];
+
+            let solve = |spec: &str| {
+                let task = SolverTask {
+                    specs: vec![MatchSpec::from_str(spec, ParseStrictness::Strict).unwrap()],
+                    ..SolverTask::new([&conda_forge, &bioconda])
+                };
+                <$T>::default()
+                    .solve(task)
+                    .unwrap()
+                    .into_iter()
+                    .map(|r| format!("{}::{}", r.channel, r.package_record))
+                    .collect::<Vec<_>>()
+            };
+
+            assert_eq!(solve("foo"), vec!["conda-forge::foo=2.0=0"]);
+            assert_eq!(
+                solve("bioconda::foo"),
+                vec!["https://conda.anaconda.org/bioconda/::foo=1.0=0"]
+            );
+            assert_eq!(
+                solve("foo[url=https://conda.anaconda.org/bioconda/linux-64/foo-1.0-0.tar.bz2]"),
+                vec!["https://conda.anaconda.org/bioconda/::foo=1.0=0"]
+            );
+        }
+
+        #[test]
+        fn test_solve_snapshot() {
+            let repo_data = dummy_removal_repo();
+            let task = SolverTask {
+                specs: vec![MatchSpec::from_str("app", ParseStrictness::Strict).unwrap()],
+                ..SolverTask::new([&repo_data])
+            };
+
+            // The snapshot only contains the records that can be reached from the specs
+            let snapshot = SolverTaskSnapshot::from_task(&task);
+            assert_eq!(snapshot.available_packages.len(), 3);
+
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("snapshot.json.zst");
+            snapshot.to_path(&path).unwrap();
+
+            let mut expected = <$T>::default().solve(task).unwrap();
+            let mut replayed = SolverTaskSnapshot::from_path(&path)
+                .unwrap()
+                .solve::<$T>()
+                .unwrap();
+            expected.sort();
+            replayed.sort();
+            assert_eq!(expected, replayed);
+        }
+
+        #[test]
+        fn test_solve_exclusions() {
+            let foo_2 = PackageExclusion::MatchSpec(MatchSpec::from_str("foo 2.*", ParseStrictness::Strict).unwrap());
+            let result = solve_with_exclusions::<$T>(&["foo"], vec![foo_2.clone()], false);
+            assert_eq!(result.unwrap(), vec!["bar=2.0=0", "foo=1.0=0"]);
+
+            // Excluded records are not selected, even if they are locked
+            let result = solve_with_exclusions::<$T>(&["foo"], vec![foo_2], true);
+            assert_eq!(result.unwrap(), vec!["bar=2.0=0", "foo=1.0=0"]);
+
+            let bar_2 = dummy_update_repo()
+                .into_iter()
+                .find(|r| r.package_record.to_string() == "bar=2.0=0")
+                .unwrap();
+            let result = solve_with_exclusions::<$T>(
+                &["foo"],
+                vec![PackageExclusion::Url(bar_2.url)],
+                false,
+            );
+            assert_eq!(result.unwrap(), vec!["bar=1.0=0", "foo=2.0=0"]);
+        }
+
+        #[test]
+        fn test_solve_exclusions_unsolvable() {
+            let exclusions = vec![PackageExclusion::MatchSpec(
+                MatchSpec::from_str("foo 2.*", ParseStrictness::Strict).unwrap(),
+            )];
+
+            // The task can only be solved with an excluded record
+            let err = solve_with_exclusions::<$T>(&["foo>=2"], exclusions.clone(), false)
+                .unwrap_err();
+            match err {
+                SolveError::ExcludedPackagesRequired(records) => {
+                    assert_eq!(records, vec!["foo=2.0=0 (excluded by foo 2.*)"])
+                }
+                _ => panic!("expected an error about excluded packages, got {err}"),
+            }
+
+            // The task is unsolvable regardless of the exclusions
+            let err = solve_with_exclusions::<$T>(&["foo>=3"], exclusions, false).unwrap_err();
+            assert!(matches!(err, SolveError::Unsolvable(_)));
+        }
+
         #[test]
         fn test_solve_dummy_repo_remove() {
             let already_installed = vec![installed_package(
@@ -478,11 +1061,8 @@ mod libsolv_c {
 
         let pkgs = rattler_solve::libsolv_c::Solver
             .solve(SolverTask {
-                locked_packages: Vec::new(),
-                virtual_packages: Vec::new(),
-                available_packages: [libsolv_repodata],
                 specs,
-                pinned_packages: Vec::new(),
+                ..SolverTask::new([libsolv_repodata])
             })
             .unwrap();
 
@@ -521,6 +1101,44 @@ mod libsolv_c {
             info.package_record.md5.as_ref().unwrap()
         );
     }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn test_solve_with_solv_file_cache() {
+        let repo_data = dummy_update_repo();
+        let url = "https://conda.anaconda.org/conda-forge/linux-64";
+        let cache_dir = tempfile::tempdir().unwrap();
+        let cache = rattler_solve::libsolv_c::SolvFileCache::new(cache_dir.path());
+
+        // The cache is empty initially
+        assert!(cache.get(url, "state-1", &repo_data).unwrap().is_none());
+
+        // Inserting creates the file, subsequent lookups load it from disk
+        let created = cache.get_or_insert(url, "state-1", &repo_data).unwrap();
+        assert!(cache.cache_path(url).is_file());
+        let loaded = cache.get(url, "state-1", &repo_data).unwrap().unwrap();
+        assert_eq!(created.as_slice(), loaded.as_slice());
+
+        // A different state invalidates the entry
+        assert!(cache.get(url, "state-2", &repo_data).unwrap().is_none());
+
+        let pkgs = rattler_solve::libsolv_c::Solver
+            .solve(SolverTask {
+                specs: vec!["foo".parse().unwrap()],
+                ..SolverTask::new([rattler_solve::libsolv_c::RepoData {
+                    records: repo_data.iter().collect(),
+                    solv_file: Some(&loaded),
+                }])
+            })
+            .unwrap();
+
+        let mut pkgs = pkgs
+            .into_iter()
+            .map(|r| r.package_record.to_string())
+            .collect::<Vec<_>>();
+        pkgs.sort();
+        assert_eq!(pkgs, vec!["bar=2.0=0", "foo=2.0=0"]);
+    }
 }
 
 #[cfg(feature = "resolvo")]
@@ -529,6 +1147,165 @@ mod resolvo {
 
     solver_backend_tests!(rattler_solve::resolvo::Solver);
 
+    #[test]
+    fn test_solve_features() {
+        let mut numpy_mkl = dummy_package("linux-64", "numpy", "2.0", "mkl", &[]);
+        numpy_mkl.package_record.features = Some(String::from("mkl"));
+        let mut mkl = dummy_package("linux-64", "mkl", "1.0", "0", &[]);
+        mkl.package_record.track_features = vec![String::from("mkl")];
+        let repo_data = vec![
+            numpy_mkl,
+            dummy_package("linux-64", "numpy", "1.0", "nomkl", &[]),
+            mkl,
+        ];
+
+        let solve = |specs: &[&str]| {
+            let task = SolverTask {
+                specs: specs
+                    .iter()
+                    .map(|s| MatchSpec::from_str(s, ParseStrictness::Strict).unwrap())
+                    .collect(),
+                ..SolverTask::new([&repo_data])
+            };
+            let mut records = rattler_solve::resolvo::Solver::default()
+                .solve(task)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.package_record.to_string())
+                .collect::<Vec<_>>();
+            records.sort();
+            records
+        };
+
+        // Packages that require a feature are avoided if possible
+        assert_eq!(solve(&["numpy"]), vec!["numpy=1.0=nomkl"]);
+
+        // Unless the feature is explicitly requested, which pulls in the package that tracks it
+        assert_eq!(
+            solve(&["numpy[features=mkl]"]),
+            vec!["mkl=1.0=0", "numpy=2.0=mkl"]
+        );
+        assert_eq!(
+            solve(&["numpy[features='']", "mkl"]),
+            vec!["mkl=1.0=0", "numpy=1.0=nomkl"]
+        );
+    }
+
+    #[test]
+    fn test_solve_features_enabled() {
+        let mut numpy_mkl = dummy_package("linux-64", "numpy", "1.0", "mkl", &[]);
+        numpy_mkl.package_record.features = Some(String::from("mkl"));
+        let mut mkl = dummy_package("linux-64", "mkl", "1.0", "0", &[]);
+        mkl.package_record.track_features = vec![String::from("mkl")];
+        let repo_data = vec![
+            numpy_mkl,
+            dummy_package("linux-64", "numpy", "1.0", "nomkl", &[]),
+            mkl.clone(),
+        ];
+
+        let solve = |specs: &[&str], locked_packages: Vec<RepoDataRecord>| {
+            let task = SolverTask {
+                specs: specs
+                    .iter()
+                    .map(|s| MatchSpec::from_str(s, ParseStrictness::Strict).unwrap())
+                    .collect(),
+                locked_packages,
+                ..SolverTask::new([&repo_data])
+            };
+            let mut records = rattler_solve::resolvo::Solver
+                .solve(task)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.package_record.to_string())
+                .collect::<Vec<_>>();
+            records.sort();
+            records
+        };
+
+        // The featureless variant is preferred as long as the feature is not enabled
+        assert_eq!(solve(&["numpy"], Vec::new()), vec!["numpy=1.0=nomkl"]);
+
+        // An installed package that tracks the feature makes the featured variant preferable
+        assert_eq!(
+            solve(&["numpy"], vec![mkl]),
+            vec!["mkl=1.0=0", "numpy=1.0=mkl"]
+        );
+
+        // And so does a spec that selects the package that tracks the feature
+        assert_eq!(
+            solve(&["numpy", "mkl"], Vec::new()),
+            vec!["mkl=1.0=0", "numpy=1.0=mkl"]
+        );
+    }
+
+    #[test]
+    fn test_solve_with_pool() {
+        let repo_data = vec![
+            dummy_package("linux-64", "foo", "1.0", "0", &["bar"]),
+            dummy_package("linux-64", "foo", "2.0", "0", &["bar >=2"]),
+            dummy_package("linux-64", "bar", "1.0", "0", &[]),
+            dummy_package("linux-64", "bar", "2.0", "0", &["__unix"]),
+        ];
+        let pool = rattler_solve::resolvo::SolverPool::new([&repo_data]);
+
+        let solve = |specs: &[&str],
+                     locked_packages: Vec<RepoDataRecord>,
+                     virtual_packages: Vec<GenericVirtualPackage>| {
+            let task = SolverTask {
+                specs: specs
+                    .iter()
+                    .map(|s| MatchSpec::from_str(s, ParseStrictness::Strict).unwrap())
+                    .collect(),
+                locked_packages,
+                virtual_packages,
+                ..SolverTask::new(&pool)
+            };
+            let mut records = rattler_solve::resolvo::Solver
+                .solve_with_pool(task)?
+                .into_iter()
+                .map(|r| r.package_record.to_string())
+                .collect::<Vec<_>>();
+            records.sort();
+            Ok::<_, SolveError>(records)
+        };
+
+        // Without the virtual package only the first version of bar is available
+        assert_eq!(
+            solve(&["foo"], Vec::new(), Vec::new()).unwrap(),
+            vec!["bar=1.0=0", "foo=1.0=0"]
+        );
+        assert_eq!(
+            solve(
+                &["foo"],
+                Vec::new(),
+                vec![dummy_virtual_package("__unix", "0")]
+            )
+            .unwrap(),
+            vec!["bar=2.0=0", "foo=2.0=0"]
+        );
+
+        // Locked packages of a previous solve don't affect subsequent solves
+        assert_eq!(
+            solve(
+                &["foo"],
+                vec![repo_data[0].clone(), repo_data[2].clone()],
+                vec![dummy_virtual_package("__unix", "0")]
+            )
+            .unwrap(),
+            vec!["bar=1.0=0", "foo=1.0=0"]
+        );
+        assert_eq!(
+            solve(
+                &["foo"],
+                Vec::new(),
+                vec![dummy_virtual_package("__unix", "0")]
+            )
+            .unwrap(),
+            vec!["bar=2.0=0", "foo=2.0=0"]
+        );
+        assert!(solve(&["foo >=2"], Vec::new(), Vec::new()).is_err());
+    }
+
     #[test]
     fn test_solve_locked() {
         let result = solve::<rattler_solve::resolvo::Solver>(
@@ -562,15 +1339,15 @@ fn solve<T: SolverImpl + Default>(
 
     let specs: Vec<_> = match_specs
         .iter()
-        .map(|m| MatchSpec::from_str(m).unwrap())
+        .map(|m| MatchSpec::from_str(m, ParseStrictness::Strict).unwrap())
         .collect();
 
     let task = SolverTask {
         locked_packages: installed_packages,
         virtual_packages,
-        available_packages: [&repo_data],
         specs,
         pinned_packages,
+        ..SolverTask::new([&repo_data])
     };
 
     let pkgs = T::default().solve(task)?;
@@ -585,7 +1362,7 @@ fn solve<T: SolverImpl + Default>(
 fn compare_solve(specs: Vec<&str>) {
     let specs = specs
         .iter()
-        .map(|s| MatchSpec::from_str(s).unwrap())
+        .map(|s| MatchSpec::from_str(s, ParseStrictness::Strict).unwrap())
         .collect::<Vec<_>>();
 
     let sparse_repo_datas = read_real_world_repo_data();
@@ -623,11 +1400,8 @@ fn compare_solve(specs: Vec<&str>) {
             extract_pkgs(
                 rattler_solve::libsolv_c::Solver
                     .solve(SolverTask {
-                        available_packages: &available_packages,
                         specs: specs.clone(),
-                        locked_packages: Default::default(),
-                        pinned_packages: Default::default(),
-                        virtual_packages: Default::default(),
+                        ..SolverTask::new(&available_packages)
                     })
                     .unwrap(),
             ),
@@ -644,11 +1418,8 @@ fn compare_solve(specs: Vec<&str>) {
             extract_pkgs(
                 rattler_solve::resolvo::Solver
                     .solve(SolverTask {
-                        available_packages: &available_packages,
                         specs: specs.clone(),
-                        locked_packages: Default::default(),
-                        pinned_packages: Default::default(),
-                        virtual_packages: Default::default(),
+                        ..SolverTask::new(&available_packages)
                     })
                     .unwrap(),
             ),
@@ -18,6 +18,7 @@ impl PyChannelConfig {
         Ok(Self {
             inner: ChannelConfig {
                 channel_alias: Url::parse(channel_alias).map_err(PyRattlerError::from)?,
+                ..ChannelConfig::default()
             },
         })
     }
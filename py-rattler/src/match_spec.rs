@@ -1,6 +1,5 @@
 use pyo3::{pyclass, pymethods};
-use rattler_conda_types::{MatchSpec, PackageName};
-use std::str::FromStr;
+use rattler_conda_types::{MatchSpec, PackageName, ParseStrictness};
 
 use crate::{
     error::PyRattlerError, nameless_match_spec::PyNamelessMatchSpec,
@@ -30,7 +29,7 @@ impl From<PyMatchSpec> for MatchSpec {
 impl PyMatchSpec {
     #[new]
     pub fn __init__(spec: &str) -> pyo3::PyResult<Self> {
-        Ok(MatchSpec::from_str(spec)
+        Ok(MatchSpec::from_str(spec, ParseStrictness::Lenient)
             .map(Into::into)
             .map_err(PyRattlerError::from)?)
     }
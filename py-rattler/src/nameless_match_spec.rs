@@ -1,6 +1,5 @@
 use pyo3::{pyclass, pymethods};
-use rattler_conda_types::{MatchSpec, NamelessMatchSpec};
-use std::str::FromStr;
+use rattler_conda_types::{MatchSpec, NamelessMatchSpec, ParseStrictness};
 
 use crate::{
     error::PyRattlerError, match_spec::PyMatchSpec, repo_data::package_record::PyPackageRecord,
@@ -36,7 +35,7 @@ impl From<PyMatchSpec> for PyNamelessMatchSpec {
 impl PyNamelessMatchSpec {
     #[new]
     pub fn __init__(spec: &str) -> pyo3::PyResult<Self> {
-        Ok(NamelessMatchSpec::from_str(spec)
+        Ok(NamelessMatchSpec::from_str(spec, ParseStrictness::Lenient)
             .map(Into::into)
             .map_err(PyRattlerError::from)?)
     }